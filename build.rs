@@ -0,0 +1,32 @@
+use std::{env, fs, path::Path, process::Command};
+
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    let commit = commit.as_deref().map(str::trim).unwrap_or("unknown");
+
+    println!("cargo:rustc-env=PEPPER_COMMIT_HASH={}", commit);
+    let target = env::var("TARGET").unwrap_or_else(|_| "unknown".into());
+    println!("cargo:rustc-env=PEPPER_TARGET={}", target);
+
+    rerun_if_exists(".git/HEAD");
+
+    // new commits only touch the ref HEAD points to, not HEAD itself
+    if let Ok(head) = fs::read_to_string(".git/HEAD") {
+        if let Some(reference) = head.trim().strip_prefix("ref: ") {
+            rerun_if_exists(&format!(".git/{}", reference));
+            rerun_if_exists(".git/packed-refs");
+        }
+    }
+}
+
+// a missing path would make cargo rerun this script on every build
+fn rerun_if_exists(path: &str) {
+    if Path::new(path).exists() {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+}
@@ -1,12 +1,22 @@
-use std::{env, fs, io, panic, path::Path, sync::mpsc, time::Duration};
+use std::{
+    env, fs, io, panic,
+    path::Path,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
 
 use crate::{
+    bookmark::BOOKMARKS_FILE_EXTENSION,
     client::{ClientHandle, ClientManager},
+    command::{CommandManager, CommandTokenizer},
     editor::{Editor, EditorControlFlow},
-    editor_utils::{load_config, MessageKind},
+    editor_utils::{
+        load_config, load_project_config, session_file_path, MessageKind, DEFAULT_CONFIG_NAME,
+        DEFAULT_CONFIG_SOURCE,
+    },
     events::{ClientEvent, ClientEventReceiver, ServerEvent, TargetClient},
     ini::Ini,
-    platform::{Key, Platform, PlatformRequest, ProcessHandle, ProcessTag, SharedBuf},
+    platform::{Key, Platform, PlatformRequest, ProcessHandle, ProcessTag, SharedBuf, TickTag},
     serialization::{DeserializeError, Serialize},
     ui, Args,
 };
@@ -23,6 +33,7 @@ where
 
 pub enum ApplicationEvent {
     Idle,
+    Tick,
     Redraw,
     ConnectionOpen {
         handle: ClientHandle,
@@ -42,8 +53,13 @@ pub enum ApplicationEvent {
         tag: ProcessTag,
         buf: SharedBuf,
     },
+    ProcessErrorOutput {
+        tag: ProcessTag,
+        buf: SharedBuf,
+    },
     ProcessExit {
         tag: ProcessTag,
+        success: bool,
     },
 }
 
@@ -57,6 +73,76 @@ impl ApplicationEventSender {
     }
 }
 
+pub struct ServerTimers {
+    idle_deadline: Option<Instant>,
+    tick_intervals: Vec<(TickTag, Duration)>,
+    tick_interval: Option<Duration>,
+    tick_deadline: Instant,
+}
+impl Default for ServerTimers {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            idle_deadline: Some(now + ServerApplication::idle_duration()),
+            tick_intervals: Vec::new(),
+            tick_interval: None,
+            tick_deadline: now,
+        }
+    }
+}
+impl ServerTimers {
+    pub fn on_client_activity(&mut self) {
+        self.idle_deadline = Some(Instant::now() + ServerApplication::idle_duration());
+    }
+
+    pub fn set_tick_interval(&mut self, tag: TickTag, interval: Option<Duration>) {
+        self.tick_intervals.retain(|&(t, _)| t != tag);
+        if let Some(interval) = interval {
+            self.tick_intervals.push((tag, interval));
+        }
+
+        let previous_interval = self.tick_interval;
+        self.tick_interval = self.tick_intervals.iter().map(|&(_, i)| i).min();
+        if let Some(interval) = self.tick_interval {
+            if previous_interval != Some(interval) {
+                self.tick_deadline = Instant::now() + interval;
+            }
+        }
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        let tick_deadline = self.tick_interval.map(|_| self.tick_deadline);
+        let deadline = match (self.idle_deadline, tick_deadline) {
+            (Some(idle), Some(tick)) => Some(idle.min(tick)),
+            (idle, tick) => idle.or(tick),
+        };
+        deadline.map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn expired_events(&mut self) -> impl Iterator<Item = ApplicationEvent> {
+        let now = Instant::now();
+
+        let idle = matches!(self.idle_deadline, Some(deadline) if deadline <= now);
+        if idle {
+            self.idle_deadline = None;
+        }
+
+        let tick = match self.tick_interval {
+            Some(interval) if self.tick_deadline <= now => {
+                self.tick_deadline = now + interval;
+                true
+            }
+            _ => false,
+        };
+
+        IntoIterator::into_iter([
+            idle.then_some(ApplicationEvent::Idle),
+            tick.then_some(ApplicationEvent::Tick),
+        ])
+        .flatten()
+    }
+}
+
 pub struct ServerApplication;
 impl ServerApplication {
     pub fn platform_request_channel() -> (
@@ -74,30 +160,31 @@ impl ServerApplication {
         Duration::from_secs(1)
     }
 
-    pub fn run(args: Args, mut platform: Platform) -> Option<ApplicationEventSender> {
+    fn new_editor(args: &Args, platform: &mut Platform) -> Editor {
         let current_dir = env::current_dir().expect("could not retrieve the current directory");
         let mut editor = Editor::new(current_dir);
 
         let mut ini = Ini::default();
         if !args.no_default_config {
-            let source = include_str!("../rc/default_config.ini");
+            editor.uses_default_config = true;
             load_config(
                 &mut editor,
-                &mut platform,
+                platform,
                 &mut ini,
-                "default_config.ini",
-                source,
+                DEFAULT_CONFIG_NAME,
+                DEFAULT_CONFIG_SOURCE,
             );
         }
 
-        for config in args.configs {
+        for config in &args.configs {
             let path = Path::new(&config.path);
             if config.suppress_file_not_found && !path.exists() {
                 continue;
             }
             match fs::read_to_string(path) {
                 Ok(source) => {
-                    load_config(&mut editor, &mut platform, &mut ini, &config.path, &source)
+                    load_config(&mut editor, platform, &mut ini, &config.path, &source);
+                    editor.config_paths.push(config.path.clone());
                 }
                 Err(_) => editor
                     .status_bar
@@ -106,6 +193,27 @@ impl ServerApplication {
             }
         }
 
+        if let Some(ref session_name) = args.session {
+            let path = session_file_path(session_name, BOOKMARKS_FILE_EXTENSION);
+            if let Err(error) = editor.bookmarks.load(path) {
+                editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!("could not load bookmarks: {}", error));
+            }
+        }
+
+        if editor.config.auto_source_project_config {
+            let current_directory = editor.current_directory.clone();
+            load_project_config(&mut editor, platform, &current_directory);
+        }
+
+        editor
+    }
+
+    pub fn run(args: Args, mut platform: Platform) -> Option<ApplicationEventSender> {
+        let editor = Self::new_editor(&args, &mut platform);
+
         let (event_sender, event_receiver) = mpsc::channel();
         let application_event_sender = ApplicationEventSender(event_sender.clone());
         std::thread::spawn(move || {
@@ -117,6 +225,74 @@ impl ServerApplication {
         Some(application_event_sender)
     }
 
+    pub fn run_batch(args: Args) -> i32 {
+        let (request_sender, _) = Self::platform_request_channel();
+        let mut platform = Platform::new(|| (), request_sender);
+        let mut editor = Self::new_editor(&args, &mut platform);
+        editor.batch_mode = true;
+        let mut clients = ClientManager::default();
+        let client_handle = ClientHandle::from_index(0).unwrap();
+        clients.on_client_joined(client_handle);
+
+        let mut failed = print_batch_status_message(&mut editor);
+
+        let open_commands = args.files.iter().map(|path| {
+            let mut command = String::new();
+            command.push_str("open '");
+            command.push_str(path);
+            command.push('\'');
+            command
+        });
+        let eval_commands = args
+            .evals
+            .iter()
+            .flat_map(|eval| split_eval_commands(eval))
+            .map(String::from);
+
+        let mut quit = false;
+        for mut command in open_commands.chain(eval_commands) {
+            if failed && args.strict {
+                return 1;
+            }
+
+            match eval_batch_command(&mut editor, &mut platform, &mut clients, &mut command) {
+                Ok(EditorControlFlow::Continue) | Ok(EditorControlFlow::Suspend) => (),
+                Ok(EditorControlFlow::Quit) | Ok(EditorControlFlow::QuitAll) => {
+                    quit = true;
+                    break;
+                }
+                Err(()) => failed = true,
+            }
+        }
+        if failed && args.strict {
+            return 1;
+        }
+
+        // a successful quit means there was nothing left to save or changes were discarded
+        if !quit {
+            let unsaved_buffers: Vec<_> = editor
+                .buffers
+                .iter()
+                .filter(|b| b.needs_save())
+                .map(|b| b.handle())
+                .collect();
+            for handle in unsaved_buffers {
+                let mut command = format!("save -buffer={}", handle.0);
+                if eval_batch_command(&mut editor, &mut platform, &mut clients, &mut command)
+                    .is_err()
+                {
+                    failed = true;
+                }
+            }
+        }
+
+        if failed {
+            1
+        } else {
+            0
+        }
+    }
+
     fn run_application(
         mut editor: Editor,
         platform: &mut Platform,
@@ -131,10 +307,12 @@ impl ServerApplication {
             loop {
                 match event {
                     ApplicationEvent::Idle => editor.on_idle(&mut clients, platform),
+                    ApplicationEvent::Tick => editor.on_tick(&mut clients, platform),
                     ApplicationEvent::Redraw => (),
                     ApplicationEvent::ConnectionOpen { handle } => clients.on_client_joined(handle),
                     ApplicationEvent::ConnectionClose { handle } => {
                         clients.on_client_left(handle);
+                        editor.commands.on_client_left(handle);
                         if clients.iter().next().is_none() {
                             break 'event_loop;
                         }
@@ -171,8 +349,11 @@ impl ServerApplication {
                     ApplicationEvent::ProcessOutput { tag, buf } => {
                         editor.on_process_output(platform, &mut clients, tag, buf.as_bytes())
                     }
-                    ApplicationEvent::ProcessExit { tag } => {
-                        editor.on_process_exit(platform, &mut clients, tag)
+                    ApplicationEvent::ProcessErrorOutput { tag, buf } => {
+                        editor.on_process_error_output(tag, buf.as_bytes())
+                    }
+                    ApplicationEvent::ProcessExit { tag, success } => {
+                        editor.on_process_exit(platform, &mut clients, tag, success)
                     }
                 }
 
@@ -224,6 +405,8 @@ impl ServerApplication {
 pub struct ClientApplication<'stdout> {
     is_pipped: bool,
     target_client: TargetClient,
+    strict: bool,
+    eval_errors: String,
     stdin_read_buf: Vec<u8>, // TODO: do something with it
     server_read_buf: Vec<u8>,
     server_write_buf: Vec<u8>,
@@ -242,6 +425,8 @@ impl<'stdout> ClientApplication<'stdout> {
         Self {
             is_pipped,
             target_client: TargetClient::Sender,
+            strict: false,
+            eval_errors: String::new(),
             stdin_read_buf: Vec::new(),
             server_read_buf: Vec::new(),
             server_write_buf: Vec::new(),
@@ -249,7 +434,7 @@ impl<'stdout> ClientApplication<'stdout> {
         }
     }
 
-    pub fn init(&mut self, args: Args) -> &[u8] {
+    pub fn init(&mut self, args: Args, stdin_content: &[u8]) -> &[u8] {
         if args.as_focused_client {
             self.target_client = TargetClient::Focused;
         }
@@ -258,6 +443,8 @@ impl<'stdout> ClientApplication<'stdout> {
             self.is_pipped = true;
         }
 
+        self.strict = args.strict;
+
         self.server_write_buf.clear();
 
         self.reinit_screen();
@@ -275,6 +462,22 @@ impl<'stdout> ClientApplication<'stdout> {
                 .serialize(&mut self.server_write_buf);
         }
 
+        if args.stdin {
+            ClientEvent::StdinInput(self.target_client, stdin_content)
+                .serialize(&mut self.server_write_buf);
+        }
+
+        for eval in &args.evals {
+            for command in split_eval_commands(eval) {
+                ClientEvent::EvalCommand {
+                    target: self.target_client,
+                    command,
+                    strict: self.strict,
+                }
+                .serialize(&mut self.server_write_buf);
+            }
+        }
+
         if args.quit {
             ClientEvent::Command(TargetClient::Sender, "quit")
                 .serialize(&mut self.server_write_buf);
@@ -283,6 +486,14 @@ impl<'stdout> ClientApplication<'stdout> {
         self.server_write_buf.as_slice()
     }
 
+    pub fn exit_code(&self) -> i32 {
+        if self.strict && !self.eval_errors.is_empty() {
+            1
+        } else {
+            0
+        }
+    }
+
     pub fn reinit_screen(&mut self) {
         if self.is_pipped {
             return;
@@ -344,7 +555,11 @@ impl<'stdout> ClientApplication<'stdout> {
                         self.stdout.write_all(output.as_bytes()).unwrap();
                         self.stdout.write_all(b"\0").unwrap();
                     }
-                    Ok(ServerEvent::Request(_)) => (),
+                    Ok(ServerEvent::Request { .. }) => (),
+                    Ok(ServerEvent::CommandError(error)) => {
+                        self.eval_errors.push_str(error);
+                        self.eval_errors.push('\n');
+                    }
                     Err(DeserializeError::InsufficientData) => {
                         let read_len = self.server_read_buf.len() - previous_slice.len();
                         self.server_read_buf.drain(..read_len);
@@ -365,9 +580,85 @@ impl<'stdout> ClientApplication<'stdout> {
 impl<'stdout> Drop for ClientApplication<'stdout> {
     fn drop(&mut self) {
         self.restore_screen();
+        if !self.eval_errors.is_empty() {
+            eprint!("{}", self.eval_errors);
+        }
+    }
+}
+
+fn eval_batch_command(
+    editor: &mut Editor,
+    platform: &mut Platform,
+    clients: &mut ClientManager,
+    command: &mut String,
+) -> Result<EditorControlFlow, ()> {
+    let client_handle = ClientHandle::from_index(0).unwrap();
+    editor.status_bar.clear();
+    let result = CommandManager::try_eval(editor, platform, clients, client_handle, command);
+    editor.trigger_event_handlers(platform, clients);
+
+    let status_failed = print_batch_status_message(editor);
+    match result {
+        Ok(_) if status_failed => Err(()),
+        Ok(flow) => Ok(flow),
+        Err(error) => {
+            eprintln!("{}", error);
+            Err(())
+        }
     }
 }
 
+// there is no status bar in batch mode, so messages go to stdout and errors to stderr
+fn print_batch_status_message(editor: &mut Editor) -> bool {
+    let (kind, message) = editor.status_bar.message();
+    let failed = match kind {
+        _ if message.is_empty() => false,
+        MessageKind::Info => {
+            println!("{}", message);
+            false
+        }
+        MessageKind::Error => {
+            eprintln!("{}", message);
+            true
+        }
+    };
+    editor.status_bar.clear();
+    failed
+}
+
+// splits at `;` outside of quoted tokens, using the same quoting rules as `CommandTokenizer`
+fn split_eval_commands(mut commands: &str) -> impl Iterator<Item = &str> {
+    std::iter::from_fn(move || loop {
+        if commands.is_empty() {
+            return None;
+        }
+
+        let mut end = commands.len();
+        let mut tokens = CommandTokenizer(commands);
+        loop {
+            let rest = tokens.0.trim_start_matches(&[' ', '\t'][..]);
+            let token = match tokens.next() {
+                Some(token) => token,
+                None => break,
+            };
+            let quoted = rest.starts_with(&['"', '\''][..]) && !token.starts_with(&['"', '\''][..]);
+            if quoted {
+                continue;
+            }
+            if let Some(i) = token.find(';') {
+                end = token.as_ptr() as usize - commands.as_ptr() as usize + i;
+                break;
+            }
+        }
+
+        let command = commands[..end].trim();
+        commands = commands.get(end + 1..).unwrap_or("");
+        if !command.is_empty() {
+            return Some(command);
+        }
+    })
+}
+
 pub fn set_panic_hook() {
     static mut ORIGINAL_PANIC_HOOK: Option<Box<dyn Fn(&panic::PanicInfo) + Sync + Send + 'static>> =
         None;
@@ -384,3 +675,127 @@ pub fn set_panic_hook() {
         }
     }));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use crate::{buffer::BufferHandle, ArgsConfig};
+
+    #[test]
+    fn eval_commands_splitting() {
+        fn split(commands: &str) -> Vec<&str> {
+            split_eval_commands(commands).collect()
+        }
+
+        assert_eq!(vec!["open a", "quit"], split("open a; quit"));
+        assert_eq!(vec!["open a", "quit"], split(";open a;;quit;"));
+        assert_eq!(vec!["print 'a;b'", "quit"], split("print 'a;b'; quit"));
+        assert_eq!(vec!["print \"a;b\" c"], split("print \"a;b\" c"));
+        assert_eq!(vec!["print don't", "quit"], split("print don't; quit"));
+        assert_eq!(vec!["print a\"b", "c"], split("print a\"b;c"));
+        assert_eq!(vec!["print 'a", "quit"], split("print 'a; quit"));
+        assert_eq!(Vec::<&str>::new(), split(" ; "));
+    }
+
+    #[test]
+    fn tick_interval_is_shortest_active_subscriber() {
+        let first = TickTag::TailedFile(BufferHandle(0));
+        let second = TickTag::TailedFile(BufferHandle(1));
+        let mut timers = ServerTimers::default();
+        assert_eq!(None, timers.tick_interval);
+
+        timers.set_tick_interval(first, Some(Duration::from_millis(200)));
+        timers.set_tick_interval(second, Some(Duration::from_millis(50)));
+        assert_eq!(Some(Duration::from_millis(50)), timers.tick_interval);
+
+        timers.set_tick_interval(first, Some(Duration::from_millis(100)));
+        assert_eq!(Some(Duration::from_millis(50)), timers.tick_interval);
+
+        timers.set_tick_interval(second, None);
+        assert_eq!(Some(Duration::from_millis(100)), timers.tick_interval);
+
+        timers.set_tick_interval(first, None);
+        assert_eq!(None, timers.tick_interval);
+    }
+
+    fn batch_test_file(name: &str, text: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("pepper_batch_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn batch_args(path: &Path, evals: &[&str]) -> Args {
+        Args {
+            no_default_config: true,
+            batch: true,
+            files: vec![path.to_str().unwrap().into()],
+            evals: evals.iter().map(|&e| e.into()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn batch_saves_changed_buffers() {
+        let path = batch_test_file("changed.txt", "hello world\n");
+        let args = batch_args(&path, &["replace-all hello bye"]);
+        assert_eq!(0, ServerApplication::run_batch(args));
+        assert_eq!("bye world\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn batch_reports_failed_commands() {
+        let path = batch_test_file("failed.txt", "hello world\n");
+        let args = batch_args(&path, &["no-such-command; replace-all hello bye"]);
+        assert_eq!(1, ServerApplication::run_batch(args));
+        assert_eq!("bye world\n", fs::read_to_string(&path).unwrap());
+
+        let path = batch_test_file("strict.txt", "hello world\n");
+        let mut args = batch_args(&path, &["no-such-command; replace-all hello bye"]);
+        args.strict = true;
+        assert_eq!(1, ServerApplication::run_batch(args));
+        assert_eq!("hello world\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn batch_discards_changes_on_quit() {
+        let path = batch_test_file("quit.txt", "hello world\n");
+        let args = batch_args(&path, &["replace-all hello bye; quit!"]);
+        assert_eq!(0, ServerApplication::run_batch(args));
+        assert_eq!("hello world\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn batch_formats_before_saving() {
+        let config = batch_test_file(
+            "format.ini",
+            "[format]\nglob=**/*.upper\ncommand=tr a-z A-Z\n",
+        );
+        let path = batch_test_file("format.upper", "hello world\n");
+        let mut args = batch_args(&path, &["replace-all hello bye"]);
+        args.configs.push(ArgsConfig {
+            path: config.to_str().unwrap().into(),
+            suppress_file_not_found: false,
+        });
+        assert_eq!(0, ServerApplication::run_batch(args));
+        assert_eq!("BYE WORLD\n", fs::read_to_string(&path).unwrap());
+
+        let config = batch_test_file(
+            "failed_format.ini",
+            "[format]\nglob=**/*.fail\ncommand=false\n",
+        );
+        let path = batch_test_file("format.fail", "hello world\n");
+        let mut args = batch_args(&path, &["replace-all hello bye"]);
+        args.configs.push(ArgsConfig {
+            path: config.to_str().unwrap().into(),
+            suppress_file_not_found: false,
+        });
+        assert_eq!(1, ServerApplication::run_batch(args));
+        assert_eq!("hello world\n", fs::read_to_string(&path).unwrap());
+    }
+}
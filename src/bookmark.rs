@@ -0,0 +1,199 @@
+use std::{fs, io, path::PathBuf};
+
+use crate::buffer_position::BufferPositionIndex;
+
+pub static BOOKMARKS_FILE_EXTENSION: &str = "bookmarks";
+
+pub struct Bookmark {
+    pub path: String,
+    pub line_index: BufferPositionIndex,
+    pub label: String,
+}
+
+fn escape_field(field: &str, output: &mut String) {
+    for c in field.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '\t' => output.push_str("\\t"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            c => output.push(c),
+        }
+    }
+}
+
+fn unescape_field(field: &str) -> String {
+    let mut output = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => output.push('\t'),
+            Some('n') => output.push('\n'),
+            Some('r') => output.push('\r'),
+            Some(c) => output.push(c),
+            None => output.push('\\'),
+        }
+    }
+    output
+}
+
+// stored one per line as `<path>\t<line>\t<label>` inside the session's bookmarks file
+// tabs, newlines and backslashes inside a field are escaped
+#[derive(Default)]
+pub struct BookmarkCollection {
+    bookmarks: Vec<Bookmark>,
+    file_path: Option<PathBuf>,
+}
+
+impl BookmarkCollection {
+    pub fn len(&self) -> usize {
+        self.bookmarks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bookmarks.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Bookmark> {
+        self.bookmarks.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Bookmark> {
+        self.bookmarks.iter()
+    }
+
+    pub fn add(&mut self, path: &str, line_index: BufferPositionIndex, label: &str) {
+        let label = label.trim();
+        match self
+            .bookmarks
+            .iter_mut()
+            .find(|b| b.path == path && b.line_index == line_index)
+        {
+            Some(bookmark) => {
+                bookmark.label.clear();
+                bookmark.label.push_str(label);
+            }
+            None => self.bookmarks.push(Bookmark {
+                path: path.into(),
+                line_index,
+                label: label.into(),
+            }),
+        }
+    }
+
+    pub fn remove(&mut self, path: &str, line_index: BufferPositionIndex) -> bool {
+        let len = self.bookmarks.len();
+        self.bookmarks
+            .retain(|b| b.path != path || b.line_index != line_index);
+        self.bookmarks.len() != len
+    }
+
+    pub fn parse(&mut self, text: &str) {
+        self.bookmarks.clear();
+        for line in text.lines() {
+            let mut fields = line.splitn(3, '\t');
+            let path = match fields.next() {
+                Some(path) if !path.is_empty() => path,
+                _ => continue,
+            };
+            let line_index = match fields.next().and_then(|l| l.parse::<u32>().ok()) {
+                Some(line_number) if line_number > 0 => line_number - 1,
+                _ => continue,
+            };
+            let label = unescape_field(fields.next().unwrap_or(""));
+            self.add(&unescape_field(path), line_index, &label);
+        }
+    }
+
+    pub fn write(&self, output: &mut String) {
+        use std::fmt::Write;
+
+        for bookmark in &self.bookmarks {
+            escape_field(&bookmark.path, output);
+            let _ = write!(output, "\t{}\t", bookmark.line_index + 1);
+            escape_field(&bookmark.label, output);
+            output.push('\n');
+        }
+    }
+
+    pub fn load(&mut self, file_path: PathBuf) -> io::Result<()> {
+        self.bookmarks.clear();
+        let result = match fs::read_to_string(&file_path) {
+            Ok(text) => {
+                self.parse(&text);
+                Ok(())
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(error),
+        };
+        self.file_path = Some(file_path);
+        result
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let file_path = match &self.file_path {
+            Some(file_path) => file_path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = file_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut text = String::new();
+        self.write(&mut text);
+        fs::write(file_path, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_write() {
+        let mut bookmarks = BookmarkCollection::default();
+        bookmarks.parse("src/main.rs\t3\tmain function\n\nbroken\ninvalid\t0\t\nREADME.md\t10\n");
+
+        let entries: Vec<_> = bookmarks
+            .iter()
+            .map(|b| (b.path.as_str(), b.line_index, b.label.as_str()))
+            .collect();
+        assert_eq!(
+            vec![("src/main.rs", 2, "main function"), ("README.md", 9, "")],
+            entries
+        );
+
+        bookmarks.add("README.md", 9, " usage\t");
+        bookmarks.add("src/lib.rs", 0, "lib");
+        assert!(bookmarks.remove("src/main.rs", 2));
+        assert!(!bookmarks.remove("src/main.rs", 2));
+
+        let mut text = String::new();
+        bookmarks.write(&mut text);
+        assert_eq!("README.md\t10\tusage\nsrc/lib.rs\t1\tlib\n", text);
+    }
+
+    #[test]
+    fn escape_fields() {
+        let mut bookmarks = BookmarkCollection::default();
+        bookmarks.add("dir\\file\tname", 4, "first\tsecond\nthird");
+
+        let mut text = String::new();
+        bookmarks.write(&mut text);
+        assert_eq!("dir\\\\file\\tname\t5\tfirst\\tsecond\\nthird\n", text);
+        assert_eq!(1, text.lines().count());
+
+        bookmarks.parse(&text);
+        let entries: Vec<_> = bookmarks
+            .iter()
+            .map(|b| (b.path.as_str(), b.line_index, b.label.as_str()))
+            .collect();
+        assert_eq!(
+            vec![("dir\\file\tname", 4, "first\tsecond\nthird")],
+            entries
+        );
+    }
+}
@@ -1,22 +1,30 @@
 use std::{
     fmt,
-    fs::File,
+    fs::{self, File},
     io,
     num::NonZeroU8,
-    ops::RangeBounds,
+    ops::{Range, RangeBounds},
     path::{Path, PathBuf},
     process::{Command, Stdio},
     str::CharIndices,
+    time::Duration,
 };
 
 use crate::{
     buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
+    config::Config,
+    cursor::Cursor,
+    diff::{self, ChangeSign},
     events::{EditorEvent, EditorEventQueue},
+    fold::FoldCollection,
     help,
     history::{Edit, EditKind, History},
-    pattern::Pattern,
-    platform::{Platform, PlatformRequest, ProcessHandle, ProcessTag, SharedBuf},
-    syntax::{HighlightResult, HighlightedBuffer, SyntaxCollection, SyntaxHandle},
+    pattern::{MatchResult, Pattern, PatternError},
+    platform::{Platform, PlatformRequest, ProcessHandle, ProcessTag, SharedBuf, TickTag},
+    syntax::{
+        for_each_code_word, shebang_syntax_name, HighlightResult, HighlightedBuffer,
+        SyntaxCollection, SyntaxHandle, Token,
+    },
     word_database::{WordDatabase, WordIter, WordKind},
 };
 
@@ -45,17 +53,71 @@ pub fn find_delimiter_pair_at(text: &str, index: usize, delimiter: char) -> Opti
     None
 }
 
+fn is_sentence_end(line: &str, index: usize, abbreviations: &str) -> bool {
+    let (before, after) = line.split_at(index);
+    let mut after = after.chars();
+    let terminator = match after.next() {
+        Some(c @ ('.' | '!' | '?')) => c,
+        _ => return false,
+    };
+    if !after.next().map(char::is_whitespace).unwrap_or(true) {
+        return false;
+    }
+
+    if terminator == '.' {
+        let word = before.rsplit(char::is_whitespace).next().unwrap_or("");
+        if abbreviations
+            .split_whitespace()
+            .any(|a| a.eq_ignore_ascii_case(word))
+        {
+            return false;
+        }
+    }
+
+    true
+}
+
 pub fn parse_path_and_position(text: &str) -> (&str, Option<BufferPosition>) {
+    fn parse_index(text: &str) -> Option<BufferPositionIndex> {
+        if text.bytes().all(|b| b.is_ascii_digit()) {
+            text.parse::<BufferPositionIndex>()
+                .ok()
+                .map(|i| i.saturating_sub(1))
+        } else {
+            None
+        }
+    }
+
     let text = text.trim();
     match text.rfind(':') {
-        Some(i) => match text[i + 1..].parse() {
-            Ok(position) => (&text[..i], Some(position)),
-            Err(_) => (text, None),
-        },
+        Some(i) => {
+            let path = &text[..i];
+            if let (Some(j), Some(column)) = (path.rfind(':'), parse_index(&text[i + 1..])) {
+                if let Some(line) = parse_index(&path[j + 1..]) {
+                    return (&path[..j], Some(BufferPosition::line_col(line, column)));
+                }
+            }
+            match text[i + 1..].parse() {
+                Ok(position) => (path, Some(position)),
+                Err(_) => (text, None),
+            }
+        }
         None => (text, None),
     }
 }
 
+pub fn parse_missing_path_and_position<'a>(
+    root: &Path,
+    text: &'a str,
+) -> (&'a str, Option<BufferPosition>) {
+    let text = text.trim();
+    if root.join(text).exists() {
+        (text, None)
+    } else {
+        parse_path_and_position(text)
+    }
+}
+
 pub fn find_path_and_position_at(text: &str, index: usize) -> (&str, Option<BufferPosition>) {
     let (left, right) = text.split_at(index);
     let from = match left.rfind(|c: char| c.is_ascii_whitespace()) {
@@ -85,6 +147,77 @@ pub fn find_path_and_position_at(text: &str, index: usize) -> (&str, Option<Buff
     }
 }
 
+#[derive(Clone, Copy)]
+pub enum CaseConversion {
+    Upper,
+    Lower,
+    Toggle,
+    Title,
+}
+
+pub fn convert_case(text: &str, conversion: CaseConversion, output: &mut String) {
+    let mut previous_kind = WordKind::Whitespace;
+    for c in text.chars() {
+        let kind = WordKind::from_char(c);
+        let upper = match conversion {
+            CaseConversion::Upper => true,
+            CaseConversion::Lower => false,
+            CaseConversion::Toggle => !c.is_uppercase(),
+            CaseConversion::Title => previous_kind != WordKind::Identifier,
+        };
+        previous_kind = kind;
+        if upper {
+            output.extend(c.to_uppercase());
+        } else {
+            output.extend(c.to_lowercase());
+        }
+    }
+}
+
+pub struct SoftWrappedChar {
+    pub char_index: usize,
+    pub row: usize,
+    pub x: usize,
+    pub width: usize,
+}
+
+// also yields the end of the text (as if it were a '\n') so the cursor can be placed there
+pub fn soft_wrapped_chars(
+    text: &str,
+    tab_size: NonZeroU8,
+    wrap_width: usize,
+) -> impl Iterator<Item = SoftWrappedChar> + '_ {
+    let mut row = 0;
+    let mut x = 0;
+    text.char_indices()
+        .chain(std::iter::once((text.len(), '\n')))
+        .map(move |(char_index, c)| {
+            let width = match c {
+                '\t' => tab_size.get() as usize,
+                _ => 1,
+            };
+            if x > 0 && x + width > wrap_width {
+                row += 1;
+                x = 0;
+            }
+            let c = SoftWrappedChar {
+                char_index,
+                row,
+                x,
+                width,
+            };
+            x += width;
+            c
+        })
+}
+
+pub fn soft_wrapped_row_count(text: &str, tab_size: NonZeroU8, wrap_width: usize) -> usize {
+    match soft_wrapped_chars(text, tab_size, wrap_width).last() {
+        Some(c) => c.row + 1,
+        None => 1,
+    }
+}
+
 pub struct CharDisplayDistance {
     pub distance: usize,
     pub char_index: usize,
@@ -307,9 +440,119 @@ impl BufferLine {
     }
 }
 
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub ignore_case: bool,
+    pub whole_word: bool,
+}
+impl SearchOptions {
+    pub fn compile_searcher(self, pattern: &mut Pattern, text: &str) -> Result<(), PatternError> {
+        pattern.compile_searcher(text)?;
+        if self.ignore_case {
+            pattern.ignore_case();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchUpdate {
+    Reused,
+    Narrowed,
+    Searched,
+}
+
+pub struct Searcher {
+    pattern: Pattern,
+    text: String,
+    options: SearchOptions,
+    buffer_handle: Option<BufferHandle>,
+    search_generation: u32,
+}
+impl Default for Searcher {
+    fn default() -> Self {
+        Self {
+            pattern: Pattern::new(),
+            text: String::new(),
+            options: SearchOptions::default(),
+            buffer_handle: None,
+            search_generation: 0,
+        }
+    }
+}
+impl Searcher {
+    // when a literal search only grows, its matches are a subset of the previous ones
+    // and can be narrowed instead of searching the whole buffer again
+    pub fn search(
+        &mut self,
+        buffer: &mut Buffer,
+        text: &str,
+        options: SearchOptions,
+    ) -> Result<SearchUpdate, PatternError> {
+        let is_cached = self.buffer_handle == Some(buffer.handle)
+            && self.search_generation == buffer.search_generation
+            && self.options == options;
+        if is_cached && self.text == text {
+            return Ok(SearchUpdate::Reused);
+        }
+
+        let can_narrow = is_cached
+            && !options.whole_word
+            && matches!(
+                (literal_search_text(&self.text), literal_search_text(text)),
+                (Some((previous_prefix, previous)), Some((prefix, text)))
+                    if previous_prefix == prefix
+                        && !previous.is_empty()
+                        && !can_overlap_itself(previous)
+                        && text.starts_with(previous)
+            );
+
+        self.buffer_handle = None;
+        if let Err(error) = options.compile_searcher(&mut self.pattern, text) {
+            self.pattern.clear();
+            buffer.set_search(&self.pattern, options);
+            return Err(error);
+        }
+
+        let update = if can_narrow {
+            buffer.narrow_search(&self.pattern);
+            SearchUpdate::Narrowed
+        } else {
+            buffer.set_search(&self.pattern, options);
+            SearchUpdate::Searched
+        };
+
+        self.text.clear();
+        self.text.push_str(text);
+        self.options = options;
+        self.buffer_handle = Some(buffer.handle);
+        self.search_generation = buffer.search_generation;
+        Ok(update)
+    }
+}
+
+// a full search skips past each match so, for needles like `aa`, not every occurrence is a match
+fn can_overlap_itself(text: &str) -> bool {
+    (1..text.len()).any(|len| {
+        let suffix_start = text.len() - len;
+        text.is_char_boundary(len)
+            && text.is_char_boundary(suffix_start)
+            && text[..len].eq_ignore_ascii_case(&text[suffix_start..])
+    })
+}
+
+fn literal_search_text(text: &str) -> Option<(&str, &str)> {
+    match text.as_bytes() {
+        [b'p' | b'P', b'/', ..] => None,
+        [b'l' | b'L', b'/', ..] => Some(text.split_at(2)),
+        _ => Some(("", text)),
+    }
+}
+
 pub struct BufferContent {
     lines: Vec<BufferLine>,
     line_pool: BufferLinePool,
+    byte_len: usize,
 }
 
 impl BufferContent {
@@ -317,6 +560,7 @@ impl BufferContent {
         Self {
             lines: vec![BufferLine::new()],
             line_pool: BufferLinePool::new(),
+            byte_len: 0,
         }
     }
 
@@ -324,6 +568,17 @@ impl BufferContent {
         self.lines.len()
     }
 
+    // byte length of the content as displayed, counting one byte per line break
+    pub fn byte_len(&self) -> usize {
+        self.byte_len
+    }
+
+    fn lines_byte_len(&self, lines: Range<usize>) -> usize {
+        let lines = &self.lines[lines];
+        let text_len: usize = lines.iter().map(|l| l.as_str().len()).sum();
+        text_len + lines.len().saturating_sub(1)
+    }
+
     pub fn lines(
         &self,
     ) -> impl ExactSizeIterator<Item = &BufferLine> + DoubleEndedIterator<Item = &BufferLine> {
@@ -379,6 +634,7 @@ impl BufferContent {
             self.lines[0].text.drain(..3);
         }
 
+        self.byte_len = self.lines_byte_len(0..self.lines.len());
         Ok(())
     }
 
@@ -424,7 +680,16 @@ impl BufferContent {
         }
     }
 
-    pub fn find_search_ranges(&self, pattern: &Pattern, ranges: &mut Vec<BufferRange>) {
+    pub fn find_search_ranges(
+        &self,
+        pattern: &Pattern,
+        options: SearchOptions,
+        ranges: &mut Vec<BufferRange>,
+    ) {
+        fn is_word_char(c: Option<char>) -> bool {
+            matches!(c.map(WordKind::from_char), Some(WordKind::Identifier))
+        }
+
         if pattern.is_empty() {
             return;
         }
@@ -432,8 +697,15 @@ impl BufferContent {
         for (line_index, line) in self.lines.iter().enumerate() {
             let line = line.as_str();
             for (column_index, text) in pattern.match_indices(line, search_anchor) {
-                let from = BufferPosition::line_col(line_index as _, column_index as _);
                 let end = column_index + text.len();
+                if options.whole_word
+                    && (is_word_char(line[..column_index].chars().next_back())
+                        || is_word_char(line[end..].chars().next()))
+                {
+                    continue;
+                }
+
+                let from = BufferPosition::line_col(line_index as _, column_index as _);
                 let to = BufferPosition::line_col(line_index as _, end as _);
                 ranges.push(BufferRange::between(from, to));
             }
@@ -446,6 +718,7 @@ impl BufferContent {
             let previous_len = line.as_str().len();
             line.insert_text(position.column_byte_index as _, text);
             let len_diff = line.as_str().len() - previous_len;
+            self.byte_len += len_diff;
 
             let end_position = BufferPosition::line_col(
                 position.line_index,
@@ -453,6 +726,7 @@ impl BufferContent {
             );
             BufferRange::between(position, end_position)
         } else {
+            let previous_len = self.lines[position.line_index as usize].as_str().len();
             let mut split_line = self.line_pool.acquire();
             self.lines[position.line_index as usize]
                 .split_off(&mut split_line, position.column_byte_index as _);
@@ -485,6 +759,10 @@ impl BufferContent {
                 BufferPosition::line_col(position.line_index + line_count, column_byte_index)
             };
 
+            let first_line_index = position.line_index as usize;
+            let inserted_lines = first_line_index..end_position.line_index as usize + 1;
+            self.byte_len += self.lines_byte_len(inserted_lines) - previous_len;
+
             BufferRange::between(position, end_position)
         }
     }
@@ -493,6 +771,9 @@ impl BufferContent {
         let from = range.from;
         let to = range.to;
 
+        let last_line_index = (to.line_index as usize).min(self.lines.len() - 1);
+        let previous_len = self.lines_byte_len(from.line_index as usize..last_line_index + 1);
+
         if from.line_index == to.line_index {
             let line = &mut self.lines[from.line_index as usize];
             line.delete_range(from.column_byte_index as usize..to.column_byte_index as usize);
@@ -511,6 +792,9 @@ impl BufferContent {
                     .push_text(&to_line.as_str()[to.column_byte_index as usize..]);
             }
         }
+
+        let line_len = self.lines[from.line_index as usize].as_str().len();
+        self.byte_len = self.byte_len + line_len - previous_len;
     }
 
     pub fn clear(&mut self) {
@@ -518,6 +802,7 @@ impl BufferContent {
             self.line_pool.release(line);
         }
         self.lines.push(self.line_pool.acquire());
+        self.byte_len = 0;
     }
 
     pub fn words_from(
@@ -559,6 +844,96 @@ impl BufferContent {
         position
     }
 
+    pub fn find_sentence_start_after(
+        &self,
+        position: BufferPosition,
+        abbreviations: &str,
+    ) -> BufferPosition {
+        let position = self.saturate_position(position);
+        let mut after_sentence_end = false;
+        for line_index in position.line_index as usize..self.lines.len() {
+            let line = self.lines[line_index].as_str();
+            if line.trim().is_empty() {
+                after_sentence_end = true;
+                continue;
+            }
+
+            let column_byte_index = if line_index == position.line_index as usize {
+                position.column_byte_index as usize
+            } else {
+                0
+            };
+            for (i, c) in line[column_byte_index..].char_indices() {
+                if c.is_whitespace() {
+                    continue;
+                }
+                let i = column_byte_index + i;
+                if after_sentence_end {
+                    return BufferPosition::line_col(line_index as _, i as _);
+                }
+                after_sentence_end = is_sentence_end(line, i, abbreviations);
+            }
+        }
+
+        self.end()
+    }
+
+    pub fn find_sentence_start_before(
+        &self,
+        position: BufferPosition,
+        abbreviations: &str,
+    ) -> BufferPosition {
+        let position = self.saturate_position(position);
+        let mut sentence_start = None;
+        let mut crossed_blank_line = false;
+        for line_index in (0..=position.line_index as usize).rev() {
+            let line = self.lines[line_index].as_str();
+            if line.trim().is_empty() {
+                crossed_blank_line = true;
+                continue;
+            }
+
+            let column_byte_index = if line_index == position.line_index as usize {
+                position.column_byte_index as usize
+            } else {
+                line.len()
+            };
+            for (i, c) in line[..column_byte_index].char_indices().rev() {
+                if c.is_whitespace() {
+                    continue;
+                }
+                if let Some(sentence_start) = sentence_start {
+                    if crossed_blank_line || is_sentence_end(line, i, abbreviations) {
+                        return sentence_start;
+                    }
+                }
+                sentence_start = Some(BufferPosition::line_col(line_index as _, i as _));
+                crossed_blank_line = false;
+            }
+        }
+
+        sentence_start.unwrap_or(BufferPosition::zero())
+    }
+
+    pub fn find_word_ranges(&self, word: &str, lines: Range<usize>, ranges: &mut Vec<BufferRange>) {
+        let lines = lines.start.min(self.lines.len())..lines.end.min(self.lines.len());
+        for line_index in lines {
+            let mut column_byte_index = 0;
+            for w in WordIter(self.lines[line_index].as_str()) {
+                if w.kind == WordKind::Identifier && w.text == word {
+                    ranges.push(BufferRange::between(
+                        BufferPosition::line_col(line_index as _, column_byte_index as _),
+                        BufferPosition::line_col(
+                            line_index as _,
+                            (column_byte_index + w.text.len()) as _,
+                        ),
+                    ));
+                }
+                column_byte_index += w.text.len();
+            }
+        }
+    }
+
     pub fn find_delimiter_pair_at(
         &self,
         position: BufferPosition,
@@ -686,12 +1061,48 @@ impl fmt::Display for BufferContent {
     }
 }
 
+#[derive(Clone, Copy)]
+enum WordIndexing {
+    Disabled,
+    AllWords,
+    // words are taken from the highlighted tokens so a line is only indexed once it's highlighted
+    SkipCommentsAndStrings,
+}
+impl WordIndexing {
+    fn for_each_word<F>(self, line: &str, tokens: Option<&[Token]>, f: F)
+    where
+        F: FnMut(&str),
+    {
+        match self {
+            Self::Disabled => (),
+            Self::AllWords => WordIter(line).of_kind(WordKind::Identifier).for_each(f),
+            Self::SkipCommentsAndStrings => {
+                if let Some(tokens) = tokens {
+                    for_each_code_word(line, tokens, f);
+                }
+            }
+        }
+    }
+
+    fn add_words(self, line: &str, tokens: Option<&[Token]>, word_database: &mut WordDatabase) {
+        self.for_each_word(line, tokens, |w| word_database.add(w));
+    }
+
+    fn remove_words(self, line: &str, tokens: Option<&[Token]>, word_database: &mut WordDatabase) {
+        self.for_each_word(line, tokens, |w| word_database.remove(w));
+    }
+}
+
 #[derive(Default)]
 pub struct BufferCapabilities {
     pub has_history: bool,
     pub can_save: bool,
     pub uses_word_database: bool,
     pub auto_close: bool,
+    // going back to the previous buffer on <esc> or whenever the main cursor moves
+    pub dismissable: bool,
+    pub format_on_save: bool,
+    pub read_only: bool,
 }
 impl BufferCapabilities {
     pub fn text() -> Self {
@@ -699,7 +1110,10 @@ impl BufferCapabilities {
             has_history: true,
             can_save: true,
             auto_close: false,
+            dismissable: false,
             uses_word_database: true,
+            format_on_save: false,
+            read_only: false,
         }
     }
 
@@ -708,7 +1122,10 @@ impl BufferCapabilities {
             has_history: false,
             can_save: false,
             auto_close: false,
+            dismissable: false,
             uses_word_database: false,
+            format_on_save: false,
+            read_only: false,
         }
     }
 }
@@ -719,10 +1136,21 @@ pub struct Buffer {
     pub path: PathBuf,
     content: BufferContent,
     syntax_handle: SyntaxHandle,
+    syntax_override: Option<SyntaxHandle>,
     highlighted: HighlightedBuffer,
     history: History,
     search_ranges: Vec<BufferRange>,
+    search_generation: u32,
     needs_save: bool,
+    edit_rejected: bool,
+    baseline: Vec<String>,
+    baseline_outdated: bool,
+    baseline_discarded: bool,
+    change_signs: Vec<ChangeSign>,
+    change_signs_dirty: bool,
+    folds: FoldCollection,
+    skip_comment_words: bool,
+    syntax_max_line_length: usize,
     pub capabilities: BufferCapabilities,
 }
 
@@ -734,10 +1162,21 @@ impl Buffer {
             path: PathBuf::new(),
             content: BufferContent::new(),
             syntax_handle: SyntaxHandle::default(),
+            syntax_override: None,
             highlighted: HighlightedBuffer::new(),
             history: History::new(),
             search_ranges: Vec::new(),
+            search_generation: 0,
             needs_save: false,
+            edit_rejected: false,
+            baseline: Vec::new(),
+            baseline_outdated: false,
+            baseline_discarded: false,
+            change_signs: Vec::new(),
+            change_signs_dirty: false,
+            folds: FoldCollection::default(),
+            skip_comment_words: false,
+            syntax_max_line_length: usize::MAX,
             capabilities: BufferCapabilities::default(),
         }
     }
@@ -749,20 +1188,46 @@ impl Buffer {
         self.alive = false;
         self.path.clear();
         self.syntax_handle = SyntaxHandle::default();
+        self.syntax_override = None;
         self.highlighted.clear();
         self.history.clear();
-        self.search_ranges.clear();
+        self.clear_search();
         self.needs_save = false;
+        self.edit_rejected = false;
+        self.baseline.clear();
+        self.baseline_outdated = false;
+        self.baseline_discarded = false;
+        self.change_signs.clear();
+        self.change_signs_dirty = false;
+        self.folds.clear();
+        self.skip_comment_words = false;
+        self.syntax_max_line_length = usize::MAX;
         self.capabilities = BufferCapabilities::default();
     }
 
+    fn word_indexing(&self) -> WordIndexing {
+        if !self.capabilities.uses_word_database {
+            WordIndexing::Disabled
+        } else if self.skip_comment_words {
+            WordIndexing::SkipCommentsAndStrings
+        } else {
+            WordIndexing::AllWords
+        }
+    }
+
     fn remove_all_words_from_database(&mut self, word_database: &mut WordDatabase) {
-        if self.capabilities.uses_word_database {
-            for line in &self.content.lines {
-                for word in WordIter(line.as_str()).of_kind(WordKind::Identifier) {
-                    word_database.remove(word);
-                }
-            }
+        let word_indexing = self.word_indexing();
+        for (i, line) in self.content.lines.iter().enumerate() {
+            let tokens = self.highlighted.clean_line_tokens(i);
+            word_indexing.remove_words(line.as_str(), tokens, word_database);
+        }
+    }
+
+    fn add_all_words_to_database(&mut self, word_database: &mut WordDatabase) {
+        let word_indexing = self.word_indexing();
+        for (i, line) in self.content.lines.iter().enumerate() {
+            let tokens = self.highlighted.clean_line_tokens(i);
+            word_indexing.add_words(line.as_str(), tokens, word_database);
         }
     }
 
@@ -770,24 +1235,76 @@ impl Buffer {
         self.handle
     }
 
+    pub fn syntax_handle(&self) -> SyntaxHandle {
+        self.syntax_handle
+    }
+
     pub fn highlighted(&self) -> &HighlightedBuffer {
         &self.highlighted
     }
 
-    pub fn update_highlighting(&mut self, syntaxes: &SyntaxCollection) -> HighlightResult {
-        self.highlighted
-            .highlight_dirty_lines(syntaxes.get(self.syntax_handle), &self.content)
+    pub fn update_highlighting(
+        &mut self,
+        syntaxes: &SyntaxCollection,
+        word_database: &mut WordDatabase,
+    ) -> HighlightResult {
+        let word_database = match self.word_indexing() {
+            WordIndexing::SkipCommentsAndStrings => Some(word_database),
+            _ => None,
+        };
+        self.highlighted.highlight_dirty_lines(
+            syntaxes.get(self.syntax_handle),
+            &self.content,
+            self.syntax_max_line_length,
+            word_database,
+        )
     }
 
-    pub fn refresh_syntax(&mut self, syntaxes: &SyntaxCollection) {
-        let path = self.path.to_str().unwrap_or("");
-        if path.is_empty() {
-            return;
-        }
+    // whether its words only get into the word database by highlighting it
+    pub fn indexes_words_when_highlighting(&self) -> bool {
+        matches!(self.word_indexing(), WordIndexing::SkipCommentsAndStrings)
+    }
 
-        let syntax_handle = syntaxes.find_handle_by_path(path).unwrap_or_default();
+    pub fn refresh_syntax(
+        &mut self,
+        syntaxes: &SyntaxCollection,
+        word_database: &mut WordDatabase,
+        config: &Config,
+    ) {
+        let skip_comment_words = config.word_database_skip_comments;
+        let max_line_length = config.syntax_max_line_length as usize;
+        let syntax_handle = match self.syntax_override {
+            Some(handle) => handle,
+            None => {
+                let path = self.path.to_str().unwrap_or("");
+                let path_handle = if path.is_empty() {
+                    None
+                } else {
+                    syntaxes.find_handle_by_path(path)
+                };
+                match path_handle {
+                    Some(handle) => handle,
+                    None if config.syntax_detect_shebang => {
+                        shebang_syntax_name(self.content.line_at(0).as_str())
+                            .and_then(|name| syntaxes.find_handle_by_name(name))
+                            .unwrap_or_default()
+                    }
+                    None => SyntaxHandle::default(),
+                }
+            }
+        };
+        let syntax_changed = self.syntax_handle != syntax_handle;
+        let max_line_length_changed = self.syntax_max_line_length != max_line_length;
+        let highlighting_changed = syntax_changed || max_line_length_changed;
+
+        // words skipped inside comments come from the highlighted tokens
+        let reindex_words = skip_comment_words != self.skip_comment_words
+            || (skip_comment_words && highlighting_changed);
+        if reindex_words {
+            self.remove_all_words_from_database(word_database);
+        }
 
-        if self.syntax_handle != syntax_handle {
+        if highlighting_changed {
             self.syntax_handle = syntax_handle;
             self.highlighted.clear();
             self.highlighted.on_insert(BufferRange::between(
@@ -795,6 +1312,16 @@ impl Buffer {
                 BufferPosition::line_col((self.content.line_count() - 1) as _, 0),
             ));
         }
+        self.syntax_max_line_length = max_line_length;
+        self.skip_comment_words = skip_comment_words;
+
+        if reindex_words {
+            self.add_all_words_to_database(word_database);
+        }
+    }
+
+    pub fn set_syntax_override(&mut self, handle: Option<SyntaxHandle>) {
+        self.syntax_override = handle;
     }
 
     pub fn content(&self) -> &BufferContent {
@@ -805,6 +1332,91 @@ impl Buffer {
         self.capabilities.can_save && self.needs_save
     }
 
+    pub fn change_signs(&self) -> &[ChangeSign] {
+        &self.change_signs
+    }
+
+    pub fn update_change_signs(&mut self) {
+        if !self.change_signs_dirty {
+            return;
+        }
+        self.change_signs_dirty = false;
+
+        let baseline: Vec<_> = self.baseline.iter().map(String::as_str).collect();
+        let lines: Vec<_> = self.content.lines().map(BufferLine::as_str).collect();
+        diff::change_signs(&baseline, &lines, &mut self.change_signs);
+    }
+
+    pub fn folds(&self) -> &FoldCollection {
+        &self.folds
+    }
+
+    pub fn folds_mut(&mut self) -> &mut FoldCollection {
+        &mut self.folds
+    }
+
+    pub fn update_folds(&mut self, tab_size: NonZeroU8, cursors: &[Cursor]) {
+        self.folds.update(&self.content, tab_size, cursors);
+    }
+
+    // the baseline is only copied when change signs are enabled and just once per frame
+    pub fn capture_outdated_baseline(&mut self) {
+        fn copy_lines(baseline: &mut Vec<String>, content: &BufferContent) {
+            baseline.truncate(content.line_count());
+            for (i, line) in content.lines().enumerate() {
+                match baseline.get_mut(i) {
+                    Some(baseline_line) => {
+                        baseline_line.clear();
+                        baseline_line.push_str(line.as_str());
+                    }
+                    None => baseline.push(line.as_str().into()),
+                }
+            }
+        }
+
+        if !self.baseline_outdated {
+            return;
+        }
+        self.baseline_outdated = false;
+        self.change_signs.clear();
+
+        if self.baseline_discarded && self.needs_save {
+            // edited while change signs were disabled, so the loaded or saved content is read back
+            let mut content = BufferContent::new();
+            if let Ok(file) = File::open(&self.path) {
+                let _ = content.read(&mut io::BufReader::new(file));
+            }
+            copy_lines(&mut self.baseline, &content);
+            self.change_signs_dirty = true;
+        } else {
+            copy_lines(&mut self.baseline, &self.content);
+            self.change_signs_dirty = false;
+        }
+        self.baseline_discarded = false;
+    }
+
+    pub fn discard_baseline(&mut self) {
+        if self.baseline_discarded {
+            return;
+        }
+        self.baseline = Vec::new();
+        self.baseline_outdated = true;
+        self.baseline_discarded = true;
+        self.change_signs.clear();
+        self.change_signs_dirty = false;
+    }
+
+    pub fn reject_if_read_only(&mut self) -> bool {
+        if self.capabilities.read_only {
+            self.edit_rejected = true;
+        }
+        self.capabilities.read_only
+    }
+
+    pub fn take_edit_rejected(&mut self) -> bool {
+        std::mem::replace(&mut self.edit_rejected, false)
+    }
+
     pub fn insert_text(
         &mut self,
         word_database: &mut WordDatabase,
@@ -812,18 +1424,35 @@ impl Buffer {
         text: &str,
         events: &mut EditorEventQueue,
     ) -> BufferRange {
-        self.search_ranges.clear();
+        if self.reject_if_read_only() {
+            let position = self.content.saturate_position(position);
+            return BufferRange::between(position, position);
+        }
+        self.insert_text_unchecked(word_database, position, text, events)
+    }
+
+    fn insert_text_unchecked(
+        &mut self,
+        word_database: &mut WordDatabase,
+        position: BufferPosition,
+        text: &str,
+        events: &mut EditorEventQueue,
+    ) -> BufferRange {
+        self.clear_search();
         let position = self.content.saturate_position(position);
 
         if text.is_empty() {
             return BufferRange::between(position, position);
         }
         self.needs_save = true;
+        self.change_signs_dirty = true;
 
+        let word_indexing = self.word_indexing();
         let range = Self::insert_text_no_history(
             &mut self.content,
             &mut self.highlighted,
-            self.capabilities.uses_word_database,
+            &mut self.folds,
+            word_indexing,
             word_database,
             position,
             text,
@@ -845,33 +1474,35 @@ impl Buffer {
     fn insert_text_no_history(
         content: &mut BufferContent,
         highlighted: &mut HighlightedBuffer,
-        uses_word_database: bool,
+        folds: &mut FoldCollection,
+        word_indexing: WordIndexing,
         word_database: &mut WordDatabase,
         position: BufferPosition,
         text: &str,
     ) -> BufferRange {
-        if uses_word_database {
-            for word in WordIter(content.line_at(position.line_index as _).as_str())
-                .of_kind(WordKind::Identifier)
-            {
-                word_database.remove(word);
-            }
-        }
+        let line_index = position.line_index as usize;
+        word_indexing.remove_words(
+            content.line_at(line_index).as_str(),
+            highlighted.clean_line_tokens(line_index),
+            word_database,
+        );
 
         let range = content.insert_text(position, text);
         highlighted.on_insert(range);
-
-        if uses_word_database {
-            let line_count = range.to.line_index - range.from.line_index + 1;
-            for line in content
-                .lines()
-                .skip(range.from.line_index as _)
-                .take(line_count as _)
-            {
-                for word in WordIter(line.as_str()).of_kind(WordKind::Identifier) {
-                    word_database.add(word);
-                }
-            }
+        folds.on_insert(range);
+
+        let line_count = range.to.line_index - range.from.line_index + 1;
+        for (i, line) in content
+            .lines()
+            .enumerate()
+            .skip(range.from.line_index as _)
+            .take(line_count as _)
+        {
+            word_indexing.add_words(
+                line.as_str(),
+                highlighted.clean_line_tokens(i),
+                word_database,
+            );
         }
 
         range
@@ -883,7 +1514,11 @@ impl Buffer {
         mut range: BufferRange,
         events: &mut EditorEventQueue,
     ) {
-        self.search_ranges.clear();
+        if self.reject_if_read_only() {
+            return;
+        }
+
+        self.clear_search();
         range.from = self.content.saturate_position(range.from);
         range.to = self.content.saturate_position(range.to);
 
@@ -891,6 +1526,7 @@ impl Buffer {
             return;
         }
         self.needs_save = true;
+        self.change_signs_dirty = true;
 
         events.enqueue(EditorEvent::BufferDeleteText {
             handle: self.handle,
@@ -942,10 +1578,12 @@ impl Buffer {
             }
         }
 
+        let word_indexing = self.word_indexing();
         Self::delete_range_no_history(
             &mut self.content,
             &mut self.highlighted,
-            self.capabilities.uses_word_database,
+            &mut self.folds,
+            word_indexing,
             word_database,
             range,
         );
@@ -954,34 +1592,85 @@ impl Buffer {
     fn delete_range_no_history(
         content: &mut BufferContent,
         highlighted: &mut HighlightedBuffer,
-        uses_word_database: bool,
+        folds: &mut FoldCollection,
+        word_indexing: WordIndexing,
         word_database: &mut WordDatabase,
         range: BufferRange,
     ) {
-        if uses_word_database {
-            let line_count = range.to.line_index - range.from.line_index + 1;
-            for line in content
-                .lines()
-                .skip(range.from.line_index as _)
-                .take(line_count as _)
-            {
-                for word in WordIter(line.as_str()).of_kind(WordKind::Identifier) {
-                    word_database.remove(word);
-                }
-            }
+        let line_count = range.to.line_index - range.from.line_index + 1;
+        for (i, line) in content
+            .lines()
+            .enumerate()
+            .skip(range.from.line_index as _)
+            .take(line_count as _)
+        {
+            let tokens = highlighted.clean_line_tokens(i);
+            word_indexing.remove_words(line.as_str(), tokens, word_database);
+        }
 
-            content.delete_range(range);
+        content.delete_range(range);
+        highlighted.on_delete(range);
+        folds.on_delete(range);
 
-            for word in WordIter(content.line_at(range.from.line_index as _).as_str())
-                .of_kind(WordKind::Identifier)
-            {
-                word_database.add(word);
-            }
-        } else {
-            content.delete_range(range);
+        let line_index = range.from.line_index as usize;
+        word_indexing.add_words(
+            content.line_at(line_index).as_str(),
+            highlighted.clean_line_tokens(line_index),
+            word_database,
+        );
+    }
+
+    // only the part that differs is replaced so cursors outside of it stay on the same text
+    pub fn replace_all_text(
+        &mut self,
+        word_database: &mut WordDatabase,
+        text: &str,
+        events: &mut EditorEventQueue,
+    ) -> bool {
+        fn position_at(text: &str, index: usize) -> BufferPosition {
+            let text = &text[..index];
+            let line_index = text.bytes().filter(|&b| b == b'\n').count();
+            let column_byte_index = match text.rfind('\n') {
+                Some(i) => index - i - 1,
+                None => index,
+            };
+            BufferPosition::line_col(line_index as _, column_byte_index as _)
         }
 
-        highlighted.on_delete(range);
+        let mut current = String::new();
+        let range = BufferRange::between(BufferPosition::zero(), self.content.end());
+        self.content
+            .append_range_text_to_string(range, &mut current);
+        if current == text {
+            return false;
+        }
+
+        let mut prefix_len = current
+            .bytes()
+            .zip(text.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !current.is_char_boundary(prefix_len) {
+            prefix_len -= 1;
+        }
+        let mut suffix_len = current
+            .bytes()
+            .rev()
+            .zip(text.bytes().rev())
+            .take(current.len().min(text.len()) - prefix_len)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !current.is_char_boundary(current.len() - suffix_len) {
+            suffix_len -= 1;
+        }
+
+        let from = position_at(&current, prefix_len);
+        let to = position_at(&current, current.len() - suffix_len);
+        self.delete_range(word_database, BufferRange::between(from, to), events);
+        let text = &text[prefix_len..text.len() - suffix_len];
+        self.insert_text(word_database, from, text, events);
+        self.commit_edits();
+        true
     }
 
     pub fn commit_edits(&mut self) {
@@ -1014,12 +1703,14 @@ impl Buffer {
         F: FnOnce(&'a mut History) -> I,
         I: 'a + Clone + ExactSizeIterator<Item = Edit<'a>>,
     {
-        self.search_ranges.clear();
+        self.clear_search();
         self.needs_save = true;
+        self.change_signs_dirty = true;
 
+        let word_indexing = self.word_indexing();
         let content = &mut self.content;
         let highlighted = &mut self.highlighted;
-        let uses_word_database = self.capabilities.uses_word_database;
+        let folds = &mut self.folds;
 
         let edits = selector(&mut self.history);
         for edit in edits.clone() {
@@ -1028,7 +1719,8 @@ impl Buffer {
                     Self::insert_text_no_history(
                         content,
                         highlighted,
-                        uses_word_database,
+                        folds,
+                        word_indexing,
                         word_database,
                         edit.range.from,
                         edit.text,
@@ -1039,7 +1731,8 @@ impl Buffer {
                     Self::delete_range_no_history(
                         content,
                         highlighted,
-                        uses_word_database,
+                        folds,
+                        word_indexing,
                         word_database,
                         edit.range,
                     );
@@ -1054,10 +1747,35 @@ impl Buffer {
         edits
     }
 
-    pub fn set_search(&mut self, pattern: &Pattern) {
+    fn clear_search(&mut self) {
         self.search_ranges.clear();
+        self.search_generation = self.search_generation.wrapping_add(1);
+    }
+
+    pub fn set_search(&mut self, pattern: &Pattern, options: SearchOptions) {
+        self.clear_search();
         self.content
-            .find_search_ranges(pattern, &mut self.search_ranges);
+            .find_search_ranges(pattern, options, &mut self.search_ranges);
+    }
+
+    fn narrow_search(&mut self, pattern: &Pattern) {
+        let content = &self.content;
+        let mut len = 0;
+        for i in 0..self.search_ranges.len() {
+            let mut range = self.search_ranges[i];
+            if len > 0 && range.from < self.search_ranges[len - 1].to {
+                continue;
+            }
+            let line = content.line_at(range.from.line_index as _).as_str();
+            let column = range.from.column_byte_index as usize;
+            if let MatchResult::Ok(match_len) = pattern.matches(&line[column..]) {
+                range.to.column_byte_index = (column + match_len) as _;
+                self.search_ranges[len] = range;
+                len += 1;
+            }
+        }
+        self.search_ranges.truncate(len);
+        self.search_generation = self.search_generation.wrapping_add(1);
     }
 
     pub fn search_ranges(&self) -> &[BufferRange] {
@@ -1088,6 +1806,7 @@ impl Buffer {
 
         self.capabilities.can_save = true;
         self.needs_save = false;
+        self.baseline_outdated = true;
 
         events.enqueue(EditorEvent::BufferSave {
             handle: self.handle,
@@ -1102,12 +1821,13 @@ impl Buffer {
         events: &mut EditorEventQueue,
     ) -> io::Result<()> {
         self.history.clear();
-        self.search_ranges.clear();
+        self.clear_search();
         self.needs_save = false;
 
         self.remove_all_words_from_database(word_database);
         self.content.clear();
         self.highlighted.clear();
+        self.folds.clear();
 
         events.enqueue(EditorEvent::BufferOpen {
             handle: self.handle,
@@ -1125,13 +1845,8 @@ impl Buffer {
             BufferPosition::line_col((self.content.line_count() - 1) as _, 0),
         ));
 
-        if self.capabilities.uses_word_database {
-            for line in &self.content.lines {
-                for word in WordIter(line.as_str()).of_kind(WordKind::Identifier) {
-                    word_database.add(word);
-                }
-            }
-        }
+        self.add_all_words_to_database(word_database);
+        self.baseline_outdated = true;
 
         Ok(())
     }
@@ -1140,18 +1855,54 @@ impl Buffer {
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct BufferHandle(pub u32);
 
+const TAIL_POLL_INTERVAL_MS: u64 = 500;
+
 pub struct InsertProcess {
     pub alive: bool,
+    pub handle: Option<ProcessHandle>,
     pub buffer_handle: BufferHandle,
     pub position: BufferPosition,
     pub input: Option<SharedBuf>,
     pub output: Vec<u8>,
 }
 
+// a save that waits for the buffer to be formatted first
+pub struct PendingSave {
+    pub new_path: Option<PathBuf>,
+    pub save_if_format_fails: bool,
+}
+
+pub struct PipeProcess {
+    pub alive: bool,
+    pub handle: Option<ProcessHandle>,
+    pub buffer_handle: BufferHandle,
+    pub input: Option<SharedBuf>,
+    pub output: Vec<u8>,
+    pub error: Vec<u8>,
+    pub save: Option<PendingSave>,
+}
+
+pub struct FinishedPipeProcess {
+    pub buffer_handle: BufferHandle,
+    // `None` if the process failed or the buffer changed while it was running
+    pub output: Option<String>,
+    // what the process wrote to its stderr
+    pub error: String,
+    pub save: Option<PendingSave>,
+}
+
+struct TailedFile {
+    buffer_handle: BufferHandle,
+    path: PathBuf,
+    len: u64,
+}
+
 #[derive(Default)]
 pub struct BufferCollection {
     buffers: Vec<Buffer>,
     insert_processes: Vec<InsertProcess>,
+    pipe_processes: Vec<PipeProcess>,
+    tailed_files: Vec<TailedFile>,
 }
 
 impl BufferCollection {
@@ -1185,6 +1936,10 @@ impl BufferCollection {
         &mut self.buffers[handle.0 as usize]
     }
 
+    pub fn try_get(&self, handle: BufferHandle) -> Option<&Buffer> {
+        self.buffers.get(handle.0 as usize).filter(|b| b.alive)
+    }
+
     pub fn find_with_path(&self, buffers_root: &Path, path: &Path) -> Option<BufferHandle> {
         if path.as_os_str().is_empty() {
             return None;
@@ -1247,6 +2002,7 @@ impl BufferCollection {
                 let index = self.insert_processes.len();
                 self.insert_processes.push(InsertProcess {
                     alive: false,
+                    handle: None,
                     buffer_handle,
                     position,
                     input: None,
@@ -1258,6 +2014,7 @@ impl BufferCollection {
 
         let process = &mut self.insert_processes[index];
         process.alive = true;
+        process.handle = None;
         process.buffer_handle = buffer_handle;
         process.position = position;
         process.input = stdin;
@@ -1284,12 +2041,26 @@ impl BufferCollection {
         index: usize,
         handle: ProcessHandle,
     ) {
-        if let Some(buf) = self.insert_processes[index].input.take() {
+        let process = &mut self.insert_processes[index];
+        process.handle = Some(handle);
+        if let Some(buf) = process.input.take() {
             platform.enqueue_request(PlatformRequest::WriteToProcess { handle, buf });
             platform.enqueue_request(PlatformRequest::CloseProcessInput { handle });
         }
     }
 
+    pub fn stop_insert_processes(&mut self, platform: &mut Platform, buffer_handle: BufferHandle) {
+        for process in &mut self.insert_processes {
+            if process.alive && process.buffer_handle == buffer_handle {
+                // output that arrives before it exits is discarded
+                if let Some(handle) = process.handle.take() {
+                    platform.enqueue_request(PlatformRequest::KillProcess { handle });
+                }
+                process.output.clear();
+            }
+        }
+    }
+
     pub fn on_process_output(
         &mut self,
         word_database: &mut WordDatabase,
@@ -1298,6 +2069,9 @@ impl BufferCollection {
         events: &mut EditorEventQueue,
     ) {
         let process = &mut self.insert_processes[index];
+        if process.handle.is_none() {
+            return;
+        }
         process.output.extend_from_slice(bytes);
 
         let len = match process.output.iter().rposition(|&b| b == b'\n') {
@@ -1311,7 +2085,7 @@ impl BufferCollection {
             let insert_range = match std::str::from_utf8(text) {
                 Ok(text) => {
                     process.position = buffer.content().saturate_position(process.position);
-                    buffer.insert_text(word_database, process.position, text, events)
+                    buffer.insert_text_unchecked(word_database, process.position, text, events)
                 }
                 Err(_) => BufferRange::zero(),
             };
@@ -1333,20 +2107,313 @@ impl BufferCollection {
     ) {
         let process = &mut self.insert_processes[index];
         process.alive = false;
+        if process.handle.take().is_none() {
+            return;
+        }
 
         let buffer = &mut self.buffers[process.buffer_handle.0 as usize];
         if buffer.alive {
             if let Ok(text) = std::str::from_utf8(&process.output) {
-                buffer.insert_text(word_database, process.position, text, events);
+                buffer.insert_text_unchecked(word_database, process.position, text, events);
             }
         }
     }
+
+    pub fn tail_file(
+        &mut self,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        path: PathBuf,
+    ) -> io::Result<()> {
+        let len = fs::metadata(&path)?.len();
+        self.tailed_files
+            .retain(|f| f.buffer_handle != buffer_handle);
+        self.tailed_files.push(TailedFile {
+            buffer_handle,
+            path,
+            len,
+        });
+        platform.enqueue_request(PlatformRequest::SetTickInterval {
+            tag: TickTag::TailedFile(buffer_handle),
+            interval: Some(Duration::from_millis(TAIL_POLL_INTERVAL_MS)),
+        });
+        Ok(())
+    }
+
+    pub fn stop_tailing_file(&mut self, platform: &mut Platform, buffer_handle: BufferHandle) {
+        let previous_len = self.tailed_files.len();
+        self.tailed_files
+            .retain(|f| f.buffer_handle != buffer_handle);
+        if self.tailed_files.len() < previous_len {
+            platform.enqueue_request(PlatformRequest::SetTickInterval {
+                tag: TickTag::TailedFile(buffer_handle),
+                interval: None,
+            });
+        }
+    }
+
+    pub fn on_tick(&mut self, word_database: &mut WordDatabase, events: &mut EditorEventQueue) {
+        let mut bytes = Vec::new();
+        for file in &mut self.tailed_files {
+            let len = match fs::metadata(&file.path) {
+                Ok(metadata) => metadata.len(),
+                Err(_) => continue,
+            };
+            if len < file.len {
+                // truncated, so keep following from its new end
+                file.len = len;
+            }
+            if len == file.len {
+                continue;
+            }
+
+            bytes.clear();
+            let read = File::open(&file.path).and_then(|mut f| {
+                use io::{Read, Seek};
+                f.seek(io::SeekFrom::Start(file.len))?;
+                f.take(len - file.len).read_to_end(&mut bytes)
+            });
+            if read.is_err() {
+                continue;
+            }
+
+            // a partially written line is read again on the next tick
+            let bytes = match bytes.iter().rposition(|&b| b == b'\n') {
+                Some(i) => &bytes[..i + 1],
+                None => continue,
+            };
+            file.len += bytes.len() as u64;
+
+            let buffer = &mut self.buffers[file.buffer_handle.0 as usize];
+            // chunks end at a line break so no utf8 sequence is split between ticks
+            let text = String::from_utf8_lossy(bytes);
+            let end = buffer.content.end();
+            buffer.insert_text_unchecked(word_database, end, &text, events);
+        }
+    }
+
+    pub fn spawn_pipe_process(
+        &mut self,
+        platform: &mut Platform,
+        mut command: Command,
+        buffer_handle: BufferHandle,
+        save: Option<PendingSave>,
+    ) {
+        let mut index = None;
+        for (i, process) in self.pipe_processes.iter().enumerate() {
+            if !process.alive {
+                index = Some(i);
+                break;
+            }
+        }
+        let index = match index {
+            Some(index) => index,
+            None => {
+                let index = self.pipe_processes.len();
+                self.pipe_processes.push(PipeProcess {
+                    alive: false,
+                    handle: None,
+                    buffer_handle,
+                    input: None,
+                    output: Vec::new(),
+                    error: Vec::new(),
+                    save: None,
+                });
+                index
+            }
+        };
+
+        let mut buf = platform.buf_pool.acquire();
+        let writer = buf.write();
+        let _ = self.buffers[buffer_handle.0 as usize].content.write(writer);
+        let buf = buf.share();
+        platform.buf_pool.release(buf.clone());
+
+        let process = &mut self.pipe_processes[index];
+        process.alive = true;
+        process.handle = None;
+        process.buffer_handle = buffer_handle;
+        process.input = Some(buf);
+        process.output.clear();
+        process.error.clear();
+        process.save = save;
+
+        command.stdin(Stdio::piped());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+
+        platform.enqueue_request(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::PipeBuffer(index),
+            command,
+            buf_len: 4 * 1024,
+        });
+    }
+
+    pub fn run_pipe_process_blocking(
+        &mut self,
+        word_database: &mut WordDatabase,
+        mut command: Command,
+        buffer_handle: BufferHandle,
+        events: &mut EditorEventQueue,
+    ) -> Result<(), String> {
+        let mut input = Vec::new();
+        let buffer = &mut self.buffers[buffer_handle.0 as usize];
+        let _ = buffer.content.write(&mut input);
+
+        command.stdin(Stdio::piped());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+
+        let mut child = command.spawn().map_err(|error| error.to_string())?;
+        let writer = child.stdin.take().map(|mut stdin| {
+            std::thread::spawn(move || {
+                use io::Write;
+                let _ = stdin.write_all(&input);
+            })
+        });
+        let output = child
+            .wait_with_output()
+            .map_err(|error| error.to_string())?;
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(error.trim().into());
+        }
+        let mut output = String::from_utf8(output.stdout).map_err(|error| error.to_string())?;
+        // the input had a new line after every line, just like when saving to a file
+        if output.ends_with('\n') {
+            output.pop();
+        }
+
+        buffer.replace_all_text(word_database, &output, events);
+        Ok(())
+    }
+
+    pub fn on_pipe_process_spawned(
+        &mut self,
+        platform: &mut Platform,
+        index: usize,
+        handle: ProcessHandle,
+    ) {
+        let process = &mut self.pipe_processes[index];
+        process.handle = Some(handle);
+        if let Some(buf) = &process.input {
+            platform.enqueue_request(PlatformRequest::WriteToProcess {
+                handle,
+                buf: buf.clone(),
+            });
+            platform.enqueue_request(PlatformRequest::CloseProcessInput { handle });
+        }
+    }
+
+    pub fn stop_pipe_processes(&mut self, platform: &mut Platform, buffer_handle: BufferHandle) {
+        for process in &mut self.pipe_processes {
+            if process.alive && process.buffer_handle == buffer_handle {
+                if let Some(handle) = process.handle.take() {
+                    platform.enqueue_request(PlatformRequest::KillProcess { handle });
+                }
+            }
+        }
+    }
+
+    pub fn on_pipe_process_output(&mut self, index: usize, bytes: &[u8]) {
+        let process = &mut self.pipe_processes[index];
+        if process.handle.is_some() {
+            process.output.extend_from_slice(bytes);
+        }
+    }
+
+    pub fn on_pipe_process_error_output(&mut self, index: usize, bytes: &[u8]) {
+        let process = &mut self.pipe_processes[index];
+        if process.handle.is_some() {
+            process.error.extend_from_slice(bytes);
+        }
+    }
+
+    pub fn on_pipe_process_exit(
+        &mut self,
+        index: usize,
+        success: bool,
+    ) -> Option<FinishedPipeProcess> {
+        let process = &mut self.pipe_processes[index];
+        process.alive = false;
+        let input = process.input.take();
+        let save = process.save.take();
+        let output = std::mem::take(&mut process.output);
+        let error = String::from_utf8_lossy(&process.error).trim().to_string();
+        process.handle.take()?;
+
+        let buffer_handle = process.buffer_handle;
+        let buffer = self.try_get(buffer_handle)?;
+        // written content has a line break after every line, including the last one
+        let unchanged = match input {
+            Some(input) if input.as_bytes().len() == buffer.content.byte_len() + 1 => {
+                let mut content = Vec::with_capacity(input.as_bytes().len());
+                let _ = buffer.content.write(&mut content);
+                input.as_bytes() == content
+            }
+            _ => false,
+        };
+
+        let output = match String::from_utf8(output) {
+            Ok(mut output) if success && unchanged => {
+                // the input had a new line after every line, just like when saving to a file
+                if output.ends_with('\n') {
+                    output.pop();
+                }
+                Some(output)
+            }
+            _ => None,
+        };
+        Some(FinishedPipeProcess {
+            buffer_handle,
+            output,
+            error,
+            save,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::buffer_position::BufferPosition;
+    use crate::{
+        buffer_position::BufferPosition,
+        syntax::{Syntax, TokenKind},
+    };
+
+    #[test]
+    fn sentence_motions() {
+        let mut content = BufferContent::new();
+        content.insert_text(
+            BufferPosition::zero(),
+            "One e.g. two. Three!\nFour? x.y\n\nFive",
+        );
+        let abbreviations = "e.g i.e";
+
+        let after = |line, column| {
+            content.find_sentence_start_after(BufferPosition::line_col(line, column), abbreviations)
+        };
+        assert_eq!(BufferPosition::line_col(0, 14), after(0, 0));
+        assert_eq!(BufferPosition::line_col(1, 0), after(0, 14));
+        assert_eq!(BufferPosition::line_col(1, 6), after(1, 0));
+        assert_eq!(BufferPosition::line_col(3, 0), after(1, 6));
+        assert_eq!(BufferPosition::line_col(3, 4), after(3, 0));
+
+        let before = |line, column| {
+            content
+                .find_sentence_start_before(BufferPosition::line_col(line, column), abbreviations)
+        };
+        assert_eq!(BufferPosition::line_col(1, 6), before(3, 0));
+        assert_eq!(BufferPosition::line_col(1, 0), before(1, 6));
+        assert_eq!(BufferPosition::line_col(0, 14), before(1, 0));
+        assert_eq!(BufferPosition::line_col(0, 14), before(0, 16));
+        assert_eq!(BufferPosition::line_col(0, 0), before(0, 14));
+        assert_eq!(BufferPosition::line_col(0, 0), before(0, 0));
+    }
 
     #[test]
     fn test_find_delimiter_pair_at() {
@@ -1491,6 +2558,163 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_path_with_position() {
+        assert_eq!(("foo.rs", None), parse_path_and_position("foo.rs"));
+        assert_eq!(
+            ("foo.rs", Some(BufferPosition::line_col(9, 0))),
+            parse_path_and_position("foo.rs:10")
+        );
+        assert_eq!(
+            ("foo.rs", Some(BufferPosition::line_col(9, 4))),
+            parse_path_and_position("foo.rs:10,5")
+        );
+        assert_eq!(
+            ("foo.rs", Some(BufferPosition::line_col(9, 4))),
+            parse_path_and_position("foo.rs:10:5")
+        );
+        assert_eq!(
+            ("c:/foo.rs", Some(BufferPosition::line_col(9, 4))),
+            parse_path_and_position("c:/foo.rs:10:5")
+        );
+        assert_eq!(("foo.rs:x", None), parse_path_and_position("foo.rs:x"));
+
+        let root =
+            std::env::temp_dir().join(format!("pepper_parse_path_test_{}", std::process::id()));
+        let _ = std::fs::create_dir_all(&root);
+        let name = "existing.rs";
+        let _ = std::fs::write(root.join(name), b"");
+        assert_eq!((name, None), parse_missing_path_and_position(&root, name));
+        assert_eq!(
+            ("existing.rs", Some(BufferPosition::line_col(9, 0))),
+            parse_missing_path_and_position(&root, "existing.rs:10")
+        );
+        assert_eq!(
+            ("missing.rs", Some(BufferPosition::line_col(9, 4))),
+            parse_missing_path_and_position(&root, " missing.rs:10:5 ")
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    // ':' is not valid in windows file names
+    #[cfg(unix)]
+    #[test]
+    fn parse_path_with_colon_in_file_name() {
+        let root = std::env::temp_dir().join(format!(
+            "pepper_parse_colon_path_test_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::create_dir_all(&root);
+        let name = "existing.rs:10";
+        let _ = std::fs::write(root.join(name), b"");
+        assert_eq!((name, None), parse_missing_path_and_position(&root, name));
+        assert_eq!(
+            ("missing.rs", Some(BufferPosition::line_col(19, 0))),
+            parse_missing_path_and_position(&root, "missing.rs:20")
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn change_signs_baseline_survives_being_disabled() {
+        let path = std::env::temp_dir().join(format!(
+            "pepper_change_signs_test_{}.txt",
+            std::process::id()
+        ));
+        std::fs::write(&path, "a\nb\n").unwrap();
+
+        let mut word_database = WordDatabase::new();
+        let mut events = EditorEventQueue::default();
+        let mut buffers = BufferCollection::default();
+        let buffer = buffers.add_new();
+        buffer.capabilities = BufferCapabilities::text();
+        buffer.path = path.clone();
+        buffer
+            .discard_and_reload_from_file(&mut word_database, &mut events)
+            .unwrap();
+        buffer.capture_outdated_baseline();
+
+        buffer.discard_baseline();
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::line_col(1, 0),
+            "x",
+            &mut events,
+        );
+        buffer.capture_outdated_baseline();
+        buffer.update_change_signs();
+        assert_eq!(
+            &[ChangeSign::None, ChangeSign::Modified],
+            buffer.change_signs()
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn tail_file_inserts_invalid_utf8_lossily() {
+        use std::io::Write;
+
+        let path =
+            std::env::temp_dir().join(format!("pepper_tail_file_test_{}.log", std::process::id()));
+        let _ = std::fs::write(&path, b"");
+
+        let (request_sender, _) = std::sync::mpsc::channel::<PlatformRequest>();
+        let mut platform = Platform::new(|| (), request_sender);
+        let mut word_database = WordDatabase::new();
+        let mut events = EditorEventQueue::default();
+        let mut buffers = BufferCollection::default();
+        let handle = buffers.add_new().handle();
+        buffers
+            .tail_file(&mut platform, handle, path.clone())
+            .unwrap();
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"a\xffb\nc").unwrap();
+        buffers.on_tick(&mut word_database, &mut events);
+
+        let mut content = String::new();
+        for line in buffers.get(handle).content().lines() {
+            content.push_str(line.as_str());
+            content.push('\n');
+        }
+        assert_eq!("a\u{fffd}b\n\n", content);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn case_conversion() {
+        fn convert(text: &str, conversion: CaseConversion) -> String {
+            let mut output = String::new();
+            convert_case(text, conversion, &mut output);
+            output
+        }
+
+        let text = "aBc_Dé straße Σ";
+        assert_eq!("ABC_DÉ STRASSE Σ", convert(text, CaseConversion::Upper));
+        assert_eq!("abc_dé straße σ", convert(text, CaseConversion::Lower));
+        assert_eq!("AbC_dÉ STRASSE σ", convert(text, CaseConversion::Toggle));
+        assert_eq!("Abc_dé Straße Σ", convert(text, CaseConversion::Title));
+        assert_eq!(
+            "The Html-To-Pdf Tool",
+            convert("the HTML-to-pdf tool", CaseConversion::Title)
+        );
+    }
+
+    #[test]
+    fn soft_wrapped_rows() {
+        let tab_size = NonZeroU8::new(4).unwrap();
+        assert_eq!(1, soft_wrapped_row_count("", tab_size, 4));
+        assert_eq!(1, soft_wrapped_row_count("abc", tab_size, 4));
+        assert_eq!(2, soft_wrapped_row_count("abcd", tab_size, 4));
+        assert_eq!(3, soft_wrapped_row_count("abcdefghi", tab_size, 4));
+        assert_eq!(3, soft_wrapped_row_count("a\t", tab_size, 4));
+        assert_eq!(2, soft_wrapped_row_count("\t", tab_size, 2));
+    }
+
     #[test]
     fn display_distance() {
         fn display_len(text: &str) -> usize {
@@ -1520,6 +2744,365 @@ mod tests {
         buffer
     }
 
+    #[test]
+    fn searcher_reuses_and_narrows_search() {
+        let mut word_database = WordDatabase::new();
+        let mut events = EditorEventQueue::default();
+        let mut buffer = Buffer::new(BufferHandle(0));
+        buffer.capabilities = BufferCapabilities::text();
+        let line = "lorem ipsum abc abd Abc dolor sit amet ab\n";
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::zero(),
+            &line.repeat(20_000),
+            &mut events,
+        );
+
+        fn full_search(buffer: &Buffer, text: &str, options: SearchOptions) -> Vec<BufferRange> {
+            let mut pattern = Pattern::new();
+            options.compile_searcher(&mut pattern, text).unwrap();
+            let mut ranges = Vec::new();
+            buffer
+                .content()
+                .find_search_ranges(&pattern, options, &mut ranges);
+            ranges
+        }
+
+        let mut searcher = Searcher::default();
+        let options = SearchOptions::default();
+        let mut assert_search = |buffer: &mut Buffer, text, expected_update| {
+            let update = searcher.search(buffer, text, options).unwrap();
+            assert_eq!(expected_update, update, "searching '{}'", text);
+            assert_eq!(full_search(buffer, text, options), buffer.search_ranges());
+        };
+
+        assert_search(&mut buffer, "a", SearchUpdate::Searched);
+        assert_eq!(5 * 20_000, buffer.search_ranges().len());
+        assert_search(&mut buffer, "ab", SearchUpdate::Narrowed);
+        assert_eq!(4 * 20_000, buffer.search_ranges().len());
+        assert_search(&mut buffer, "ab", SearchUpdate::Reused);
+        assert_search(&mut buffer, "abc", SearchUpdate::Narrowed);
+        assert_eq!(2 * 20_000, buffer.search_ranges().len());
+        assert_search(&mut buffer, "Abc", SearchUpdate::Searched);
+        assert_eq!(20_000, buffer.search_ranges().len());
+        assert_search(&mut buffer, "ab", SearchUpdate::Searched);
+        assert_search(&mut buffer, "abC", SearchUpdate::Narrowed);
+        assert_eq!(0, buffer.search_ranges().len());
+        assert_search(&mut buffer, "p/ab", SearchUpdate::Searched);
+        assert_search(&mut buffer, "p/abc", SearchUpdate::Searched);
+        assert_search(&mut buffer, "l/ab", SearchUpdate::Searched);
+        assert_search(&mut buffer, "l/abd", SearchUpdate::Narrowed);
+        assert_eq!(20_000, buffer.search_ranges().len());
+
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::zero(),
+            "abd ",
+            &mut events,
+        );
+        assert_search(&mut buffer, "l/abd", SearchUpdate::Searched);
+        assert_eq!(20_001, buffer.search_ranges().len());
+
+        let whole_word = SearchOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            SearchUpdate::Searched,
+            searcher.search(&mut buffer, "l/abd", whole_word).unwrap()
+        );
+        assert_eq!(
+            SearchUpdate::Searched,
+            searcher.search(&mut buffer, "l/abdx", whole_word).unwrap()
+        );
+
+        let mut buffer = Buffer::new(BufferHandle(1));
+        buffer.capabilities = BufferCapabilities::text();
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::zero(),
+            "aaaa\naaab\nababa\n",
+            &mut events,
+        );
+        let mut searcher = Searcher::default();
+        let mut assert_search = |buffer: &mut Buffer, text, expected_update| {
+            let update = searcher.search(buffer, text, options).unwrap();
+            assert_eq!(expected_update, update, "searching '{}'", text);
+            assert_eq!(full_search(buffer, text, options), buffer.search_ranges());
+        };
+        assert_search(&mut buffer, "a", SearchUpdate::Searched);
+        assert_search(&mut buffer, "aa", SearchUpdate::Narrowed);
+        assert_eq!(3, buffer.search_ranges().len());
+        assert_search(&mut buffer, "aab", SearchUpdate::Searched);
+        assert_eq!(1, buffer.search_ranges().len());
+        assert_search(&mut buffer, "ab", SearchUpdate::Searched);
+        assert_search(&mut buffer, "aba", SearchUpdate::Narrowed);
+        assert_eq!(1, buffer.search_ranges().len());
+        assert_search(&mut buffer, "abab", SearchUpdate::Searched);
+    }
+
+    #[test]
+    fn buffer_content_find_search_ranges() {
+        fn find(buffer: &BufferContent, pattern: &str, options: SearchOptions) -> Vec<BufferRange> {
+            let mut compiled = Pattern::new();
+            options.compile_searcher(&mut compiled, pattern).unwrap();
+            let mut ranges = Vec::new();
+            buffer.find_search_ranges(&compiled, options, &mut ranges);
+            ranges
+        }
+        fn range(
+            line: BufferPositionIndex,
+            from: BufferPositionIndex,
+            to: BufferPositionIndex,
+        ) -> BufferRange {
+            BufferRange::between(
+                BufferPosition::line_col(line, from),
+                BufferPosition::line_col(line, to),
+            )
+        }
+
+        let buffer = buffer_from_str("Word word\nsword words_ word");
+        let default = SearchOptions::default();
+        let ignore_case = SearchOptions {
+            ignore_case: true,
+            whole_word: false,
+        };
+        let whole_word = SearchOptions {
+            ignore_case: false,
+            whole_word: true,
+        };
+        let both = SearchOptions {
+            ignore_case: true,
+            whole_word: true,
+        };
+
+        assert_eq!(vec![range(0, 0, 4)], find(&buffer, "Word", default));
+        assert_eq!(
+            vec![
+                range(0, 0, 4),
+                range(0, 5, 9),
+                range(1, 1, 5),
+                range(1, 6, 10),
+                range(1, 13, 17)
+            ],
+            find(&buffer, "Word", ignore_case)
+        );
+        assert_eq!(vec![range(0, 0, 4)], find(&buffer, "Word", whole_word));
+        assert_eq!(
+            vec![range(0, 0, 4), range(0, 5, 9), range(1, 13, 17)],
+            find(&buffer, "WORD", both)
+        );
+    }
+
+    #[test]
+    fn buffer_replace_all_text() {
+        let mut word_database = WordDatabase::new();
+        let mut events = EditorEventQueue::default();
+        let mut buffer = Buffer::new(BufferHandle(0));
+        buffer.capabilities = BufferCapabilities::text();
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::zero(),
+            "fn  main(){\n  ã\n}",
+            &mut events,
+        );
+        buffer.commit_edits();
+
+        assert!(!buffer.replace_all_text(&mut word_database, "fn  main(){\n  ã\n}", &mut events));
+
+        let text = "fn main() {\n    õ\n}";
+        assert!(buffer.replace_all_text(&mut word_database, text, &mut events));
+        assert_eq!(text, buffer.content().to_string());
+
+        buffer.undo(&mut word_database, &mut events).for_each(drop);
+        assert_eq!("fn  main(){\n  ã\n}", buffer.content().to_string());
+    }
+
+    #[test]
+    fn buffer_word_database_skip_comments() {
+        fn contains(word_database: &WordDatabase, word: &str) -> bool {
+            word_database.word_indices().any(|(_, w)| w == word)
+        }
+
+        let mut syntax = Syntax::new();
+        syntax.set_glob("*.rs").unwrap();
+        syntax
+            .set_rule(TokenKind::Comment, "//{.}|/*{!(*/).$}")
+            .unwrap();
+        let mut syntaxes = SyntaxCollection::new();
+        syntaxes.add(syntax);
+
+        let mut word_database = WordDatabase::new();
+        let mut events = EditorEventQueue::default();
+        let mut buffer = Buffer::new(BufferHandle(0));
+        buffer.capabilities = BufferCapabilities::text();
+        buffer.path.push("file.rs");
+        let mut config = Config {
+            word_database_skip_comments: true,
+            ..Config::default()
+        };
+        buffer.refresh_syntax(&syntaxes, &mut word_database, &config);
+
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::zero(),
+            "code // unique\nmore",
+            &mut events,
+        );
+        assert!(!contains(&word_database, "code"));
+        buffer.update_highlighting(&syntaxes, &mut word_database);
+        assert!(contains(&word_database, "code"));
+        assert!(contains(&word_database, "more"));
+        assert!(!contains(&word_database, "unique"));
+
+        config.word_database_skip_comments = false;
+        buffer.refresh_syntax(&syntaxes, &mut word_database, &config);
+        assert!(contains(&word_database, "unique"));
+
+        config.word_database_skip_comments = true;
+        buffer.refresh_syntax(&syntaxes, &mut word_database, &config);
+        assert!(!contains(&word_database, "unique"));
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::line_col(1, 0),
+            "/* block\ninside */ ",
+            &mut events,
+        );
+        buffer.update_highlighting(&syntaxes, &mut word_database);
+        assert!(!contains(&word_database, "block"));
+        assert!(!contains(&word_database, "inside"));
+        assert!(contains(&word_database, "more"));
+
+        buffer.delete_range(
+            &mut word_database,
+            BufferRange::between(
+                BufferPosition::line_col(1, 0),
+                BufferPosition::line_col(1, 2),
+            ),
+            &mut events,
+        );
+        buffer.update_highlighting(&syntaxes, &mut word_database);
+        assert!(contains(&word_database, "block"));
+        assert!(contains(&word_database, "inside"));
+
+        buffer.dispose(&mut word_database);
+        assert_eq!(0, word_database.word_indices().count());
+    }
+
+    #[test]
+    fn buffer_syntax_override() {
+        let mut rust_syntax = Syntax::new();
+        rust_syntax.set_glob("**/*.rs").unwrap();
+        let mut lua_syntax = Syntax::new();
+        lua_syntax.set_name("lua");
+        lua_syntax.set_glob("**/*.lua").unwrap();
+        let mut syntaxes = SyntaxCollection::new();
+        syntaxes.add(rust_syntax);
+        syntaxes.add(lua_syntax);
+
+        let rust_handle = syntaxes.find_handle_by_name("**/*.rs").unwrap();
+        let lua_handle = syntaxes.find_handle_by_name("lua").unwrap();
+        assert!(syntaxes.find_handle_by_name("").is_none());
+        assert!(syntaxes.find_handle_by_name("python").is_none());
+
+        let mut word_database = WordDatabase::new();
+        let mut buffer = Buffer::new(BufferHandle(0));
+        buffer.path.push("file.rs");
+        let config = Config::default();
+        buffer.refresh_syntax(&syntaxes, &mut word_database, &config);
+        assert!(buffer.syntax_handle() == rust_handle);
+
+        buffer.set_syntax_override(Some(lua_handle));
+        buffer.refresh_syntax(&syntaxes, &mut word_database, &config);
+        assert!(buffer.syntax_handle() == lua_handle);
+
+        buffer.set_syntax_override(None);
+        buffer.refresh_syntax(&syntaxes, &mut word_database, &config);
+        assert!(buffer.syntax_handle() == rust_handle);
+
+        let mut buffer = Buffer::new(BufferHandle(1));
+        buffer.set_syntax_override(Some(lua_handle));
+        buffer.refresh_syntax(&syntaxes, &mut word_database, &config);
+        assert!(buffer.syntax_handle() == lua_handle);
+        buffer.set_syntax_override(None);
+        buffer.refresh_syntax(&syntaxes, &mut word_database, &config);
+        assert!(buffer.syntax_handle() == SyntaxHandle::default());
+
+        assert_eq!(vec!["lua"], syntaxes.names().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn buffer_syntax_from_shebang() {
+        let mut python_syntax = Syntax::new();
+        python_syntax.set_name("python");
+        python_syntax.set_glob("**/*.py").unwrap();
+        let mut lua_syntax = Syntax::new();
+        lua_syntax.set_name("lua");
+        lua_syntax.set_glob("**/*.lua").unwrap();
+        let mut syntaxes = SyntaxCollection::new();
+        syntaxes.add(python_syntax);
+        syntaxes.add(lua_syntax);
+        let python_handle = syntaxes.find_handle_by_name("python").unwrap();
+        let lua_handle = syntaxes.find_handle_by_name("lua").unwrap();
+
+        let mut word_database = WordDatabase::new();
+        let mut events = EditorEventQueue::default();
+        let mut buffer = Buffer::new(BufferHandle(0));
+        buffer.path.push("script");
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::zero(),
+            "#!/usr/bin/env python\nprint(1)",
+            &mut events,
+        );
+
+        let mut config = Config {
+            syntax_detect_shebang: false,
+            ..Config::default()
+        };
+        buffer.refresh_syntax(&syntaxes, &mut word_database, &config);
+        assert!(buffer.syntax_handle() == SyntaxHandle::default());
+        config.syntax_detect_shebang = true;
+        buffer.refresh_syntax(&syntaxes, &mut word_database, &config);
+        assert!(buffer.syntax_handle() == python_handle);
+
+        buffer.set_syntax_override(Some(lua_handle));
+        buffer.refresh_syntax(&syntaxes, &mut word_database, &config);
+        assert!(buffer.syntax_handle() == lua_handle);
+    }
+
+    #[test]
+    fn buffer_content_find_word_ranges() {
+        let content = buffer_from_str("foo bar_foo foo\nfoo.foo\n\nfoo");
+        let mut ranges = Vec::new();
+        content.find_word_ranges("foo", 0..3, &mut ranges);
+        assert_eq!(
+            vec![
+                BufferRange::between(
+                    BufferPosition::line_col(0, 0),
+                    BufferPosition::line_col(0, 3)
+                ),
+                BufferRange::between(
+                    BufferPosition::line_col(0, 12),
+                    BufferPosition::line_col(0, 15)
+                ),
+                BufferRange::between(
+                    BufferPosition::line_col(1, 0),
+                    BufferPosition::line_col(1, 3)
+                ),
+                BufferRange::between(
+                    BufferPosition::line_col(1, 4),
+                    BufferPosition::line_col(1, 7)
+                ),
+            ],
+            ranges
+        );
+
+        ranges.clear();
+        content.find_word_ranges("foo", 3..10, &mut ranges);
+        assert_eq!(1, ranges.len());
+    }
+
     #[test]
     fn buffer_utf8_support() {
         let mut buffer = buffer_from_str("abd");
@@ -1658,6 +3241,85 @@ mod tests {
         assert_eq!("first line\n\nthird line", buffer.to_string());
     }
 
+    #[test]
+    fn buffer_content_byte_len() {
+        fn assert_byte_len(buffer: &BufferContent) {
+            assert_eq!(buffer.to_string().len(), buffer.byte_len());
+        }
+
+        let mut buffer = BufferContent::new();
+        assert_eq!(0, buffer.byte_len());
+
+        buffer.insert_text(BufferPosition::zero(), "first line\nsecond line\n");
+        assert_byte_len(&buffer);
+        buffer.insert_text(BufferPosition::line_col(1, 6), " ünïcode");
+        assert_byte_len(&buffer);
+        buffer.insert_text(BufferPosition::line_col(0, 5), "\r\nmiddle\nlines");
+        assert_byte_len(&buffer);
+        buffer.insert_text(buffer.end(), "last");
+        assert_byte_len(&buffer);
+
+        buffer.delete_range(BufferRange::between(
+            BufferPosition::line_col(0, 2),
+            BufferPosition::line_col(0, 4),
+        ));
+        assert_byte_len(&buffer);
+        buffer.delete_range(BufferRange::between(
+            BufferPosition::line_col(0, 1),
+            BufferPosition::line_col(2, 3),
+        ));
+        assert_byte_len(&buffer);
+        buffer.delete_range(BufferRange::between(
+            BufferPosition::line_col(0, 0),
+            BufferPosition::line_col(1, 0),
+        ));
+        assert_byte_len(&buffer);
+        buffer.delete_range(BufferRange::between(BufferPosition::zero(), buffer.end()));
+        assert_eq!(0, buffer.byte_len());
+
+        buffer.insert_text(BufferPosition::zero(), "some\ntext");
+        buffer.clear();
+        assert_eq!(0, buffer.byte_len());
+
+        let mut reader = io::Cursor::new(b"\xef\xbb\xbfread\r\nfrom\nreader\n");
+        buffer.read(&mut reader).unwrap();
+        assert_byte_len(&buffer);
+    }
+
+    #[test]
+    fn read_only_buffer_rejects_edits() {
+        let mut word_database = WordDatabase::new();
+        let mut events = EditorEventQueue::default();
+
+        let mut buffer = Buffer::new(BufferHandle(0));
+        buffer.capabilities = BufferCapabilities::text();
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::zero(),
+            "abc",
+            &mut events,
+        );
+        assert!(!buffer.take_edit_rejected());
+
+        buffer.capabilities.read_only = true;
+        let range = buffer.insert_text(
+            &mut word_database,
+            BufferPosition::line_col(0, 1),
+            "x",
+            &mut events,
+        );
+        assert_eq!(BufferPosition::line_col(0, 1), range.from);
+        assert_eq!(range.from, range.to);
+        buffer.delete_range(
+            &mut word_database,
+            BufferRange::between(BufferPosition::zero(), BufferPosition::line_col(0, 2)),
+            &mut events,
+        );
+        assert_eq!("abc", buffer.content().line_at(0).as_str());
+        assert!(buffer.take_edit_rejected());
+        assert!(!buffer.take_edit_rejected());
+    }
+
     #[test]
     fn buffer_delete_undo_redo_single_line() {
         let mut word_database = WordDatabase::new();
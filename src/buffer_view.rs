@@ -1,7 +1,10 @@
 use std::num::NonZeroU8;
 
 use crate::{
-    buffer::{Buffer, BufferCollection, BufferHandle, CharDisplayDistances},
+    buffer::{
+        soft_wrapped_chars, soft_wrapped_row_count, Buffer, BufferCollection, BufferHandle,
+        CharDisplayDistances,
+    },
     buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
     client::ClientHandle,
     cursor::{Cursor, CursorCollection},
@@ -10,20 +13,43 @@ use crate::{
     word_database::{WordDatabase, WordIter, WordKind},
 };
 
+const LARGE_MOVEMENT_LINE_COUNT: usize = 4;
+
 pub enum CursorMovement {
     ColumnsForward(usize),
     ColumnsBackward(usize),
     LinesForward(usize),
     LinesBackward(usize),
+    RowsForward { count: usize, wrap_width: usize },
+    RowsBackward { count: usize, wrap_width: usize },
     WordsForward(usize),
     WordsBackward(usize),
+    WordEndsForward(usize),
+    ParagraphsForward(usize),
+    ParagraphsBackward(usize),
     Home,
     HomeNonWhitespace,
+    HomeSmart,
     End,
     FirstLine,
     LastLine,
 }
 
+impl CursorMovement {
+    pub fn is_large(&self) -> bool {
+        matches!(
+            self,
+            Self::ParagraphsForward(_) | Self::ParagraphsBackward(_)
+        )
+    }
+
+    // same check for motions that have no `CursorMovement` variant (like sentence motions)
+    pub fn is_large_distance(count: usize, from: BufferPosition, to: BufferPosition) -> bool {
+        let line_distance = to.line_index.abs_diff(from.line_index);
+        count > 1 || line_distance as usize >= LARGE_MOVEMENT_LINE_COUNT
+    }
+}
+
 #[derive(Clone, Copy)]
 pub enum CursorMovementKind {
     PositionAndAnchor,
@@ -66,7 +92,43 @@ impl BufferView {
             Err(n)
         }
 
-        let buffer = buffers.get(self.buffer_handle).content();
+        fn soft_wrapped_position(
+            line: &str,
+            column_byte_index: usize,
+            tab_size: NonZeroU8,
+            wrap_width: usize,
+        ) -> (usize, usize) {
+            match soft_wrapped_chars(line, tab_size, wrap_width)
+                .find(|c| c.char_index >= column_byte_index)
+            {
+                Some(c) => (c.row, c.x),
+                None => (0, 0),
+            }
+        }
+
+        fn soft_wrapped_column(
+            line: &str,
+            row: usize,
+            x: usize,
+            tab_size: NonZeroU8,
+            wrap_width: usize,
+        ) -> usize {
+            let mut column_byte_index = 0;
+            for c in soft_wrapped_chars(line, tab_size, wrap_width) {
+                if c.row > row {
+                    break;
+                }
+                column_byte_index = c.char_index;
+                if c.row == row && c.x + c.width > x {
+                    break;
+                }
+            }
+            column_byte_index
+        }
+
+        let buffer = buffers.get(self.buffer_handle);
+        let folds = buffer.folds();
+        let buffer = buffer.content();
 
         let mut cursors = self.cursors.mut_guard();
         match movement {
@@ -171,11 +233,10 @@ impl BufferView {
                 for i in 0..cursors[..].len() {
                     let saved_display_distance = cursors.get_saved_display_distance(i);
                     let c = &mut cursors[i];
-                    c.position.line_index = buffer
-                        .line_count()
-                        .saturating_sub(1)
-                        .min(c.position.line_index as usize + n)
-                        as _;
+                    let last_line_index = folds.to_visible_line(buffer.line_count() as u32 - 1);
+                    let line_index = folds.to_visible_line(c.position.line_index);
+                    let line_index = (last_line_index as usize).min(line_index as usize + n);
+                    c.position.line_index = folds.from_visible_line(line_index as _);
                     if let Some(distance) = saved_display_distance {
                         let line = buffer.line_at(c.position.line_index as _).as_str();
                         c.position.column_byte_index = CharDisplayDistances::new(line, tab_size)
@@ -192,7 +253,9 @@ impl BufferView {
                 for i in 0..cursors[..].len() {
                     let saved_display_distance = cursors.get_saved_display_distance(i);
                     let c = &mut cursors[i];
-                    c.position.line_index = c.position.line_index.saturating_sub(n as _);
+                    let line_index = folds.to_visible_line(c.position.line_index);
+                    c.position.line_index =
+                        folds.from_visible_line(line_index.saturating_sub(n as _));
                     if let Some(distance) = saved_display_distance {
                         let line = buffer.line_at(c.position.line_index as _).as_str();
                         c.position.column_byte_index = CharDisplayDistances::new(line, tab_size)
@@ -204,6 +267,66 @@ impl BufferView {
                     c.position = buffer.saturate_position(c.position);
                 }
             }
+            CursorMovement::RowsForward { count, wrap_width } => {
+                let last_line_index = folds.to_visible_line(buffer.line_count() as u32 - 1);
+                for c in &mut cursors[..] {
+                    let mut line_index = c.position.line_index;
+                    let mut line = buffer.line_at(line_index as _).as_str();
+                    let (mut row, x) = soft_wrapped_position(
+                        line,
+                        c.position.column_byte_index as _,
+                        tab_size,
+                        wrap_width,
+                    );
+                    row += count;
+                    loop {
+                        let row_count = soft_wrapped_row_count(line, tab_size, wrap_width);
+                        if row < row_count {
+                            break;
+                        }
+                        let visible_line_index = folds.to_visible_line(line_index);
+                        if visible_line_index >= last_line_index {
+                            row = row_count - 1;
+                            break;
+                        }
+                        row -= row_count;
+                        line_index = folds.from_visible_line(visible_line_index + 1);
+                        line = buffer.line_at(line_index as _).as_str();
+                    }
+                    c.position.line_index = line_index;
+                    c.position.column_byte_index =
+                        soft_wrapped_column(line, row, x, tab_size, wrap_width) as _;
+                }
+            }
+            CursorMovement::RowsBackward { count, wrap_width } => {
+                for c in &mut cursors[..] {
+                    let mut line_index = c.position.line_index;
+                    let mut line = buffer.line_at(line_index as _).as_str();
+                    let (row, x) = soft_wrapped_position(
+                        line,
+                        c.position.column_byte_index as _,
+                        tab_size,
+                        wrap_width,
+                    );
+                    let mut count = count;
+                    let mut row = row;
+                    while count > row {
+                        let visible_line_index = folds.to_visible_line(line_index);
+                        if visible_line_index == 0 {
+                            row = 0;
+                            break;
+                        }
+                        count -= row + 1;
+                        line_index = folds.from_visible_line(visible_line_index - 1);
+                        line = buffer.line_at(line_index as _).as_str();
+                        row = soft_wrapped_row_count(line, tab_size, wrap_width) - 1;
+                    }
+                    row -= count.min(row);
+                    c.position.line_index = line_index;
+                    c.position.column_byte_index =
+                        soft_wrapped_column(line, row, x, tab_size, wrap_width) as _;
+                }
+            }
             CursorMovement::WordsForward(n) => {
                 let last_line_index = buffer.line_count() - 1;
                 for c in &mut cursors[..] {
@@ -244,6 +367,30 @@ impl BufferView {
                     }
                 }
             }
+            CursorMovement::WordEndsForward(n) => {
+                let last_line_index = buffer.line_count() - 1;
+                for c in &mut cursors[..] {
+                    let mut n = n;
+                    while n > 0 {
+                        let line = buffer.line_at(c.position.line_index as _).as_str();
+                        let mut column_byte_index = c.position.column_byte_index as usize;
+                        let word = WordIter(&line[column_byte_index..])
+                            .inspect(|w| column_byte_index += w.text.len())
+                            .find(|w| w.kind != WordKind::Whitespace);
+
+                        if word.is_some() {
+                            c.position.column_byte_index = column_byte_index as _;
+                            n -= 1;
+                        } else if c.position.line_index == last_line_index as _ {
+                            c.position.column_byte_index = line.len() as _;
+                            break;
+                        } else {
+                            c.position.line_index += 1;
+                            c.position.column_byte_index = 0;
+                        }
+                    }
+                }
+            }
             CursorMovement::WordsBackward(n) => {
                 for c in &mut cursors[..] {
                     let mut n = n;
@@ -299,6 +446,21 @@ impl BufferView {
                     }
                 }
             }
+            CursorMovement::HomeSmart => {
+                for c in &mut cursors[..] {
+                    let first_word = buffer.line_at(c.position.line_index as _).word_at(0);
+                    let non_whitespace_column = match first_word.kind {
+                        WordKind::Whitespace => first_word.text.len() as _,
+                        _ => 0,
+                    };
+                    c.position.column_byte_index =
+                        if c.position.column_byte_index == non_whitespace_column {
+                            0
+                        } else {
+                            non_whitespace_column
+                        };
+                }
+            }
             CursorMovement::End => {
                 for c in &mut cursors[..] {
                     c.position.column_byte_index =
@@ -317,6 +479,44 @@ impl BufferView {
                     c.position = buffer.saturate_position(c.position);
                 }
             }
+            CursorMovement::ParagraphsForward(n) => {
+                let last_line_index = buffer.line_count() - 1;
+                let is_blank = |i: usize| buffer.line_at(i).as_str().trim().is_empty();
+                for c in &mut cursors[..] {
+                    let mut line_index = c.position.line_index as usize;
+                    for _ in 0..n {
+                        while line_index < last_line_index && is_blank(line_index) {
+                            line_index += 1;
+                        }
+                        while line_index < last_line_index && !is_blank(line_index) {
+                            line_index += 1;
+                        }
+                    }
+
+                    c.position.line_index = line_index as _;
+                    c.position.column_byte_index = if is_blank(line_index) {
+                        0
+                    } else {
+                        buffer.line_at(line_index).as_str().len() as _
+                    };
+                }
+            }
+            CursorMovement::ParagraphsBackward(n) => {
+                let is_blank = |i: usize| buffer.line_at(i).as_str().trim().is_empty();
+                for c in &mut cursors[..] {
+                    let mut line_index = c.position.line_index as usize;
+                    for _ in 0..n {
+                        while line_index > 0 && is_blank(line_index) {
+                            line_index -= 1;
+                        }
+                        while line_index > 0 && !is_blank(line_index) {
+                            line_index -= 1;
+                        }
+                    }
+
+                    c.position = BufferPosition::line_col(line_index as _, 0);
+                }
+            }
         }
 
         if let CursorMovementKind::PositionAndAnchor = movement_kind {
@@ -419,9 +619,11 @@ impl BufferView {
         word_database: &mut WordDatabase,
         events: &mut EditorEventQueue,
     ) {
-        let edits = buffers
-            .get_mut(self.buffer_handle)
-            .undo(word_database, events);
+        let buffer = buffers.get_mut(self.buffer_handle);
+        if buffer.reject_if_read_only() {
+            return;
+        }
+        let edits = buffer.undo(word_database, events);
         let mut cursors = self.cursors.mut_guard();
         let mut last_edit_kind = None;
         for edit in edits {
@@ -449,9 +651,11 @@ impl BufferView {
         word_database: &mut WordDatabase,
         events: &mut EditorEventQueue,
     ) {
-        let edits = buffers
-            .get_mut(self.buffer_handle)
-            .redo(word_database, events);
+        let buffer = buffers.get_mut(self.buffer_handle);
+        if buffer.reject_if_read_only() {
+            return;
+        }
+        let edits = buffer.redo(word_database, events);
         let mut cursors = self.cursors.mut_guard();
         let mut last_edit_kind = None;
         for edit in edits {
@@ -531,6 +735,10 @@ impl BufferViewCollection {
         &mut self.buffer_views[handle.0 as usize]
     }
 
+    pub fn try_get(&self, handle: BufferViewHandle) -> Option<&BufferView> {
+        self.buffer_views.get(handle.0 as usize).filter(|v| v.alive)
+    }
+
     pub fn on_buffer_load(&mut self, buffer: &Buffer) {
         let buffer_handle = buffer.handle();
         let buffer = buffer.content();
@@ -715,5 +923,50 @@ mod tests {
         assert_movement(&mut ctx, 1..9, 2..0, CursorMovement::WordsForward(1));
         assert_movement(&mut ctx, 1..2, 1..0, CursorMovement::WordsBackward(1));
         assert_movement(&mut ctx, 2..0, 1..9, CursorMovement::WordsBackward(1));
+
+        assert_movement(&mut ctx, 0..0, 0..3, CursorMovement::WordEndsForward(1));
+        assert_movement(&mut ctx, 0..3, 1..5, CursorMovement::WordEndsForward(1));
+        assert_movement(&mut ctx, 1..3, 1..5, CursorMovement::WordEndsForward(1));
+        assert_movement(&mut ctx, 1..0, 1..9, CursorMovement::WordEndsForward(2));
+        assert_movement(&mut ctx, 1..5, 2..3, CursorMovement::WordEndsForward(2));
+        assert_movement(&mut ctx, 2..0, 2..3, CursorMovement::WordEndsForward(999));
+
+        assert_movement(&mut ctx, 1..6, 1..2, CursorMovement::HomeSmart);
+        assert_movement(&mut ctx, 1..0, 1..2, CursorMovement::HomeSmart);
+        assert_movement(&mut ctx, 1..2, 1..0, CursorMovement::HomeSmart);
+        assert_movement(&mut ctx, 0..2, 0..0, CursorMovement::HomeSmart);
+        assert_movement(&mut ctx, 0..0, 0..0, CursorMovement::HomeSmart);
+
+        let mut ctx = TestContext::with_buffer("a\nb\n\n\nc\n\nd");
+        assert_movement(&mut ctx, 0..1, 2..0, CursorMovement::ParagraphsForward(1));
+        assert_movement(&mut ctx, 2..0, 5..0, CursorMovement::ParagraphsForward(1));
+        assert_movement(&mut ctx, 0..0, 5..0, CursorMovement::ParagraphsForward(2));
+        assert_movement(&mut ctx, 5..0, 6..1, CursorMovement::ParagraphsForward(1));
+        assert_movement(&mut ctx, 6..1, 5..0, CursorMovement::ParagraphsBackward(1));
+        assert_movement(&mut ctx, 5..0, 3..0, CursorMovement::ParagraphsBackward(1));
+        assert_movement(&mut ctx, 4..0, 0..0, CursorMovement::ParagraphsBackward(2));
+
+        let mut ctx = TestContext::with_buffer("abcdefgh\nij");
+        let forward = |count| CursorMovement::RowsForward {
+            count,
+            wrap_width: 3,
+        };
+        let backward = |count| CursorMovement::RowsBackward {
+            count,
+            wrap_width: 3,
+        };
+        assert_movement(&mut ctx, 0..1, 0..4, forward(1));
+        assert_movement(&mut ctx, 0..1, 0..7, forward(2));
+        assert_movement(&mut ctx, 0..1, 1..1, forward(3));
+        assert_movement(&mut ctx, 0..1, 1..1, forward(999));
+        assert_movement(&mut ctx, 0..8, 1..2, forward(1));
+        assert_movement(&mut ctx, 1..1, 0..7, backward(1));
+        assert_movement(&mut ctx, 1..1, 0..1, backward(3));
+        assert_movement(&mut ctx, 1..1, 0..1, backward(999));
+
+        let mut ctx = TestContext::with_buffer("a.b::c");
+        assert_movement(&mut ctx, 0..0, 0..1, CursorMovement::WordEndsForward(1));
+        assert_movement(&mut ctx, 0..1, 0..2, CursorMovement::WordEndsForward(1));
+        assert_movement(&mut ctx, 0..3, 0..5, CursorMovement::WordEndsForward(1));
     }
 }
@@ -0,0 +1,251 @@
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CalcError {
+    UnexpectedEndOfExpression,
+    UnexpectedChar(char),
+    InvalidNumber,
+    DivisionByZero,
+}
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnexpectedEndOfExpression => write!(f, "unexpected end of expression"),
+            Self::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            Self::InvalidNumber => write!(f, "invalid number"),
+            Self::DivisionByZero => write!(f, "division by zero"),
+        }
+    }
+}
+
+pub fn evaluate(expression: &str) -> Result<f64, CalcError> {
+    let mut parser = Parser {
+        text: expression,
+        chars: expression.char_indices().peekable(),
+    };
+    let value = parser.sum()?;
+    match parser.peek() {
+        Some(c) => Err(CalcError::UnexpectedChar(c)),
+        None => Ok(value),
+    }
+}
+
+pub fn convert_base(text: &str, from: Option<u32>, to: u32, output: &mut String) -> bool {
+    use fmt::Write;
+
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let prefix = match digits.get(..2) {
+        Some("0x" | "0X") => Some(16),
+        Some("0o" | "0O") => Some(8),
+        Some("0b" | "0B") => Some(2),
+        _ => None,
+    };
+    let base = match (from, prefix) {
+        (Some(from), Some(prefix)) if from != prefix => return false,
+        (_, Some(prefix)) => prefix,
+        (Some(from), None) => from,
+        (None, None) => 10,
+    };
+    let digits = if prefix.is_some() {
+        &digits[2..]
+    } else {
+        digits
+    };
+    if digits.is_empty() || digits.starts_with('_') {
+        return false;
+    }
+
+    let mut value: u64 = 0;
+    for c in digits.chars() {
+        if c == '_' {
+            continue;
+        }
+        let digit = match c.to_digit(base) {
+            Some(digit) => digit,
+            None => return false,
+        };
+        value = match value
+            .checked_mul(base as _)
+            .and_then(|v| v.checked_add(digit as _))
+        {
+            Some(value) => value,
+            None => return false,
+        };
+    }
+
+    if negative {
+        output.push('-');
+    }
+    if prefix.is_some() {
+        output.push_str(match to {
+            16 => "0x",
+            8 => "0o",
+            2 => "0b",
+            _ => "",
+        });
+    }
+    let _ = match to {
+        16 => write!(output, "{:x}", value),
+        8 => write!(output, "{:o}", value),
+        2 => write!(output, "{:b}", value),
+        _ => write!(output, "{}", value),
+    };
+    true
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+impl<'a> Parser<'a> {
+    fn peek(&mut self) -> Option<char> {
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !c.is_whitespace() {
+                return Some(c);
+            }
+            self.chars.next();
+        }
+        None
+    }
+
+    fn sum(&mut self) -> Result<f64, CalcError> {
+        let mut value = self.product()?;
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.chars.next();
+                    value += self.product()?;
+                }
+                Some('-') => {
+                    self.chars.next();
+                    value -= self.product()?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<f64, CalcError> {
+        let mut value = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(c @ ('*' | '/' | '%')) => c,
+                _ => return Ok(value),
+            };
+            self.chars.next();
+
+            let rhs = self.unary()?;
+            if op == '*' {
+                value *= rhs;
+            } else if rhs == 0.0 {
+                return Err(CalcError::DivisionByZero);
+            } else if op == '/' {
+                value /= rhs;
+            } else {
+                value %= rhs;
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<f64, CalcError> {
+        match self.peek() {
+            Some('-') => {
+                self.chars.next();
+                Ok(-self.unary()?)
+            }
+            Some('+') => {
+                self.chars.next();
+                self.unary()
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<f64, CalcError> {
+        match self.peek() {
+            Some('(') => {
+                self.chars.next();
+                let value = self.sum()?;
+                match self.peek() {
+                    Some(')') => {
+                        self.chars.next();
+                        Ok(value)
+                    }
+                    Some(c) => Err(CalcError::UnexpectedChar(c)),
+                    None => Err(CalcError::UnexpectedEndOfExpression),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = match self.chars.peek() {
+                    Some(&(i, _)) => i,
+                    None => self.text.len(),
+                };
+                let mut end = self.text.len();
+                while let Some(&(i, c)) = self.chars.peek() {
+                    if !c.is_ascii_digit() && c != '.' {
+                        end = i;
+                        break;
+                    }
+                    self.chars.next();
+                }
+                self.text[start..end]
+                    .parse()
+                    .map_err(|_| CalcError::InvalidNumber)
+            }
+            Some(c) => Err(CalcError::UnexpectedChar(c)),
+            None => Err(CalcError::UnexpectedEndOfExpression),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate_expressions() {
+        assert_eq!(Ok(3.0), evaluate("1 + 2"));
+        assert_eq!(Ok(7.0), evaluate("1 + 2 * 3"));
+        assert_eq!(Ok(9.0), evaluate("(1 + 2) * 3"));
+        assert_eq!(Ok(-1.5), evaluate("-3 / 2"));
+        assert_eq!(Ok(1.0), evaluate("7 % 3"));
+        assert_eq!(Ok(0.75), evaluate(" .5 + 0.25 "));
+        assert_eq!(Ok(4.0), evaluate("--4"));
+        assert_eq!(Ok(2.0), evaluate("10 - 4 - 4"));
+    }
+
+    #[test]
+    fn evaluate_errors() {
+        assert_eq!(Err(CalcError::DivisionByZero), evaluate("1 / (2 - 2)"));
+        assert_eq!(Err(CalcError::UnexpectedEndOfExpression), evaluate("1 +"));
+        assert_eq!(Err(CalcError::UnexpectedEndOfExpression), evaluate("(1"));
+        assert_eq!(Err(CalcError::UnexpectedChar('x')), evaluate("2x"));
+        assert_eq!(Err(CalcError::UnexpectedChar(')')), evaluate("1)"));
+        assert_eq!(Err(CalcError::InvalidNumber), evaluate("1.2.3"));
+        assert_eq!(Err(CalcError::UnexpectedEndOfExpression), evaluate(""));
+    }
+
+    #[test]
+    fn convert_bases() {
+        fn convert(text: &str, from: Option<u32>, to: u32) -> Option<String> {
+            let mut output = String::new();
+            convert_base(text, from, to, &mut output).then_some(output)
+        }
+
+        assert_eq!(Some("ff".into()), convert("255", None, 16));
+        assert_eq!(Some("255".into()), convert("0xFF", None, 10));
+        assert_eq!(Some("0b101".into()), convert("0x5", None, 2));
+        assert_eq!(Some("0o17".into()), convert("0b1111", None, 8));
+        assert_eq!(Some("-10".into()), convert("-a", Some(16), 10));
+        assert_eq!(Some("1000000".into()), convert("1_000_000", None, 10));
+        assert_eq!(Some("10".into()), convert("1010", Some(2), 10));
+        assert_eq!(None, convert("0x10", Some(2), 10));
+        assert_eq!(None, convert("12a", None, 16));
+        assert_eq!(None, convert("0x", None, 10));
+        assert_eq!(None, convert("", None, 10));
+        assert_eq!(None, convert("99999999999999999999999", None, 16));
+    }
+}
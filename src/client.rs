@@ -1,7 +1,7 @@
 use std::{fmt, str::FromStr};
 
 use crate::{
-    buffer::{BufferHandle, CharDisplayDistances},
+    buffer::{soft_wrapped_row_count, BufferHandle, CharDisplayDistances},
     buffer_position::BufferPositionIndex,
     buffer_view::BufferViewHandle,
     editor::Editor,
@@ -67,6 +67,7 @@ pub struct Client {
 
     pub viewport_size: (u16, u16),
     pub scroll: (BufferPositionIndex, BufferPositionIndex),
+    pub scroll_locked: bool,
     pub height: u16,
     pub navigation_history: NavigationHistory,
 
@@ -79,6 +80,7 @@ impl Client {
 
         self.viewport_size = (0, 0);
         self.scroll = (0, 0);
+        self.scroll_locked = false;
         self.height = 0;
         self.navigation_history.clear();
 
@@ -123,23 +125,55 @@ impl Client {
         }
     }
 
+    pub fn set_viewport_size(&mut self, size: (u16, u16)) {
+        if self.viewport_size.0 != size.0 {
+            self.scroll.0 = 0;
+        }
+        self.viewport_size = size;
+    }
+
     pub fn has_ui(&self) -> bool {
         self.viewport_size.0 != 0 && self.viewport_size.1 != 0
     }
 
+    pub fn soft_wrap_width(&self, editor: &Editor) -> Option<usize> {
+        let buffer_view = editor.buffer_views.get(self.buffer_view_handle?);
+        let line_count = editor
+            .buffers
+            .get(buffer_view.buffer_handle)
+            .content()
+            .line_count();
+        let has_diagnostics = !editor
+            .lsp
+            .buffer_diagnostics(buffer_view.buffer_handle)
+            .is_empty();
+        let gutter_width = editor.config.gutter_width(line_count, has_diagnostics);
+        let width = self.viewport_size.0.saturating_sub(gutter_width);
+        editor.config.soft_wrap_width(width)
+    }
+
     pub fn update_view(&mut self, editor: &Editor, picker_height: u16) {
         fn calculate_scroll(
             this: &Client,
             editor: &Editor,
         ) -> Option<(BufferPositionIndex, BufferPositionIndex)> {
-            let width = this.viewport_size.0 as BufferPositionIndex;
+            let buffer_view = editor.buffer_views.get(this.buffer_view_handle()?);
+            let buffer = editor.buffers.get(buffer_view.buffer_handle);
+            let folds = buffer.folds();
+            let buffer = buffer.content();
+
+            let has_diagnostics = !editor
+                .lsp
+                .buffer_diagnostics(buffer_view.buffer_handle)
+                .is_empty();
+            let gutter_width = editor
+                .config
+                .gutter_width(buffer.line_count(), has_diagnostics);
+            let width = this.viewport_size.0.saturating_sub(gutter_width);
             if width == 0 {
                 return None;
             }
 
-            let buffer_view = editor.buffer_views.get(this.buffer_view_handle()?);
-            let buffer = editor.buffers.get(buffer_view.buffer_handle).content();
-
             let position = buffer_view.cursors.main_cursor().position;
 
             let line_index = position.line_index;
@@ -149,18 +183,74 @@ impl Client {
             let height = this.height as BufferPositionIndex;
             let half_height = height / 2;
 
-            let (mut scroll_x, mut scroll_y) = this.scroll;
+            let last_line_index = (buffer.line_count() - 1) as BufferPositionIndex;
+            if this.scroll_locked && line_index < last_line_index {
+                return Some((this.scroll.0, this.scroll.1.min(last_line_index)));
+            }
+
+            // vertical scroll is calculated over visible lines so folded lines are not counted
+            let line_index = folds.to_visible_line(line_index);
+            let last_line_index = folds.to_visible_line(last_line_index);
+
+            let margin = editor.config.scroll_margin as BufferPositionIndex;
+            let margin = margin.min(height.saturating_sub(1) / 2);
+            let top_line_index = line_index - margin.min(line_index);
+            let bottom_margin = margin.min(last_line_index - line_index);
+            let bottom_line_index = line_index + bottom_margin;
+
+            let (mut scroll_x, scroll_y) = this.scroll;
+            let mut scroll_y = folds.to_visible_line(scroll_y);
 
-            if column_index < scroll_x {
-                scroll_x = column_index
+            let tab_size = editor.config.tab_size;
+            if let Some(wrap_width) = editor.config.soft_wrap_width(width) {
+                if top_line_index < scroll_y {
+                    return Some((0, folds.from_visible_line(top_line_index)));
+                }
+
+                let mut row_count =
+                    soft_wrapped_row_count(&line[..column_index as usize], tab_size, wrap_width);
+                for i in line_index + 1..=bottom_line_index {
+                    let next_line = buffer.line_at(folds.from_visible_line(i) as _).as_str();
+                    row_count += soft_wrapped_row_count(next_line, tab_size, wrap_width);
+                }
+                let mut first_line_index = line_index;
+                while first_line_index > scroll_y {
+                    let previous_line_index = folds.from_visible_line(first_line_index - 1);
+                    let previous_line = buffer.line_at(previous_line_index as _).as_str();
+                    row_count += soft_wrapped_row_count(previous_line, tab_size, wrap_width);
+                    if row_count > height as _ {
+                        break;
+                    }
+                    first_line_index -= 1;
+                }
+                return Some((0, folds.from_visible_line(first_line_index)));
+            }
+
+            let index = column_index as usize;
+            let side_margin = editor.config.side_scroll_margin as usize;
+            let side_margin = side_margin.min((width as usize - 1) / 2);
+            let left_index = match line[..index].char_indices().rev().take(side_margin).last() {
+                Some((i, _)) => i,
+                None => index,
+            };
+
+            if left_index < scroll_x as usize {
+                scroll_x = left_index as _;
             } else {
-                let index = column_index as usize;
-                let (width, text) = match line[index..].chars().next() {
-                    Some(c) => (width, &line[..index + c.len_utf8()]),
-                    None => (width - 1, line),
+                let mut right_index = index;
+                let mut right_char_count = 0;
+                for c in line[index..].chars().take(side_margin + 1) {
+                    right_index += c.len_utf8();
+                    right_char_count += 1;
+                }
+                let text = &line[..right_index];
+                let width = if right_char_count > side_margin {
+                    width as BufferPositionIndex
+                } else {
+                    width as BufferPositionIndex - 1
                 };
 
-                if let Some(d) = CharDisplayDistances::new(text, editor.config.tab_size)
+                if let Some(d) = CharDisplayDistances::new(text, tab_size)
                     .rev()
                     .take_while(|d| d.distance <= width as _)
                     .last()
@@ -169,17 +259,17 @@ impl Client {
                 }
             }
 
-            if line_index < scroll_y.saturating_sub(half_height) {
+            if top_line_index < scroll_y.saturating_sub(half_height) {
                 scroll_y = line_index.saturating_sub(half_height);
-            } else if line_index < scroll_y {
-                scroll_y = line_index;
-            } else if line_index >= scroll_y + height + half_height {
+            } else if top_line_index < scroll_y {
+                scroll_y = top_line_index;
+            } else if bottom_line_index >= scroll_y + height + half_height {
                 scroll_y = line_index + 1 - half_height;
-            } else if line_index >= scroll_y + height {
-                scroll_y = line_index + 1 - height;
+            } else if bottom_line_index >= scroll_y + height {
+                scroll_y = bottom_line_index + 1 - height;
             }
 
-            Some((scroll_x, scroll_y))
+            Some((scroll_x, folds.from_visible_line(scroll_y)))
         }
 
         self.height = self.viewport_size.1.saturating_sub(1 + picker_height);
@@ -253,3 +343,192 @@ impl ClientManager {
         self.clients.iter_mut().filter(|c| c.active)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use crate::{buffer::BufferCapabilities, buffer_position::BufferPosition, cursor::Cursor};
+
+    fn editor_with_buffer(text: &str) -> (Editor, BufferViewHandle) {
+        let mut editor = Editor::new(PathBuf::new());
+        let buffer = editor.buffers.add_new();
+        buffer.capabilities = BufferCapabilities::text();
+        buffer.insert_text(
+            &mut editor.word_database,
+            BufferPosition::zero(),
+            text,
+            &mut editor.events,
+        );
+        let buffer_handle = buffer.handle();
+        let buffer_view_handle = editor
+            .buffer_views
+            .add_new(ClientHandle::from_index(0).unwrap(), buffer_handle);
+        (editor, buffer_view_handle)
+    }
+
+    fn set_main_cursor(editor: &mut Editor, handle: BufferViewHandle, line: u32, column: u32) {
+        let position = BufferPosition::line_col(line as _, column as _);
+        let mut cursors = editor.buffer_views.get_mut(handle).cursors.mut_guard();
+        cursors.clear();
+        cursors.add(Cursor {
+            anchor: position,
+            position,
+        });
+    }
+
+    #[test]
+    fn resize_clamps_scroll() {
+        let (mut editor, handle) =
+            editor_with_buffer("0123456789abcdefghij\n1\n2\n3\n4\n5\n6\n7\n8\n9");
+        set_main_cursor(&mut editor, handle, 0, 15);
+
+        let mut client = Client {
+            buffer_view_handle: Some(handle),
+            ..Default::default()
+        };
+
+        client.set_viewport_size((10, 20));
+        client.update_view(&editor, 0);
+        assert_eq!((10, 20), client.viewport_size);
+        assert_eq!(19, client.height);
+        assert_eq!((6, 0), client.scroll);
+
+        client.set_viewport_size((40, 20));
+        client.update_view(&editor, 0);
+        assert_eq!((40, 20), client.viewport_size);
+        assert_eq!((0, 0), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 9, 0);
+        client.set_viewport_size((40, 5));
+        client.update_view(&editor, 0);
+        assert_eq!(4, client.height);
+        assert_eq!((0, 8), client.scroll);
+    }
+
+    #[test]
+    fn scroll_margin() {
+        let (mut editor, handle) = editor_with_buffer("0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11");
+        editor.config.scroll_margin = 2;
+
+        let mut client = Client {
+            buffer_view_handle: Some(handle),
+            ..Default::default()
+        };
+        client.set_viewport_size((40, 7));
+
+        set_main_cursor(&mut editor, handle, 3, 0);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 0), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 4, 0);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 1), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 6, 0);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 3), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 11, 0);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 6), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 7, 0);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 5), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 0, 0);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 0), client.scroll);
+    }
+
+    #[test]
+    fn side_scroll_margin() {
+        let (mut editor, handle) = editor_with_buffer("0123456789abcdefghij");
+        editor.config.side_scroll_margin = 2;
+
+        let mut client = Client {
+            buffer_view_handle: Some(handle),
+            ..Default::default()
+        };
+        client.set_viewport_size((10, 5));
+
+        set_main_cursor(&mut editor, handle, 0, 7);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 0), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 0, 8);
+        client.update_view(&editor, 0);
+        assert_eq!((1, 0), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 0, 20);
+        client.update_view(&editor, 0);
+        assert_eq!((11, 0), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 0, 13);
+        client.update_view(&editor, 0);
+        assert_eq!((11, 0), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 0, 12);
+        client.update_view(&editor, 0);
+        assert_eq!((10, 0), client.scroll);
+    }
+
+    #[test]
+    fn scroll_lock() {
+        let (mut editor, handle) = editor_with_buffer("0\n1\n2\n3\n4\n5\n6\n7\n8\n9");
+        set_main_cursor(&mut editor, handle, 0, 0);
+
+        let mut client = Client {
+            buffer_view_handle: Some(handle),
+            ..Default::default()
+        };
+        client.set_viewport_size((40, 5));
+        client.scroll_locked = true;
+
+        client.scroll.1 = 3;
+        client.update_view(&editor, 0);
+        assert_eq!((0, 3), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 7, 0);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 3), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 9, 0);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 8), client.scroll);
+
+        client.scroll_locked = false;
+        set_main_cursor(&mut editor, handle, 0, 0);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 0), client.scroll);
+    }
+
+    #[test]
+    fn scroll_over_folds() {
+        let text: Vec<_> = (0..20).map(|i| i.to_string()).collect();
+        let (mut editor, handle) = editor_with_buffer(&text.join("\n"));
+        let buffer_handle = editor.buffer_views.get(handle).buffer_handle;
+        editor.buffers.get_mut(buffer_handle).folds_mut().add(5, 14);
+
+        let mut client = Client {
+            buffer_view_handle: Some(handle),
+            ..Default::default()
+        };
+        client.set_viewport_size((40, 10));
+
+        set_main_cursor(&mut editor, handle, 16, 0);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 0), client.scroll);
+
+        set_main_cursor(&mut editor, handle, 19, 0);
+        client.update_view(&editor, 0);
+        assert_eq!((0, 2), client.scroll);
+
+        editor.buffers.get_mut(buffer_handle).folds_mut().clear();
+        client.update_view(&editor, 0);
+        assert_eq!((0, 16), client.scroll);
+    }
+}
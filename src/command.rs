@@ -2,13 +2,17 @@ use std::{collections::VecDeque, fmt, io};
 
 use crate::{
     buffer::{Buffer, BufferHandle},
+    buffer_position::{BufferPosition, BufferPositionIndex},
     buffer_view::BufferViewHandle,
+    calc::CalcError,
     client::{ClientHandle, ClientManager},
     config::ParseConfigError,
     editor::{Editor, EditorControlFlow},
     editor_utils::MessageKind,
+    events::ServerEvent,
     keymap::ParseKeyMapError,
-    platform::Platform,
+    pattern::PatternError,
+    platform::{Platform, PlatformRequest},
 };
 
 mod builtin;
@@ -20,6 +24,7 @@ pub enum CommandError {
     TooManyArguments,
     TooFewArguments,
     NoBufferOpened,
+    NoBufferPath,
     UnsavedChanges,
     IoError(io::Error),
     ConfigError(ParseConfigError),
@@ -28,6 +33,30 @@ pub enum CommandError {
     KeyMapError(ParseKeyMapError),
     LspServerNotRunning,
     LspServerNotLogging,
+    LspServerBusy,
+    InvalidTraceValue,
+    NoRenamePreview,
+    InvalidRequestName,
+    InvalidBufferPosition,
+    InvalidCount,
+    UnknownFlag,
+    InvalidBufferHandle,
+    NoClipboardProvider,
+    NoFormatter,
+    FormatterFailed(String),
+    NoLineComment,
+    ChangeSignsDisabled,
+    NoSuchSyntax,
+    InvalidBase,
+    NoSuchResetTarget,
+    InvalidToggleValue,
+    InvalidRenderWhitespaceValue,
+    InvalidRegisterKey,
+    NoSuchMark,
+    InvalidDelimiter,
+    ReplacementCapture,
+    PatternError(PatternError),
+    CalcError(CalcError),
 }
 impl fmt::Display for CommandError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -36,6 +65,7 @@ impl fmt::Display for CommandError {
             Self::TooManyArguments => f.write_str("too many arguments"),
             Self::TooFewArguments => f.write_str("too few arguments"),
             Self::NoBufferOpened => f.write_str("no buffer opened"),
+            Self::NoBufferPath => f.write_str("buffer has no path"),
             Self::UnsavedChanges => f.write_str("unsaved changes"),
             Self::IoError(error) => write!(f, "{}", error),
             Self::ConfigError(error) => write!(f, "{}", error),
@@ -44,6 +74,37 @@ impl fmt::Display for CommandError {
             Self::KeyMapError(error) => write!(f, "{}", error),
             Self::LspServerNotRunning => f.write_str("no lsp server running"),
             Self::LspServerNotLogging => f.write_str("lsp server is not logging"),
+            Self::LspServerBusy => {
+                f.write_str("lsp server has a pending request (use 'lsp-cancel' to cancel it)")
+            }
+            Self::InvalidTraceValue => f.write_str("expected 'off', 'messages' or 'verbose'"),
+            Self::NoRenamePreview => f.write_str("no rename preview to apply"),
+            Self::InvalidRequestName => f.write_str("invalid request name"),
+            Self::InvalidBufferPosition => f.write_str("invalid buffer position"),
+            Self::InvalidCount => f.write_str("invalid count"),
+            Self::UnknownFlag => f.write_str("unknown flag"),
+            Self::InvalidBufferHandle => f.write_str("invalid buffer handle"),
+            Self::NoClipboardProvider => f.write_str("no clipboard provider found"),
+            Self::NoFormatter => f.write_str("no formatter for buffer"),
+            Self::FormatterFailed(error) if error.is_empty() => f.write_str("formatter failed"),
+            Self::FormatterFailed(error) => write!(f, "formatter failed: {}", error),
+            Self::ChangeSignsDisabled => f.write_str("change_signs config is disabled"),
+            Self::NoLineComment => f.write_str("no line comment for buffer"),
+            Self::NoSuchSyntax => f.write_str("no such syntax"),
+            Self::InvalidBase => f.write_str("invalid base. expected 2, 8, 10 or 16"),
+            Self::NoSuchResetTarget => f.write_str("no such reset target"),
+            Self::InvalidToggleValue => f.write_str("expected 'on' or 'off'"),
+            Self::InvalidRenderWhitespaceValue => {
+                f.write_str("expected 'off', 'trailing' or 'all'")
+            }
+            Self::InvalidRegisterKey => f.write_str("invalid register key"),
+            Self::NoSuchMark => f.write_str("no such mark"),
+            Self::InvalidDelimiter => f.write_str("invalid delimiter"),
+            Self::ReplacementCapture => {
+                f.write_str("patterns have no captures to reference from the replacement")
+            }
+            Self::PatternError(error) => write!(f, "{}", error),
+            Self::CalcError(error) => write!(f, "{}", error),
         }
     }
 }
@@ -55,11 +116,53 @@ pub enum CompletionSource {
     Commands,
     Buffers,
     Files,
+    Syntaxes,
+    Flags(&'static [(&'static str, CompletionSource)]),
     Custom(&'static [&'static str]),
+    // a flag that takes a value with no completions. it completes as `-name=`
+    Value,
+}
+
+// flags are `-name` or `-name=value` where name starts with a letter so `-5` is still an argument
+pub fn parse_flag(token: &str) -> Option<(&str, &str)> {
+    let flag = token.strip_prefix('-')?;
+    let (name, value) = match flag.find('=') {
+        Some(i) => (&flag[..i], &flag[i + 1..]),
+        None => (flag, ""),
+    };
+    let mut chars = name.chars();
+    if !matches!(chars.next(), Some(c) if c.is_ascii_alphabetic()) {
+        return None;
+    }
+    if !chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return None;
+    }
+    Some((name, value))
 }
 
-pub struct CommandArgs<'command>(CommandTokenizer<'command>);
+pub struct CommandArgs<'command>(
+    CommandTokenizer<'command>,
+    &'static [(&'static str, CompletionSource)],
+);
 impl<'command> CommandArgs<'command> {
+    // flag values are written in the same order the command declares its `flags`
+    pub fn get_flags(&mut self, flags: &mut [Option<&'command str>]) -> Result<(), CommandError> {
+        debug_assert_eq!(self.1.len(), flags.len());
+        loop {
+            let mut tokenizer = self.0.clone();
+            let (name, value) = match tokenizer.next().and_then(parse_flag) {
+                Some(flag) => flag,
+                None => break,
+            };
+            match self.1.iter().position(|(n, _)| *n == name) {
+                Some(i) => flags[i] = Some(value),
+                None => return Err(CommandError::UnknownFlag),
+            }
+            self.0 = tokenizer;
+        }
+        Ok(())
+    }
+
     pub fn try_next(&mut self) -> Option<&'command str> {
         self.0.next()
     }
@@ -77,6 +180,12 @@ impl<'command> CommandArgs<'command> {
             None => Ok(()),
         }
     }
+
+    pub fn rest(&mut self) -> &'command str {
+        let rest = (self.0).0.trim_start_matches(&[' ', '\t'][..]);
+        (self.0).0 = "";
+        rest
+    }
 }
 
 pub struct CommandContext<'state, 'command> {
@@ -87,6 +196,7 @@ pub struct CommandContext<'state, 'command> {
 
     pub args: CommandArgs<'command>,
     pub bang: bool,
+    pub count: u32,
 }
 impl<'state, 'command> CommandContext<'state, 'command> {
     pub fn current_buffer_view_handle(&self) -> Result<BufferViewHandle, CommandError> {
@@ -106,6 +216,28 @@ impl<'state, 'command> CommandContext<'state, 'command> {
         Ok(buffer_handle)
     }
 
+    pub fn buffer_handle_or_current(
+        &self,
+        flag_value: Option<&str>,
+    ) -> Result<BufferHandle, CommandError> {
+        let value = match flag_value {
+            Some(value) => value,
+            None => return self.current_buffer_handle(),
+        };
+        let id = match value.find(':') {
+            Some(i) => &value[..i],
+            None => value,
+        };
+        let handle = match id.parse() {
+            Ok(id) => BufferHandle(id),
+            Err(_) => return Err(CommandError::InvalidBufferHandle),
+        };
+        match self.editor.buffers.try_get(handle) {
+            Some(_) => Ok(handle),
+            None => Err(CommandError::InvalidBufferHandle),
+        }
+    }
+
     pub fn assert_can_discard_all_buffers(&self) -> Result<(), CommandError> {
         if self.bang || !self.editor.buffers.iter().any(Buffer::needs_save) {
             Ok(())
@@ -167,6 +299,7 @@ impl<'a> Iterator for CommandTokenizer<'a> {
 pub struct BuiltinCommand {
     pub name: &'static str,
     pub completions: &'static [CompletionSource],
+    pub flags: &'static [(&'static str, CompletionSource)],
     pub func: CommandFn,
 }
 
@@ -235,18 +368,30 @@ impl AliasCollection {
     }
 }
 
+pub struct RequestCommand {
+    pub name: String,
+    pub client_handle: ClientHandle,
+    pub hidden: bool,
+}
+
 pub struct CommandManager {
     builtin_commands: &'static [BuiltinCommand],
+    request_commands: Vec<RequestCommand>,
     history: VecDeque<String>,
+    last_command: String,
     pub aliases: AliasCollection,
+    pub pending_count: u32,
 }
 
 impl CommandManager {
     pub fn new() -> Self {
         Self {
             builtin_commands: builtin::COMMANDS,
+            request_commands: Vec::new(),
             history: VecDeque::with_capacity(HISTORY_CAPACITY),
+            last_command: String::new(),
             aliases: AliasCollection::default(),
+            pending_count: 0,
         }
     }
 
@@ -258,6 +403,29 @@ impl CommandManager {
         &self.builtin_commands
     }
 
+    pub fn request_commands(&self) -> &[RequestCommand] {
+        &self.request_commands
+    }
+
+    pub fn register_request(&mut self, name: &str, client_handle: ClientHandle, hidden: bool) {
+        match self.request_commands.iter_mut().find(|r| r.name == name) {
+            Some(request) => {
+                request.client_handle = client_handle;
+                request.hidden = hidden;
+            }
+            None => self.request_commands.push(RequestCommand {
+                name: name.into(),
+                client_handle,
+                hidden,
+            }),
+        }
+    }
+
+    pub fn on_client_left(&mut self, client_handle: ClientHandle) {
+        self.request_commands
+            .retain(|r| r.client_handle != client_handle);
+    }
+
     pub fn history_len(&self) -> usize {
         self.history.len()
     }
@@ -290,6 +458,23 @@ impl CommandManager {
         self.history.push_back(s);
     }
 
+    pub fn last_command(&self) -> &str {
+        &self.last_command
+    }
+
+    pub fn set_last_command(&mut self, command: &str) {
+        let name = CommandTokenizer(command).next().unwrap_or("");
+        let name = name.trim_end_matches('!');
+        let name = match self.aliases.find(name) {
+            Some(aliased) => CommandTokenizer(aliased).next().unwrap_or(""),
+            None => name,
+        };
+        if !name.is_empty() && name != "repeat-last-command" {
+            self.last_command.clear();
+            self.last_command.push_str(command);
+        }
+    }
+
     pub fn eval(
         editor: &mut Editor,
         platform: &mut Platform,
@@ -309,6 +494,68 @@ impl CommandManager {
         }
     }
 
+    pub fn eval_repl_line(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut ClientManager,
+        client_handle: ClientHandle,
+        buffer_handle: BufferHandle,
+        line_index: BufferPositionIndex,
+    ) -> EditorControlFlow {
+        let mut text = editor.string_pool.acquire();
+        let line = editor
+            .buffers
+            .get(buffer_handle)
+            .content()
+            .line_at(line_index as _)
+            .as_str()
+            .trim();
+        if line.is_empty() || line.starts_with('#') {
+            editor.string_pool.release(text);
+            return EditorControlFlow::Continue;
+        }
+        text.push_str(line);
+
+        editor.status_bar.clear();
+        let result = Self::try_eval(editor, platform, clients, client_handle, &mut text);
+        text.clear();
+        let flow = match result {
+            Ok(flow) => {
+                for line in editor.status_bar.message().1.lines() {
+                    text.push_str("# ");
+                    text.push_str(line);
+                    text.push('\n');
+                }
+                flow
+            }
+            Err(error) => {
+                use fmt::Write;
+                let _ = writeln!(text, "# error: {}", error);
+                editor
+                    .status_bar
+                    .write(MessageKind::Error)
+                    .fmt(format_args!("{}", error));
+                EditorControlFlow::Continue
+            }
+        };
+
+        if !text.is_empty() {
+            if let Some(buffer) = editor.buffers.try_get(buffer_handle) {
+                let position = buffer
+                    .content()
+                    .saturate_position(BufferPosition::line_col(line_index + 1, 0));
+                editor.buffers.get_mut(buffer_handle).insert_text(
+                    &mut editor.word_database,
+                    position,
+                    &text,
+                    &mut editor.events,
+                );
+            }
+        }
+        editor.string_pool.release(text);
+        flow
+    }
+
     pub fn try_eval(
         editor: &mut Editor,
         platform: &mut Platform,
@@ -335,6 +582,7 @@ impl CommandManager {
         client_handle: ClientHandle,
         command: &str,
     ) -> Result<EditorControlFlow, CommandError> {
+        let count = std::mem::take(&mut editor.commands.pending_count);
         let mut tokenizer = CommandTokenizer(command);
         let command = match tokenizer.next() {
             Some(command) => command,
@@ -344,9 +592,27 @@ impl CommandManager {
             Some(command) => (command, true),
             None => (command, false),
         };
-        let command_func = match editor.commands.find_command(command) {
-            Some(command) => command.func,
-            None => return Err(CommandError::NoSuchCommand),
+        let (command_func, command_flags) = match editor.commands.find_command(command) {
+            Some(command) => (command.func, command.flags),
+            None => {
+                let request = editor
+                    .commands
+                    .request_commands
+                    .iter()
+                    .find(|r| r.name == command);
+                return match request {
+                    Some(request) => {
+                        let mut buf = platform.buf_pool.acquire();
+                        ServerEvent::serialize_request(buf.write(), &request.name, tokenizer);
+                        platform.enqueue_request(PlatformRequest::WriteToClient {
+                            handle: request.client_handle,
+                            buf: buf.share(),
+                        });
+                        Ok(EditorControlFlow::Continue)
+                    }
+                    None => Err(CommandError::NoSuchCommand),
+                };
+            }
         };
 
         let mut ctx = CommandContext {
@@ -354,8 +620,9 @@ impl CommandManager {
             platform,
             clients,
             client_handle,
-            args: CommandArgs(tokenizer),
+            args: CommandArgs(tokenizer, command_flags),
             bang,
+            count,
         };
         (command_func)(&mut ctx)
     }
@@ -365,6 +632,499 @@ impl CommandManager {
 mod tests {
     use super::*;
 
+    use std::path::{Path, PathBuf};
+
+    use crate::{
+        buffer::BufferCapabilities,
+        config::RenderWhitespace,
+        cursor::Cursor,
+        events::{ClientEvent, KeyParser, TargetClient},
+        platform::PlatformRequest,
+        register::RegisterKey,
+        syntax::Syntax,
+    };
+
+    // ((anchor line, anchor column), (position line, position column))
+    type TestCursor = ((u32, u32), (u32, u32));
+
+    struct TestEditor {
+        editor: Editor,
+        platform: Platform,
+        clients: ClientManager,
+        client_handle: ClientHandle,
+        buffer_handle: BufferHandle,
+        buffer_view_handle: BufferViewHandle,
+    }
+    impl TestEditor {
+        fn new(text: &str) -> Self {
+            let (request_sender, _) = std::sync::mpsc::channel::<PlatformRequest>();
+            let mut platform = Platform::new(|| (), request_sender);
+            let mut editor = Editor::new(PathBuf::new());
+            let mut clients = ClientManager::default();
+            let client_handle = ClientHandle::from_index(0).unwrap();
+            clients.on_client_joined(client_handle);
+
+            let buffer = editor.buffers.add_new();
+            buffer.capabilities = BufferCapabilities::text();
+            buffer.insert_text(
+                &mut editor.word_database,
+                BufferPosition::zero(),
+                text,
+                &mut editor.events,
+            );
+            buffer.commit_edits();
+            let buffer_handle = buffer.handle();
+            let buffer_view_handle = editor.buffer_views.add_new(client_handle, buffer_handle);
+            clients
+                .get_mut(client_handle)
+                .set_buffer_view_handle(Some(buffer_view_handle), &mut editor.events);
+            editor.trigger_event_handlers(&mut platform, &mut clients);
+
+            Self {
+                editor,
+                platform,
+                clients,
+                client_handle,
+                buffer_handle,
+                buffer_view_handle,
+            }
+        }
+
+        fn eval(&mut self, command: &str) -> Result<EditorControlFlow, CommandError> {
+            let mut command = String::from(command);
+            let result = CommandManager::try_eval(
+                &mut self.editor,
+                &mut self.platform,
+                &mut self.clients,
+                self.client_handle,
+                &mut command,
+            );
+            self.editor
+                .trigger_event_handlers(&mut self.platform, &mut self.clients);
+            result
+        }
+
+        fn keys(&mut self, keys: &str) {
+            for key in KeyParser::new(keys) {
+                self.editor.on_client_event(
+                    &mut self.platform,
+                    &mut self.clients,
+                    self.client_handle,
+                    ClientEvent::Key(TargetClient::Sender, key.unwrap()),
+                );
+            }
+        }
+
+        fn assert_eval(&mut self, command: &str) {
+            if let Err(error) = self.eval(command) {
+                panic!("command '{}' failed: {}", command, error);
+            }
+        }
+
+        fn content(&self) -> String {
+            self.editor
+                .buffers
+                .get(self.buffer_handle)
+                .content()
+                .to_string()
+        }
+
+        fn set_cursors(&mut self, cursors: &[TestCursor]) {
+            let buffer_view = self.editor.buffer_views.get_mut(self.buffer_view_handle);
+            let mut guard = buffer_view.cursors.mut_guard();
+            guard.clear();
+            for &(anchor, position) in cursors {
+                guard.add(Cursor {
+                    anchor: BufferPosition::line_col(anchor.0 as _, anchor.1 as _),
+                    position: BufferPosition::line_col(position.0 as _, position.1 as _),
+                });
+            }
+        }
+
+        fn set_main_cursor_index(&mut self, index: usize) {
+            let buffer_view = self.editor.buffer_views.get_mut(self.buffer_view_handle);
+            buffer_view.cursors.set_main_cursor_index(index);
+        }
+
+        fn main_cursor_index(&self) -> usize {
+            let buffer_view = self.editor.buffer_views.get(self.buffer_view_handle);
+            buffer_view.cursors.main_cursor_index()
+        }
+
+        fn cursors(&self) -> Vec<TestCursor> {
+            self.editor
+                .buffer_views
+                .get(self.buffer_view_handle)
+                .cursors[..]
+                .iter()
+                .map(|c| {
+                    (
+                        (c.anchor.line_index as _, c.anchor.column_byte_index as _),
+                        (
+                            c.position.line_index as _,
+                            c.position.column_byte_index as _,
+                        ),
+                    )
+                })
+                .collect()
+        }
+
+        fn undo(&mut self) {
+            let buffer = self.editor.buffers.get_mut(self.buffer_handle);
+            buffer.commit_edits();
+            buffer
+                .undo(&mut self.editor.word_database, &mut self.editor.events)
+                .for_each(drop);
+        }
+    }
+
+    #[test]
+    fn cursor_introspection_commands() {
+        let mut editor = TestEditor::new("one\ntwo\nthree");
+        editor.set_cursors(&[((0, 0), (0, 2)), ((2, 4), (1, 1))]);
+        editor.assert_eval("cursor-count");
+        assert_eq!("2", editor.editor.status_bar.message().1);
+        editor.assert_eval("cursors");
+        assert_eq!("1:1-1:3\n3:5-2:2", editor.editor.status_bar.message().1);
+    }
+
+    #[test]
+    fn main_cursor_commands() {
+        let mut editor = TestEditor::new("0\n1\n2\n3\n4\n5\n6\n7\n8");
+        editor.set_cursors(&[((0, 0), (0, 0)), ((4, 0), (4, 0)), ((8, 0), (8, 0))]);
+        let main_line_index = |editor: &TestEditor| {
+            let buffer_view = editor.editor.buffer_views.get(editor.buffer_view_handle);
+            buffer_view.cursors.main_cursor().position.line_index
+        };
+
+        editor.assert_eval("rotate-main-cursor");
+        assert_eq!(0, main_line_index(&editor));
+        editor.assert_eval("rotate-main-cursor -reverse");
+        editor.assert_eval("rotate-main-cursor -reverse");
+        assert_eq!(4, main_line_index(&editor));
+
+        editor.clients.get_mut(editor.client_handle).height = 10;
+        editor.assert_eval("rotate-main-cursor");
+        editor.assert_eval("set-main-cursor-closest");
+        assert_eq!(4, main_line_index(&editor));
+    }
+
+    #[test]
+    fn home_smart_command() {
+        let mut editor = TestEditor::new("    indented");
+        editor.set_cursors(&[((0, 8), (0, 8))]);
+        editor.assert_eval("home-smart");
+        assert_eq!(vec![((0, 4), (0, 4))], editor.cursors());
+        editor.assert_eval("home-smart -select");
+        assert_eq!(vec![((0, 4), (0, 0))], editor.cursors());
+    }
+
+    #[test]
+    fn word_motion_commands() {
+        let mut editor = TestEditor::new("foo.bar baz");
+        editor.set_cursors(&[((0, 0), (0, 0))]);
+        editor.assert_eval("move-next-word");
+        assert_eq!(vec![((0, 3), (0, 3))], editor.cursors());
+        editor.assert_eval("move-word-end -select");
+        assert_eq!(vec![((0, 3), (0, 4))], editor.cursors());
+        editor.assert_eval("with-count 2 move-word-end");
+        assert_eq!(vec![((0, 11), (0, 11))], editor.cursors());
+        editor.assert_eval("with-count 3 move-prev-word -select");
+        assert_eq!(vec![((0, 11), (0, 3))], editor.cursors());
+    }
+
+    #[test]
+    fn prose_motion_commands() {
+        let mut editor = TestEditor::new("One. Two.\n\nThree");
+        editor.set_cursors(&[((0, 0), (0, 0))]);
+        editor.assert_eval("move-next-sentence");
+        assert_eq!(vec![((0, 5), (0, 5))], editor.cursors());
+        editor.assert_eval("move-prev-sentence -select");
+        assert_eq!(vec![((0, 5), (0, 0))], editor.cursors());
+        editor.assert_eval("move-next-paragraph");
+        assert_eq!(vec![((1, 0), (1, 0))], editor.cursors());
+        editor.assert_eval("with-count 2 move-next-paragraph -select");
+        assert_eq!(vec![((1, 0), (2, 5))], editor.cursors());
+        editor.assert_eval("move-prev-paragraph");
+        assert_eq!(vec![((1, 0), (1, 0))], editor.cursors());
+    }
+
+    #[test]
+    fn jumplist_commands() {
+        let mut editor = TestEditor::new("a\n\nb\n\nc");
+        editor.set_cursors(&[((0, 0), (0, 0))]);
+        editor.assert_eval("with-count 2 move-next-paragraph");
+        assert_eq!(vec![((3, 0), (3, 0))], editor.cursors());
+        editor.assert_eval("jump-back");
+        assert_eq!(vec![((0, 0), (0, 0))], editor.cursors());
+        editor.assert_eval("jump-forward");
+        assert_eq!(vec![((3, 0), (3, 0))], editor.cursors());
+
+        editor.assert_eval("config jumplist false");
+        editor.assert_eval("move-prev-paragraph");
+        editor.assert_eval("jump-back");
+        assert_eq!(vec![((0, 0), (0, 0))], editor.cursors());
+    }
+
+    #[test]
+    fn jumplist_sentence_commands() {
+        let mut editor = TestEditor::new("One. Two. Three.");
+        editor.set_cursors(&[((0, 0), (0, 0))]);
+        editor.assert_eval("with-count 2 move-next-sentence");
+        assert_eq!(vec![((0, 10), (0, 10))], editor.cursors());
+        editor.assert_eval("jump-back");
+        assert_eq!(vec![((0, 0), (0, 0))], editor.cursors());
+
+        let mut editor = TestEditor::new("One. Two. Three.");
+        editor.set_cursors(&[((0, 0), (0, 0))]);
+        editor.assert_eval("move-next-sentence");
+        assert_eq!(vec![((0, 5), (0, 5))], editor.cursors());
+        editor.assert_eval("jump-back");
+        assert_eq!(vec![((0, 5), (0, 5))], editor.cursors());
+    }
+
+    #[test]
+    fn command_repl() {
+        let mut editor = TestEditor::new("");
+        editor.assert_eval("command-repl");
+        editor.buffer_view_handle = editor
+            .clients
+            .get(editor.client_handle)
+            .buffer_view_handle()
+            .unwrap();
+        editor.buffer_handle = editor
+            .editor
+            .buffer_views
+            .get(editor.buffer_view_handle)
+            .buffer_handle;
+
+        editor.keys("i# comment<enter>print hello<enter>not-a-command<enter>");
+        assert_eq!(
+            "# comment\nprint hello\n# hello\nnot-a-command\n# error: no such command\n",
+            editor.content()
+        );
+
+        editor.keys("print a<left><enter>");
+        assert_eq!(
+            "# comment\nprint hello\n# hello\nnot-a-command\n# error: no such command\nprint \na",
+            editor.content()
+        );
+
+        editor.keys("<esc>");
+        editor.set_cursors(&[((0, 9), (0, 9)), ((1, 11), (1, 11))]);
+        editor.keys("i<enter>");
+        assert_eq!(
+            "# comment\n\nprint hello\n\n# hello\nnot-a-command\n# error: no such command\nprint \na",
+            editor.content()
+        );
+    }
+
+    #[test]
+    fn render_whitespace_command() {
+        let mut editor = TestEditor::new("");
+        editor.assert_eval("render-whitespace");
+        assert!(editor.editor.config.render_whitespace == RenderWhitespace::All);
+        editor.assert_eval("render-whitespace");
+        assert!(editor.editor.config.render_whitespace == RenderWhitespace::Off);
+        editor.assert_eval("render-whitespace trailing");
+        assert!(editor.editor.config.render_whitespace == RenderWhitespace::Trailing);
+        assert_eq!(
+            "render-whitespace trailing",
+            editor.editor.status_bar.message().1
+        );
+        assert!(matches!(
+            editor.eval("render-whitespace some"),
+            Err(CommandError::InvalidRenderWhitespaceValue)
+        ));
+    }
+
+    #[test]
+    fn indent_guides_command() {
+        let mut editor = TestEditor::new("");
+        editor.assert_eval("indent-guides");
+        assert!(editor.editor.config.indent_guides);
+        editor.assert_eval("indent-guides on");
+        assert!(editor.editor.config.indent_guides);
+        editor.assert_eval("indent-guides off");
+        assert!(!editor.editor.config.indent_guides);
+        assert!(matches!(
+            editor.eval("indent-guides maybe"),
+            Err(CommandError::InvalidToggleValue)
+        ));
+    }
+
+    #[test]
+    fn reset_config_applies_effects() {
+        let mut editor = TestEditor::new("#!/bin/sh\necho");
+        let mut syntax = Syntax::new();
+        syntax.set_name("sh");
+        syntax.set_glob("**/*.sh").unwrap();
+        editor.editor.syntaxes.add(syntax);
+        let sh = editor.editor.syntaxes.find_handle_by_name("sh").unwrap();
+
+        editor.assert_eval("config syntax_detect_shebang false");
+        let buffer = editor.editor.buffers.get(editor.buffer_handle);
+        assert!(buffer.syntax_handle() != sh);
+
+        editor.assert_eval("reset config");
+        let buffer = editor.editor.buffers.get(editor.buffer_handle);
+        assert!(buffer.syntax_handle() == sh);
+    }
+
+    #[test]
+    fn lsp_recipe_starts_one_client_for_matching_buffers() {
+        let mut editor = TestEditor::new("");
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        editor.editor.current_directory = root.into();
+        editor
+            .editor
+            .lsp
+            .add_recipe("**/*.rs", "lsp-server", None, None)
+            .unwrap();
+
+        editor.assert_eval("open src/not-a-rust-file.txt");
+        assert_eq!(0, editor.editor.lsp.clients().count());
+
+        editor.assert_eval("open src/first-lsp-test-file.rs");
+        assert_eq!(1, editor.editor.lsp.clients().count());
+        let client = editor.editor.lsp.clients().next().unwrap();
+        assert_eq!(root, client.root());
+
+        editor.assert_eval("open src/second-lsp-test-file.rs");
+        assert_eq!(1, editor.editor.lsp.clients().count());
+    }
+
+    #[test]
+    fn harness_unknown_command() {
+        let mut editor = TestEditor::new("text");
+        assert!(matches!(
+            editor.eval("not-a-command"),
+            Err(CommandError::NoSuchCommand)
+        ));
+        assert_eq!("text", editor.content());
+    }
+
+    #[test]
+    fn print_command() {
+        let mut editor = TestEditor::new("");
+        editor.assert_eval("print a b");
+        let (kind, message) = editor.editor.status_bar.message();
+        assert!(matches!(kind, MessageKind::Info));
+        assert_eq!("a b", message);
+
+        editor.assert_eval("print -error -dbg oops");
+        let (kind, message) = editor.editor.status_bar.message();
+        assert!(matches!(kind, MessageKind::Error));
+        assert_eq!("oops", message);
+    }
+
+    #[test]
+    fn repeat_last_command_ignores_replayed_keys() {
+        let mut editor = TestEditor::new("");
+        editor.keys(":print<space>typed<enter>");
+        assert_eq!("typed", editor.editor.status_bar.message().1);
+
+        editor.assert_eval("map-normal X :print<space>mapped<enter>");
+        editor.keys("X");
+        assert_eq!("mapped", editor.editor.status_bar.message().1);
+        editor.assert_eval("repeat-last-command");
+        assert_eq!("typed", editor.editor.status_bar.message().1);
+
+        let register = RegisterKey::from_char('q').unwrap();
+        editor
+            .editor
+            .registers
+            .get_mut(register)
+            .push_str(":print<space>macro<enter>");
+        editor.keys("Qq");
+        assert_eq!("macro", editor.editor.status_bar.message().1);
+        editor.assert_eval("repeat-last-command");
+        assert_eq!("typed", editor.editor.status_bar.message().1);
+    }
+
+    #[test]
+    fn replace_all_command() {
+        let mut editor = TestEditor::new("foo bar foo\nbaz foo");
+        editor.assert_eval("replace-all foo qux");
+        assert_eq!("qux bar qux\nbaz qux", editor.content());
+        editor.undo();
+        assert_eq!("foo bar foo\nbaz foo", editor.content());
+
+        editor.set_cursors(&[((0, 4), (1, 3))]);
+        editor.assert_eval("replace-all -in-selection foo qux");
+        assert_eq!("foo bar qux\nbaz foo", editor.content());
+
+        assert!(matches!(
+            editor.eval("replace-all qux $1"),
+            Err(CommandError::ReplacementCapture)
+        ));
+        editor.assert_eval("replace-all qux $$1");
+        assert_eq!("foo bar $1\nbaz foo", editor.content());
+
+        editor.assert_eval("replace-all {x} y");
+        assert_eq!("foo bar $1\nbaz foo", editor.content());
+    }
+
+    #[test]
+    fn surround_commands() {
+        let mut editor = TestEditor::new("a word here");
+        editor.set_cursors(&[((0, 2), (0, 6))]);
+        editor.assert_eval("surround (");
+        assert_eq!("a (word) here", editor.content());
+        assert_eq!(vec![((0, 3), (0, 7))], editor.cursors());
+
+        editor.assert_eval("change-surround ( [");
+        assert_eq!("a [word] here", editor.content());
+
+        editor.assert_eval("delete-surround [");
+        assert_eq!("a word here", editor.content());
+    }
+
+    #[test]
+    fn line_commands() {
+        let mut editor = TestEditor::new("first\nsecond\nthird");
+        editor.set_cursors(&[((0, 1), (0, 1))]);
+        editor.assert_eval("move-line-down");
+        assert_eq!("second\nfirst\nthird", editor.content());
+        assert_eq!(vec![((1, 1), (1, 1))], editor.cursors());
+
+        editor.set_cursors(&[((0, 0), (0, 0)), ((2, 0), (2, 0))]);
+        editor.set_main_cursor_index(0);
+        editor.assert_eval("move-line-up");
+        assert_eq!("second\nthird\nfirst", editor.content());
+        assert_eq!(vec![((0, 0), (0, 0)), ((1, 0), (1, 0))], editor.cursors());
+        assert_eq!(0, editor.main_cursor_index());
+        editor.undo();
+
+        editor.set_cursors(&[((1, 1), (1, 1))]);
+        editor.assert_eval("duplicate");
+        assert_eq!("second\nfirst\nfirst\nthird", editor.content());
+        assert_eq!(2, editor.cursors()[0].1 .0);
+        editor.undo();
+
+        editor.set_cursors(&[((0, 0), (0, 0)), ((1, 0), (1, 0))]);
+        editor.set_main_cursor_index(0);
+        editor.assert_eval("duplicate");
+        assert_eq!("second\nsecond\nfirst\nfirst\nthird", editor.content());
+        assert_eq!(vec![((1, 0), (1, 0)), ((3, 0), (3, 0))], editor.cursors());
+        assert_eq!(0, editor.main_cursor_index());
+
+        editor.undo();
+        assert_eq!("second\nfirst\nthird", editor.content());
+    }
+
+    #[test]
+    fn word_commands() {
+        let mut editor = TestEditor::new("one two");
+        editor.set_cursors(&[((0, 3), (0, 3))]);
+        editor.assert_eval("transpose-words");
+        assert_eq!("two one", editor.content());
+
+        editor.set_cursors(&[((0, 0), (0, 3)), ((0, 4), (0, 4))]);
+        editor.assert_eval("uppercase");
+        assert_eq!("TWO One", editor.content());
+    }
+
     #[test]
     fn command_tokens() {
         let mut tokens = CommandTokenizer("cmd arg");
@@ -384,4 +1144,50 @@ mod tests {
         assert_eq!(Some("arg'1"), tokens.next());
         assert_eq!(None, tokens.next());
     }
+
+    #[test]
+    fn command_flags() {
+        const NO_COMPLETION: CompletionSource = CompletionSource::Custom(&[]);
+
+        let mut args = CommandArgs(
+            CommandTokenizer("-a -b=value arg -c"),
+            &[
+                ("a", NO_COMPLETION),
+                ("b", NO_COMPLETION),
+                ("c", NO_COMPLETION),
+            ],
+        );
+        let mut flags = [None; 3];
+        assert!(args.get_flags(&mut flags).is_ok());
+        assert_eq!(Some(""), flags[0]);
+        assert_eq!(Some("value"), flags[1]);
+        assert_eq!(None, flags[2]);
+        assert_eq!(Some("arg"), args.try_next());
+        assert_eq!(Some("-c"), args.try_next());
+
+        let mut args = CommandArgs(CommandTokenizer("-d arg"), &[("a", NO_COMPLETION)]);
+        let mut flags = [None; 1];
+        assert!(matches!(
+            args.get_flags(&mut flags),
+            Err(CommandError::UnknownFlag)
+        ));
+
+        let mut args = CommandArgs(
+            CommandTokenizer("-a -5 -c"),
+            &[("a", NO_COMPLETION), ("c", NO_COMPLETION)],
+        );
+        let mut flags = [None; 2];
+        assert!(args.get_flags(&mut flags).is_ok());
+        assert_eq!(Some(""), flags[0]);
+        assert_eq!(None, flags[1]);
+        assert_eq!(Some("-5"), args.try_next());
+
+        assert_eq!(Some(("name", "")), parse_flag("-name"));
+        assert_eq!(Some(("with-dash", "v=1")), parse_flag("-with-dash=v=1"));
+        assert_eq!(None, parse_flag("-"));
+        assert_eq!(None, parse_flag("-=value"));
+        assert_eq!(None, parse_flag("-1"));
+        assert_eq!(None, parse_flag("--"));
+        assert_eq!(None, parse_flag("name"));
+    }
 }
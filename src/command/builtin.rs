@@ -1,31 +1,76 @@
-use std::path::Path;
+use std::{
+    fs::{self, File},
+    io,
+    path::Path,
+    process::Stdio,
+};
 
 use crate::{
-    buffer::{parse_path_and_position, BufferCapabilities, BufferHandle},
-    buffer_position::BufferPosition,
+    buffer::{
+        convert_case, parse_missing_path_and_position, BufferCapabilities, BufferContent,
+        BufferHandle, CaseConversion, PendingSave, SearchOptions,
+    },
+    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
+    buffer_view::{BufferViewHandle, CursorMovement, CursorMovementKind},
+    calc,
     client::ClientManager,
-    command::{BuiltinCommand, CommandContext, CommandError, CompletionSource},
-    config::{ParseConfigError, CONFIG_NAMES},
+    command::{BuiltinCommand, CommandContext, CommandError, CommandManager, CompletionSource},
+    config::{ParseConfigError, RenderWhitespace, CONFIG_EFFECTS, CONFIG_NAMES},
     cursor::Cursor,
-    editor::{Editor, EditorControlFlow},
-    editor_utils::MessageKind,
-    help, lsp,
-    mode::ModeKind,
-    navigation_history::NavigationHistory,
-    platform::Platform,
+    datetime::{DateTime, DEFAULT_DATETIME_FORMAT},
+    diff::{self, ChangeSign},
+    editor::{Editor, EditorControlFlow, COMMAND_REPL_BUFFER_PATH},
+    editor_utils::{
+        load_config, parse_process_command, EditorOutputWrite, MessageKind, DEFAULT_CONFIG_NAME,
+        DEFAULT_CONFIG_SOURCE,
+    },
+    fold, help,
+    ini::Ini,
+    lsp,
+    mode::{picker, read_line, ModeContext, ModeKind},
+    navigation_history::{NavigationHistory, NavigationMovement},
+    pattern::Pattern,
+    platform::{Platform, PlatformRequest, ProcessTag},
+    register::{RegisterCollection, RegisterKey, SEARCH_REGISTER},
     theme::{Color, THEME_COLOR_NAMES},
+    word_database::WordKind,
 };
 
 pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "help",
         completions: &[CompletionSource::Commands],
+        flags: &[
+            ("search", CompletionSource::Value),
+            ("list", CompletionSource::Custom(&[])),
+        ],
         func: |ctx| {
+            let mut flags = [None; 2];
+            ctx.args.get_flags(&mut flags)?;
             let keyword = ctx.args.try_next();
             ctx.args.assert_empty()?;
 
+            if let Some(text) = flags[0] {
+                let mut write = ctx.editor.status_bar.write(MessageKind::Info);
+                let mut count = 0;
+                for (name, line) in help::search_commands(text) {
+                    if count > 0 {
+                        write.str("\n");
+                    }
+                    write.fmt(format_args!("{}: {}", name, line));
+                    count += 1;
+                }
+                if count == 0 {
+                    write.str("no command found");
+                }
+                return Ok(EditorControlFlow::Continue);
+            }
+
             let (path, position) = match keyword.and_then(|k| help::search(k)) {
                 Some((path, line_index)) => (path, BufferPosition::line_col(line_index as _, 0)),
+                None if flags[1].is_some() => {
+                    (Path::new(help::COMMAND_LIST_PATH), BufferPosition::zero())
+                }
                 None => (help::main_help_path(), BufferPosition::zero()),
             };
 
@@ -49,9 +94,110 @@ pub static COMMANDS: &[BuiltinCommand] = &[
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "version",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!(
+                    "{} {} ({}) {}",
+                    env!("CARGO_PKG_NAME"),
+                    env!("CARGO_PKG_VERSION"),
+                    env!("PEPPER_COMMIT_HASH"),
+                    env!("PEPPER_TARGET"),
+                ));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "print",
+        completions: &[],
+        flags: &[
+            ("error", CompletionSource::Custom(&[])),
+            ("dbg", CompletionSource::Custom(&[])),
+            ("no-interpolate", CompletionSource::Custom(&[])),
+        ],
+        func: |ctx| {
+            let mut flags = [None; 3];
+            ctx.args.get_flags(&mut flags)?;
+            let kind = match flags[0] {
+                Some(_) => MessageKind::Error,
+                None => MessageKind::Info,
+            };
+            let dbg = flags[1].is_some();
+            let interpolate = flags[2].is_none();
+
+            let mut write = ctx.editor.status_bar.write(kind);
+            let mut first = true;
+            while let Some(arg) = ctx.args.try_next() {
+                if !first {
+                    write.str(" ");
+                }
+                first = false;
+
+                if interpolate {
+                    write_interpolated_registers(&mut write, &ctx.editor.registers, arg);
+                } else {
+                    write.str(arg);
+                }
+            }
+
+            if dbg {
+                eprintln!("{}", ctx.editor.status_bar.message().1);
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "request",
+        completions: &[],
+        flags: &[("hidden", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let hidden = flags[0].is_some();
+            let name = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            if name.is_empty() || ctx.editor.commands.find_command(name).is_some() {
+                return Err(CommandError::InvalidRequestName);
+            }
+            ctx.editor
+                .commands
+                .register_request(name, ctx.client_handle, hidden);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "command-repl",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            NavigationHistory::save_client_snapshot(
+                ctx.clients.get_mut(ctx.client_handle),
+                &ctx.editor.buffer_views,
+            );
+            let handle = ctx.editor.buffer_view_handle_from_path(
+                ctx.client_handle,
+                Path::new(COMMAND_REPL_BUFFER_PATH),
+                BufferCapabilities::log(),
+            );
+            ctx.clients
+                .get_mut(ctx.client_handle)
+                .set_buffer_view_handle(Some(handle), &mut ctx.editor.events);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
     BuiltinCommand {
         name: "quit",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
             if ctx.clients.iter().count() == 1 {
@@ -63,31 +209,90 @@ pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "quit-all",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
             ctx.assert_can_discard_all_buffers()?;
             Ok(EditorControlFlow::QuitAll)
         },
     },
+    BuiltinCommand {
+        name: "with-count",
+        completions: &[CompletionSource::Custom(&[]), CompletionSource::Commands],
+        flags: &[],
+        func: |ctx| {
+            let count = ctx.args.next()?;
+            let count = count
+                .parse::<u32>()
+                .map_err(|_| CommandError::InvalidCount)?;
+            let command = ctx.args.rest();
+            if command.is_empty() {
+                return Err(CommandError::TooFewArguments);
+            }
+
+            ctx.editor.commands.pending_count = count;
+            let mut command = ctx.editor.string_pool.acquire_with(command);
+            let result = CommandManager::try_eval(
+                ctx.editor,
+                ctx.platform,
+                ctx.clients,
+                ctx.client_handle,
+                &mut command,
+            );
+            ctx.editor.string_pool.release(command);
+            result
+        },
+    },
+    BuiltinCommand {
+        name: "repeat-last-command",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let entry = ctx.editor.commands.last_command();
+            if entry.is_empty() {
+                return Ok(EditorControlFlow::Continue);
+            }
+
+            let mut command = ctx.editor.string_pool.acquire_with(entry);
+            let result = CommandManager::try_eval(
+                ctx.editor,
+                ctx.platform,
+                ctx.clients,
+                ctx.client_handle,
+                &mut command,
+            );
+            ctx.editor.string_pool.release(command);
+            result
+        },
+    },
     BuiltinCommand {
         name: "open",
         completions: &[CompletionSource::Files],
+        flags: &[("format-on-save", CompletionSource::Custom(&[]))],
         func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
             let path = ctx.args.next()?;
             ctx.args.assert_empty()?;
 
-            let (path, position) = parse_path_and_position(path);
+            let (path, position) =
+                parse_missing_path_and_position(&ctx.editor.current_directory, path);
 
             NavigationHistory::save_client_snapshot(
                 ctx.clients.get_mut(ctx.client_handle),
                 &ctx.editor.buffer_views,
             );
 
+            let mut capabilities = BufferCapabilities::text();
+            capabilities.format_on_save = flags[0].is_some();
+
             let path = ctx.editor.string_pool.acquire_with(path);
             let handle = ctx.editor.buffer_view_handle_from_path(
                 ctx.client_handle,
                 Path::new(&path),
-                BufferCapabilities::text(),
+                capabilities,
             );
             ctx.editor.string_pool.release(path);
 
@@ -108,56 +313,197 @@ pub static COMMANDS: &[BuiltinCommand] = &[
         },
     },
     BuiltinCommand {
-        name: "save",
-        completions: &[],
+        name: "tail",
+        completions: &[CompletionSource::Files],
+        flags: &[("stop", CompletionSource::Custom(&[]))],
         func: |ctx| {
-            let path = ctx.args.try_next().map(|p| Path::new(p));
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let path = ctx.args.next()?;
             ctx.args.assert_empty()?;
 
-            let buffer_handle = ctx.current_buffer_handle()?;
-            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            if flags[0].is_some() {
+                let buffer_handle = ctx
+                    .editor
+                    .buffers
+                    .find_with_path(&ctx.editor.current_directory, Path::new(path))
+                    .ok_or(CommandError::NoBufferOpened)?;
+                ctx.editor
+                    .buffers
+                    .stop_tailing_file(ctx.platform, buffer_handle);
+                return Ok(EditorControlFlow::Continue);
+            }
 
-            buffer
-                .save_to_file(path, &mut ctx.editor.events)
-                .map_err(CommandError::IoError)?;
+            let full_path = ctx.editor.current_directory.join(path);
+            fs::metadata(&full_path).map_err(CommandError::IoError)?;
+
+            NavigationHistory::save_client_snapshot(
+                ctx.clients.get_mut(ctx.client_handle),
+                &ctx.editor.buffer_views,
+            );
+
+            let handle = ctx.editor.buffer_view_handle_from_path(
+                ctx.client_handle,
+                Path::new(path),
+                BufferCapabilities::log(),
+            );
+            let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+            let buffer_handle = buffer_view.buffer_handle;
+            let end = ctx.editor.buffers.get(buffer_handle).content().end();
+
+            let mut cursors = buffer_view.cursors.mut_guard();
+            cursors.clear();
+            cursors.add(Cursor {
+                anchor: end,
+                position: end,
+            });
 
+            // measured after loading so only what's written from now on is appended
             ctx.editor
-                .status_bar
-                .write(MessageKind::Info)
-                .fmt(format_args!("buffer saved to {:?}", &buffer.path));
+                .buffers
+                .tail_file(ctx.platform, buffer_handle, full_path)
+                .map_err(CommandError::IoError)?;
+
+            ctx.clients
+                .get_mut(ctx.client_handle)
+                .set_buffer_view_handle(Some(handle), &mut ctx.editor.events);
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "save",
+        completions: &[],
+        flags: &[
+            ("buffer", CompletionSource::Buffers),
+            ("format-best-effort", CompletionSource::Custom(&[])),
+        ],
+        func: |ctx| {
+            let mut flags = [None; 2];
+            ctx.args.get_flags(&mut flags)?;
+            let path = ctx.args.try_next().map(|p| Path::new(p));
+            ctx.args.assert_empty()?;
+
+            let buffer_handle = ctx.buffer_handle_or_current(flags[0])?;
+            if format_and_save_buffer(ctx, buffer_handle, path, flags[1].is_some())? {
+                let buffer = ctx.editor.buffers.get(buffer_handle);
+                ctx.editor
+                    .status_bar
+                    .write(MessageKind::Info)
+                    .fmt(format_args!("buffer saved to {:?}", &buffer.path));
+            }
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
         name: "save-all",
         completions: &[],
+        flags: &[("format-best-effort", CompletionSource::Custom(&[]))],
         func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
             ctx.args.assert_empty()?;
 
-            let mut count = 0;
-            for buffer in ctx.editor.buffers.iter_mut() {
-                if buffer.capabilities.can_save {
-                    buffer
-                        .save_to_file(None, &mut ctx.editor.events)
-                        .map_err(CommandError::IoError)?;
-                    count += 1;
+            let buffer_handles: Vec<_> = ctx
+                .editor
+                .buffers
+                .iter()
+                .filter(|b| b.capabilities.can_save)
+                .map(|b| b.handle())
+                .collect();
+
+            // each buffer is formatted and saved on its own so one failing does not stop the others
+            let mut saved_count = 0;
+            let mut formatting_count = 0;
+            let mut result = Ok(());
+            for handle in buffer_handles {
+                match format_and_save_buffer(ctx, handle, None, flags[0].is_some()) {
+                    Ok(true) => saved_count += 1,
+                    Ok(false) => formatting_count += 1,
+                    Err(error) => result = Err(error),
                 }
             }
+            result?;
 
-            ctx.editor
-                .status_bar
-                .write(MessageKind::Info)
-                .fmt(format_args!("{} buffers saved", count));
+            let mut write = ctx.editor.status_bar.write(MessageKind::Info);
+            write.fmt(format_args!("{} buffers saved", saved_count));
+            if formatting_count > 0 {
+                write.fmt(format_args!(
+                    ", {} will be saved after formatting",
+                    formatting_count
+                ));
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "diff",
+        completions: &[],
+        flags: &[("buffer", CompletionSource::Buffers)],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            ctx.args.assert_empty()?;
+
+            let buffer_handle = ctx.buffer_handle_or_current(flags[0])?;
+            let buffer = ctx.editor.buffers.get(buffer_handle);
+            let path = buffer.path.to_str().unwrap_or("");
+
+            let mut disk_content = BufferContent::new();
+            if let Ok(file) = File::open(&buffer.path) {
+                disk_content
+                    .read(&mut io::BufReader::new(file))
+                    .map_err(CommandError::IoError)?;
+            }
+
+            let old: Vec<_> = disk_content.lines().map(|l| l.as_str()).collect();
+            let new: Vec<_> = buffer.content().lines().map(|l| l.as_str()).collect();
+            let mut text = String::new();
+            if path.is_empty() || !diff::unified_diff(path, &old, &new, &mut text) {
+                ctx.editor
+                    .status_bar
+                    .write(MessageKind::Info)
+                    .str("no changes");
+                return Ok(EditorControlFlow::Continue);
+            }
+
+            NavigationHistory::save_client_snapshot(
+                ctx.clients.get_mut(ctx.client_handle),
+                &ctx.editor.buffer_views,
+            );
+
+            let view_handle = ctx.editor.buffer_view_handle_from_path(
+                ctx.client_handle,
+                Path::new(diff::DIFF_BUFFER_PATH),
+                BufferCapabilities::log(),
+            );
+            let buffer_handle = ctx.editor.buffer_views.get(view_handle).buffer_handle;
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+            buffer.delete_range(&mut ctx.editor.word_database, range, &mut ctx.editor.events);
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                BufferPosition::zero(),
+                &text,
+                &mut ctx.editor.events,
+            );
+
+            ctx.clients
+                .get_mut(ctx.client_handle)
+                .set_buffer_view_handle(Some(view_handle), &mut ctx.editor.events);
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
         name: "reopen",
         completions: &[],
+        flags: &[("buffer", CompletionSource::Buffers)],
         func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
             ctx.args.assert_empty()?;
 
-            let buffer_handle = ctx.current_buffer_handle()?;
+            let buffer_handle = ctx.buffer_handle_or_current(flags[0])?;
             ctx.assert_can_discard_buffer(buffer_handle)?;
             let buffer = ctx.editor.buffers.get_mut(buffer_handle);
 
@@ -175,6 +521,7 @@ pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "reopen-all",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
 
@@ -200,10 +547,13 @@ pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "close",
         completions: &[],
+        flags: &[("buffer", CompletionSource::Buffers)],
         func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
             ctx.args.assert_empty()?;
 
-            let buffer_handle = ctx.current_buffer_handle()?;
+            let buffer_handle = ctx.buffer_handle_or_current(flags[0])?;
             ctx.assert_can_discard_buffer(buffer_handle)?;
             ctx.editor
                 .buffers
@@ -220,6 +570,7 @@ pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "close-all",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
 
@@ -240,102 +591,1720 @@ pub static COMMANDS: &[BuiltinCommand] = &[
         },
     },
     BuiltinCommand {
-        name: "status",
+        name: "goto-line",
         completions: &[],
+        flags: &[],
         func: |ctx| {
+            let position = match ctx.args.try_next() {
+                Some(position) => position
+                    .parse::<BufferPosition>()
+                    .map_err(|_| CommandError::InvalidBufferPosition)?,
+                None if ctx.count > 0 => {
+                    BufferPosition::line_col((ctx.count - 1) as BufferPositionIndex, 0)
+                }
+                None => return Err(CommandError::TooFewArguments),
+            };
             ctx.args.assert_empty()?;
 
-            let client = ctx.clients.get_mut(ctx.client_handle);
-            NavigationHistory::save_client_snapshot(client, &ctx.editor.buffer_views);
-            // TODO
-            client.set_buffer_view_handle(None, &mut ctx.editor.events);
-
-            Ok(EditorControlFlow::Continue)
-        },
-    },
-    BuiltinCommand {
-        name: "config",
-        completions: &[(CompletionSource::Custom(CONFIG_NAMES))],
-        func: |ctx| {
-            let key = ctx.args.next()?;
-            let value = ctx.args.try_next();
-            ctx.args.assert_empty()?;
-
-            match value {
-                Some(value) => match ctx.editor.config.parse_config(key, value) {
-                    Ok(()) => Ok(EditorControlFlow::Continue),
-                    Err(error) => Err(CommandError::ConfigError(error)),
-                },
-                None => match ctx.editor.config.display_config(key) {
-                    Some(display) => {
-                        ctx.editor
-                            .status_bar
-                            .write(MessageKind::Info)
-                            .fmt(format_args!("{}", display));
-                        Ok(EditorControlFlow::Continue)
-                    }
-                    None => Err(CommandError::ConfigError(ParseConfigError::NoSuchConfig)),
-                },
-            }
-        },
-    },
-    BuiltinCommand {
-        name: "color",
-        completions: &[CompletionSource::Custom(THEME_COLOR_NAMES)],
-        func: |ctx| {
-            let key = ctx.args.next()?;
-            let value = ctx.args.try_next();
-            ctx.args.assert_empty()?;
+            let handle = ctx.current_buffer_view_handle()?;
+            NavigationHistory::save_client_snapshot(
+                ctx.clients.get_mut(ctx.client_handle),
+                &ctx.editor.buffer_views,
+            );
 
-            let color = ctx
-                .editor
-                .theme
-                .color_from_name(key)
-                .ok_or(CommandError::NoSuchColor)?;
+            let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+            let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle).content();
+            let position = buffer.saturate_position(position);
 
-            match value {
-                Some(value) => {
-                    let encoded =
-                        u32::from_str_radix(value, 16).map_err(|_| CommandError::NoSuchColor)?;
-                    *color = Color::from_u32(encoded);
-                }
-                None => ctx
-                    .editor
-                    .status_bar
-                    .write(MessageKind::Info)
-                    .fmt(format_args!("0x{:0<6x}", color.into_u32())),
-            }
+            let mut cursors = buffer_view.cursors.mut_guard();
+            cursors.clear();
+            cursors.add(Cursor {
+                anchor: position,
+                position,
+            });
 
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
-        name: "map-normal",
+        name: "jump-back",
         completions: &[],
+        flags: &[],
         func: |ctx| {
-            map(ctx, ModeKind::Normal)?;
+            ctx.args.assert_empty()?;
+            for _ in 0..ctx.count.max(1) {
+                NavigationHistory::move_in_history(
+                    ctx.clients.get_mut(ctx.client_handle),
+                    ctx.editor,
+                    NavigationMovement::Backward,
+                );
+            }
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
-        name: "map-insert",
+        name: "jump-forward",
         completions: &[],
+        flags: &[],
         func: |ctx| {
-            map(ctx, ModeKind::Insert)?;
+            ctx.args.assert_empty()?;
+            for _ in 0..ctx.count.max(1) {
+                NavigationHistory::move_in_history(
+                    ctx.clients.get_mut(ctx.client_handle),
+                    ctx.editor,
+                    NavigationMovement::Forward,
+                );
+            }
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
-        name: "map-command",
+        name: "goto-percent",
         completions: &[],
+        flags: &[("select", CompletionSource::Custom(&[]))],
         func: |ctx| {
-            map(ctx, ModeKind::Command)?;
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let percent = match ctx.args.try_next() {
+                Some(percent) => percent
+                    .parse::<u32>()
+                    .map_err(|_| CommandError::InvalidCount)?,
+                None if ctx.count > 0 => ctx.count,
+                None => return Err(CommandError::TooFewArguments),
+            };
+            ctx.args.assert_empty()?;
+            let percent = percent.min(100) as usize;
+
+            let handle = ctx.current_buffer_view_handle()?;
+            NavigationHistory::save_client_snapshot(
+                ctx.clients.get_mut(ctx.client_handle),
+                &ctx.editor.buffer_views,
+            );
+
+            let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+            let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle).content();
+            let line_index = (buffer.line_count() - 1) * percent / 100;
+            let position = BufferPosition::line_col(line_index as _, 0);
+
+            let mut cursors = buffer_view.cursors.mut_guard();
+            if flags[0].is_some() {
+                cursors.main_cursor().position = position;
+            } else {
+                cursors.clear();
+                cursors.add(Cursor {
+                    anchor: position,
+                    position,
+                });
+            }
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "next-buffer",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            cycle_buffers(ctx, true)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "previous-buffer",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            cycle_buffers(ctx, false)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "goto-next-change",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            goto_change(ctx, true)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "goto-prev-change",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            goto_change(ctx, false)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "scroll-lock",
+        completions: &[CompletionSource::Custom(&["on", "off"])],
+        flags: &[],
+        func: |ctx| {
+            let value = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let client = ctx.clients.get_mut(ctx.client_handle);
+            client.scroll_locked = match value {
+                Some("on") => true,
+                Some("off") => false,
+                Some(_) => return Err(CommandError::InvalidToggleValue),
+                None => !client.scroll_locked,
+            };
+
+            let state = if client.scroll_locked { "on" } else { "off" };
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("scroll-lock {}", state));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "render-whitespace",
+        completions: &[CompletionSource::Custom(&["off", "trailing", "all"])],
+        flags: &[],
+        func: |ctx| {
+            let value = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let config = &mut ctx.editor.config;
+            config.render_whitespace = match value {
+                Some(value) => value
+                    .parse()
+                    .map_err(|_| CommandError::InvalidRenderWhitespaceValue)?,
+                None => match config.render_whitespace {
+                    RenderWhitespace::Off => RenderWhitespace::All,
+                    _ => RenderWhitespace::Off,
+                },
+            };
+
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!(
+                    "render-whitespace {}",
+                    ctx.editor.config.render_whitespace
+                ));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "indent-guides",
+        completions: &[CompletionSource::Custom(&["on", "off"])],
+        flags: &[],
+        func: |ctx| {
+            let value = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let config = &mut ctx.editor.config;
+            config.indent_guides = match value {
+                Some("on") => true,
+                Some("off") => false,
+                Some(_) => return Err(CommandError::InvalidToggleValue),
+                None => !config.indent_guides,
+            };
+
+            let state = if config.indent_guides { "on" } else { "off" };
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("indent-guides {}", state));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "center-view",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            scroll_view_to_main_cursor(ctx, |height| height / 2)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "view-top",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            scroll_view_to_main_cursor(ctx, |_| 0)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "view-bottom",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            scroll_view_to_main_cursor(ctx, |height| height.saturating_sub(1))?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "scroll-half-page-down",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            scroll_pages(ctx, true, false)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "scroll-half-page-up",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            scroll_pages(ctx, false, false)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "scroll-page-down",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            scroll_pages(ctx, true, true)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "scroll-page-up",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            scroll_pages(ctx, false, true)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "search",
+        completions: &[],
+        flags: &[
+            ("ignore-case", CompletionSource::Custom(&[])),
+            ("whole-word", CompletionSource::Custom(&[])),
+        ],
+        func: |ctx| {
+            let mut flags = [None; 2];
+            ctx.args.get_flags(&mut flags)?;
+            let pattern = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            ctx.editor.search_options = SearchOptions {
+                ignore_case: flags[0].is_some(),
+                whole_word: flags[1].is_some(),
+            };
+            if let Some(pattern) = pattern {
+                let register = ctx.editor.registers.get_mut(SEARCH_REGISTER);
+                register.clear();
+                register.push_str(pattern);
+            }
+
+            search_from_register(ctx)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "search-pattern",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            let pattern = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            ctx.editor
+                .aux_pattern
+                .compile(pattern)
+                .map_err(CommandError::PatternError)?;
+
+            let register = ctx.editor.registers.get_mut(SEARCH_REGISTER);
+            register.clear();
+            register.push_str("P/");
+            register.push_str(pattern);
+
+            search_from_register(ctx)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "search-next",
+        completions: &[],
+        flags: &[("select", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            ctx.args.assert_empty()?;
+            move_to_search_match(ctx, true, flags[0].is_some())?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "search-previous",
+        completions: &[],
+        flags: &[("select", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            ctx.args.assert_empty()?;
+            move_to_search_match(ctx, false, flags[0].is_some())?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "read-only",
+        completions: &[CompletionSource::Custom(&["on", "off"])],
+        flags: &[("buffer", CompletionSource::Buffers)],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let value = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let buffer_handle = ctx.buffer_handle_or_current(flags[0])?;
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            buffer.capabilities.read_only = match value {
+                Some("on") => true,
+                Some("off") => false,
+                Some(_) => return Err(CommandError::InvalidToggleValue),
+                None => !buffer.capabilities.read_only,
+            };
+
+            let state = if buffer.capabilities.read_only {
+                "on"
+            } else {
+                "off"
+            };
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("read-only {}", state));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "pipe-buffer",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            let command = ctx.args.rest();
+            let command = parse_process_command(command, ctx.platform.environment())
+                .ok_or(CommandError::TooFewArguments)?;
+            let buffer_handle = ctx.current_buffer_handle()?;
+            ctx.editor
+                .buffers
+                .spawn_pipe_process(ctx.platform, command, buffer_handle, None);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "spawn",
+        completions: &[],
+        flags: &[("to-buffer", CompletionSource::Files)],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let command = ctx.args.rest();
+            let mut command = parse_process_command(command, ctx.platform.environment())
+                .ok_or(CommandError::TooFewArguments)?;
+
+            match flags[0] {
+                Some(path) => {
+                    let view_handle = ctx.editor.buffer_view_handle_from_path(
+                        ctx.client_handle,
+                        Path::new(path),
+                        BufferCapabilities::log(),
+                    );
+                    let buffer_handle = ctx.editor.buffer_views.get(view_handle).buffer_handle;
+                    let position = ctx.editor.buffers.get(buffer_handle).content().end();
+                    ctx.editor.buffers.spawn_insert_process(
+                        ctx.platform,
+                        command,
+                        buffer_handle,
+                        position,
+                        None,
+                    );
+                }
+                None => {
+                    command.stdin(Stdio::null());
+                    command.stdout(Stdio::piped());
+                    command.stderr(Stdio::null());
+                    ctx.platform.enqueue_request(PlatformRequest::SpawnProcess {
+                        tag: ProcessTag::Ignored,
+                        command,
+                        buf_len: 512,
+                    });
+                }
+            }
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "replace-all",
+        completions: &[],
+        flags: &[("in-selection", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let pattern = ctx.args.next()?;
+            let replacement = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            let replacement = &parse_replacement(replacement)?[..];
+
+            let in_selection = flags[0].is_some();
+
+            let mut compiled = Pattern::new();
+            compiled
+                .compile(pattern)
+                .map_err(CommandError::PatternError)?;
+
+            let view_handle = ctx.current_buffer_view_handle()?;
+            let buffer_view = ctx.editor.buffer_views.get(view_handle);
+            let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+            let mut ranges = Vec::new();
+            buffer
+                .content()
+                .find_search_ranges(&compiled, SearchOptions::default(), &mut ranges);
+            ranges.retain(|r| r.from != r.to);
+            if in_selection {
+                let cursors = &buffer_view.cursors[..];
+                ranges.retain(|r| {
+                    cursors.iter().any(|c| {
+                        let selection = c.to_range();
+                        selection.from <= r.from && r.to <= selection.to
+                    })
+                });
+            }
+
+            for &range in ranges.iter().rev() {
+                buffer.delete_range(&mut ctx.editor.word_database, range, &mut ctx.editor.events);
+                buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    range.from,
+                    replacement,
+                    &mut ctx.editor.events,
+                );
+            }
+            buffer.commit_edits();
+
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("{} replacements", ranges.len()));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "replace-confirm",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            let pattern = ctx.args.next()?;
+            let replacement = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            let replacement = &parse_replacement(replacement)?[..];
+
+            ctx.current_buffer_view_handle()?;
+            let mut mode_ctx = ModeContext {
+                editor: ctx.editor,
+                platform: ctx.platform,
+                clients: ctx.clients,
+                client_handle: ctx.client_handle,
+            };
+            read_line::replace_confirm::enter_mode(&mut mode_ctx, pattern, replacement)
+                .map_err(CommandError::PatternError)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "status",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let client = ctx.clients.get_mut(ctx.client_handle);
+            NavigationHistory::save_client_snapshot(client, &ctx.editor.buffer_views);
+            // TODO
+            client.set_buffer_view_handle(None, &mut ctx.editor.events);
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "config",
+        completions: &[(CompletionSource::Custom(CONFIG_NAMES))],
+        flags: &[],
+        func: |ctx| {
+            let key = ctx.args.next()?;
+            let value = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            match value {
+                Some(value) => match ctx.editor.config.parse_config(key, value) {
+                    Ok(effect) => {
+                        ctx.editor.apply_config_effect(effect);
+                        Ok(EditorControlFlow::Continue)
+                    }
+                    Err(error) => Err(CommandError::ConfigError(error)),
+                },
+                None => match ctx.editor.config.display_config(key) {
+                    Some(display) => {
+                        ctx.editor
+                            .status_bar
+                            .write(MessageKind::Info)
+                            .fmt(format_args!("{}", display));
+                        Ok(EditorControlFlow::Continue)
+                    }
+                    None => Err(CommandError::ConfigError(ParseConfigError::NoSuchConfig)),
+                },
+            }
+        },
+    },
+    BuiltinCommand {
+        name: "clipboard",
+        completions: &[CompletionSource::Custom(&["auto"])],
+        flags: &[],
+        func: |ctx| {
+            let provider = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            if provider != "auto" {
+                return Err(CommandError::NoClipboardProvider);
+            }
+
+            let name = ctx
+                .platform
+                .detect_clipboard_commands()
+                .ok_or(CommandError::NoClipboardProvider)?;
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("using clipboard provider '{}'", name));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "yank",
+        completions: &[],
+        flags: &[("register", CompletionSource::Value)],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            ctx.args.assert_empty()?;
+
+            let register_key = flags[0].map(parse_register_key).transpose()?;
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let mut text = ctx.editor.string_pool.acquire();
+            let mut ranges = Vec::new();
+            ctx.editor.buffer_views.get(handle).append_selection_text(
+                &ctx.editor.buffers,
+                &mut text,
+                &mut ranges,
+            );
+
+            match register_key {
+                Some(key) => {
+                    let register = ctx.editor.registers.get_mut(key);
+                    register.clear();
+                    register.push_str(&text);
+                }
+                None => ctx.platform.write_to_clipboard(&text),
+            }
+
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("copied {} bytes", text.len()));
+            ctx.editor.string_pool.release(text);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "paste",
+        completions: &[],
+        flags: &[
+            ("register", CompletionSource::Value),
+            ("replace", CompletionSource::Custom(&[])),
+            ("per-cursor", CompletionSource::Custom(&[])),
+        ],
+        func: |ctx| {
+            let mut flags = [None; 3];
+            ctx.args.get_flags(&mut flags)?;
+            ctx.args.assert_empty()?;
+
+            let register_key = flags[0].map(parse_register_key).transpose()?;
+            let handle = ctx.current_buffer_view_handle()?;
+
+            let mut text = ctx.editor.string_pool.acquire();
+            match register_key {
+                Some(key) => text.push_str(ctx.editor.registers.get(key)),
+                None => ctx.platform.read_from_clipboard(&mut text),
+            }
+
+            if flags[1].is_some() {
+                ctx.editor
+                    .buffer_views
+                    .get(handle)
+                    .delete_text_in_cursor_ranges(
+                        &mut ctx.editor.buffers,
+                        &mut ctx.editor.word_database,
+                        &mut ctx.editor.events,
+                    );
+                ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+            }
+
+            let buffer_view = ctx.editor.buffer_views.get(handle);
+            if flags[2].is_some() {
+                let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+                let lines: Vec<_> = text.lines().collect();
+                for (cursor, line) in buffer_view.cursors[..].iter().zip(lines).rev() {
+                    buffer.insert_text(
+                        &mut ctx.editor.word_database,
+                        cursor.position,
+                        line,
+                        &mut ctx.editor.events,
+                    );
+                }
+            } else {
+                buffer_view.insert_text_at_cursor_positions(
+                    &mut ctx.editor.buffers,
+                    &mut ctx.editor.word_database,
+                    &text,
+                    &mut ctx.editor.events,
+                );
+            }
+            ctx.editor
+                .buffers
+                .get_mut(buffer_view.buffer_handle)
+                .commit_edits();
+
+            ctx.editor.string_pool.release(text);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "duplicate",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let buffer_view = ctx.editor.buffer_views.get(handle);
+            let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+            let mut text = ctx.editor.string_pool.acquire();
+            let mut duplicated: Vec<Cursor> = Vec::with_capacity(buffer_view.cursors[..].len());
+            for cursor in buffer_view.cursors[..].iter().rev() {
+                text.clear();
+                let content = buffer.content();
+                let is_collapsed = cursor.anchor == cursor.position;
+                let line_index = cursor.position.line_index;
+
+                let position = if is_collapsed {
+                    let line = content.line_at(line_index as _).as_str();
+                    if line_index as usize + 1 < content.line_count() {
+                        text.push_str(line);
+                        text.push('\n');
+                        BufferPosition::line_col(line_index + 1, 0)
+                    } else {
+                        text.push('\n');
+                        text.push_str(line);
+                        BufferPosition::line_col(line_index, line.len() as _)
+                    }
+                } else {
+                    let range = cursor.to_range();
+                    content.append_range_text_to_string(range, &mut text);
+                    range.to
+                };
+
+                let range = buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    position,
+                    &text,
+                    &mut ctx.editor.events,
+                );
+
+                // copies made so far are below this one and get pushed down by it
+                for copy in &mut duplicated {
+                    copy.anchor = copy.anchor.insert(range);
+                    copy.position = copy.position.insert(range);
+                }
+
+                duplicated.push(if is_collapsed {
+                    let position =
+                        BufferPosition::line_col(line_index + 1, cursor.position.column_byte_index);
+                    Cursor {
+                        anchor: position,
+                        position,
+                    }
+                } else if cursor.anchor <= cursor.position {
+                    Cursor {
+                        anchor: range.from,
+                        position: range.to,
+                    }
+                } else {
+                    Cursor {
+                        anchor: range.to,
+                        position: range.from,
+                    }
+                });
+            }
+            buffer.commit_edits();
+            ctx.editor.string_pool.release(text);
+
+            ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+
+            let cursors = &mut ctx.editor.buffer_views.get_mut(handle).cursors;
+            let main_cursor_index = cursors.main_cursor_index();
+            let mut cursors = cursors.mut_guard();
+            cursors.clear();
+            for cursor in duplicated.into_iter().rev() {
+                cursors.add(cursor);
+            }
+            cursors.set_main_cursor_index(main_cursor_index);
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "move-line-up",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            move_lines(ctx, false)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "move-line-down",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            move_lines(ctx, true)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "transpose-chars",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            transpose(ctx, false)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "transpose-words",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            transpose(ctx, true)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "surround",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            let left = ctx.args.next()?;
+            let right = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let right = right.unwrap_or(match left {
+                "(" => ")",
+                "[" => "]",
+                "{" => "}",
+                "<" => ">",
+                _ => left,
+            });
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let buffer_view = ctx.editor.buffer_views.get(handle);
+            let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+            let mut surrounded: Vec<Cursor> = Vec::with_capacity(buffer_view.cursors[..].len());
+            for cursor in buffer_view.cursors[..].iter().rev() {
+                let range = cursor.to_range();
+                let right_range = buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    range.to,
+                    right,
+                    &mut ctx.editor.events,
+                );
+                let left_range = buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    range.from,
+                    left,
+                    &mut ctx.editor.events,
+                );
+
+                for cursor in &mut surrounded {
+                    cursor.anchor = cursor.anchor.insert(right_range).insert(left_range);
+                    cursor.position = cursor.position.insert(right_range).insert(left_range);
+                }
+
+                let from = range.from.insert(left_range);
+                let to = range.to.insert(left_range);
+                surrounded.push(if cursor.anchor <= cursor.position {
+                    Cursor {
+                        anchor: from,
+                        position: to,
+                    }
+                } else {
+                    Cursor {
+                        anchor: to,
+                        position: from,
+                    }
+                });
+            }
+            buffer.commit_edits();
+
+            ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+
+            let cursors = &mut ctx.editor.buffer_views.get_mut(handle).cursors;
+            let main_cursor_index = cursors.main_cursor_index();
+            let mut cursors = cursors.mut_guard();
+            cursors.clear();
+            for cursor in surrounded.into_iter().rev() {
+                cursors.add(cursor);
+            }
+            cursors.set_main_cursor_index(main_cursor_index);
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "delete-surround",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            let delimiter = parse_delimiter(ctx.args.next()?)?;
+            ctx.args.assert_empty()?;
+            replace_surround(ctx, delimiter, None)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "change-surround",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            let from = parse_delimiter(ctx.args.next()?)?;
+            let mut to = ctx.args.next()?.chars();
+            let to = match (to.next(), to.next()) {
+                (Some(c), None) => c,
+                _ => return Err(CommandError::InvalidDelimiter),
+            };
+            ctx.args.assert_empty()?;
+            replace_surround(ctx, from, Some(to))?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "uppercase",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            change_case(ctx, CaseConversion::Upper)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "lowercase",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            change_case(ctx, CaseConversion::Lower)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "toggle-case",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            change_case(ctx, CaseConversion::Toggle)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "title-case",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            change_case(ctx, CaseConversion::Title)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "set-syntax",
+        completions: &[CompletionSource::Syntaxes],
+        flags: &[],
+        func: |ctx| {
+            let name = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let syntax_handle = match name {
+                Some(name) => match ctx.editor.syntaxes.find_handle_by_name(name) {
+                    Some(handle) => Some(handle),
+                    None => return Err(CommandError::NoSuchSyntax),
+                },
+                None => None,
+            };
+
+            let buffer_handle = ctx.current_buffer_handle()?;
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            buffer.set_syntax_override(syntax_handle);
+            buffer.refresh_syntax(
+                &ctx.editor.syntaxes,
+                &mut ctx.editor.word_database,
+                &ctx.editor.config,
+            );
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "comment-banner",
+        completions: &[],
+        flags: &[("width", CompletionSource::Value)],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let text = ctx.args.try_next().unwrap_or("");
+            ctx.args.assert_empty()?;
+
+            let width = match flags[0] {
+                Some(width) => width
+                    .parse::<usize>()
+                    .map_err(|_| CommandError::InvalidCount)?,
+                None if ctx.editor.config.color_column > 0 => ctx.editor.config.color_column as _,
+                None => 80,
+            };
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let buffer_view = ctx.editor.buffer_views.get(handle);
+            let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+            let prefix = ctx
+                .editor
+                .syntaxes
+                .get(buffer.syntax_handle())
+                .line_comment();
+            if prefix.is_empty() {
+                return Err(CommandError::NoLineComment);
+            }
+
+            let mut banner = ctx.editor.string_pool.acquire();
+            for cursor in buffer_view.cursors[..].iter().rev() {
+                let column = cursor.position.column_byte_index as usize;
+                let fill = width
+                    .saturating_sub(column)
+                    .saturating_sub(prefix.chars().count() + 1);
+
+                banner.clear();
+                banner.push_str(prefix);
+                banner.push(' ');
+                if text.is_empty() {
+                    for _ in 0..fill {
+                        banner.push('=');
+                    }
+                } else {
+                    let fill = fill.saturating_sub(text.chars().count() + 2);
+                    let left = (fill / 2).max(4);
+                    let right = (fill - fill / 2).max(4);
+                    for _ in 0..left {
+                        banner.push('=');
+                    }
+                    banner.push(' ');
+                    banner.push_str(text);
+                    banner.push(' ');
+                    for _ in 0..right {
+                        banner.push('=');
+                    }
+                }
+
+                buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    cursor.position,
+                    &banner,
+                    &mut ctx.editor.events,
+                );
+            }
+            buffer.commit_edits();
+            ctx.editor.string_pool.release(banner);
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "insert-datetime",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            let format = ctx.args.try_next().unwrap_or(DEFAULT_DATETIME_FORMAT);
+            ctx.args.assert_empty()?;
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let buffer_view = ctx.editor.buffer_views.get(handle);
+            let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+            let mut text = ctx.editor.string_pool.acquire();
+            DateTime::now().format(format, &mut text);
+            for cursor in buffer_view.cursors[..].iter().rev() {
+                buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    cursor.position,
+                    &text,
+                    &mut ctx.editor.events,
+                );
+            }
+            buffer.commit_edits();
+            ctx.editor.string_pool.release(text);
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "calc",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            let expression = ctx.args.rest();
+            let value = calc::evaluate(expression).map_err(CommandError::CalcError)?;
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("{}", value));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "calc-replace",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            replace_selections(ctx, |text, output| {
+                let value = calc::evaluate(text).map_err(CommandError::CalcError)?;
+                output.push_str(&value.to_string());
+                Ok(true)
+            })?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "to-hex",
+        completions: &[],
+        flags: &[("from", CompletionSource::Custom(&["2", "8", "10", "16"]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            ctx.args.assert_empty()?;
+            let from = parse_base(flags[0])?;
+            replace_selections(ctx, |text, output| {
+                Ok(calc::convert_base(text, from, 16, output))
+            })?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "to-dec",
+        completions: &[],
+        flags: &[("from", CompletionSource::Custom(&["2", "8", "10", "16"]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            ctx.args.assert_empty()?;
+            let from = parse_base(flags[0])?;
+            replace_selections(ctx, |text, output| {
+                Ok(calc::convert_base(text, from, 10, output))
+            })?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "to-oct",
+        completions: &[],
+        flags: &[("from", CompletionSource::Custom(&["2", "8", "10", "16"]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            ctx.args.assert_empty()?;
+            let from = parse_base(flags[0])?;
+            replace_selections(ctx, |text, output| {
+                Ok(calc::convert_base(text, from, 8, output))
+            })?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "to-bin",
+        completions: &[],
+        flags: &[("from", CompletionSource::Custom(&["2", "8", "10", "16"]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            ctx.args.assert_empty()?;
+            let from = parse_base(flags[0])?;
+            replace_selections(ctx, |text, output| {
+                Ok(calc::convert_base(text, from, 2, output))
+            })?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "jump-to-matching-indent",
+        completions: &[],
+        flags: &[
+            ("up", CompletionSource::Custom(&[])),
+            ("down", CompletionSource::Custom(&[])),
+        ],
+        func: |ctx| {
+            let mut flags = [None; 2];
+            ctx.args.get_flags(&mut flags)?;
+            ctx.args.assert_empty()?;
+            let up = flags[0].is_some() && flags[1].is_none();
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let buffer_view = ctx.editor.buffer_views.get(handle);
+            let content = ctx.editor.buffers.get(buffer_view.buffer_handle).content();
+            let tab_size = ctx.editor.config.tab_size;
+            let indentation = |i| fold::indentation(content.line_at(i).as_str(), tab_size);
+
+            let mut line_index = buffer_view.cursors.main_cursor().position.line_index as usize;
+            let (width, _) = indentation(line_index).unwrap_or((0, 0));
+            let mut target = None;
+            for _ in 0..ctx.count.max(1) {
+                let next = if up {
+                    (0..line_index)
+                        .rev()
+                        .filter_map(|i| Some((i, indentation(i)?)))
+                        .find(|&(_, (w, _))| w <= width)
+                } else {
+                    (line_index + 1..content.line_count())
+                        .filter_map(|i| Some((i, indentation(i)?)))
+                        .find(|&(_, (w, _))| w <= width)
+                };
+                match next {
+                    Some((i, (_, len))) => {
+                        line_index = i;
+                        target = Some(BufferPosition::line_col(i as _, len as _));
+                    }
+                    None => break,
+                }
+            }
+
+            if let Some(position) = target {
+                NavigationHistory::save_client_snapshot(
+                    ctx.clients.get_mut(ctx.client_handle),
+                    &ctx.editor.buffer_views,
+                );
+                let mut cursors = ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
+                cursors.clear();
+                cursors.add(Cursor {
+                    anchor: position,
+                    position,
+                });
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "fold",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            fold_lines(ctx, |_| true)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "unfold",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            fold_lines(ctx, |_| false)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "toggle-fold",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            fold_lines(ctx, |folded| !folded)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "home-smart",
+        completions: &[],
+        flags: &[("select", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let movement_kind = match flags[0] {
+                Some(_) => CursorMovementKind::PositionOnly,
+                None => CursorMovementKind::PositionAndAnchor,
+            };
+            ctx.args.assert_empty()?;
+
+            let handle = ctx.current_buffer_view_handle()?;
+            ctx.editor.buffer_views.get_mut(handle).move_cursors(
+                &ctx.editor.buffers,
+                CursorMovement::HomeSmart,
+                movement_kind,
+                ctx.editor.config.tab_size,
+            );
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "move-next-word",
+        completions: &[],
+        flags: &[("select", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            move_cursors_with_count(ctx, CursorMovement::WordsForward)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "move-prev-word",
+        completions: &[],
+        flags: &[("select", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            move_cursors_with_count(ctx, CursorMovement::WordsBackward)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "move-word-end",
+        completions: &[],
+        flags: &[("select", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            move_cursors_with_count(ctx, CursorMovement::WordEndsForward)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "move-next-paragraph",
+        completions: &[],
+        flags: &[("select", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            move_cursors_with_count(ctx, CursorMovement::ParagraphsForward)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "move-prev-paragraph",
+        completions: &[],
+        flags: &[("select", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            move_cursors_with_count(ctx, CursorMovement::ParagraphsBackward)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "move-next-sentence",
+        completions: &[],
+        flags: &[("select", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            move_cursors_by_sentences(ctx, true)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "move-prev-sentence",
+        completions: &[],
+        flags: &[("select", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            move_cursors_by_sentences(ctx, false)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "cursor-count",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let count = ctx.editor.buffer_views.get(handle).cursors[..].len();
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("{}", count));
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "cursors",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let cursors = &ctx.editor.buffer_views.get(handle).cursors;
+            let mut write = ctx.editor.status_bar.write(MessageKind::Info);
+            for (i, cursor) in cursors[..].iter().enumerate() {
+                if i > 0 {
+                    write.str("\n");
+                }
+                write.fmt(format_args!(
+                    "{}:{}-{}:{}",
+                    cursor.anchor.line_index + 1,
+                    cursor.anchor.column_byte_index + 1,
+                    cursor.position.line_index + 1,
+                    cursor.position.column_byte_index + 1,
+                ));
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "rotate-main-cursor",
+        completions: &[],
+        flags: &[("reverse", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let forward = flags[0].is_none();
+            ctx.args.assert_empty()?;
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let cursors = &mut ctx.editor.buffer_views.get_mut(handle).cursors;
+            cursors.rotate_main_cursor(ctx.count.max(1) as _, forward);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "set-main-cursor-closest",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let client = ctx.clients.get(ctx.client_handle);
+            let center_line_index = client.scroll.1 + client.height as BufferPositionIndex / 2;
+
+            let cursors = &mut ctx.editor.buffer_views.get_mut(handle).cursors;
+            let closest_index = cursors[..]
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, c)| c.position.line_index.abs_diff(center_line_index))
+                .map(|(i, _)| i)
+                .unwrap_or(0);
+            cursors.set_main_cursor_index(closest_index);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "set-mark",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let buffer_view = ctx.editor.buffer_views.get(handle);
+            let position = buffer_view.cursors.main_cursor().position;
+            ctx.editor
+                .marks
+                .set(name, buffer_view.buffer_handle, position);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "goto-mark",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+
+            let (buffer_handle, position) = match ctx.editor.marks.get(name) {
+                Some(mark) => (mark.buffer_handle, mark.position),
+                None => return Err(CommandError::NoSuchMark),
+            };
+
+            NavigationHistory::save_client_snapshot(
+                ctx.clients.get_mut(ctx.client_handle),
+                &ctx.editor.buffer_views,
+            );
+
+            let handle = ctx
+                .editor
+                .buffer_views
+                .buffer_view_handle_from_buffer_handle(ctx.client_handle, buffer_handle);
+            let position = ctx
+                .editor
+                .buffers
+                .get(buffer_handle)
+                .content()
+                .saturate_position(position);
+            let mut cursors = ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
+            cursors.clear();
+            cursors.add(Cursor {
+                anchor: position,
+                position,
+            });
+
+            ctx.clients
+                .get_mut(ctx.client_handle)
+                .set_buffer_view_handle(Some(handle), &mut ctx.editor.events);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "list-marks",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let mut write = ctx.editor.status_bar.write(MessageKind::Info);
+            let mut first = true;
+            for mark in ctx.editor.marks.iter() {
+                if !first {
+                    write.str("\n");
+                }
+                first = false;
+
+                let path = ctx
+                    .editor
+                    .buffers
+                    .get(mark.buffer_handle)
+                    .path
+                    .to_string_lossy();
+                write.fmt(format_args!(
+                    "{} {}:{},{}",
+                    mark.name,
+                    path,
+                    mark.position.line_index + 1,
+                    mark.position.column_byte_index + 1,
+                ));
+            }
+            if first {
+                write.str("no marks");
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "bookmark",
+        completions: &[],
+        flags: &[("remove", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let remove = flags[0].is_some();
+            let label = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let handle = ctx.current_buffer_view_handle()?;
+            let buffer_view = ctx.editor.buffer_views.get(handle);
+            let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+            let path = match buffer.path.to_str() {
+                Some(path) if !path.is_empty() => path,
+                _ => return Err(CommandError::NoBufferPath),
+            };
+            let line_index = buffer_view.cursors.main_cursor().position.line_index;
+
+            let bookmarks = &mut ctx.editor.bookmarks;
+            if remove {
+                bookmarks.remove(path, line_index);
+            } else {
+                let label = match label {
+                    Some(label) => label,
+                    None => buffer.content().line_at(line_index as _).as_str().trim(),
+                };
+                let label = match label.char_indices().nth(40) {
+                    Some((i, _)) => &label[..i],
+                    None => label,
+                };
+                bookmarks.add(path, line_index, label);
+            }
+            bookmarks.save().map_err(CommandError::IoError)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "bookmarks",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let mut mode_ctx = ModeContext {
+                editor: ctx.editor,
+                platform: ctx.platform,
+                clients: ctx.clients,
+                client_handle: ctx.client_handle,
+            };
+            picker::bookmark::enter_mode(&mut mode_ctx);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "env",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            let name = ctx.args.next()?;
+            let value = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            match value {
+                Some(value) => ctx.platform.set_environment_var(name, value),
+                None => {
+                    let value = ctx.platform.environment_var(name).unwrap_or_default();
+                    ctx.editor.status_bar.write(MessageKind::Info).str(&value);
+                }
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "color",
+        completions: &[CompletionSource::Custom(THEME_COLOR_NAMES)],
+        flags: &[],
+        func: |ctx| {
+            let key = ctx.args.next()?;
+            let value = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+
+            let color = ctx
+                .editor
+                .theme
+                .color_from_name(key)
+                .ok_or(CommandError::NoSuchColor)?;
+
+            match value {
+                Some(value) => {
+                    let encoded =
+                        u32::from_str_radix(value, 16).map_err(|_| CommandError::NoSuchColor)?;
+                    *color = Color::from_u32(encoded);
+                }
+                None => ctx
+                    .editor
+                    .status_bar
+                    .write(MessageKind::Info)
+                    .fmt(format_args!("0x{:0<6x}", color.into_u32())),
+            }
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "reset",
+        completions: &[CompletionSource::Custom(&[
+            "config", "theme", "keymaps", "all",
+        ])],
+        flags: &[],
+        func: |ctx| {
+            let what = ctx.args.next()?;
+            ctx.args.assert_empty()?;
+            reset(ctx.editor, what)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "reload-config",
+        completions: &[],
+        flags: &[("reset", CompletionSource::Custom(&[]))],
+        func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            ctx.args.assert_empty()?;
+
+            let mut sources = Vec::with_capacity(ctx.editor.config_paths.len());
+            for path in &ctx.editor.config_paths {
+                let source = fs::read_to_string(path).map_err(CommandError::IoError)?;
+                sources.push(source);
+            }
+
+            if flags[0].is_some() {
+                reset(ctx.editor, "all")?;
+            }
+
+            let mut ini = Ini::default();
+            if ctx.editor.uses_default_config {
+                load_config(
+                    ctx.editor,
+                    ctx.platform,
+                    &mut ini,
+                    DEFAULT_CONFIG_NAME,
+                    DEFAULT_CONFIG_SOURCE,
+                );
+            }
+            let paths = std::mem::take(&mut ctx.editor.config_paths);
+            for (path, source) in paths.iter().zip(sources.iter()) {
+                load_config(ctx.editor, ctx.platform, &mut ini, path, source);
+            }
+            ctx.editor.config_paths = paths;
+
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "map-normal",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            map(ctx, ModeKind::Normal)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "map-insert",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            map(ctx, ModeKind::Insert)?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "map-command",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            map(ctx, ModeKind::Command)?;
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
         name: "map-readline",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             map(ctx, ModeKind::Command)?;
             Ok(EditorControlFlow::Continue)
@@ -344,6 +2313,7 @@ pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "map-picker",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             map(ctx, ModeKind::Picker)?;
             Ok(EditorControlFlow::Continue)
@@ -352,6 +2322,7 @@ pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "lsp-open-log",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
             let buffer_handle = ctx.current_buffer_handle()?;
@@ -377,9 +2348,43 @@ pub static COMMANDS: &[BuiltinCommand] = &[
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "lsp-trace",
+        completions: &[CompletionSource::Custom(&["off", "messages", "verbose"])],
+        flags: &[],
+        func: |ctx| {
+            let trace = ctx.args.try_next();
+            ctx.args.assert_empty()?;
+            let trace = match trace {
+                Some(trace) => Some(
+                    trace
+                        .parse::<lsp::TraceValue>()
+                        .map_err(|_| CommandError::InvalidTraceValue)?,
+                ),
+                None => None,
+            };
+
+            let buffer_handle = ctx.current_buffer_handle()?;
+            access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
+                if client.log_file_path().is_none() {
+                    return Err(CommandError::LspServerNotLogging);
+                }
+                match trace {
+                    Some(trace) => client.set_trace(platform, trace),
+                    None => editor
+                        .status_bar
+                        .write(MessageKind::Info)
+                        .str(client.trace().as_str()),
+                }
+                Ok(())
+            })??;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
     BuiltinCommand {
         name: "lsp-stop",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
             let buffer_handle = ctx.current_buffer_handle()?;
@@ -393,27 +2398,58 @@ pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "lsp-stop-all",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
             ctx.editor.lsp.stop_all(ctx.platform);
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "lsp-cancel",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let buffer_handle = ctx.current_buffer_handle()?;
+            access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
+                let count = client.cancel_pending_requests(platform);
+                editor
+                    .status_bar
+                    .write(MessageKind::Info)
+                    .fmt(format_args!("cancelled {} pending requests", count));
+            })?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
     BuiltinCommand {
         name: "lsp-hover",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
             let (buffer_handle, cursor) = current_buffer_and_main_cursor(&ctx)?;
+            let client_handle = ctx.client_handle;
             access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
-                client.hover(editor, platform, buffer_handle, cursor.position)
-            })?;
+                if client.has_pending_request() {
+                    return Err(CommandError::LspServerBusy);
+                }
+                client.hover(
+                    editor,
+                    platform,
+                    client_handle,
+                    buffer_handle,
+                    cursor.position,
+                );
+                Ok(())
+            })??;
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
         name: "lsp-definition",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
             let (buffer_handle, cursor) = current_buffer_and_main_cursor(&ctx)?;
@@ -430,9 +2466,49 @@ pub static COMMANDS: &[BuiltinCommand] = &[
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "goto-symbol",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+            let client_handle = ctx.client_handle;
+            if find_lsp_client_for_buffer(ctx.editor, buffer_handle).is_some() {
+                access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
+                    client.definition(
+                        editor,
+                        platform,
+                        buffer_handle,
+                        cursor.position,
+                        client_handle,
+                    )
+                })?;
+                return Ok(EditorControlFlow::Continue);
+            }
+
+            let buffer = ctx.editor.buffers.get(buffer_handle);
+            let word = buffer.content().word_at(cursor.position);
+            if word.kind != WordKind::Identifier {
+                return Ok(EditorControlFlow::Continue);
+            }
+            let word = ctx.editor.string_pool.acquire_with(word.text);
+
+            let mut mode_ctx = ModeContext {
+                editor: ctx.editor,
+                platform: ctx.platform,
+                clients: ctx.clients,
+                client_handle,
+            };
+            picker::goto_symbol::enter_mode(&mut mode_ctx, &word);
+            ctx.editor.string_pool.release(word);
+            Ok(EditorControlFlow::Continue)
+        },
+    },
     BuiltinCommand {
         name: "lsp-references",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             let context_len = 2;
             ctx.args.assert_empty()?;
@@ -457,42 +2533,99 @@ pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "lsp-rename",
         completions: &[],
+        flags: &[("preview", CompletionSource::Custom(&[]))],
         func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let preview = flags[0].is_some();
             ctx.args.assert_empty()?;
 
             let (buffer_handle, cursor) = current_buffer_and_main_cursor(&ctx)?;
             let client_handle = ctx.client_handle;
 
             access_lsp(ctx, buffer_handle, |editor, platform, clients, client| {
-                client.rename(
-                    editor,
-                    platform,
-                    clients,
-                    client_handle,
+                let params = lsp::RenameParams {
                     buffer_handle,
-                    cursor.position,
-                )
+                    buffer_position: cursor.position,
+                    preview,
+                };
+                client.rename(editor, platform, clients, client_handle, params)
             })?;
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "lsp-rename-apply",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let client = ctx
+                .editor
+                .lsp
+                .clients()
+                .find(|c| c.has_rename_preview())
+                .map(|c| c.handle())
+                .ok_or(CommandError::NoRenamePreview)?;
+            lsp::ClientManager::access(ctx.editor, client, |editor, client| {
+                client.apply_rename_preview(editor)
+            });
+            Ok(EditorControlFlow::Continue)
+        },
+    },
     BuiltinCommand {
         name: "lsp-code-action",
         completions: &[],
+        flags: &[(
+            "only",
+            CompletionSource::Custom(&[
+                "quickfix",
+                "refactor",
+                "refactor.extract",
+                "refactor.inline",
+                "refactor.rewrite",
+                "source",
+                "source.organizeImports",
+            ]),
+        )],
         func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let only_kinds = flags[0].unwrap_or("");
             ctx.args.assert_empty()?;
 
             let (buffer_handle, cursor) = current_buffer_and_main_cursor(&ctx)?;
             let client_handle = ctx.client_handle;
 
             access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
-                client.code_action(
-                    editor,
-                    platform,
-                    client_handle,
-                    buffer_handle,
-                    cursor.to_range(),
-                )
+                let params = lsp::CodeActionParams {
+                    range: cursor.to_range(),
+                    only_kinds,
+                    apply_single: false,
+                };
+                client.code_action(editor, platform, client_handle, buffer_handle, params)
+            })?;
+            Ok(EditorControlFlow::Continue)
+        },
+    },
+    BuiltinCommand {
+        name: "lsp-organize-imports",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+
+            let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx)?;
+            let client_handle = ctx.client_handle;
+
+            access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
+                let params = lsp::CodeActionParams {
+                    range: cursor.to_range(),
+                    only_kinds: "source.organizeImports",
+                    apply_single: true,
+                };
+                client.code_action(editor, platform, client_handle, buffer_handle, params)
             })?;
             Ok(EditorControlFlow::Continue)
         },
@@ -500,6 +2633,7 @@ pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "lsp-document-symbols",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
 
@@ -516,31 +2650,73 @@ pub static COMMANDS: &[BuiltinCommand] = &[
     BuiltinCommand {
         name: "lsp-workspace-symbols",
         completions: &[],
+        flags: &[("interactive", CompletionSource::Custom(&[]))],
         func: |ctx| {
+            let mut flags = [None; 1];
+            ctx.args.get_flags(&mut flags)?;
+            let interactive = flags[0].is_some();
             let query = ctx.args.try_next().unwrap_or("");
             ctx.args.assert_empty()?;
 
             let buffer_handle = ctx.current_buffer_handle()?;
             let client_handle = ctx.client_handle;
 
-            access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
-                client.workspace_symbols(editor, platform, client_handle, query)
-            })?;
+            let lsp_client_handle =
+                access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
+                    client
+                        .workspace_symbols(editor, platform, client_handle, query, interactive)
+                        .then_some(client.handle())
+                })?;
+            if let (true, Some(lsp_client_handle)) = (interactive, lsp_client_handle) {
+                let mut mode_ctx = ModeContext {
+                    editor: ctx.editor,
+                    platform: ctx.platform,
+                    clients: ctx.clients,
+                    client_handle: ctx.client_handle,
+                };
+                picker::lsp_workspace_symbol::enter_interactive_mode(
+                    &mut mode_ctx,
+                    lsp_client_handle,
+                    query,
+                );
+            }
             Ok(EditorControlFlow::Continue)
         },
     },
     BuiltinCommand {
         name: "lsp-format",
         completions: &[],
+        flags: &[],
         func: |ctx| {
             ctx.args.assert_empty()?;
             let buffer_handle = ctx.current_buffer_handle()?;
             access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
-                client.formatting(editor, platform, buffer_handle)
+                client.formatting(editor, platform, buffer_handle, None)
             })?;
             Ok(EditorControlFlow::Continue)
         },
     },
+    BuiltinCommand {
+        name: "lsp-fold-all",
+        completions: &[],
+        flags: &[],
+        func: |ctx| {
+            ctx.args.assert_empty()?;
+            let view_handle = ctx.current_buffer_view_handle()?;
+            let buffer_handle = ctx.editor.buffer_views.get(view_handle).buffer_handle;
+            let requested = match access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
+                client.folding_range(editor, platform, view_handle)
+            }) {
+                Ok(requested) => requested,
+                Err(CommandError::LspServerNotRunning) => false,
+                Err(error) => return Err(error),
+            };
+            if !requested {
+                fold_all_indentation_blocks(ctx, view_handle);
+            }
+            Ok(EditorControlFlow::Continue)
+        },
+    },
 ];
 
 fn map(ctx: &mut CommandContext, mode: ModeKind) -> Result<(), CommandError> {
@@ -554,6 +2730,876 @@ fn map(ctx: &mut CommandContext, mode: ModeKind) -> Result<(), CommandError> {
         .map_err(CommandError::KeyMapError)
 }
 
+fn cycle_buffers(ctx: &mut CommandContext, forward: bool) -> Result<(), CommandError> {
+    let buffer_count = ctx.editor.buffers.iter().count();
+    if buffer_count == 0 {
+        return Err(CommandError::NoBufferOpened);
+    }
+
+    let current_index = ctx
+        .current_buffer_handle()
+        .ok()
+        .and_then(|handle| ctx.editor.buffers.iter().position(|b| b.handle() == handle));
+    let offset = ctx.count.max(1) as usize % buffer_count;
+    let index = match current_index {
+        Some(i) if forward => (i + offset) % buffer_count,
+        Some(i) => (i + buffer_count - offset) % buffer_count,
+        None => 0,
+    };
+    let buffer_handle = match ctx.editor.buffers.iter().nth(index) {
+        Some(buffer) => buffer.handle(),
+        None => return Err(CommandError::NoBufferOpened),
+    };
+
+    let client = ctx.clients.get_mut(ctx.client_handle);
+    NavigationHistory::save_client_snapshot(client, &ctx.editor.buffer_views);
+    let buffer_view_handle = ctx
+        .editor
+        .buffer_views
+        .buffer_view_handle_from_buffer_handle(ctx.client_handle, buffer_handle);
+    client.set_buffer_view_handle(Some(buffer_view_handle), &mut ctx.editor.events);
+    Ok(())
+}
+
+fn parse_register_key(key: &str) -> Result<RegisterKey, CommandError> {
+    let mut chars = key.chars();
+    match (chars.next().and_then(RegisterKey::from_char), chars.next()) {
+        (Some(key), None) => Ok(key),
+        _ => Err(CommandError::InvalidRegisterKey),
+    }
+}
+
+fn reset(editor: &mut Editor, what: &str) -> Result<(), CommandError> {
+    let (config, theme, keymaps) = match what {
+        "config" => (true, false, false),
+        "theme" => (false, true, false),
+        "keymaps" => (false, false, true),
+        "all" => (true, true, true),
+        _ => return Err(CommandError::NoSuchResetTarget),
+    };
+
+    if config {
+        editor.config.reset();
+        for (i, &effect) in CONFIG_EFFECTS.iter().enumerate() {
+            if !CONFIG_EFFECTS[..i].contains(&effect) {
+                editor.apply_config_effect(effect);
+            }
+        }
+    }
+    if theme {
+        editor.theme.reset();
+    }
+    if keymaps {
+        editor.keymaps.reset();
+    }
+    Ok(())
+}
+
+fn goto_change(ctx: &mut CommandContext, forward: bool) -> Result<(), CommandError> {
+    if !ctx.editor.config.change_signs {
+        return Err(CommandError::ChangeSignsDisabled);
+    }
+    let handle = ctx.current_buffer_view_handle()?;
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let line_index = buffer_view.cursors.main_cursor().position.line_index as usize;
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+    buffer.update_change_signs();
+
+    let signs = buffer.change_signs();
+    let hunk_starts: Vec<_> = (0..signs.len())
+        .filter(|&i| signs[i] != ChangeSign::None && (i == 0 || signs[i - 1] == ChangeSign::None))
+        .collect();
+    if hunk_starts.is_empty() {
+        ctx.editor
+            .status_bar
+            .write(MessageKind::Info)
+            .str("no changes");
+        return Ok(());
+    }
+
+    let hunk_count = hunk_starts.len();
+    let offset = (ctx.count.max(1) as usize - 1) % hunk_count;
+    let hunk_index = if forward {
+        let next = hunk_starts
+            .iter()
+            .position(|&start| start > line_index)
+            .unwrap_or(0);
+        (next + offset) % hunk_count
+    } else {
+        let previous = hunk_starts
+            .iter()
+            .rposition(|&start| start < line_index)
+            .unwrap_or(hunk_count - 1);
+        (previous + hunk_count - offset) % hunk_count
+    };
+
+    NavigationHistory::save_client_snapshot(
+        ctx.clients.get_mut(ctx.client_handle),
+        &ctx.editor.buffer_views,
+    );
+
+    let position = BufferPosition::line_col(hunk_starts[hunk_index] as _, 0);
+    let mut cursors = ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
+    cursors.clear();
+    cursors.add(Cursor {
+        anchor: position,
+        position,
+    });
+
+    ctx.editor
+        .status_bar
+        .write(MessageKind::Info)
+        .fmt(format_args!("change {}/{}", hunk_index + 1, hunk_count));
+    Ok(())
+}
+
+fn move_lines(ctx: &mut CommandContext, down: bool) -> Result<(), CommandError> {
+    let handle = ctx.current_buffer_view_handle()?;
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+    let last_line_index = buffer.content().line_count() as u32 - 1;
+
+    // cursors touching the same lines move them together as a single block
+    let mut blocks: Vec<(u32, u32)> = Vec::new();
+    for cursor in &buffer_view.cursors[..] {
+        let range = cursor.to_range();
+        match blocks.last_mut() {
+            Some((_, end)) if range.from.line_index <= *end + 1 => {
+                *end = (*end).max(range.to.line_index)
+            }
+            _ => blocks.push((range.from.line_index, range.to.line_index)),
+        }
+    }
+    blocks.retain(|&(start, end)| {
+        if down {
+            end < last_line_index
+        } else {
+            start > 0
+        }
+    });
+
+    let mut text = ctx.editor.string_pool.acquire();
+    for &(start, end) in blocks.iter().rev() {
+        text.clear();
+        let content = buffer.content();
+        let (delete_range, insert_position) = if down {
+            let line = content.line_at(end as usize + 1).as_str();
+            text.push_str(line);
+            text.push('\n');
+            let range = BufferRange::between(
+                BufferPosition::line_col(end, content.line_at(end as _).as_str().len() as _),
+                BufferPosition::line_col(end + 1, line.len() as _),
+            );
+            (range, BufferPosition::line_col(start, 0))
+        } else {
+            let line = content.line_at(start as usize - 1).as_str();
+            text.push('\n');
+            text.push_str(line);
+            let range = BufferRange::between(
+                BufferPosition::line_col(start - 1, 0),
+                BufferPosition::line_col(start, 0),
+            );
+            let end_len = content.line_at(end as _).as_str().len();
+            (range, BufferPosition::line_col(end - 1, end_len as _))
+        };
+
+        buffer.delete_range(
+            &mut ctx.editor.word_database,
+            delete_range,
+            &mut ctx.editor.events,
+        );
+        buffer.insert_text(
+            &mut ctx.editor.word_database,
+            insert_position,
+            &text,
+            &mut ctx.editor.events,
+        );
+    }
+    buffer.commit_edits();
+    ctx.editor.string_pool.release(text);
+
+    let mut moved: Vec<_> = buffer_view.cursors[..].to_vec();
+    for cursor in &mut moved {
+        let line_index = cursor.position.line_index;
+        if blocks
+            .iter()
+            .any(|&(start, end)| start <= line_index && line_index <= end)
+        {
+            if down {
+                cursor.anchor.line_index += 1;
+                cursor.position.line_index += 1;
+            } else {
+                cursor.anchor.line_index -= 1;
+                cursor.position.line_index -= 1;
+            }
+        }
+    }
+
+    ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+
+    let cursors = &mut ctx.editor.buffer_views.get_mut(handle).cursors;
+    let main_cursor_index = cursors.main_cursor_index();
+    let mut cursors = cursors.mut_guard();
+    cursors.clear();
+    for cursor in moved {
+        cursors.add(cursor);
+    }
+    cursors.set_main_cursor_index(main_cursor_index);
+    Ok(())
+}
+
+fn transpose(ctx: &mut CommandContext, words: bool) -> Result<(), CommandError> {
+    // returns the byte ranges of the two pieces of `line` to be swapped
+    fn chars_around(line: &str, column: usize) -> Option<[usize; 4]> {
+        if column == 0 {
+            return None;
+        }
+        let mut after = line[column..].char_indices();
+        let (left, right) = match after.next() {
+            Some((_, c)) => (column, column + c.len_utf8()),
+            None => (line.len(), line.len()),
+        };
+        let mut before = line[..left].char_indices().rev();
+        let (right_start, right_end) = if right > left {
+            (left, right)
+        } else {
+            let (i, _) = before.next()?;
+            (i, left)
+        };
+        let (left_start, _) = before.next()?;
+        Some([left_start, right_start, right_start, right_end])
+    }
+
+    fn words_around(line: &str, column: usize) -> Option<[usize; 4]> {
+        let mut words = Vec::new();
+        let mut start = None;
+        for (i, c) in line.char_indices() {
+            match (WordKind::from_char(c), start) {
+                (WordKind::Identifier, None) => start = Some(i),
+                (WordKind::Identifier, Some(_)) => (),
+                (_, Some(s)) => {
+                    words.push((s, i));
+                    start = None;
+                }
+                (_, None) => (),
+            }
+        }
+        if let Some(s) = start {
+            words.push((s, line.len()));
+        }
+
+        let left = words.iter().rposition(|&(s, _)| s < column)?;
+        let (left_start, left_end) = words[left];
+        let &(right_start, right_end) = words.get(left + 1)?;
+        Some([left_start, left_end, right_start, right_end])
+    }
+
+    let handle = ctx.current_buffer_view_handle()?;
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+    let mut transposed: Vec<_> = buffer_view.cursors[..].to_vec();
+    let mut text = ctx.editor.string_pool.acquire();
+    let mut previous_start = None;
+    for cursor in transposed.iter_mut().rev() {
+        let line_index = cursor.position.line_index;
+        let line = buffer.content().line_at(line_index as _).as_str();
+        let column = cursor.position.column_byte_index as usize;
+        let ranges = if words {
+            words_around(line, column)
+        } else {
+            chars_around(line, column)
+        };
+        let [left_start, left_end, right_start, right_end] = match ranges {
+            Some(ranges) => ranges,
+            None => continue,
+        };
+        if matches!(previous_start, Some(p) if p < (line_index, right_end)) {
+            continue;
+        }
+        previous_start = Some((line_index, left_start));
+
+        text.clear();
+        text.push_str(&line[right_start..right_end]);
+        text.push_str(&line[left_end..right_start]);
+        text.push_str(&line[left_start..left_end]);
+
+        let from = BufferPosition::line_col(line_index, left_start as _);
+        let to = BufferPosition::line_col(line_index, right_end as _);
+        buffer.delete_range(
+            &mut ctx.editor.word_database,
+            BufferRange::between(from, to),
+            &mut ctx.editor.events,
+        );
+        buffer.insert_text(
+            &mut ctx.editor.word_database,
+            from,
+            &text,
+            &mut ctx.editor.events,
+        );
+
+        cursor.anchor = to;
+        cursor.position = to;
+    }
+    buffer.commit_edits();
+    ctx.editor.string_pool.release(text);
+
+    ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+
+    let cursors = &mut ctx.editor.buffer_views.get_mut(handle).cursors;
+    let main_cursor_index = cursors.main_cursor_index();
+    let mut cursors = cursors.mut_guard();
+    cursors.clear();
+    for cursor in transposed {
+        cursors.add(cursor);
+    }
+    cursors.set_main_cursor_index(main_cursor_index);
+    Ok(())
+}
+
+fn delimiter_pair(delimiter: char) -> (char, char) {
+    match delimiter {
+        '(' | ')' => ('(', ')'),
+        '[' | ']' => ('[', ']'),
+        '{' | '}' => ('{', '}'),
+        '<' | '>' => ('<', '>'),
+        _ => (delimiter, delimiter),
+    }
+}
+
+fn parse_delimiter(delimiter: &str) -> Result<char, CommandError> {
+    let mut chars = delimiter.chars();
+    match (chars.next(), chars.next()) {
+        (
+            Some(c @ ('(' | ')' | '[' | ']' | '{' | '}' | '<' | '>' | '|' | '"' | '\'' | '`')),
+            None,
+        ) => Ok(c),
+        _ => Err(CommandError::InvalidDelimiter),
+    }
+}
+
+fn replace_surround(
+    ctx: &mut CommandContext,
+    delimiter: char,
+    replacement: Option<char>,
+) -> Result<(), CommandError> {
+    let handle = ctx.current_buffer_view_handle()?;
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+    let (left, right) = delimiter_pair(delimiter);
+    let mut pairs = Vec::new();
+    for cursor in &buffer_view.cursors[..] {
+        let content = buffer.content();
+        let inner = if left == right {
+            content.find_delimiter_pair_at(cursor.position, left)
+        } else {
+            content.find_balanced_chars_at(cursor.position, left, right)
+        };
+        if let Some(inner) = inner {
+            if !pairs.contains(&inner) {
+                pairs.push(inner);
+            }
+        }
+    }
+
+    // both ends of every pair are edited from the bottom up so earlier positions stay valid
+    let mut ends = Vec::with_capacity(pairs.len() * 2);
+    for inner in pairs {
+        let mut from = inner.from;
+        from.column_byte_index -= left.len_utf8() as BufferPositionIndex;
+        let mut to = inner.to;
+        to.column_byte_index += right.len_utf8() as BufferPositionIndex;
+        ends.push((BufferRange::between(from, inner.from), true));
+        ends.push((BufferRange::between(inner.to, to), false));
+    }
+    ends.sort_unstable_by_key(|(range, _)| range.from);
+
+    let replacement = replacement.map(delimiter_pair);
+    let mut buf = [0; 4];
+    for (range, is_left) in ends.into_iter().rev() {
+        // inserting after the old delimiter keeps cursors that were on it in place
+        if let Some(pair) = replacement {
+            let c = if is_left { pair.0 } else { pair.1 };
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                range.to,
+                c.encode_utf8(&mut buf),
+                &mut ctx.editor.events,
+            );
+        }
+        buffer.delete_range(&mut ctx.editor.word_database, range, &mut ctx.editor.events);
+    }
+    buffer.commit_edits();
+    Ok(())
+}
+
+fn change_case(ctx: &mut CommandContext, conversion: CaseConversion) -> Result<(), CommandError> {
+    let handle = ctx.current_buffer_view_handle()?;
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+    let mut text = ctx.editor.string_pool.acquire();
+    let mut converted = ctx.editor.string_pool.acquire();
+    let mut changed: Vec<Cursor> = Vec::with_capacity(buffer_view.cursors[..].len());
+    for cursor in buffer_view.cursors[..].iter().rev() {
+        let content = buffer.content();
+        let mut range = cursor.to_range();
+        if range.from == range.to {
+            let line = content.line_at(range.from.line_index as _).as_str();
+            match line[range.from.column_byte_index as usize..].chars().next() {
+                Some(c) => range.to.column_byte_index += c.len_utf8() as BufferPositionIndex,
+                None => {
+                    changed.push(*cursor);
+                    continue;
+                }
+            }
+        }
+
+        text.clear();
+        content.append_range_text_to_string(range, &mut text);
+        converted.clear();
+        convert_case(&text, conversion, &mut converted);
+        if converted == text {
+            changed.push(*cursor);
+            continue;
+        }
+
+        buffer.delete_range(&mut ctx.editor.word_database, range, &mut ctx.editor.events);
+        let new_range = buffer.insert_text(
+            &mut ctx.editor.word_database,
+            range.from,
+            &converted,
+            &mut ctx.editor.events,
+        );
+
+        for cursor in &mut changed {
+            cursor.anchor = cursor.anchor.delete(range).insert(new_range);
+            cursor.position = cursor.position.delete(range).insert(new_range);
+        }
+
+        changed.push(if cursor.anchor == cursor.position {
+            *cursor
+        } else if cursor.anchor < cursor.position {
+            Cursor {
+                anchor: new_range.from,
+                position: new_range.to,
+            }
+        } else {
+            Cursor {
+                anchor: new_range.to,
+                position: new_range.from,
+            }
+        });
+    }
+    buffer.commit_edits();
+    ctx.editor.string_pool.release(text);
+    ctx.editor.string_pool.release(converted);
+
+    ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+
+    let cursors = &mut ctx.editor.buffer_views.get_mut(handle).cursors;
+    let main_cursor_index = cursors.main_cursor_index();
+    let mut cursors = cursors.mut_guard();
+    cursors.clear();
+    for cursor in changed.into_iter().rev() {
+        cursors.add(cursor);
+    }
+    cursors.set_main_cursor_index(main_cursor_index);
+    Ok(())
+}
+
+// patterns have no captures so `$$` escapes a literal `$` and references like `$1` are rejected
+fn parse_replacement(replacement: &str) -> Result<String, CommandError> {
+    let mut parsed = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            parsed.push(c);
+            continue;
+        }
+        match chars.clone().next() {
+            Some('$') => {
+                chars.next();
+                parsed.push('$');
+            }
+            Some('0'..='9' | '{') => return Err(CommandError::ReplacementCapture),
+            _ => parsed.push('$'),
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_base(base: Option<&str>) -> Result<Option<u32>, CommandError> {
+    match base {
+        None => Ok(None),
+        Some("2") => Ok(Some(2)),
+        Some("8") => Ok(Some(8)),
+        Some("10") => Ok(Some(10)),
+        Some("16") => Ok(Some(16)),
+        Some(_) => Err(CommandError::InvalidBase),
+    }
+}
+
+fn replace_selections<F>(ctx: &mut CommandContext, mut replace: F) -> Result<(), CommandError>
+where
+    F: FnMut(&str, &mut String) -> Result<bool, CommandError>,
+{
+    let handle = ctx.current_buffer_view_handle()?;
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+    // replace everything at once only after all selections were processed
+    // so an error leaves the buffer untouched
+    let mut text = ctx.editor.string_pool.acquire();
+    let mut replacements = Vec::with_capacity(buffer_view.cursors[..].len());
+    for cursor in &buffer_view.cursors[..] {
+        let range = cursor.to_range();
+        if range.from == range.to {
+            continue;
+        }
+        text.clear();
+        buffer
+            .content()
+            .append_range_text_to_string(range, &mut text);
+        let mut replacement = ctx.editor.string_pool.acquire();
+        match replace(&text, &mut replacement) {
+            Ok(true) => replacements.push((range, replacement)),
+            Ok(false) => ctx.editor.string_pool.release(replacement),
+            Err(error) => {
+                ctx.editor.string_pool.release(replacement);
+                for (_, replacement) in replacements {
+                    ctx.editor.string_pool.release(replacement);
+                }
+                ctx.editor.string_pool.release(text);
+                return Err(error);
+            }
+        }
+    }
+    ctx.editor.string_pool.release(text);
+
+    let mut replaced: Vec<Cursor> = buffer_view.cursors[..].to_vec();
+    for (range, replacement) in replacements.into_iter().rev() {
+        buffer.delete_range(&mut ctx.editor.word_database, range, &mut ctx.editor.events);
+        let new_range = buffer.insert_text(
+            &mut ctx.editor.word_database,
+            range.from,
+            &replacement,
+            &mut ctx.editor.events,
+        );
+        ctx.editor.string_pool.release(replacement);
+
+        for cursor in &mut replaced {
+            if cursor.to_range() == range {
+                let forward = cursor.anchor <= cursor.position;
+                cursor.anchor = if forward {
+                    new_range.from
+                } else {
+                    new_range.to
+                };
+                cursor.position = if forward {
+                    new_range.to
+                } else {
+                    new_range.from
+                };
+            } else if cursor.to_range().from >= range.to {
+                cursor.anchor = cursor.anchor.delete(range).insert(new_range);
+                cursor.position = cursor.position.delete(range).insert(new_range);
+            }
+        }
+    }
+    buffer.commit_edits();
+
+    ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+
+    let cursors = &mut ctx.editor.buffer_views.get_mut(handle).cursors;
+    let main_cursor_index = cursors.main_cursor_index();
+    let mut cursors = cursors.mut_guard();
+    cursors.clear();
+    for cursor in replaced {
+        cursors.add(cursor);
+    }
+    cursors.set_main_cursor_index(main_cursor_index);
+    Ok(())
+}
+
+fn fold_lines<F>(ctx: &mut CommandContext, mut should_fold: F) -> Result<(), CommandError>
+where
+    F: FnMut(bool) -> bool,
+{
+    let handle = ctx.current_buffer_view_handle()?;
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+    let tab_size = ctx.editor.config.tab_size;
+
+    let mut previous_line_index = None;
+    for cursor in &buffer_view.cursors[..] {
+        let line_index = cursor.position.line_index;
+        if previous_line_index == Some(line_index) {
+            continue;
+        }
+        previous_line_index = Some(line_index);
+
+        let folded = buffer.folds().fold_at(line_index).is_some();
+        if should_fold(folded) {
+            if let Some(end) =
+                fold::indentation_block_end(buffer.content(), line_index as _, tab_size)
+            {
+                buffer.folds_mut().add(line_index, end as _);
+            }
+        } else {
+            buffer.folds_mut().remove_at(line_index);
+        }
+    }
+
+    // cursors hidden by a new fold are moved to its first line so it does not open right away
+    let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+    let folds = buffer.folds();
+    let mut cursors = ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
+    for cursor in &mut cursors[..] {
+        for position in [&mut cursor.anchor, &mut cursor.position] {
+            let line_index = folds.from_visible_line(folds.to_visible_line(position.line_index));
+            if line_index != position.line_index {
+                *position = buffer.content().saturate_position(BufferPosition::line_col(
+                    line_index,
+                    position.column_byte_index,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn fold_all_indentation_blocks(ctx: &mut CommandContext, handle: BufferViewHandle) {
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+    let tab_size = ctx.editor.config.tab_size;
+
+    let mut ranges = Vec::new();
+    for line_index in 0..buffer.content().line_count() {
+        if let Some(end) = fold::indentation_block_end(buffer.content(), line_index, tab_size) {
+            ranges.push((line_index as _, end as _));
+        }
+    }
+    buffer
+        .folds_mut()
+        .add_all(&mut ranges, &buffer_view.cursors[..]);
+}
+
+fn parse_select_flag(ctx: &mut CommandContext) -> Result<CursorMovementKind, CommandError> {
+    let mut flags = [None; 1];
+    ctx.args.get_flags(&mut flags)?;
+    let movement_kind = match flags[0] {
+        Some(_) => CursorMovementKind::PositionOnly,
+        None => CursorMovementKind::PositionAndAnchor,
+    };
+    ctx.args.assert_empty()?;
+    Ok(movement_kind)
+}
+
+fn move_cursors_with_count(
+    ctx: &mut CommandContext,
+    movement: fn(usize) -> CursorMovement,
+) -> Result<(), CommandError> {
+    let movement_kind = parse_select_flag(ctx)?;
+    let movement = movement(ctx.count.max(1) as _);
+
+    let handle = ctx.current_buffer_view_handle()?;
+    if movement.is_large() {
+        NavigationHistory::save_client_jump_snapshot(
+            ctx.clients.get_mut(ctx.client_handle),
+            &ctx.editor.buffer_views,
+            &ctx.editor.config,
+        );
+    }
+
+    ctx.editor.buffer_views.get_mut(handle).move_cursors(
+        &ctx.editor.buffers,
+        movement,
+        movement_kind,
+        ctx.editor.config.tab_size,
+    );
+    Ok(())
+}
+
+fn move_cursors_by_sentences(ctx: &mut CommandContext, forward: bool) -> Result<(), CommandError> {
+    let movement_kind = parse_select_flag(ctx)?;
+
+    let count = ctx.count.max(1) as usize;
+    let handle = ctx.current_buffer_view_handle()?;
+
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle).content();
+    let abbreviations = &ctx.editor.config.sentence_abbreviations[..];
+    let from = buffer_view.cursors.main_cursor().position;
+    let mut to = from;
+    for _ in 0..count {
+        to = if forward {
+            buffer.find_sentence_start_after(to, abbreviations)
+        } else {
+            buffer.find_sentence_start_before(to, abbreviations)
+        };
+    }
+    if CursorMovement::is_large_distance(count, from, to) {
+        NavigationHistory::save_client_jump_snapshot(
+            ctx.clients.get_mut(ctx.client_handle),
+            &ctx.editor.buffer_views,
+            &ctx.editor.config,
+        );
+    }
+
+    let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+    let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle).content();
+    let abbreviations = &ctx.editor.config.sentence_abbreviations[..];
+
+    let mut cursors = buffer_view.cursors.mut_guard();
+    for cursor in &mut cursors[..] {
+        for _ in 0..count {
+            cursor.position = if forward {
+                buffer.find_sentence_start_after(cursor.position, abbreviations)
+            } else {
+                buffer.find_sentence_start_before(cursor.position, abbreviations)
+            };
+        }
+        if let CursorMovementKind::PositionAndAnchor = movement_kind {
+            cursor.anchor = cursor.position;
+        }
+    }
+    Ok(())
+}
+
+fn scroll_view_to_main_cursor(
+    ctx: &mut CommandContext,
+    lines_above_cursor: fn(u16) -> u16,
+) -> Result<(), CommandError> {
+    let handle = ctx.current_buffer_view_handle()?;
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let line_index = buffer_view.cursors.main_cursor().position.line_index;
+
+    let client = ctx.clients.get_mut(ctx.client_handle);
+    client.scroll.0 = 0;
+    client.scroll.1 = line_index.saturating_sub(lines_above_cursor(client.height) as _);
+    Ok(())
+}
+
+fn scroll_pages(
+    ctx: &mut CommandContext,
+    forward: bool,
+    full_page: bool,
+) -> Result<(), CommandError> {
+    let handle = ctx.current_buffer_view_handle()?;
+    let client = ctx.clients.get_mut(ctx.client_handle);
+    let page_height = if full_page {
+        client.height
+    } else {
+        client.height / 2
+    };
+    let line_count = page_height.max(1) as usize * ctx.count.max(1) as usize;
+
+    let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+    let last_line_index = ctx
+        .editor
+        .buffers
+        .get(buffer_view.buffer_handle)
+        .content()
+        .line_count()
+        - 1;
+    let scroll = client.scroll.1 as usize;
+    client.scroll.1 = if forward {
+        (scroll + line_count).min(last_line_index) as _
+    } else {
+        scroll.saturating_sub(line_count) as _
+    };
+
+    let movement = if forward {
+        CursorMovement::LinesForward(line_count)
+    } else {
+        CursorMovement::LinesBackward(line_count)
+    };
+    buffer_view.move_cursors(
+        &ctx.editor.buffers,
+        movement,
+        CursorMovementKind::PositionAndAnchor,
+        ctx.editor.config.tab_size,
+    );
+    Ok(())
+}
+
+fn search_from_register(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    ctx.editor.aux_pattern.clear();
+    for buffer in ctx.editor.buffers.iter_mut() {
+        buffer.set_search(&ctx.editor.aux_pattern, ctx.editor.search_options);
+    }
+
+    if ctx.current_buffer_view_handle().is_ok() {
+        move_to_search_match(ctx, true, false)?;
+    }
+    Ok(())
+}
+
+fn move_to_search_match(
+    ctx: &mut CommandContext,
+    forward: bool,
+    select: bool,
+) -> Result<(), CommandError> {
+    let handle = ctx.current_buffer_view_handle()?;
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+    if buffer.search_ranges().is_empty() {
+        let search = ctx.editor.registers.get(SEARCH_REGISTER);
+        if search.is_empty() {
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .str("no search pattern");
+            return Ok(());
+        }
+        ctx.editor
+            .search_options
+            .compile_searcher(&mut ctx.editor.aux_pattern, search)
+            .map_err(CommandError::PatternError)?;
+        buffer.set_search(&ctx.editor.aux_pattern, ctx.editor.search_options);
+    }
+
+    let search_ranges = buffer.search_ranges();
+    if search_ranges.is_empty() {
+        ctx.editor
+            .status_bar
+            .write(MessageKind::Info)
+            .str("no search result");
+        return Ok(());
+    }
+
+    let len = search_ranges.len();
+    let count = ctx.count.max(1) as usize;
+    let main_position = buffer_view.cursors.main_cursor().position;
+    let index = match search_ranges.binary_search_by_key(&main_position, |r| r.from) {
+        Ok(i) if forward => (i + count) % len,
+        Err(i) if forward => (i + count - 1) % len,
+        Ok(i) | Err(i) => (i + len - count % len) % len,
+    };
+    let range = search_ranges[index];
+
+    NavigationHistory::save_client_snapshot(
+        ctx.clients.get_mut(ctx.client_handle),
+        &ctx.editor.buffer_views,
+    );
+
+    let mut cursors = ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
+    let main_cursor = cursors.main_cursor();
+    main_cursor.anchor = range.from;
+    main_cursor.position = if select { range.to } else { range.from };
+    drop(cursors);
+
+    ctx.editor.mode.normal_state.search_index = index;
+    ctx.editor
+        .status_bar
+        .write(MessageKind::Info)
+        .fmt(format_args!("{} of {}", index + 1, len));
+    Ok(())
+}
+
 fn current_buffer_and_main_cursor(
     ctx: &CommandContext,
 ) -> Result<(BufferHandle, Cursor), CommandError> {
@@ -593,3 +3639,115 @@ where
     }
 }
 
+fn write_interpolated_registers(
+    write: &mut EditorOutputWrite,
+    registers: &RegisterCollection,
+    text: &str,
+) {
+    let mut chars = text.char_indices();
+    let mut start = 0;
+    while let Some((i, c)) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        let key = match chars.clone().next() {
+            Some((_, c)) => c,
+            None => break,
+        };
+        let register = match key {
+            '%' => None,
+            _ => match RegisterKey::from_char(key) {
+                Some(key) => Some(registers.get(key)),
+                None => continue,
+            },
+        };
+
+        chars.next();
+        write.str(&text[start..i]);
+        match register {
+            Some(register) => write.str(register),
+            None => write.str("%"),
+        }
+        start = i + 2;
+    }
+    write.str(&text[start..]);
+}
+
+// saves right away unless the buffer has to be formatted first, in which case it's only
+// saved once formatting finishes. returns whether it was saved right away
+fn format_and_save_buffer(
+    ctx: &mut CommandContext,
+    buffer_handle: BufferHandle,
+    new_path: Option<&Path>,
+    save_if_format_fails: bool,
+) -> Result<bool, CommandError> {
+    let buffer = ctx.editor.buffers.get(buffer_handle);
+    let path = new_path.unwrap_or(&buffer.path);
+    let formatters = &ctx.editor.formatters;
+    let recipe = path.to_str().and_then(|p| formatters.find(p));
+    let should_format = (buffer.needs_save() || new_path.is_some())
+        && (buffer.capabilities.format_on_save || matches!(recipe, Some(r) if r.on_save));
+    let formatter_command = match recipe {
+        Some(recipe) if should_format => Some(ctx.editor.string_pool.acquire_with(&recipe.command)),
+        _ => None,
+    };
+
+    if should_format {
+        let save = PendingSave {
+            new_path: new_path.map(Into::into),
+            save_if_format_fails,
+        };
+
+        // batch mode has no event loop to receive lsp responses
+        let can_format_with_lsp = !ctx.editor.batch_mode
+            && access_lsp(ctx, buffer_handle, |_, _, _, client| client.can_format())
+                .unwrap_or(false);
+        if can_format_with_lsp {
+            if let Some(command) = formatter_command {
+                ctx.editor.string_pool.release(command);
+            }
+            access_lsp(ctx, buffer_handle, |editor, platform, _, client| {
+                client.formatting(editor, platform, buffer_handle, Some(save))
+            })?;
+            return Ok(false);
+        }
+
+        match formatter_command {
+            Some(command_text) => {
+                let command = parse_process_command(&command_text, ctx.platform.environment());
+                ctx.editor.string_pool.release(command_text);
+                let command = command.ok_or(CommandError::TooFewArguments)?;
+                if ctx.editor.batch_mode {
+                    // there is no event loop to finish an async save, so format in place
+                    let result = ctx.editor.buffers.run_pipe_process_blocking(
+                        &mut ctx.editor.word_database,
+                        command,
+                        buffer_handle,
+                        &mut ctx.editor.events,
+                    );
+                    match result {
+                        Ok(()) => (),
+                        Err(_) if save_if_format_fails => (),
+                        Err(error) => return Err(CommandError::FormatterFailed(error)),
+                    }
+                } else {
+                    ctx.editor.buffers.spawn_pipe_process(
+                        ctx.platform,
+                        command,
+                        buffer_handle,
+                        Some(save),
+                    );
+                    return Ok(false);
+                }
+            }
+            None if !save_if_format_fails => return Err(CommandError::NoFormatter),
+            None => (),
+        }
+    }
+
+    let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+    buffer
+        .save_to_file(new_path, &mut ctx.editor.events)
+        .map_err(CommandError::IoError)?;
+    Ok(true)
+}
@@ -1,4 +1,4 @@
-use std::{fmt, num::NonZeroU8};
+use std::{fmt, num::NonZeroU8, str::FromStr};
 
 pub enum ParseConfigError {
     NoSuchConfig,
@@ -13,24 +13,39 @@ impl fmt::Display for ParseConfigError {
     }
 }
 
+// what else needs updating once a config changes, declared with `=> <effect>` after its default
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ConfigEffect {
+    None,
+    RefreshSyntaxes,
+    RefreshPicker,
+}
+
 macro_rules! config_values {
-    ($($name:ident: $type:ty = $default:expr,)*) => {
+    ($($name:ident: $type:ty = $default:expr $(=> $effect:ident)?,)*) => {
         pub static CONFIG_NAMES: &[&str] = &[$(stringify!($name),)*];
+        pub static CONFIG_EFFECTS: &[ConfigEffect] = &[$($(ConfigEffect::$effect,)?)*];
 
         pub struct Config {
             $(pub $name: $type,)*
         }
 
         impl Config {
-            pub fn parse_config(&mut self, key: &str, value: &str) -> Result<(), ParseConfigError> {
+            pub fn parse_config(
+                &mut self,
+                key: &str,
+                value: &str,
+            ) -> Result<ConfigEffect, ParseConfigError> {
                 match key {
                     $(stringify!($name) => match value.parse() {
-                        Ok(value) => self.$name = value,
-                        Err(_) => return Err(ParseConfigError::InvalidValue),
+                        Ok(value) => {
+                            self.$name = value;
+                            Ok([$(ConfigEffect::$effect,)? ConfigEffect::None][0])
+                        }
+                        Err(_) => Err(ParseConfigError::InvalidValue),
                     },)*
-                    _ => return Err(ParseConfigError::NoSuchConfig),
+                    _ => Err(ParseConfigError::NoSuchConfig),
                 }
-                Ok(())
             }
 
             pub fn display_config(&self, key: &str) -> Option<DisplayConfig> {
@@ -65,6 +80,97 @@ macro_rules! config_values {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    Relative,
+    Hybrid,
+}
+impl FromStr for LineNumbers {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "absolute" => Ok(Self::Absolute),
+            "relative" => Ok(Self::Relative),
+            "hybrid" => Ok(Self::Hybrid),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for LineNumbers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Off => f.write_str("off"),
+            Self::Absolute => f.write_str("absolute"),
+            Self::Relative => f.write_str("relative"),
+            Self::Hybrid => f.write_str("hybrid"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RenderWhitespace {
+    Off,
+    Trailing,
+    All,
+}
+impl FromStr for RenderWhitespace {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "trailing" => Ok(Self::Trailing),
+            "all" => Ok(Self::All),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for RenderWhitespace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Off => f.write_str("off"),
+            Self::Trailing => f.write_str("trailing"),
+            Self::All => f.write_str("all"),
+        }
+    }
+}
+
+impl Config {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    // the column after the line numbers also holds the diagnostic marker,
+    // so it is kept even with line numbers off when there are diagnostics
+    pub fn gutter_width(&self, line_count: usize, has_diagnostics: bool) -> u16 {
+        let number_width = match self.line_numbers {
+            LineNumbers::Off => has_diagnostics as u16,
+            _ => {
+                let mut digit_count = 1;
+                let mut n = line_count;
+                while n >= 10 {
+                    n /= 10;
+                    digit_count += 1;
+                }
+                digit_count + 1
+            }
+        };
+        number_width + self.change_signs as u16
+    }
+
+    pub fn soft_wrap_width(&self, viewport_width: u16) -> Option<usize> {
+        if !self.soft_wrap || viewport_width == 0 {
+            None
+        } else if self.wrap_column > 0 && self.wrap_column < viewport_width {
+            Some(self.wrap_column as _)
+        } else {
+            Some(viewport_width as _)
+        }
+    }
+}
+
 config_values! {
     tab_size: NonZeroU8 = NonZeroU8::new(4).unwrap(),
     indent_with_tabs: bool = true,
@@ -73,7 +179,35 @@ config_values! {
     visual_space: u8 = b'.',
     visual_tab_first: u8 = b'|',
     visual_tab_repeat: u8 = b' ',
+    render_whitespace: RenderWhitespace = RenderWhitespace::Off,
+    indent_guides: bool = false,
+
+    highlight_current_word: bool = false,
+    highlight_trailing_whitespace: bool = false,
+    highlight_mixed_indent: bool = false,
 
+    scroll_margin: u8 = 0,
+    side_scroll_margin: u8 = 0,
+    line_numbers: LineNumbers = LineNumbers::Off,
+    change_signs: bool = false,
+    color_column: u16 = 0,
+    soft_wrap: bool = false,
+    wrap_column: u16 = 0,
+    status_line_format: String = String::from("%m%f:%l,%c"),
+
+    search_with_pattern: bool = false,
+    jumplist: bool = true,
+    syntax_max_line_length: u32 = 10000 => RefreshSyntaxes,
+    syntax_detect_shebang: bool = true => RefreshSyntaxes,
+
+    sentence_abbreviations: String = String::from("e.g i.e etc vs mr mrs ms dr"),
+
+    word_database_skip_comments: bool = false => RefreshSyntaxes,
     completion_min_len: u8 = 3,
     picker_max_height: u8 = 8,
+    picker_fuzzy_matching: bool = true => RefreshPicker,
+    auto_source_project_config: bool = false,
+
+    lsp_raw_markdown: bool = false,
+    lsp_root_markers: String = String::from(".git Cargo.toml package.json go.mod pyproject.toml"),
 }
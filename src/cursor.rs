@@ -69,6 +69,22 @@ impl CursorCollection {
         &self.cursors[self.main_cursor_index as usize]
     }
 
+    pub fn set_main_cursor_index(&mut self, index: usize) {
+        self.main_cursor_index = index.min(self.len as usize - 1) as _;
+    }
+
+    pub fn rotate_main_cursor(&mut self, offset: usize, forward: bool) {
+        let len = self.len as usize;
+        let offset = offset % len;
+        let index = self.main_cursor_index as usize;
+        let index = if forward {
+            (index + offset) % len
+        } else {
+            (index + len - offset) % len
+        };
+        self.main_cursor_index = index as _;
+    }
+
     pub fn mut_guard(&mut self) -> CursorCollectionMutGuard {
         CursorCollectionMutGuard {
             inner: self,
@@ -77,32 +93,32 @@ impl CursorCollection {
     }
 
     fn sort_and_merge(&mut self) {
-        let main_cursor = self.cursors[self.main_cursor_index as usize];
-        self.cursors[..self.len as usize].sort_unstable_by_key(|c| c.to_range().from);
-        self.main_cursor_index = self.cursors[..self.len as usize]
-            .binary_search_by_key(&main_cursor.position, |c| c.position)
-            .unwrap_or(0) as _;
+        let cursors = &mut self.cursors[..self.len as usize];
+        let main_cursor = cursors[self.main_cursor_index as usize];
+        cursors.sort_unstable_by_key(|c| c.to_range().from);
+        let main_cursor_index = cursors.iter().position(|c| *c == main_cursor).unwrap_or(0);
 
+        let mut len = 0;
         let mut i = 0;
-        while i < self.len {
-            let mut range = self.cursors[i as usize].to_range();
-            for j in ((i + 1)..self.len).rev() {
-                let other_range = self.cursors[j as usize].to_range();
-                if range.from <= other_range.from && other_range.from <= range.to {
-                    range.to = range.to.max(other_range.to);
-
-                    self.cursors
-                        .copy_within((j + 1) as usize..self.len as usize, j as _);
-                    self.len -= 1;
-
-                    if j <= self.main_cursor_index {
-                        self.main_cursor_index -= 1;
-                    }
+        while i < cursors.len() {
+            let forward = cursors[i].anchor <= cursors[i].position;
+            let mut range = cursors[i].to_range();
+
+            let mut j = i + 1;
+            while j < cursors.len() {
+                let other_range = cursors[j].to_range();
+                if range.to < other_range.from {
+                    break;
                 }
+                range.to = range.to.max(other_range.to);
+                j += 1;
             }
 
-            let cursor = &mut self.cursors[i as usize];
-            *cursor = if cursor.anchor <= cursor.position {
+            if (i..j).contains(&main_cursor_index) {
+                self.main_cursor_index = len as _;
+            }
+
+            cursors[len] = if forward {
                 Cursor {
                     anchor: range.from,
                     position: range.to,
@@ -114,8 +130,11 @@ impl CursorCollection {
                 }
             };
 
-            i += 1;
+            len += 1;
+            i = j;
         }
+
+        self.len = len as _;
     }
 }
 
@@ -320,6 +339,104 @@ mod tests {
         assert!(cursors.next().is_none());
     }
 
+    #[test]
+    fn merge_overlapping_added_cursors() {
+        let mut cursors = CursorCollection::new();
+        let mut cursors_mut = cursors.mut_guard();
+        cursors_mut.clear();
+        cursors_mut.add(Cursor {
+            anchor: BufferPosition::line_col(0, 2),
+            position: BufferPosition::line_col(0, 6),
+        });
+        cursors_mut.add(Cursor {
+            anchor: BufferPosition::line_col(0, 4),
+            position: BufferPosition::line_col(1, 1),
+        });
+        drop(cursors_mut);
+
+        assert_eq!(1, cursors[..].len());
+        let cursor = cursors.main_cursor();
+        assert_eq!(BufferPosition::line_col(0, 2), cursor.anchor);
+        assert_eq!(BufferPosition::line_col(1, 1), cursor.position);
+    }
+
+    #[test]
+    fn merge_chained_overlapping_cursors() {
+        let mut cursors = CursorCollection::new();
+        let mut cursors_mut = cursors.mut_guard();
+        cursors_mut.clear();
+        for &(from, to) in &[(8, 12), (0, 5), (20, 21), (4, 10)] {
+            cursors_mut.add(Cursor {
+                anchor: BufferPosition::line_col(0, from),
+                position: BufferPosition::line_col(0, to),
+            });
+        }
+        cursors_mut.set_main_cursor_index(0);
+        drop(cursors_mut);
+
+        assert_eq!(2, cursors[..].len());
+        assert_eq!(BufferPosition::line_col(0, 0), cursors[0].anchor);
+        assert_eq!(BufferPosition::line_col(0, 12), cursors[0].position);
+        assert_eq!(BufferPosition::line_col(0, 20), cursors[1].anchor);
+        assert_eq!(BufferPosition::line_col(0, 21), cursors[1].position);
+        assert_eq!(0, cursors.main_cursor_index());
+    }
+
+    #[test]
+    fn keep_main_cursor_after_sort() {
+        let mut cursors = CursorCollection::new();
+        let mut cursors_mut = cursors.mut_guard();
+        cursors_mut.clear();
+        cursors_mut.add(Cursor {
+            anchor: BufferPosition::line_col(3, 0),
+            position: BufferPosition::line_col(1, 0),
+        });
+        cursors_mut.add(Cursor {
+            anchor: BufferPosition::line_col(0, 0),
+            position: BufferPosition::line_col(0, 5),
+        });
+        cursors_mut.add(Cursor {
+            anchor: BufferPosition::line_col(2, 0),
+            position: BufferPosition::line_col(2, 0),
+        });
+        cursors_mut.set_main_cursor_index(2);
+        drop(cursors_mut);
+
+        assert_eq!(2, cursors[..].len());
+        assert_eq!(1, cursors.main_cursor_index());
+        let cursor = cursors.main_cursor();
+        assert_eq!(BufferPosition::line_col(3, 0), cursor.anchor);
+        assert_eq!(BufferPosition::line_col(1, 0), cursor.position);
+    }
+
+    #[test]
+    fn rotate_main_cursor() {
+        let mut cursors = CursorCollection::new();
+        let mut cursors_mut = cursors.mut_guard();
+        cursors_mut.clear();
+        for line_index in 0..3 {
+            cursors_mut.add(Cursor {
+                anchor: BufferPosition::line_col(line_index, 0),
+                position: BufferPosition::line_col(line_index, 0),
+            });
+        }
+        drop(cursors_mut);
+
+        assert_eq!(2, cursors.main_cursor_index());
+        cursors.rotate_main_cursor(1, true);
+        assert_eq!(0, cursors.main_cursor_index());
+        cursors.rotate_main_cursor(1, true);
+        assert_eq!(1, cursors.main_cursor_index());
+        cursors.rotate_main_cursor(1, false);
+        cursors.rotate_main_cursor(1, false);
+        assert_eq!(2, cursors.main_cursor_index());
+        cursors.rotate_main_cursor(5, true);
+        assert_eq!(1, cursors.main_cursor_index());
+
+        cursors.set_main_cursor_index(10);
+        assert_eq!(2, cursors.main_cursor_index());
+    }
+
     #[test]
     fn no_merge_cursor() {
         let mut cursors = CursorCollection::new();
@@ -0,0 +1,169 @@
+use std::{
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub static DEFAULT_DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+static WEEKDAY_NAMES: &[&str] = &[
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+static MONTH_NAMES: &[&str] = &[
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+// utc only as there's no portable way to get the local timezone from std
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub weekday: u32,
+    pub unix_seconds: i64,
+}
+
+impl DateTime {
+    pub fn now() -> Self {
+        let seconds = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(error) => -(error.duration().as_secs() as i64),
+        };
+        Self::from_unix_seconds(seconds)
+    }
+
+    pub fn from_unix_seconds(unix_seconds: i64) -> Self {
+        let days = unix_seconds.div_euclid(86400);
+        let seconds_of_day = unix_seconds.rem_euclid(86400) as u32;
+
+        // days to civil date from Howard Hinnant's 'chrono-compatible low-level date algorithms'
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        } as u32;
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self {
+            year,
+            month,
+            day,
+            hour: seconds_of_day / 3600,
+            minute: seconds_of_day / 60 % 60,
+            second: seconds_of_day % 60,
+            weekday: (days + 3).rem_euclid(7) as u32,
+            unix_seconds,
+        }
+    }
+
+    pub fn day_of_year(&self) -> u32 {
+        static DAYS_BEFORE_MONTH: [u32; 12] =
+            [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+        let is_leap_year = self.year % 4 == 0 && (self.year % 100 != 0 || self.year % 400 == 0);
+        let leap_day = if is_leap_year && self.month > 2 { 1 } else { 0 };
+        DAYS_BEFORE_MONTH[self.month as usize - 1] + leap_day + self.day
+    }
+
+    pub fn format(&self, format: &str, output: &mut String) {
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                output.push(c);
+                continue;
+            }
+
+            let _ = match chars.next() {
+                Some('Y') => write!(output, "{}", self.year),
+                Some('y') => write!(output, "{:02}", self.year.rem_euclid(100)),
+                Some('m') => write!(output, "{:02}", self.month),
+                Some('d') => write!(output, "{:02}", self.day),
+                Some('j') => write!(output, "{:03}", self.day_of_year()),
+                Some('H') => write!(output, "{:02}", self.hour),
+                Some('M') => write!(output, "{:02}", self.minute),
+                Some('S') => write!(output, "{:02}", self.second),
+                Some('s') => write!(output, "{}", self.unix_seconds),
+                Some('A') => write!(output, "{}", WEEKDAY_NAMES[self.weekday as usize]),
+                Some('a') => write!(output, "{}", &WEEKDAY_NAMES[self.weekday as usize][..3]),
+                Some('B') => write!(output, "{}", MONTH_NAMES[self.month as usize - 1]),
+                Some('b') => write!(output, "{}", &MONTH_NAMES[self.month as usize - 1][..3]),
+                Some('%') => write!(output, "%"),
+                Some(c) => write!(output, "%{}", c),
+                None => write!(output, "%"),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(unix_seconds: i64, format: &str) -> String {
+        let mut output = String::new();
+        DateTime::from_unix_seconds(unix_seconds).format(format, &mut output);
+        output
+    }
+
+    #[test]
+    fn from_unix_seconds() {
+        assert_eq!(
+            DateTime {
+                year: 1970,
+                month: 1,
+                day: 1,
+                hour: 0,
+                minute: 0,
+                second: 0,
+                weekday: 3,
+                unix_seconds: 0,
+            },
+            DateTime::from_unix_seconds(0)
+        );
+        assert_eq!(
+            "2000-02-29T23:59:59Z",
+            format(951868799, DEFAULT_DATETIME_FORMAT)
+        );
+        assert_eq!(
+            "2024-12-31T12:30:05Z",
+            format(1735648205, DEFAULT_DATETIME_FORMAT)
+        );
+        assert_eq!("1969-12-31T23:59:59Z", format(-1, DEFAULT_DATETIME_FORMAT));
+    }
+
+    #[test]
+    fn format_specifiers() {
+        assert_eq!(
+            "Thursday Thu January Jan 70 001",
+            format(0, "%A %a %B %b %y %j")
+        );
+        assert_eq!("366 Tue", format(1735648205, "%j %a"));
+        assert_eq!("100% %q %", format(0, "100%% %q %"));
+    }
+}
@@ -0,0 +1,352 @@
+use std::fmt::Write;
+
+pub static DIFF_BUFFER_PATH: &str = "[diff]";
+
+const CONTEXT_LEN: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+// myers diff, keeping only the `-d - 1..=d + 1` window of each round's furthest reaching paths
+// so we can backtrack. round `d` window is stored at `trace[d * (d + 2)..(d + 1) * (d + 3)]`
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let old_len = old.len() as isize;
+    let new_len = new.len() as isize;
+    let max = old_len + new_len;
+    let offset = max as usize + 1;
+
+    let mut furthest = vec![0isize; 2 * offset + 1];
+    let mut trace = Vec::new();
+    let mut last_d = 0;
+
+    'search: for d in 0..=max {
+        let window = offset - d as usize - 1..=offset + d as usize + 1;
+        trace.extend_from_slice(&furthest[window]);
+        last_d = d;
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset as isize) as usize;
+            let mut x = if k == -d || (k != d && furthest[index - 1] < furthest[index + 1]) {
+                furthest[index + 1]
+            } else {
+                furthest[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < old_len && y < new_len && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[index] = x;
+            if x >= old_len && y >= new_len {
+                break 'search;
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    let mut x = old_len;
+    let mut y = new_len;
+    for d in (0..=last_d).rev() {
+        let window_start = (d * (d + 2)) as usize;
+        let furthest = &trace[window_start..((d + 1) * (d + 3)) as usize];
+        let at = |k: isize| furthest[(k + d + 1) as usize];
+
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Equal(x as _, y as _));
+        }
+        if d > 0 {
+            if x == previous_x {
+                edits.push(Edit::Insert(previous_y as _));
+            } else {
+                edits.push(Edit::Delete(previous_x as _));
+            }
+        }
+
+        x = previous_x;
+        y = previous_y;
+    }
+
+    edits.reverse();
+    edits
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeSign {
+    None,
+    Added,
+    Modified,
+    Deleted,
+}
+
+pub fn change_signs(old: &[&str], new: &[&str], signs: &mut Vec<ChangeSign>) {
+    signs.clear();
+    signs.resize(new.len(), ChangeSign::None);
+
+    let edits = edit_script(old, new);
+    let mut new_index: usize = 0;
+    let mut i = 0;
+    while i < edits.len() {
+        if let Edit::Equal(..) = edits[i] {
+            new_index += 1;
+            i += 1;
+            continue;
+        }
+
+        let mut deleted_count = 0;
+        let inserted_start = new_index;
+        while i < edits.len() {
+            match edits[i] {
+                Edit::Equal(..) => break,
+                Edit::Delete(_) => deleted_count += 1,
+                Edit::Insert(_) => new_index += 1,
+            }
+            i += 1;
+        }
+
+        if inserted_start == new_index {
+            // deleted lines are shown on the line right above them
+            if let Some(sign) = signs.get_mut(new_index.saturating_sub(1)) {
+                if *sign == ChangeSign::None {
+                    *sign = ChangeSign::Deleted;
+                }
+            }
+        } else {
+            let sign = if deleted_count > 0 {
+                ChangeSign::Modified
+            } else {
+                ChangeSign::Added
+            };
+            for s in &mut signs[inserted_start..new_index] {
+                *s = sign;
+            }
+        }
+    }
+}
+
+fn write_hunk_range(output: &mut String, start: usize, len: usize) {
+    match len {
+        0 => {
+            let _ = write!(output, "{},0", start);
+        }
+        1 => {
+            let _ = write!(output, "{}", start + 1);
+        }
+        _ => {
+            let _ = write!(output, "{},{}", start + 1, len);
+        }
+    }
+}
+
+pub fn unified_diff(path: &str, old: &[&str], new: &[&str], output: &mut String) -> bool {
+    let edits = edit_script(old, new);
+    let is_change = |e: &Edit| !matches!(e, Edit::Equal(..));
+
+    let mut hunk_start = match edits.iter().position(is_change) {
+        Some(index) => index.saturating_sub(CONTEXT_LEN),
+        None => return false,
+    };
+
+    let _ = writeln!(output, "--- {}", path);
+    let _ = writeln!(output, "+++ {}", path);
+
+    let mut old_index = 0;
+    let mut new_index = 0;
+    for edit in &edits[..hunk_start] {
+        if let Edit::Equal(..) = edit {
+            old_index += 1;
+            new_index += 1;
+        }
+    }
+
+    loop {
+        let mut hunk_end = hunk_start;
+        let mut equal_run = 0;
+        for (i, edit) in edits.iter().enumerate().skip(hunk_start) {
+            if is_change(edit) {
+                equal_run = 0;
+                hunk_end = i + 1;
+            } else {
+                equal_run += 1;
+                if equal_run > 2 * CONTEXT_LEN {
+                    break;
+                }
+            }
+        }
+        let hunk_end = (hunk_end + CONTEXT_LEN).min(edits.len());
+        let hunk = &edits[hunk_start..hunk_end];
+
+        let old_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Insert(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Delete(_)))
+            .count();
+
+        output.push_str("@@ -");
+        write_hunk_range(output, old_index, old_len);
+        output.push_str(" +");
+        write_hunk_range(output, new_index, new_len);
+        output.push_str(" @@\n");
+
+        for edit in hunk {
+            let (prefix, line) = match *edit {
+                Edit::Equal(i, _) => (' ', old[i]),
+                Edit::Delete(i) => ('-', old[i]),
+                Edit::Insert(i) => ('+', new[i]),
+            };
+            output.push(prefix);
+            output.push_str(line);
+            output.push('\n');
+        }
+
+        old_index += old_len;
+        new_index += new_len;
+
+        match edits[hunk_end..].iter().position(is_change) {
+            Some(index) => {
+                let next_start = hunk_end + index - CONTEXT_LEN.min(index);
+                for _ in hunk_end..next_start {
+                    old_index += 1;
+                    new_index += 1;
+                }
+                hunk_start = next_start;
+            }
+            None => break,
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> String {
+        let old: Vec<_> = old.lines().collect();
+        let new: Vec<_> = new.lines().collect();
+        let mut output = String::new();
+        unified_diff("file", &old, &new, &mut output);
+        output
+    }
+
+    #[test]
+    fn edit_script_of_small_texts() {
+        assert_eq!(Vec::<Edit>::new(), edit_script(&[], &[]));
+        assert_eq!(vec![Edit::Insert(0)], edit_script(&[], &["a"]));
+        assert_eq!(vec![Edit::Delete(0)], edit_script(&["a"], &[]));
+        assert_eq!(
+            vec![
+                Edit::Equal(0, 0),
+                Edit::Delete(1),
+                Edit::Insert(1),
+                Edit::Equal(2, 2)
+            ],
+            edit_script(&["a", "b", "c"], &["a", "x", "c"]),
+        );
+    }
+
+    #[test]
+    fn edit_script_rebuilds_both_texts() {
+        fn assert_rebuilds(old: &str, new: &str) {
+            let old: Vec<_> = old.split(' ').collect();
+            let new: Vec<_> = new.split(' ').collect();
+            let mut rebuilt_old = Vec::new();
+            let mut rebuilt_new = Vec::new();
+            for edit in edit_script(&old, &new) {
+                match edit {
+                    Edit::Equal(i, j) => {
+                        assert_eq!(old[i], new[j]);
+                        rebuilt_old.push(old[i]);
+                        rebuilt_new.push(new[j]);
+                    }
+                    Edit::Delete(i) => rebuilt_old.push(old[i]),
+                    Edit::Insert(j) => rebuilt_new.push(new[j]),
+                }
+            }
+            assert_eq!(old, rebuilt_old);
+            assert_eq!(new, rebuilt_new);
+        }
+
+        assert_rebuilds("a b c a b b a", "c b a b a c");
+        assert_rebuilds("a b c d e f g h", "x y z");
+        assert_rebuilds("a a a a a a", "a b a b a b a");
+        assert_rebuilds("1 2 3 4 5 6 7 8 9", "9 8 7 6 5 4 3 2 1");
+    }
+
+    #[test]
+    fn no_changes() {
+        let mut output = String::new();
+        assert!(!unified_diff("file", &["a", "b"], &["a", "b"], &mut output));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn single_hunk() {
+        assert_eq!(
+            "--- file\n+++ file\n@@ -1,3 +1,3 @@\n a\n-b\n+x\n c\n",
+            diff("a\nb\nc", "a\nx\nc"),
+        );
+        assert_eq!("--- file\n+++ file\n@@ -0,0 +1 @@\n+a\n", diff("", "a"));
+    }
+
+    #[test]
+    fn context_is_trimmed() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9";
+        let new = "1\n2\n3\n4\nx\n6\n7\n8\n9";
+        assert_eq!(
+            "--- file\n+++ file\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+x\n 6\n 7\n 8\n",
+            diff(old, new),
+        );
+    }
+
+    #[test]
+    fn multiple_hunks() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12";
+        let new = "x\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\ny";
+        assert_eq!(
+            concat!(
+                "--- file\n+++ file\n",
+                "@@ -1,4 +1,4 @@\n-1\n+x\n 2\n 3\n 4\n",
+                "@@ -9,4 +9,4 @@\n 9\n 10\n 11\n-12\n+y\n",
+            ),
+            diff(old, new),
+        );
+    }
+
+    #[test]
+    fn line_change_signs() {
+        fn signs(old: &str, new: &str) -> Vec<ChangeSign> {
+            let old: Vec<_> = old.lines().collect();
+            let new: Vec<_> = new.lines().collect();
+            let mut signs = Vec::new();
+            change_signs(&old, &new, &mut signs);
+            signs
+        }
+
+        use ChangeSign::*;
+        assert_eq!(vec![None, None], signs("a\nb", "a\nb"));
+        assert_eq!(vec![None, Added, None], signs("a\nc", "a\nb\nc"));
+        assert_eq!(vec![None, Modified, None], signs("a\nb\nc", "a\nx\nc"));
+        assert_eq!(vec![Deleted, None], signs("a\nb\nc", "a\nc"));
+        assert_eq!(vec![Deleted, None], signs("x\na\nc", "a\nc"));
+        assert_eq!(vec![None, Deleted], signs("a\nc\nx", "a\nc"));
+        assert_eq!(vec![Added], signs("", "a"));
+    }
+}
@@ -4,28 +4,40 @@ use std::{
 };
 
 use crate::{
-    buffer::{BufferCapabilities, BufferCollection},
+    bookmark::BookmarkCollection,
+    buffer::{
+        BufferCapabilities, BufferCollection, BufferHandle, FinishedPipeProcess, PendingSave,
+        SearchOptions, Searcher,
+    },
+    buffer_position::{BufferPosition, BufferRange},
     buffer_view::{BufferViewCollection, BufferViewHandle},
     client::{Client, ClientHandle, ClientManager},
     command::CommandManager,
-    config::Config,
-    editor_utils::{ReadLine, StatusBar, StringPool},
+    config::{Config, ConfigEffect},
+    editor_utils::{
+        load_project_config, FormatterCollection, MessageKind, ReadLine, StatusBar, StringPool,
+    },
     events::{
         ClientEvent, EditorEvent, EditorEventIter, EditorEventQueue, KeyParseAllError, KeyParser,
-        TargetClient,
+        ServerEvent, TargetClient,
     },
     keymap::{KeyMapCollection, MatchResult},
     lsp,
+    mark::MarkCollection,
     mode::{Mode, ModeContext, ModeKind},
     pattern::Pattern,
     picker::Picker,
-    platform::{Key, Platform, ProcessHandle, ProcessTag},
+    platform::{Key, Platform, PlatformRequest, ProcessHandle, ProcessTag},
     register::{RegisterCollection, RegisterKey},
+    serialization::Serialize,
     syntax::{HighlightResult, SyntaxCollection},
     theme::Theme,
     word_database::WordDatabase,
 };
 
+pub static STDIN_BUFFER_PATH: &str = "[stdin]";
+pub static COMMAND_REPL_BUFFER_PATH: &str = "[commands]";
+
 #[derive(Clone, Copy)]
 pub enum EditorControlFlow {
     Continue,
@@ -84,6 +96,9 @@ impl BufferedKeys {
 
 pub struct Editor {
     pub current_directory: PathBuf,
+    pub uses_default_config: bool,
+    pub batch_mode: bool,
+    pub config_paths: Vec<String>,
     pub config: Config,
     pub theme: Theme,
     pub syntaxes: SyntaxCollection,
@@ -96,15 +111,22 @@ pub struct Editor {
 
     pub buffered_keys: BufferedKeys,
     pub recording_macro: Option<RegisterKey>,
+    // true while executing keys that came from a keymap or a macro instead of a client
+    pub replaying_keys: bool,
     pub registers: RegisterCollection,
+    pub marks: MarkCollection,
+    pub bookmarks: BookmarkCollection,
     pub read_line: ReadLine,
     pub picker: Picker,
     pub string_pool: StringPool,
 
     pub status_bar: StatusBar,
     pub aux_pattern: Pattern,
+    pub search_options: SearchOptions,
+    pub searcher: Searcher,
 
     pub commands: CommandManager,
+    pub formatters: FormatterCollection,
     pub lsp: lsp::ClientManager,
     pub events: EditorEventQueue,
 }
@@ -112,6 +134,9 @@ impl Editor {
     pub fn new(current_directory: PathBuf) -> Self {
         Self {
             current_directory,
+            uses_default_config: false,
+            batch_mode: false,
+            config_paths: Vec::new(),
             config: Config::default(),
             theme: Theme::default(),
             syntaxes: SyntaxCollection::new(),
@@ -125,15 +150,21 @@ impl Editor {
 
             buffered_keys: BufferedKeys::default(),
             recording_macro: None,
+            replaying_keys: false,
             registers: RegisterCollection::new(),
+            marks: MarkCollection::default(),
+            bookmarks: BookmarkCollection::default(),
             read_line: ReadLine::default(),
             picker: Picker::default(),
             string_pool: StringPool::default(),
 
             status_bar: StatusBar::new(),
             aux_pattern: Pattern::new(),
+            search_options: SearchOptions::default(),
+            searcher: Searcher::default(),
 
             commands: CommandManager::new(),
+            formatters: FormatterCollection::default(),
             lsp: lsp::ClientManager::new(),
             events: EditorEventQueue::default(),
         }
@@ -160,6 +191,20 @@ impl Editor {
         }
     }
 
+    pub fn apply_config_effect(&mut self, effect: ConfigEffect) {
+        match effect {
+            ConfigEffect::None => (),
+            ConfigEffect::RefreshSyntaxes => {
+                for buffer in self.buffers.iter_mut() {
+                    buffer.refresh_syntax(&self.syntaxes, &mut self.word_database, &self.config);
+                }
+            }
+            ConfigEffect::RefreshPicker => self
+                .picker
+                .set_fuzzy_matching(self.config.picker_fuzzy_matching),
+        }
+    }
+
     pub fn execute_keys(
         &mut self,
         platform: &mut Platform,
@@ -168,6 +213,7 @@ impl Editor {
         mut keys: KeysIterator,
     ) -> EditorControlFlow {
         let start_index = keys.index;
+        let replaying_keys = self.replaying_keys;
 
         match self
             .keymaps
@@ -178,12 +224,14 @@ impl Editor {
             MatchResult::ReplaceWith(replaced_keys) => {
                 self.buffered_keys.0.truncate(start_index);
                 self.buffered_keys.0.extend_from_slice(replaced_keys);
+                self.replaying_keys = true;
             }
         }
 
-        loop {
+        let flow = loop {
             if keys.index == self.buffered_keys.0.len() {
-                break;
+                self.buffered_keys.0.truncate(start_index);
+                break EditorControlFlow::Continue;
             }
             let from_index = self.recording_macro.map(|_| keys.index);
 
@@ -194,12 +242,12 @@ impl Editor {
                 client_handle,
             };
             match Mode::on_client_keys(&mut ctx, &mut keys) {
-                None => return EditorControlFlow::Continue,
+                None => break EditorControlFlow::Continue,
                 Some(EditorControlFlow::Continue) => (),
                 Some(flow) => {
                     Mode::change_to(&mut ctx, ModeKind::default());
                     self.buffered_keys.0.truncate(start_index);
-                    return flow;
+                    break flow;
                 }
             }
 
@@ -212,10 +260,10 @@ impl Editor {
             }
 
             self.trigger_event_handlers(platform, clients);
-        }
+        };
 
-        self.buffered_keys.0.truncate(start_index);
-        EditorControlFlow::Continue
+        self.replaying_keys = replaying_keys;
+        flow
     }
 
     pub fn on_pre_render(&mut self, clients: &mut ClientManager) -> bool {
@@ -236,14 +284,39 @@ impl Editor {
             if let Some(handle) = c.buffer_view_handle() {
                 let buffer_view = self.buffer_views.get(handle);
                 let buffer = self.buffers.get_mut(buffer_view.buffer_handle);
-                if let HighlightResult::Pending = buffer.update_highlighting(&self.syntaxes) {
+                if let HighlightResult::Pending =
+                    buffer.update_highlighting(&self.syntaxes, &mut self.word_database)
+                {
                     needs_redraw = true;
                 }
+                buffer.update_folds(self.config.tab_size, &buffer_view.cursors[..]);
             }
 
             c.update_view(self, picker_height);
         }
 
+        let mut edit_rejected = false;
+        for buffer in self.buffers.iter_mut() {
+            if buffer.indexes_words_when_highlighting() {
+                if let HighlightResult::Pending =
+                    buffer.update_highlighting(&self.syntaxes, &mut self.word_database)
+                {
+                    needs_redraw = true;
+                }
+            }
+            edit_rejected |= buffer.take_edit_rejected();
+            if self.config.change_signs {
+                buffer.capture_outdated_baseline();
+            } else {
+                buffer.discard_baseline();
+            }
+        }
+        if edit_rejected {
+            self.status_bar
+                .write(MessageKind::Info)
+                .str("buffer is read-only");
+        }
+
         needs_redraw
     }
 
@@ -294,7 +367,7 @@ impl Editor {
             }
             ClientEvent::Resize(width, height) => {
                 let client = clients.get_mut(client_handle);
-                client.viewport_size = (width, height);
+                client.set_viewport_size((width, height));
                 EditorControlFlow::Continue
             }
             ClientEvent::Command(target, command) => {
@@ -305,6 +378,63 @@ impl Editor {
                 self.string_pool.release(command);
                 flow
             }
+            ClientEvent::EvalCommand {
+                target,
+                command,
+                strict,
+            } => {
+                let target_handle = get_client_handle(clients, client_handle, target);
+                let mut command = self.string_pool.acquire_with(command);
+                let result =
+                    CommandManager::try_eval(self, platform, clients, target_handle, &mut command);
+                self.string_pool.release(command);
+                match result {
+                    Ok(flow) => flow,
+                    Err(error) => {
+                        let mut message = self.string_pool.acquire();
+                        let _ = fmt::write(&mut message, format_args!("{}", error));
+                        self.status_bar.write(MessageKind::Error).str(&message);
+
+                        let mut buf = platform.buf_pool.acquire();
+                        ServerEvent::CommandError(&message).serialize(buf.write());
+                        platform.enqueue_request(PlatformRequest::WriteToClient {
+                            handle: client_handle,
+                            buf: buf.share(),
+                        });
+                        self.string_pool.release(message);
+
+                        // closing the client drops the commands it sent after the failing one
+                        if strict {
+                            EditorControlFlow::Quit
+                        } else {
+                            EditorControlFlow::Continue
+                        }
+                    }
+                }
+            }
+            ClientEvent::StdinInput(target, bytes) => {
+                let client_handle = get_client_handle(clients, client_handle, target);
+                let buffer_view_handle = self.buffer_view_handle_from_path(
+                    client_handle,
+                    Path::new(STDIN_BUFFER_PATH),
+                    BufferCapabilities::log(),
+                );
+                let buffer_handle = self.buffer_views.get(buffer_view_handle).buffer_handle;
+                let buffer = self.buffers.get_mut(buffer_handle);
+                let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+                buffer.delete_range(&mut self.word_database, range, &mut self.events);
+                buffer.insert_text(
+                    &mut self.word_database,
+                    BufferPosition::zero(),
+                    &String::from_utf8_lossy(bytes),
+                    &mut self.events,
+                );
+
+                clients
+                    .get_mut(client_handle)
+                    .set_buffer_view_handle(Some(buffer_view_handle), &mut self.events);
+                EditorControlFlow::Continue
+            }
         }
     }
 
@@ -313,6 +443,13 @@ impl Editor {
         self.trigger_event_handlers(platform, clients);
     }
 
+    pub fn on_tick(&mut self, clients: &mut ClientManager, platform: &mut Platform) {
+        self.buffers
+            .on_tick(&mut self.word_database, &mut self.events);
+        self.events.enqueue(EditorEvent::Tick);
+        self.trigger_event_handlers(platform, clients);
+    }
+
     pub fn on_process_spawned(
         &mut self,
         platform: &mut Platform,
@@ -321,9 +458,13 @@ impl Editor {
     ) {
         match tag {
             ProcessTag::Buffer(index) => self.buffers.on_process_spawned(platform, index, handle),
+            ProcessTag::PipeBuffer(index) => self
+                .buffers
+                .on_pipe_process_spawned(platform, index, handle),
             ProcessTag::Lsp(client_handle) => {
                 lsp::ClientManager::on_process_spawned(self, platform, client_handle, handle)
             }
+            ProcessTag::Ignored => (),
         }
     }
 
@@ -341,33 +482,131 @@ impl Editor {
                 bytes,
                 &mut self.events,
             ),
+            ProcessTag::PipeBuffer(index) => self.buffers.on_pipe_process_output(index, bytes),
             ProcessTag::Lsp(client_handle) => {
                 lsp::ClientManager::on_process_output(self, platform, clients, client_handle, bytes)
             }
+            ProcessTag::Ignored => (),
         }
 
         self.trigger_event_handlers(platform, clients);
     }
 
+    pub fn on_process_error_output(&mut self, tag: ProcessTag, bytes: &[u8]) {
+        if let ProcessTag::PipeBuffer(index) = tag {
+            self.buffers.on_pipe_process_error_output(index, bytes);
+        }
+    }
+
     pub fn on_process_exit(
         &mut self,
         platform: &mut Platform,
         clients: &mut ClientManager,
         tag: ProcessTag,
+        success: bool,
     ) {
         match tag {
             ProcessTag::Buffer(index) => {
                 self.buffers
                     .on_process_exit(&mut self.word_database, index, &mut self.events)
             }
+            ProcessTag::PipeBuffer(index) => {
+                if let Some(process) = self.buffers.on_pipe_process_exit(index, success) {
+                    self.on_pipe_process_finished(process);
+                }
+            }
             ProcessTag::Lsp(client_handle) => {
                 lsp::ClientManager::on_process_exit(self, client_handle)
             }
+            ProcessTag::Ignored => (),
         }
 
         self.trigger_event_handlers(platform, clients);
     }
 
+    fn on_pipe_process_finished(&mut self, process: FinishedPipeProcess) {
+        let buffer_handle = process.buffer_handle;
+        let succeeded = match &process.output {
+            Some(output) => {
+                self.buffers.get_mut(buffer_handle).replace_all_text(
+                    &mut self.word_database,
+                    output,
+                    &mut self.events,
+                );
+                true
+            }
+            None => false,
+        };
+
+        match process.save {
+            Some(save) => self.finish_pending_save(buffer_handle, save, succeeded),
+            None if !succeeded => self
+                .status_bar
+                .write(MessageKind::Error)
+                .str("process failed or buffer changed while piping it"),
+            None => (),
+        }
+
+        // the process error output is what tells why it failed
+        if !succeeded && !process.error.is_empty() {
+            if let (MessageKind::Error, message) = self.status_bar.message() {
+                let message = format!("{}\n{}", message, process.error);
+                self.status_bar.write(MessageKind::Error).str(&message);
+            }
+        }
+    }
+
+    pub fn finish_pending_save(
+        &mut self,
+        buffer_handle: BufferHandle,
+        save: PendingSave,
+        formatted: bool,
+    ) {
+        if self.buffers.try_get(buffer_handle).is_none() {
+            return;
+        }
+        let buffer = self.buffers.get_mut(buffer_handle);
+        if !formatted && !save.save_if_format_fails {
+            self.status_bar.write(MessageKind::Error).fmt(format_args!(
+                "could not format {:?}, it was not saved",
+                &buffer.path
+            ));
+            return;
+        }
+
+        match buffer.save_to_file(save.new_path.as_deref(), &mut self.events) {
+            Ok(()) => self
+                .status_bar
+                .write(MessageKind::Info)
+                .fmt(format_args!("buffer saved to {:?}", &buffer.path)),
+            Err(error) => self
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!("{}", error)),
+        }
+    }
+
+    fn load_project_configs_for_opened_buffers(&mut self, platform: &mut Platform) {
+        let mut directories = Vec::new();
+        let mut events = EditorEventIter::new();
+        while let Some(event) = events.next(&self.events) {
+            if let EditorEvent::BufferOpen { handle } = *event {
+                let buffer = self.buffers.get(handle);
+                if !buffer.capabilities.can_save {
+                    continue;
+                }
+                if let Some(directory) = self.current_directory.join(&buffer.path).parent() {
+                    if !directories.iter().any(|d| d == directory) {
+                        directories.push(directory.to_path_buf());
+                    }
+                }
+            }
+        }
+        for directory in directories {
+            load_project_config(self, platform, &directory);
+        }
+    }
+
     pub fn trigger_event_handlers(&mut self, platform: &mut Platform, clients: &mut ClientManager) {
         loop {
             self.events.flip();
@@ -376,34 +615,61 @@ impl Editor {
                 return;
             }
 
+            // loaded before lsp handles the events so project lsp servers start for these buffers
+            if self.config.auto_source_project_config {
+                self.load_project_configs_for_opened_buffers(platform);
+            }
+
             lsp::ClientManager::on_editor_events(self, platform);
 
             let mut events = EditorEventIter::new();
             while let Some(event) = events.next(&self.events) {
                 match *event {
-                    EditorEvent::Idle => (),
+                    // change signs diff the whole buffer so they're only updated once editing pauses
+                    EditorEvent::Idle => {
+                        if self.config.change_signs {
+                            for buffer in self.buffers.iter_mut() {
+                                buffer.update_change_signs();
+                            }
+                        }
+                    }
+                    EditorEvent::Tick => (),
                     EditorEvent::BufferOpen { handle } => {
                         let buffer = self.buffers.get_mut(handle);
-                        buffer.refresh_syntax(&self.syntaxes);
+                        buffer.refresh_syntax(
+                            &self.syntaxes,
+                            &mut self.word_database,
+                            &self.config,
+                        );
                         self.buffer_views.on_buffer_load(buffer);
                     }
                     EditorEvent::BufferInsertText { handle, range, .. } => {
                         self.buffer_views.on_buffer_insert_text(handle, range);
+                        self.marks.on_buffer_insert_text(handle, range);
                     }
                     EditorEvent::BufferDeleteText { handle, range } => {
                         self.buffer_views.on_buffer_delete_text(handle, range);
+                        self.marks.on_buffer_delete_text(handle, range);
                     }
                     EditorEvent::BufferSave { handle, new_path } => {
                         if new_path {
-                            self.buffers.get_mut(handle).refresh_syntax(&self.syntaxes);
+                            self.buffers.get_mut(handle).refresh_syntax(
+                                &self.syntaxes,
+                                &mut self.word_database,
+                                &self.config,
+                            );
                         }
                     }
                     EditorEvent::BufferClose { handle } => {
+                        self.buffers.stop_insert_processes(platform, handle);
+                        self.buffers.stop_pipe_processes(platform, handle);
+                        self.buffers.stop_tailing_file(platform, handle);
                         self.buffers.remove(handle, &mut self.word_database);
                         for client in clients.iter_mut() {
                             client.on_buffer_close(self, handle);
                         }
                         self.buffer_views.remove_buffer_views(handle);
+                        self.marks.on_buffer_close(handle);
                     }
                     EditorEvent::FixCursors { handle, cursors } => {
                         let mut view_cursors =
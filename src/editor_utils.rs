@@ -1,9 +1,14 @@
-use std::{fmt, path::Path, process::Command};
+use std::{
+    collections::VecDeque,
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 use crate::{
-    command::CommandTokenizer,
+    command::{CommandTokenizer, HISTORY_CAPACITY},
     editor::{BufferedKeys, Editor, KeysIterator},
-    glob::InvalidGlobError,
+    glob::{Glob, InvalidGlobError},
     ini::{Ini, PropertyIterator},
     keymap::KeyMapCollection,
     mode::ModeKind,
@@ -20,10 +25,18 @@ pub enum ReadLinePoll {
     Canceled,
 }
 
+struct ReadLineHistory {
+    name: String,
+    entries: VecDeque<String>,
+}
+
 #[derive(Default)]
 pub struct ReadLine {
     prompt: String,
     input: String,
+    histories: Vec<ReadLineHistory>,
+    history_name: String,
+    history_index: usize,
 }
 impl ReadLine {
     pub fn prompt(&self) -> &str {
@@ -33,6 +46,71 @@ impl ReadLine {
     pub fn set_prompt(&mut self, prompt: &str) {
         self.prompt.clear();
         self.prompt.push_str(prompt);
+        self.set_history_name(prompt);
+    }
+
+    pub fn set_history_name(&mut self, name: &str) {
+        self.history_name.clear();
+        self.history_name.push_str(name);
+        self.history_index = self.current_history().map(|h| h.entries.len()).unwrap_or(0);
+    }
+
+    fn current_history(&self) -> Option<&ReadLineHistory> {
+        self.histories.iter().find(|h| h.name == self.history_name)
+    }
+
+    pub fn add_to_history(&mut self) {
+        if self.input.is_empty() {
+            return;
+        }
+
+        let index = match self
+            .histories
+            .iter()
+            .position(|h| h.name == self.history_name)
+        {
+            Some(index) => index,
+            None => {
+                self.histories.push(ReadLineHistory {
+                    name: self.history_name.clone(),
+                    entries: VecDeque::with_capacity(HISTORY_CAPACITY),
+                });
+                self.histories.len() - 1
+            }
+        };
+        let entries = &mut self.histories[index].entries;
+        if let Some(back) = entries.back() {
+            if *back == self.input {
+                self.history_index = entries.len();
+                return;
+            }
+        }
+
+        let mut entry = if entries.len() == entries.capacity() {
+            entries.pop_front().unwrap()
+        } else {
+            String::new()
+        };
+        entry.clear();
+        entry.push_str(&self.input);
+        entries.push_back(entry);
+        self.history_index = entries.len();
+    }
+
+    pub fn move_in_history(&mut self, offset: isize) {
+        let entries = match self.histories.iter().find(|h| h.name == self.history_name) {
+            Some(history) => &history.entries,
+            None => return,
+        };
+
+        let index = self.history_index as isize + offset;
+        let index = index.max(0).min(entries.len() as _) as usize;
+        self.history_index = index;
+
+        self.input.clear();
+        if let Some(entry) = entries.get(index) {
+            self.input.push_str(entry);
+        }
     }
 
     pub fn input(&self) -> &str {
@@ -159,6 +237,24 @@ impl StringPool {
     }
 }
 
+// unix sockets use a fixed directory so clients with a different TMPDIR still find the server
+pub fn session_directory() -> PathBuf {
+    let mut path = if cfg!(windows) {
+        env::temp_dir()
+    } else {
+        PathBuf::from("/tmp")
+    };
+    path.push(env!("CARGO_PKG_NAME"));
+    path
+}
+
+// files that belong to a session are kept next to its server socket instead of inside the project
+pub fn session_file_path(session_name: &str, extension: &str) -> PathBuf {
+    let mut path = session_directory();
+    path.push(format!("{}.{}", session_name, extension));
+    path
+}
+
 // FNV-1a : https://en.wikipedia.org/wiki/Fowler–Noll–Vo_hash_function
 // TODO: is it still a good hash if we hash 8 bytes at a time and then combine them at the end?
 // or should we just jump directly to a more complex hash that is simd-friendly?
@@ -172,16 +268,106 @@ pub const fn hash_bytes(mut bytes: &[u8]) -> u64 {
     hash
 }
 
-pub fn parse_process_command(command: &str) -> Option<Command> {
+pub struct FormatterRecipe {
+    glob_hash: u64,
+    glob: Glob,
+    pub command: String,
+    pub on_save: bool,
+}
+
+#[derive(Default)]
+pub struct FormatterCollection {
+    recipes: Vec<FormatterRecipe>,
+}
+impl FormatterCollection {
+    pub fn add(
+        &mut self,
+        glob: &str,
+        command: &str,
+        on_save: bool,
+    ) -> Result<(), InvalidGlobError> {
+        let glob_hash = hash_bytes(glob.as_bytes());
+        for recipe in &mut self.recipes {
+            if recipe.glob_hash == glob_hash {
+                recipe.command.clear();
+                recipe.command.push_str(command);
+                recipe.on_save = on_save;
+                return Ok(());
+            }
+        }
+
+        let mut recipe_glob = Glob::default();
+        recipe_glob.compile(glob)?;
+        self.recipes.push(FormatterRecipe {
+            glob_hash,
+            glob: recipe_glob,
+            command: command.into(),
+            on_save,
+        });
+        Ok(())
+    }
+
+    pub fn find(&self, path: &str) -> Option<&FormatterRecipe> {
+        self.recipes.iter().find(|r| r.glob.matches(path))
+    }
+}
+
+pub fn parse_process_command(command: &str, environment: &[(String, String)]) -> Option<Command> {
     let mut tokenizer = CommandTokenizer(command);
     let name = tokenizer.next()?;
     let mut command = Command::new(name);
     for arg in tokenizer {
         command.arg(arg);
     }
+    for (name, value) in environment {
+        command.env(name, value);
+    }
     Some(command)
 }
 
+pub static DEFAULT_CONFIG_NAME: &str = "default_config.ini";
+pub static DEFAULT_CONFIG_SOURCE: &str = include_str!("../rc/default_config.ini");
+
+pub static PROJECT_CONFIG_NAME: &str = ".pepper";
+
+pub fn find_project_config(directory: &Path) -> Option<PathBuf> {
+    directory
+        .ancestors()
+        .map(|d| d.join(PROJECT_CONFIG_NAME))
+        .find(|p| p.is_file())
+}
+
+pub fn find_project_root(directory: &Path, markers: &str) -> Option<PathBuf> {
+    directory
+        .ancestors()
+        .find(|d| markers.split_whitespace().any(|m| d.join(m).exists()))
+        .map(Path::to_path_buf)
+}
+
+// project configs are remembered in `config_paths` so each is only loaded once
+// and is also reloaded by `reload-config`
+pub fn load_project_config(editor: &mut Editor, platform: &mut Platform, directory: &Path) {
+    let path = match find_project_config(directory).and_then(|p| p.to_str().map(String::from)) {
+        Some(path) => path,
+        None => return,
+    };
+    if editor.config_paths.contains(&path) {
+        return;
+    }
+
+    match fs::read_to_string(&path) {
+        Ok(source) => {
+            let mut ini = Ini::default();
+            load_config(editor, platform, &mut ini, &path, &source);
+            editor.config_paths.push(path);
+        }
+        Err(_) => editor
+            .status_bar
+            .write(MessageKind::Error)
+            .fmt(format_args!("could not load config '{}'", path)),
+    }
+}
+
 pub fn load_config(
     editor: &mut Editor,
     platform: &mut Platform,
@@ -224,12 +410,17 @@ pub fn load_config(
         }
     };
 
+    let mut config_effects = Vec::new();
     'section_loop: for (section, line_index, properties) in sections {
         match section {
             "config" => {
                 for (key, value, line_index) in properties {
                     match editor.config.parse_config(key, value) {
-                        Ok(()) => (),
+                        Ok(effect) => {
+                            if !config_effects.contains(&effect) {
+                                config_effects.push(effect);
+                            }
+                        }
                         Err(error) => output.fmt(format_args!(
                             "{} at {}:{}\n",
                             error,
@@ -285,6 +476,9 @@ pub fn load_config(
                                 continue 'section_loop;
                             }
                         },
+                        "name" => syntax.set_name(value),
+                        "line_comment" => syntax.set_line_comment(value),
+                        "definitions" => syntax.set_definitions(value),
                         _ => match key.parse() {
                             Ok(kind) => match syntax.set_rule(kind, value) {
                                 Ok(()) => (),
@@ -342,6 +536,65 @@ pub fn load_config(
                     }
                 }
             }
+            "format" => {
+                let mut glob = None;
+                let mut command = None;
+                let mut on_save = true;
+
+                for (key, value, line_index) in properties {
+                    match key {
+                        "glob" => glob = Some(value),
+                        "command" => command = Some(value),
+                        "on_save" => match value.parse() {
+                            Ok(value) => on_save = value,
+                            Err(_) => output.fmt(format_args!(
+                                "invalid format on_save value '{}' at {}:{}\n",
+                                value,
+                                config_name,
+                                line_index + 1,
+                            )),
+                        },
+                        _ => output.fmt(format_args!(
+                            "no such format property '{}' at {}:{}\n",
+                            key,
+                            config_name,
+                            line_index + 1,
+                        )),
+                    }
+                }
+
+                let glob = match glob {
+                    Some(glob) => glob,
+                    None => {
+                        output.fmt(format_args!(
+                            "format has no glob property at {}:{}\n",
+                            config_name,
+                            line_index + 1,
+                        ));
+                        continue;
+                    }
+                };
+                let command = match command {
+                    Some(command) => command,
+                    None => {
+                        output.fmt(format_args!(
+                            "format has no command property at {}:{}\n",
+                            config_name,
+                            line_index + 1,
+                        ));
+                        continue;
+                    }
+                };
+
+                if let Err(InvalidGlobError) = editor.formatters.add(glob, command, on_save) {
+                    output.fmt(format_args!(
+                        "invalid format glob '{}' at {}:{}\n",
+                        glob,
+                        config_name,
+                        line_index + 1
+                    ));
+                }
+            }
             "alias" => {
                 for (from, to, _) in properties {
                     editor.commands.aliases.add(from, to);
@@ -444,4 +697,41 @@ pub fn load_config(
             )),
         }
     }
+
+    for effect in config_effects {
+        editor.apply_config_effect(effect);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::platform::PlatformRequest;
+
+    #[test]
+    fn project_config_is_loaded_from_ancestor_directory() {
+        let root =
+            env::temp_dir().join(format!("pepper_project_config_test_{}", std::process::id()));
+        let nested = root.join("src").join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        let config_path = root.join(PROJECT_CONFIG_NAME);
+        fs::write(&config_path, "[config]\ntab_size=7\n").unwrap();
+
+        assert_eq!(Some(config_path.clone()), find_project_config(&nested));
+
+        let (request_sender, _) = std::sync::mpsc::channel::<PlatformRequest>();
+        let mut platform = Platform::new(|| (), request_sender);
+        let mut editor = Editor::new(PathBuf::new());
+        load_project_config(&mut editor, &mut platform, &nested);
+        assert_eq!(7, editor.config.tab_size.get());
+        assert_eq!(vec![config_path.to_str().unwrap()], editor.config_paths);
+
+        editor.config.tab_size = std::num::NonZeroU8::new(4).unwrap();
+        load_project_config(&mut editor, &mut platform, &root);
+        assert_eq!(4, editor.config.tab_size.get());
+        assert_eq!(1, editor.config_paths.len());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -34,6 +34,7 @@ impl EditorEventCursors {
 
 pub enum EditorEvent {
     Idle,
+    Tick,
     BufferOpen {
         handle: BufferHandle,
     },
@@ -468,11 +469,55 @@ where
     }
 }
 
+// serialized as a u32 byte length followed by each arg as a `&str`
+#[derive(Clone, Copy)]
+pub struct RequestArgs<'a>(&'a [u8]);
+impl<'a> RequestArgs<'a> {
+    pub fn iter(&self) -> RequestArgsIter<'a> {
+        RequestArgsIter(self.0)
+    }
+}
+impl<'de> Serialize<'de> for RequestArgs<'de> {
+    fn serialize<S>(&self, serializer: &mut S)
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer);
+    }
+
+    fn deserialize<D>(deserializer: &mut D) -> Result<Self, DeserializeError>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = <&[u8]>::deserialize(deserializer)?;
+        let mut args = RequestArgsIter(bytes);
+        while !args.0.is_empty() {
+            <&str>::deserialize(&mut args.0)?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+pub struct RequestArgsIter<'a>(&'a [u8]);
+impl<'a> Iterator for RequestArgsIter<'a> {
+    type Item = &'a str;
+    fn next(&mut self) -> Option<Self::Item> {
+        <&str>::deserialize(&mut self.0).ok()
+    }
+}
+
+// a u8 tag followed by its payload: 0 display (&[u8]), 1 suspend, 2 command output (&str),
+// 3 request (&str name then RequestArgs) and 4 command error (&str).
+// &[u8] and &str are a little endian u32 length followed by the bytes
 pub enum ServerEvent<'a> {
     Display(&'a [u8]),
     Suspend,
     CommandOutput(&'a str),
-    Request(&'a str),
+    Request {
+        name: &'a str,
+        args: RequestArgs<'a>,
+    },
+    CommandError(&'a str),
 }
 impl<'a> ServerEvent<'a> {
     pub const fn display_header_len() -> usize {
@@ -485,6 +530,21 @@ impl<'a> ServerEvent<'a> {
         let len_buf = len.to_le_bytes();
         buf[1..Self::display_header_len()].copy_from_slice(&len_buf);
     }
+
+    pub fn serialize_request<'s, I>(buf: &mut Vec<u8>, name: &str, args: I)
+    where
+        I: Iterator<Item = &'s str>,
+    {
+        3u8.serialize(buf);
+        name.serialize(buf);
+        let len_index = buf.len();
+        0u32.serialize(buf);
+        for arg in args {
+            arg.serialize(buf);
+        }
+        let len = (buf.len() - len_index - std::mem::size_of::<u32>()) as u32;
+        buf[len_index..len_index + std::mem::size_of::<u32>()].copy_from_slice(&len.to_le_bytes());
+    }
 }
 impl<'de> Serialize<'de> for ServerEvent<'de> {
     fn serialize<S>(&self, serializer: &mut S)
@@ -501,9 +561,14 @@ impl<'de> Serialize<'de> for ServerEvent<'de> {
                 2u8.serialize(serializer);
                 output.serialize(serializer);
             }
-            Self::Request(request) => {
+            Self::Request { name, args } => {
                 3u8.serialize(serializer);
-                request.serialize(serializer);
+                name.serialize(serializer);
+                args.serialize(serializer);
+            }
+            Self::CommandError(error) => {
+                4u8.serialize(serializer);
+                error.serialize(serializer);
             }
         }
     }
@@ -524,8 +589,13 @@ impl<'de> Serialize<'de> for ServerEvent<'de> {
                 Ok(Self::CommandOutput(output))
             }
             3 => {
-                let request = Serialize::deserialize(deserializer)?;
-                Ok(Self::Request(request))
+                let name = Serialize::deserialize(deserializer)?;
+                let args = Serialize::deserialize(deserializer)?;
+                Ok(Self::Request { name, args })
+            }
+            4 => {
+                let error = Serialize::deserialize(deserializer)?;
+                Ok(Self::CommandError(error))
             }
             _ => Err(DeserializeError::InvalidData),
         }
@@ -565,6 +635,12 @@ pub enum ClientEvent<'a> {
     Key(TargetClient, Key),
     Resize(u16, u16),
     Command(TargetClient, &'a str),
+    StdinInput(TargetClient, &'a [u8]),
+    EvalCommand {
+        target: TargetClient,
+        command: &'a str,
+        strict: bool,
+    },
 }
 impl<'de> Serialize<'de> for ClientEvent<'de> {
     fn serialize<S>(&self, serializer: &mut S)
@@ -587,6 +663,21 @@ impl<'de> Serialize<'de> for ClientEvent<'de> {
                 target.serialize(serializer);
                 command.serialize(serializer);
             }
+            Self::StdinInput(target, bytes) => {
+                3u8.serialize(serializer);
+                target.serialize(serializer);
+                bytes.serialize(serializer);
+            }
+            Self::EvalCommand {
+                target,
+                command,
+                strict,
+            } => {
+                4u8.serialize(serializer);
+                target.serialize(serializer);
+                command.serialize(serializer);
+                (*strict as u8).serialize(serializer);
+            }
         }
     }
 
@@ -611,6 +702,21 @@ impl<'de> Serialize<'de> for ClientEvent<'de> {
                 let command = Serialize::deserialize(deserializer)?;
                 Ok(Self::Command(target, command))
             }
+            3 => {
+                let target = Serialize::deserialize(deserializer)?;
+                let bytes = Serialize::deserialize(deserializer)?;
+                Ok(Self::StdinInput(target, bytes))
+            }
+            4 => {
+                let target = Serialize::deserialize(deserializer)?;
+                let command = Serialize::deserialize(deserializer)?;
+                let strict = u8::deserialize(deserializer)? != 0;
+                Ok(Self::EvalCommand {
+                    target,
+                    command,
+                    strict,
+                })
+            }
             _ => Err(DeserializeError::InvalidData),
         }
     }
@@ -769,6 +875,43 @@ mod tests {
         assert_key_serialization(Key::Esc);
     }
 
+    #[test]
+    fn server_event_request_serialization() {
+        let mut bytes = Vec::new();
+        ServerEvent::serialize_request(
+            &mut bytes,
+            "my-request",
+            ["arg0", "", "arg 2"].iter().copied(),
+        );
+
+        let mut slice = bytes.as_slice();
+        let event = match ServerEvent::deserialize(&mut slice) {
+            Ok(event @ ServerEvent::Request { .. }) => event,
+            _ => panic!("could not deserialize request"),
+        };
+        assert!(slice.is_empty());
+
+        let mut reserialized = Vec::new();
+        event.serialize(&mut reserialized);
+        assert_eq!(bytes, reserialized);
+
+        if let ServerEvent::Request { name, args } = event {
+            assert_eq!("my-request", name);
+            let mut args = args.iter();
+            assert_eq!(Some("arg0"), args.next());
+            assert_eq!(Some(""), args.next());
+            assert_eq!(Some("arg 2"), args.next());
+            assert_eq!(None, args.next());
+        }
+
+        let mut bytes = Vec::new();
+        3u8.serialize(&mut bytes);
+        "my-request".serialize(&mut bytes);
+        (&[1, 0, 0, 0][..]).serialize(&mut bytes);
+        let mut slice = bytes.as_slice();
+        assert!(ServerEvent::deserialize(&mut slice).is_err());
+    }
+
     #[test]
     fn client_event_deserialize_splitted() {
         const CHAR: char = 'x';
@@ -0,0 +1,321 @@
+use std::{cmp::Reverse, num::NonZeroU8};
+
+use crate::{
+    buffer::BufferContent,
+    buffer_position::{BufferPositionIndex, BufferRange},
+    cursor::Cursor,
+};
+
+// returns the indentation display width and its byte len or None for blank lines
+pub fn indentation(line: &str, tab_size: NonZeroU8) -> Option<(usize, usize)> {
+    let tab_size = tab_size.get() as usize;
+    let mut width = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\t' => width += tab_size - width % tab_size,
+            c if c.is_whitespace() => width += 1,
+            _ => return Some((width, i)),
+        }
+    }
+    None
+}
+
+// last line of the block indented under `line_index`, not counting trailing blank lines
+pub fn indentation_block_end(
+    content: &BufferContent,
+    line_index: usize,
+    tab_size: NonZeroU8,
+) -> Option<usize> {
+    let (width, _) = indentation(content.line_at(line_index).as_str(), tab_size)?;
+    let mut end = line_index;
+    for i in line_index + 1..content.line_count() {
+        match indentation(content.line_at(i).as_str(), tab_size) {
+            Some((w, _)) if w > width => end = i,
+            Some(_) => break,
+            None => (),
+        }
+    }
+    if end > line_index {
+        Some(end)
+    } else {
+        None
+    }
+}
+
+// `start` is the line that stays visible while lines `start + 1..=end` are hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fold {
+    pub start: BufferPositionIndex,
+    pub end: BufferPositionIndex,
+    dirty: bool,
+}
+impl Fold {
+    pub fn hidden_line_count(&self) -> BufferPositionIndex {
+        self.end - self.start
+    }
+
+    fn hides(&self, line_index: BufferPositionIndex) -> bool {
+        self.start < line_index && line_index <= self.end
+    }
+}
+
+#[derive(Default)]
+pub struct FoldCollection {
+    folds: Vec<Fold>,
+}
+
+impl FoldCollection {
+    pub fn is_empty(&self) -> bool {
+        self.folds.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Fold> {
+        self.folds.iter()
+    }
+
+    pub fn fold_at(&self, line_index: BufferPositionIndex) -> Option<&Fold> {
+        self.folds.iter().find(|f| f.start == line_index)
+    }
+
+    pub fn is_hidden(&self, line_index: BufferPositionIndex) -> bool {
+        self.folds.iter().any(|f| f.hides(line_index))
+    }
+
+    pub fn add(&mut self, start: BufferPositionIndex, end: BufferPositionIndex) {
+        if start >= end || self.is_hidden(start) {
+            return;
+        }
+        self.folds.retain(|f| f.start < start || f.start > end);
+        let index = self.folds.partition_point(|f| f.start < start);
+        self.folds.insert(
+            index,
+            Fold {
+                start,
+                end,
+                dirty: false,
+            },
+        );
+    }
+
+    // adds outer folds first so nested ones are only kept when their parent would hide a cursor
+    pub fn add_all(
+        &mut self,
+        ranges: &mut [(BufferPositionIndex, BufferPositionIndex)],
+        cursors: &[Cursor],
+    ) {
+        ranges.sort_unstable_by_key(|&(start, end)| (start, Reverse(end)));
+        for &(start, end) in ranges.iter() {
+            let fold = Fold {
+                start,
+                end,
+                dirty: false,
+            };
+            if !cursors.iter().any(|c| fold.hides(c.position.line_index)) {
+                self.add(start, end);
+            }
+        }
+    }
+
+    pub fn remove_at(&mut self, line_index: BufferPositionIndex) -> bool {
+        let len = self.folds.len();
+        self.folds.retain(|f| f.start != line_index);
+        self.folds.len() != len
+    }
+
+    pub fn clear(&mut self) {
+        self.folds.clear();
+    }
+
+    pub fn to_visible_line(&self, line_index: BufferPositionIndex) -> BufferPositionIndex {
+        let mut visible = line_index;
+        for fold in &self.folds {
+            if fold.start >= line_index {
+                break;
+            }
+            visible -= fold.hidden_line_count().min(line_index - fold.start);
+        }
+        visible
+    }
+
+    pub fn from_visible_line(&self, visible_index: BufferPositionIndex) -> BufferPositionIndex {
+        let mut line_index = visible_index;
+        for fold in &self.folds {
+            if fold.start >= line_index {
+                break;
+            }
+            line_index += fold.hidden_line_count();
+        }
+        line_index
+    }
+
+    pub fn on_insert(&mut self, range: BufferRange) {
+        let line_count = range.to.line_index - range.from.line_index;
+        // whole lines inserted right before a fold leave its first line intact
+        let inserted_lines = range.from.column_byte_index == 0 && range.to.column_byte_index == 0;
+        for fold in &mut self.folds {
+            if range.from.line_index < fold.start
+                || (range.from.line_index == fold.start && inserted_lines)
+            {
+                fold.start += line_count;
+                fold.end += line_count;
+            } else if range.from.line_index <= fold.end {
+                fold.end += line_count;
+                fold.dirty = true;
+            }
+        }
+    }
+
+    pub fn on_delete(&mut self, range: BufferRange) {
+        let line_count = range.to.line_index - range.from.line_index;
+        let deleted_lines = range.from.column_byte_index == 0 && range.to.column_byte_index == 0;
+        for fold in &mut self.folds {
+            if range.to.line_index < fold.start
+                || (range.to.line_index == fold.start && deleted_lines)
+            {
+                fold.start -= line_count;
+                fold.end -= line_count;
+            } else if range.from.line_index <= fold.end {
+                fold.start = fold.start.min(range.from.line_index);
+                fold.end = fold.end.saturating_sub(line_count).max(fold.start);
+                fold.dirty = true;
+            }
+        }
+    }
+
+    // recomputes the bounds of folds that were edited and opens the ones hiding a cursor
+    pub fn update(&mut self, content: &BufferContent, tab_size: NonZeroU8, cursors: &[Cursor]) {
+        if self.folds.is_empty() {
+            return;
+        }
+
+        for fold in &mut self.folds {
+            if fold.dirty {
+                fold.dirty = false;
+                fold.end = indentation_block_end(content, fold.start as _, tab_size)
+                    .unwrap_or(fold.start as _) as _;
+            }
+        }
+
+        let last_line_index = (content.line_count() - 1) as BufferPositionIndex;
+        let mut previous_end = None;
+        self.folds.retain(|f| {
+            let keep = f.start < f.end
+                && f.end <= last_line_index
+                && !matches!(previous_end, Some(end) if f.start <= end)
+                && !cursors.iter().any(|c| f.hides(c.position.line_index));
+            if keep {
+                previous_end = Some(f.end);
+            }
+            keep
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_position::BufferPosition;
+
+    fn content(text: &str) -> BufferContent {
+        let mut content = BufferContent::new();
+        content.insert_text(BufferPosition::zero(), text);
+        content
+    }
+
+    fn tab_size() -> NonZeroU8 {
+        NonZeroU8::new(4).unwrap()
+    }
+
+    fn range(from: u32, to: u32) -> BufferRange {
+        BufferRange::between(
+            BufferPosition::line_col(from, 0),
+            BufferPosition::line_col(to, 0),
+        )
+    }
+
+    #[test]
+    fn indentation_blocks() {
+        assert_eq!(None, indentation("", tab_size()));
+        assert_eq!(None, indentation(" \t ", tab_size()));
+        assert_eq!(Some((0, 0)), indentation("a", tab_size()));
+        assert_eq!(Some((4, 1)), indentation("\ta", tab_size()));
+        assert_eq!(Some((8, 4)), indentation("  \t\ta", tab_size()));
+
+        let content = content("a:\n  b\n\n    c\n\nd\n  e");
+        assert_eq!(Some(3), indentation_block_end(&content, 0, tab_size()));
+        assert_eq!(Some(3), indentation_block_end(&content, 1, tab_size()));
+        assert_eq!(None, indentation_block_end(&content, 2, tab_size()));
+        assert_eq!(None, indentation_block_end(&content, 3, tab_size()));
+        assert_eq!(Some(6), indentation_block_end(&content, 5, tab_size()));
+    }
+
+    #[test]
+    fn visible_lines() {
+        let mut folds = FoldCollection::default();
+        folds.add(2, 4);
+        folds.add(6, 9);
+
+        let visible: Vec<_> = (0..12).map(|i| folds.to_visible_line(i)).collect();
+        assert_eq!(vec![0, 1, 2, 2, 2, 3, 4, 4, 4, 4, 5, 6], visible);
+        let lines: Vec<_> = (0..7).map(|i| folds.from_visible_line(i)).collect();
+        assert_eq!(vec![0, 1, 2, 5, 6, 10, 11], lines);
+
+        assert!(!folds.is_hidden(2));
+        assert!(folds.is_hidden(3));
+        assert!(folds.is_hidden(4));
+        assert!(!folds.is_hidden(5));
+
+        folds.add(1, 7);
+        assert_eq!(1, folds.iter().count());
+        folds.add(3, 5);
+        assert_eq!(1, folds.iter().count());
+    }
+
+    #[test]
+    fn add_nested_folds() {
+        let cursor = Cursor {
+            anchor: BufferPosition::line_col(3, 0),
+            position: BufferPosition::line_col(3, 0),
+        };
+
+        let mut folds = FoldCollection::default();
+        folds.add_all(&mut [(4, 5), (0, 9), (2, 3), (7, 8)], &[cursor]);
+        let folds: Vec<_> = folds.iter().map(|f| (f.start, f.end)).collect();
+        assert_eq!(vec![(4, 5), (7, 8)], folds);
+
+        let mut folds = FoldCollection::default();
+        folds.add_all(&mut [(4, 5), (0, 9), (2, 3)], &[]);
+        let folds: Vec<_> = folds.iter().map(|f| (f.start, f.end)).collect();
+        assert_eq!(vec![(0, 9)], folds);
+    }
+
+    #[test]
+    fn edits_around_folds() {
+        let mut folds = FoldCollection::default();
+        folds.add(2, 4);
+
+        folds.on_insert(range(0, 2));
+        assert_eq!((4, 6), folds.fold_at(4).map(|f| (f.start, f.end)).unwrap());
+        folds.on_insert(range(8, 10));
+        assert_eq!((4, 6), folds.fold_at(4).map(|f| (f.start, f.end)).unwrap());
+        folds.on_delete(range(0, 1));
+        assert_eq!((3, 5), folds.fold_at(3).map(|f| (f.start, f.end)).unwrap());
+        folds.on_insert(range(3, 4));
+        assert_eq!((4, 6), folds.fold_at(4).map(|f| (f.start, f.end)).unwrap());
+        folds.on_delete(range(3, 4));
+        assert_eq!((3, 5), folds.fold_at(3).map(|f| (f.start, f.end)).unwrap());
+
+        let content = content("a\nb\nc\nd:\n  e\n  f\n  g\nh");
+        folds.on_insert(range(4, 5));
+        assert_eq!((3, 6), folds.fold_at(3).map(|f| (f.start, f.end)).unwrap());
+        folds.update(&content, tab_size(), &[]);
+        assert_eq!((3, 6), folds.fold_at(3).map(|f| (f.start, f.end)).unwrap());
+
+        let cursor = Cursor {
+            anchor: BufferPosition::line_col(5, 0),
+            position: BufferPosition::line_col(5, 0),
+        };
+        folds.update(&content, tab_size(), &[cursor]);
+        assert!(folds.is_empty());
+    }
+}
@@ -14,6 +14,7 @@ impl From<TryFromIntError> for InvalidGlobError {
     }
 }
 
+#[derive(Clone)]
 pub enum Op {
     Slice { from: u16, to: u16 },
     Separator,
@@ -26,7 +27,7 @@ pub enum Op {
     SubPattern { len: u16 },
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Glob {
     pub texts: String,
     pub ops: Vec<Op>,
@@ -1,6 +1,7 @@
-use std::{io, path::Path};
+use std::{borrow::Cow, io, path::Path};
 
 pub static HELP_PREFIX: &str = "help://";
+pub static COMMAND_LIST_PATH: &str = "help://command_list.md";
 
 static HELP_SOURCES: &[(&str, &str)] = &[
     (
@@ -28,14 +29,43 @@ pub fn open(path: &Path) -> Option<impl io::BufRead> {
         Some(path) => path,
         None => return None,
     };
+    if path == &COMMAND_LIST_PATH[HELP_PREFIX.len()..] {
+        let list = command_list();
+        return Some(io::Cursor::new(Cow::Owned(list.into_bytes())));
+    }
     for &(help_path, help_source) in HELP_SOURCES {
         if path == &help_path[HELP_PREFIX.len()..] {
-            return Some(io::Cursor::new(help_source));
+            return Some(io::Cursor::new(Cow::Borrowed(help_source.as_bytes())));
         }
     }
     None
 }
 
+fn command_list() -> String {
+    let mut list = String::new();
+    let mut lines = HELP_SOURCES[0].1.lines();
+    while let Some(line) = lines.next() {
+        let name = match line.strip_prefix("## `").and_then(|l| l.strip_suffix('`')) {
+            Some(name) => name,
+            None => continue,
+        };
+        let summary = lines.next().unwrap_or("");
+        let summary = if summary.starts_with("- ") {
+            ""
+        } else {
+            summary
+        };
+
+        list.push_str(name);
+        if !summary.is_empty() {
+            list.push_str(" - ");
+            list.push_str(summary);
+        }
+        list.push('\n');
+    }
+    list
+}
+
 pub fn search(keyword: &str) -> Option<(&'static Path, usize)> {
     let mut last_match = None;
     for &(path, source) in HELP_SOURCES {
@@ -55,3 +85,51 @@ pub fn search(keyword: &str) -> Option<(&'static Path, usize)> {
     }
     last_match
 }
+
+pub fn search_commands(text: &str) -> impl '_ + Iterator<Item = (&'static str, &'static str)> {
+    let text = text.as_bytes();
+    let contains = move |line: &str| {
+        text.is_empty()
+            || line
+                .as_bytes()
+                .windows(text.len())
+                .any(|w| w.eq_ignore_ascii_case(text))
+    };
+
+    let mut command_name = "";
+    let mut matched = false;
+    HELP_SOURCES[0].1.lines().filter_map(move |line| {
+        if let Some(name) = line.strip_prefix("## `").and_then(|l| l.strip_suffix('`')) {
+            command_name = name;
+            matched = false;
+            None
+        } else if command_name.is_empty() || matched || !contains(line) {
+            None
+        } else {
+            matched = true;
+            Some((command_name, line))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_list_summaries() {
+        let list = command_list();
+        assert!(list.starts_with("help - "));
+        assert!(list.lines().any(|l| l.starts_with("quit - ")));
+    }
+
+    #[test]
+    fn search_commands_help() {
+        let mut matches = search_commands("ADD-PICKER-OPTION");
+        assert_eq!(Some("pick"), matches.next().map(|(name, _)| name));
+        assert_eq!(
+            Some("add-picker-option"),
+            matches.next().map(|(name, _)| name)
+        );
+    }
+}
@@ -37,6 +37,12 @@ pub struct KeyMapCollection {
 }
 
 impl KeyMapCollection {
+    pub fn reset(&mut self) {
+        for maps in &mut self.maps {
+            maps.clear();
+        }
+    }
+
     pub fn parse_and_map(
         &mut self,
         mode_kind: ModeKind,
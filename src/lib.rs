@@ -1,14 +1,19 @@
 pub mod application;
+pub mod bookmark;
 pub mod buffer;
 pub mod buffer_position;
 pub mod buffer_view;
+pub mod calc;
 pub mod client;
 pub mod command;
 pub mod config;
 pub mod cursor;
+pub mod datetime;
+pub mod diff;
 pub mod editor;
 pub mod editor_utils;
 pub mod events;
+pub mod fold;
 pub mod glob;
 pub mod help;
 pub mod history;
@@ -16,6 +21,7 @@ pub mod ini;
 pub mod json;
 pub mod keymap;
 pub mod lsp;
+pub mod mark;
 pub mod mode;
 pub mod navigation_history;
 pub mod pattern;
@@ -43,6 +49,10 @@ pub struct Args {
     pub server: bool,
     pub configs: Vec<ArgsConfig>,
     pub no_default_config: bool,
+    pub evals: Vec<String>,
+    pub strict: bool,
+    pub batch: bool,
+    pub stdin: bool,
     pub files: Vec<String>,
 }
 
@@ -59,7 +69,9 @@ fn print_help() {
     println!("usage: pepper [<options...>] [<files...>]");
     println!();
     println!("  files: file paths to open as a buffer (clients only)");
-    println!("         you can append ':<line>[,<column>]' to open it at that position");
+    println!("         you can append ':<line>[,<column>]' or ':<line>:<column>'");
+    println!("         to open it at that position");
+    println!("         a single '-' reads stdin until it closes into a '[stdin]' buffer");
     println!();
     println!("options:");
     println!();
@@ -71,10 +83,23 @@ fn print_help() {
     println!("  --quit                   sends a `quit` event on start");
     println!("  --server                 only run as server");
     println!("  -c, --config             sources config file at path (repeatable) (server only)");
-    println!("  --try-config             like `--config` but suppresses the 'file not found' error (repeatable)");
+    println!(
+        "  --try-config             like `--config` but suppresses the 'file not found' error"
+    );
+    println!("                           (repeatable)");
     println!(
         "  --no-default-config      does not source the default config included in the editor"
     );
+    println!(
+        "  -e, --eval               evaluates commands separated by ';' on start (repeatable)"
+    );
+    println!("                           (clients only)");
+    println!("  --strict                 stops at the first failing `--eval` command and quits");
+    println!("                           with a nonzero exit code");
+    println!(
+        "  --batch                  runs `--eval` commands on the files without a ui and exits"
+    );
+    println!("                           (buffers with unsaved changes are saved before exiting)");
 }
 
 impl Args {
@@ -143,6 +168,16 @@ impl Args {
                     None => error(format_args!("expected config path after {}", arg)),
                 },
                 "--no-default-config" => parsed.no_default_config = true,
+                "-e" | "--eval" => match args.next() {
+                    Some(arg) => {
+                        let arg = arg_to_str(&arg);
+                        parsed.evals.push(arg.into());
+                    }
+                    None => error(format_args!("expected commands after {}", arg)),
+                },
+                "--strict" => parsed.strict = true,
+                "--batch" => parsed.batch = true,
+                "-" => parsed.stdin = true,
                 "--" => {
                     while let Some(arg) = args.next() {
                         let arg = arg_to_str(&arg);
@@ -1,9 +1,15 @@
 mod capabilities;
 mod client;
+mod markdown;
 mod protocol;
+mod utf16;
 
 pub use client::Client;
 pub use client::ClientHandle;
 pub use client::ClientManager;
+pub use client::CodeActionParams;
 pub use client::Diagnostic;
+pub use client::RenameParams;
+pub use client::TraceValue;
+pub use protocol::DiagnosticSeverity;
 pub use protocol::ServerEvent;
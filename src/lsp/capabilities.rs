@@ -291,6 +291,13 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
 
         text_document_capabilities.set("selectionRange".into(), JsonObject::default().into(), json);
 
+        {
+            let mut folding_range = JsonObject::default();
+            folding_range.set("lineFoldingOnly".into(), true.into(), json);
+
+            text_document_capabilities.set("foldingRange".into(), folding_range.into(), json);
+        }
+
         capabilities.set(
             "textDocument".into(),
             text_document_capabilities.into(),
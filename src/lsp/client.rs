@@ -6,29 +6,32 @@ use std::{
     path::{Path, PathBuf},
     process::{self, Command, Stdio},
     str::FromStr,
+    time::Instant,
 };
 
 use crate::{
-    buffer::{BufferCapabilities, BufferContent, BufferHandle},
+    buffer::{BufferCapabilities, BufferContent, BufferHandle, PendingSave},
     buffer_position::{BufferPosition, BufferRange},
     buffer_view::BufferViewHandle,
     client,
     cursor::Cursor,
     editor::Editor,
-    editor_utils::{hash_bytes, parse_process_command, MessageKind, StatusBar},
+    editor_utils::{find_project_root, hash_bytes, parse_process_command, MessageKind, StatusBar},
     events::{EditorEvent, EditorEventIter},
     glob::{Glob, InvalidGlobError},
     json::{
         FromJson, Json, JsonArray, JsonConvertError, JsonInteger, JsonObject, JsonString, JsonValue,
     },
     lsp::{
-        capabilities,
+        capabilities, markdown,
         protocol::{
-            self, DocumentCodeAction, DocumentCompletionItem, DocumentDiagnostic, DocumentLocation,
-            DocumentPosition, DocumentRange, DocumentSymbolInformation, PendingRequestColection,
-            Protocol, ProtocolError, ResponseError, ServerEvent, ServerNotification, ServerRequest,
+            self, DiagnosticSeverity, DocumentCodeAction, DocumentCompletionItem,
+            DocumentDiagnostic, DocumentFoldingRange, DocumentLocation, DocumentPosition,
+            DocumentRange, DocumentSymbolInformation, PendingRequestColection, Protocol,
+            ProtocolError, ResponseError, ServerEvent, ServerNotification, ServerRequest,
             ServerResponse, TextEdit, Uri, WorkspaceEdit,
         },
+        utf16,
     },
     mode::{picker, read_line, ModeContext, ModeKind},
     navigation_history::NavigationHistory,
@@ -205,6 +208,7 @@ struct ServerCapabilities {
     document_symbol_provider: GenericCapability,
     code_action_provider: GenericCapability,
     document_formatting_provider: GenericCapability,
+    folding_range_provider: GenericCapability,
     rename_provider: RenameCapability,
     workspace_symbol_provider: GenericCapability,
 }
@@ -242,6 +246,9 @@ impl<'json> FromJson<'json> for ServerCapabilities {
                 "documentFormattingProvider" => {
                     this.document_formatting_provider = FromJson::from_json(value, json)?
                 }
+                "foldingRangeProvider" => {
+                    this.folding_range_provider = FromJson::from_json(value, json)?
+                }
                 "renameProvider" => this.rename_provider = FromJson::from_json(value, json)?,
                 "workspaceSymbolProvider" => {
                     this.workspace_symbol_provider = FromJson::from_json(value, json)?
@@ -256,10 +263,15 @@ impl<'json> FromJson<'json> for ServerCapabilities {
 pub struct Diagnostic {
     pub message: String,
     pub range: BufferRange,
+    pub severity: DiagnosticSeverity,
     pub data: Vec<u8>,
 }
 impl Diagnostic {
-    pub fn as_document_diagnostic(&self, json: &mut Json) -> DocumentDiagnostic {
+    pub fn as_document_diagnostic(
+        &self,
+        content: &BufferContent,
+        json: &mut Json,
+    ) -> DocumentDiagnostic {
         let mut reader = io::Cursor::new(&self.data);
         let data = match json.read(&mut reader) {
             Ok(value) => value,
@@ -267,7 +279,8 @@ impl Diagnostic {
         };
         DocumentDiagnostic {
             message: json.create_string(&self.message),
-            range: self.range.into(),
+            range: DocumentRange::from_buffer_range(self.range, content),
+            severity: self.severity,
             data,
         }
     }
@@ -280,20 +293,31 @@ struct BufferDiagnosticCollection {
     len: usize,
 }
 impl BufferDiagnosticCollection {
-    pub fn add(&mut self, diagnostic: DocumentDiagnostic, json: &Json) {
+    pub fn add(
+        &mut self,
+        diagnostic: DocumentDiagnostic,
+        content: Option<&BufferContent>,
+        json: &Json,
+    ) {
         let message = diagnostic.message.as_str(json);
-        let range = diagnostic.range.into();
+        let range = match content {
+            Some(content) => diagnostic.range.to_buffer_range(content),
+            None => diagnostic.range.into(),
+        };
+        let severity = diagnostic.severity;
 
         if self.len < self.diagnostics.len() {
             let diagnostic = &mut self.diagnostics[self.len];
             diagnostic.message.clear();
             diagnostic.message.push_str(message);
             diagnostic.range = range;
+            diagnostic.severity = severity;
             diagnostic.data.clear();
         } else {
             self.diagnostics.push(Diagnostic {
                 message: message.into(),
                 range,
+                severity,
                 data: Vec::new(),
             });
         }
@@ -305,6 +329,20 @@ impl BufferDiagnosticCollection {
     pub fn sort(&mut self) {
         self.diagnostics.sort_unstable_by_key(|d| d.range.from);
     }
+
+    pub fn on_insert_text(&mut self, range: BufferRange) {
+        for diagnostic in &mut self.diagnostics[..self.len] {
+            diagnostic.range.from = diagnostic.range.from.insert(range);
+            diagnostic.range.to = diagnostic.range.to.insert(range);
+        }
+    }
+
+    pub fn on_delete_text(&mut self, range: BufferRange) {
+        for diagnostic in &mut self.diagnostics[..self.len] {
+            diagnostic.range.from = diagnostic.range.from.delete(range);
+            diagnostic.range.to = diagnostic.range.to.delete(range);
+        }
+    }
 }
 
 fn is_editor_path_equals_to_lsp_path(
@@ -325,11 +363,13 @@ fn is_editor_path_equals_to_lsp_path(
 }
 
 struct VersionedBufferEdit {
-    buffer_range: BufferRange,
+    utf16_range: BufferRange,
     text_range: Range<u32>,
 }
 struct VersionedBuffer {
     version: usize,
+    // the document as the server sees it, kept to find the utf16 columns of incremental edits
+    lines: Vec<String>,
     texts: String,
     pending_edits: Vec<VersionedBufferEdit>,
 }
@@ -337,6 +377,7 @@ impl VersionedBuffer {
     pub fn new() -> Self {
         Self {
             version: 2,
+            lines: Vec::new(),
             texts: String::new(),
             pending_edits: Vec::new(),
         }
@@ -351,6 +392,60 @@ impl VersionedBuffer {
     pub fn dispose(&mut self) {
         self.flush();
         self.version = 1;
+        self.lines.clear();
+    }
+
+    fn utf16_position(&self, position: BufferPosition) -> BufferPosition {
+        let column = match self.lines.get(position.line_index as usize) {
+            Some(line) => utf16::buffer_pos_to_utf16(line, position.column_byte_index as _),
+            None => position.column_byte_index as _,
+        };
+        BufferPosition::line_col(position.line_index, column as _)
+    }
+
+    fn line_column(&self, position: BufferPosition) -> Option<(usize, usize)> {
+        let line_index = position.line_index as usize;
+        let line = self.lines.get(line_index)?;
+        let mut column = line.len().min(position.column_byte_index as _);
+        while !line.is_char_boundary(column) {
+            column -= 1;
+        }
+        Some((line_index, column))
+    }
+
+    fn insert_lines(&mut self, position: BufferPosition, text: &str) {
+        let (line_index, column) = match self.line_column(position) {
+            Some(line_column) => line_column,
+            None => return,
+        };
+        let rest = self.lines[line_index].split_off(column);
+        let mut text_lines = text.split('\n');
+        if let Some(text_line) = text_lines.next() {
+            self.lines[line_index].push_str(text_line);
+        }
+        let inserted_index = line_index + 1;
+        self.lines
+            .splice(inserted_index..inserted_index, text_lines.map(String::from));
+        let last_index = line_index + text.matches('\n').count();
+        self.lines[last_index].push_str(&rest);
+    }
+
+    fn delete_lines(&mut self, range: BufferRange) {
+        let (from_index, from_column) = match self.line_column(range.from) {
+            Some(line_column) => line_column,
+            None => return,
+        };
+        let (to_index, to_column) = match self.line_column(range.to) {
+            Some(line_column) => line_column,
+            None => return,
+        };
+        if to_index < from_index {
+            return;
+        }
+        let rest = self.lines[to_index][to_column..].to_string();
+        self.lines[from_index].truncate(from_column);
+        self.lines[from_index].push_str(&rest);
+        self.lines.drain(from_index + 1..=to_index);
     }
 }
 #[derive(Default)]
@@ -358,16 +453,44 @@ struct VersionedBufferCollection {
     buffers: Vec<VersionedBuffer>,
 }
 impl VersionedBufferCollection {
-    pub fn add_edit(&mut self, buffer_handle: BufferHandle, range: BufferRange, text: &str) {
+    fn get_mut(&mut self, buffer_handle: BufferHandle) -> &mut VersionedBuffer {
         let index = buffer_handle.0 as usize;
         if index >= self.buffers.len() {
             self.buffers.resize_with(index + 1, VersionedBuffer::new);
         }
-        let buffer = &mut self.buffers[index];
+        &mut self.buffers[index]
+    }
+
+    pub fn open(&mut self, buffer_handle: BufferHandle, content: &BufferContent) {
+        let buffer = self.get_mut(buffer_handle);
+        buffer.dispose();
+        buffer
+            .lines
+            .extend(content.lines().map(|l| l.as_str().to_string()));
+    }
+
+    pub fn add_insert(&mut self, buffer_handle: BufferHandle, range: BufferRange, text: &str) {
+        let buffer = self.get_mut(buffer_handle);
+        let utf16_from = buffer.utf16_position(range.from);
+        buffer.insert_lines(range.from, text);
+        Self::add_edit(buffer, BufferRange::between(utf16_from, utf16_from), text);
+    }
+
+    pub fn add_delete(&mut self, buffer_handle: BufferHandle, range: BufferRange) {
+        let buffer = self.get_mut(buffer_handle);
+        let utf16_range = BufferRange::between(
+            buffer.utf16_position(range.from),
+            buffer.utf16_position(range.to),
+        );
+        buffer.delete_lines(range);
+        Self::add_edit(buffer, utf16_range, "");
+    }
+
+    fn add_edit(buffer: &mut VersionedBuffer, utf16_range: BufferRange, text: &str) {
         let text_range_start = buffer.texts.len();
         buffer.texts.push_str(text);
         buffer.pending_edits.push(VersionedBufferEdit {
-            buffer_range: range,
+            utf16_range,
             text_range: text_range_start as u32..buffer.texts.len() as u32,
         });
     }
@@ -403,6 +526,15 @@ impl DiagnosticCollection {
         &[]
     }
 
+    fn buffer_diagnostics_mut(
+        &mut self,
+        buffer_handle: BufferHandle,
+    ) -> Option<&mut BufferDiagnosticCollection> {
+        self.buffer_diagnostics
+            .iter_mut()
+            .find(|d| d.buffer_handle == Some(buffer_handle))
+    }
+
     fn diagnostics_at_path(
         &mut self,
         editor: &Editor,
@@ -512,6 +644,9 @@ impl DiagnosticCollection {
 
 enum RequestState {
     Idle,
+    Hover {
+        client_handle: client::ClientHandle,
+    },
     Definition {
         client_handle: client::ClientHandle,
     },
@@ -524,13 +659,20 @@ enum RequestState {
         client_handle: client::ClientHandle,
         buffer_handle: BufferHandle,
         buffer_position: BufferPosition,
+        preview: bool,
     },
     FinishRename {
+        client_handle: client::ClientHandle,
         buffer_handle: BufferHandle,
         buffer_position: BufferPosition,
+        preview: bool,
+    },
+    RenamePreview {
+        client_handle: client::ClientHandle,
     },
     CodeAction {
         client_handle: client::ClientHandle,
+        apply_single: bool,
     },
     FinishCodeAction,
     DocumentSymbols {
@@ -542,10 +684,15 @@ enum RequestState {
     },
     WorkspaceSymbols {
         client_handle: client::ClientHandle,
+        interactive: bool,
     },
     FinishWorkspaceSymbols,
     Formatting {
         buffer_handle: BufferHandle,
+        save: Option<PendingSave>,
+    },
+    FoldingRange {
+        buffer_view_handle: BufferViewHandle,
     },
     Completion {
         client_handle: client::ClientHandle,
@@ -558,6 +705,45 @@ impl RequestState {
     }
 }
 
+pub struct CodeActionParams<'a> {
+    pub range: BufferRange,
+    pub only_kinds: &'a str,
+    pub apply_single: bool,
+}
+
+pub struct RenameParams {
+    pub buffer_handle: BufferHandle,
+    pub buffer_position: BufferPosition,
+    pub preview: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TraceValue {
+    Off,
+    Messages,
+    Verbose,
+}
+impl TraceValue {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Messages => "messages",
+            Self::Verbose => "verbose",
+        }
+    }
+}
+impl FromStr for TraceValue {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "messages" => Ok(Self::Messages),
+            "verbose" => Ok(Self::Verbose),
+            _ => Err(()),
+        }
+    }
+}
+
 pub struct Client {
     handle: ClientHandle,
     protocol: Protocol,
@@ -575,10 +761,17 @@ pub struct Client {
     temp_edits: Vec<(BufferRange, BufferRange)>,
 
     request_state: RequestState,
+    queued_formatting: Vec<(BufferHandle, Option<PendingSave>)>,
+    queued_folding_range: Option<BufferViewHandle>,
     request_raw_json: Vec<u8>,
+    code_action_kinds: String,
+    workspace_symbols_query: String,
+    rename_preview: Vec<u8>,
 
     log_file_path: String,
     log_file: Option<io::BufWriter<File>>,
+    trace: TraceValue,
+    start_time: Instant,
 }
 
 impl Client {
@@ -590,6 +783,10 @@ impl Client {
             },
             None => (String::new(), None),
         };
+        let trace = match log_file {
+            Some(_) => TraceValue::Messages,
+            None => TraceValue::Off,
+        };
 
         Self {
             handle,
@@ -606,11 +803,18 @@ impl Client {
             diagnostics: DiagnosticCollection::default(),
 
             request_state: RequestState::Idle,
+            queued_formatting: Vec::new(),
+            queued_folding_range: None,
             request_raw_json: Vec::new(),
+            code_action_kinds: String::new(),
+            workspace_symbols_query: String::new(),
+            rename_preview: Vec::new(),
             temp_edits: Vec::new(),
 
             log_file_path,
             log_file,
+            trace,
+            start_time: Instant::now(),
         }
     }
 
@@ -618,6 +822,10 @@ impl Client {
         self.handle
     }
 
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
     pub fn handles_path(&self, path: &str) -> bool {
         if self.document_selectors.is_empty() {
             true
@@ -634,6 +842,38 @@ impl Client {
         }
     }
 
+    pub fn trace(&self) -> TraceValue {
+        self.trace
+    }
+
+    // messages are only written to the log file while tracing is not off
+    pub fn set_trace(&mut self, platform: &mut Platform, trace: TraceValue) {
+        self.trace = trace;
+        let mut params = JsonObject::default();
+        params.set("value".into(), trace.as_str().into(), &mut self.json);
+        self.notify(platform, "$/setTrace", params);
+    }
+
+    // responses to cancelled requests are ignored since they're no longer pending
+    pub fn cancel_pending_requests(&mut self, platform: &mut Platform) -> usize {
+        let ids: Vec<_> = self
+            .pending_requests
+            .iter()
+            .filter(|(_, method)| *method != "initialize")
+            .map(|(id, _)| id)
+            .collect();
+        for &id in &ids {
+            self.pending_requests.take(id);
+            let mut params = JsonObject::default();
+            params.set("id".into(), id.into(), &mut self.json);
+            self.notify(platform, "$/cancelRequest", params);
+        }
+        self.request_state = RequestState::Idle;
+        self.queued_formatting.clear();
+        self.queued_folding_range = None;
+        ids.len()
+    }
+
     pub fn diagnostics(&self) -> &DiagnosticCollection {
         &self.diagnostics
     }
@@ -652,6 +892,10 @@ impl Client {
             .trigger_characters
     }
 
+    pub fn has_pending_request(&self) -> bool {
+        !self.request_state.is_idle()
+    }
+
     pub fn cancel_current_request(&mut self) {
         self.request_state = RequestState::Idle;
     }
@@ -660,18 +904,19 @@ impl Client {
         &mut self,
         editor: &Editor,
         platform: &mut Platform,
+        client_handle: client::ClientHandle,
         buffer_handle: BufferHandle,
         buffer_position: BufferPosition,
     ) {
-        if !self.server_capabilities.hover_provider.0 {
+        if !self.server_capabilities.hover_provider.0 || !self.request_state.is_idle() {
             return;
         }
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(buffer_position, buffer.content());
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -681,6 +926,7 @@ impl Client {
             &mut self.json,
         );
 
+        self.request_state = RequestState::Hover { client_handle };
         self.request(platform, "textDocument/hover", params);
     }
 
@@ -697,9 +943,9 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(buffer_position, buffer.content());
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -726,9 +972,9 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(buffer_position, buffer.content());
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -758,9 +1004,9 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(buffer_position, buffer.content());
 
         let mut context = JsonObject::default();
         context.set("includeDeclaration".into(), true.into(), &mut self.json);
@@ -788,18 +1034,23 @@ impl Client {
         platform: &mut Platform,
         clients: &mut client::ClientManager,
         client_handle: client::ClientHandle,
-        buffer_handle: BufferHandle,
-        buffer_position: BufferPosition,
+        params: RenameParams,
     ) {
         if !self.server_capabilities.rename_provider.on || !self.request_state.is_idle() {
             return;
         }
 
+        let RenameParams {
+            buffer_handle,
+            buffer_position,
+            preview,
+        } = params;
+
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(buffer_position, buffer.content());
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -814,12 +1065,15 @@ impl Client {
                 client_handle,
                 buffer_handle,
                 buffer_position,
+                preview,
             };
             self.request(platform, "textDocument/prepareRename", params);
         } else {
             self.request_state = RequestState::FinishRename {
+                client_handle,
                 buffer_handle,
                 buffer_position,
+                preview,
             };
             let mut ctx = ModeContext {
                 editor,
@@ -832,11 +1086,13 @@ impl Client {
     }
 
     pub fn finish_rename(&mut self, editor: &Editor, platform: &mut Platform) {
-        let (buffer_handle, buffer_position) = match self.request_state {
+        let (client_handle, buffer_handle, buffer_position, preview) = match self.request_state {
             RequestState::FinishRename {
+                client_handle,
                 buffer_handle,
                 buffer_position,
-            } => (buffer_handle, buffer_position),
+                preview,
+            } => (client_handle, buffer_handle, buffer_position, preview),
             _ => return,
         };
         self.request_state = RequestState::Idle;
@@ -846,9 +1102,9 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(buffer_position, buffer.content());
         let new_name = self.json.create_string(editor.read_line.input());
 
         let mut params = JsonObject::default();
@@ -860,49 +1116,88 @@ impl Client {
         );
         params.set("newName".into(), new_name.into(), &mut self.json);
 
+        if preview {
+            self.request_state = RequestState::RenamePreview { client_handle };
+        }
         self.request(platform, "textDocument/rename", params);
     }
 
+    pub fn has_rename_preview(&self) -> bool {
+        !self.rename_preview.is_empty()
+    }
+
+    pub fn apply_rename_preview(&mut self, editor: &mut Editor) {
+        let mut reader = io::Cursor::new(&self.rename_preview);
+        if let Ok(edit) = self.json.read(&mut reader) {
+            if let Ok(edit) = WorkspaceEdit::from_json(edit, &self.json) {
+                edit.apply(editor, &mut self.temp_edits, &self.root, &self.json);
+            }
+        }
+        self.rename_preview.clear();
+    }
+
     pub fn code_action(
         &mut self,
         editor: &Editor,
         platform: &mut Platform,
         client_handle: client::ClientHandle,
         buffer_handle: BufferHandle,
-        range: BufferRange,
+        params: CodeActionParams,
     ) {
         if !self.server_capabilities.code_action_provider.0 || !self.request_state.is_idle() {
             return;
         }
 
+        let CodeActionParams {
+            range,
+            only_kinds,
+            apply_single,
+        } = params;
+
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
 
         let mut diagnostics = JsonArray::default();
         for diagnostic in self.diagnostics.buffer_diagnostics(buffer_handle) {
-            if diagnostic.range.from <= range.from && range.from < diagnostic.range.to
-                || diagnostic.range.from <= range.to && range.to < diagnostic.range.to
-            {
-                let diagnostic = diagnostic.as_document_diagnostic(&mut self.json);
+            if diagnostic.range.from <= range.to && range.from <= diagnostic.range.to {
+                let diagnostic =
+                    diagnostic.as_document_diagnostic(buffer.content(), &mut self.json);
                 diagnostics.push(diagnostic.to_json_value(&mut self.json), &mut self.json);
             }
         }
 
         let mut context = JsonObject::default();
         context.set("diagnostics".into(), diagnostics.into(), &mut self.json);
+        self.code_action_kinds.clear();
+        if !only_kinds.is_empty() {
+            let mut only = JsonArray::default();
+            for kind in only_kinds
+                .split(',')
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+            {
+                let kind = self.json.create_string(kind);
+                only.push(kind.into(), &mut self.json);
+            }
+            context.set("only".into(), only.into(), &mut self.json);
+            self.code_action_kinds.push_str(only_kinds);
+        }
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
         params.set(
             "range".into(),
-            DocumentRange::from(range).to_json_value(&mut self.json),
+            DocumentRange::from_buffer_range(range, buffer.content()).to_json_value(&mut self.json),
             &mut self.json,
         );
         params.set("context".into(), context.into(), &mut self.json);
 
-        self.request_state = RequestState::CodeAction { client_handle };
+        self.request_state = RequestState::CodeAction {
+            client_handle,
+            apply_single,
+        };
         self.request(platform, "textDocument/codeAction", params);
     }
 
@@ -1012,12 +1307,10 @@ impl Client {
                 &editor.buffer_views,
             );
 
-            let position = position.into();
-            let mut cursors = editor
-                .buffer_views
-                .get_mut(buffer_view_handle)
-                .cursors
-                .mut_guard();
+            let buffer_view = editor.buffer_views.get_mut(buffer_view_handle);
+            let content = editor.buffers.get(buffer_view.buffer_handle).content();
+            let position = position.to_buffer_position(content);
+            let mut cursors = buffer_view.cursors.mut_guard();
             cursors.clear();
             cursors.add(Cursor {
                 anchor: position,
@@ -1032,19 +1325,42 @@ impl Client {
         platform: &mut Platform,
         client_handle: client::ClientHandle,
         query: &str,
-    ) {
+        interactive: bool,
+    ) -> bool {
         if !self.server_capabilities.workspace_symbol_provider.0 || !self.request_state.is_idle() {
-            return;
+            return false;
         }
 
         helper::send_pending_did_change(self, editor, platform);
 
+        self.workspace_symbols_query.clear();
+        self.workspace_symbols_query.push_str(query);
         let query = self.json.create_string(query);
         let mut params = JsonObject::default();
         params.set("query".into(), query.into(), &mut self.json);
 
-        self.request_state = RequestState::WorkspaceSymbols { client_handle };
+        self.request_state = RequestState::WorkspaceSymbols {
+            client_handle,
+            interactive,
+        };
         self.request(platform, "workspace/symbol", params);
+        true
+    }
+
+    // while a query is in flight, input changes are picked up once its response arrives
+    pub fn requery_workspace_symbols(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        client_handle: client::ClientHandle,
+        query: &str,
+    ) {
+        if let RequestState::FinishWorkspaceSymbols = self.request_state {
+            if query != self.workspace_symbols_query {
+                self.request_state = RequestState::Idle;
+                self.workspace_symbols(editor, platform, client_handle, query, true);
+            }
+        }
     }
 
     pub fn finish_workspace_symbols(
@@ -1077,7 +1393,6 @@ impl Client {
             let client = clients.get_mut(client_handle);
             NavigationHistory::save_client_snapshot(client, &editor.buffer_views);
 
-            let position = symbol.range.start.into();
             let buffer_view_handle = editor.buffer_view_handle_from_path(
                 client_handle,
                 path,
@@ -1085,11 +1400,10 @@ impl Client {
             );
             client.set_buffer_view_handle(Some(buffer_view_handle), &mut editor.events);
 
-            let mut cursors = editor
-                .buffer_views
-                .get_mut(buffer_view_handle)
-                .cursors
-                .mut_guard();
+            let buffer_view = editor.buffer_views.get_mut(buffer_view_handle);
+            let content = editor.buffers.get(buffer_view.buffer_handle).content();
+            let position = symbol.range.start.to_buffer_position(content);
+            let mut cursors = buffer_view.cursors.mut_guard();
             cursors.clear();
             cursors.add(Cursor {
                 anchor: position,
@@ -1098,14 +1412,24 @@ impl Client {
         }
     }
 
+    pub fn can_format(&self) -> bool {
+        self.server_capabilities.document_formatting_provider.0
+    }
+
+    // when `save` is present, the buffer is saved once the formatting edits are applied
+    // if another request is pending, formatting is queued and sent once that one finishes
     pub fn formatting(
         &mut self,
         editor: &Editor,
         platform: &mut Platform,
         buffer_handle: BufferHandle,
+        save: Option<PendingSave>,
     ) {
-        if !self.server_capabilities.document_formatting_provider.0 || !self.request_state.is_idle()
-        {
+        if !self.can_format() {
+            return;
+        }
+        if !self.request_state.is_idle() {
+            self.queued_formatting.push((buffer_handle, save));
             return;
         }
 
@@ -1131,10 +1455,58 @@ impl Client {
         params.set("textDocument".into(), text_document.into(), &mut self.json);
         params.set("options".into(), options.into(), &mut self.json);
 
-        self.request_state = RequestState::Formatting { buffer_handle };
+        self.request_state = RequestState::Formatting {
+            buffer_handle,
+            save,
+        };
         self.request(platform, "textDocument/formatting", params);
     }
 
+    fn send_queued_requests(&mut self, editor: &Editor, platform: &mut Platform) {
+        while self.request_state.is_idle() && !self.queued_formatting.is_empty() {
+            let (buffer_handle, save) = self.queued_formatting.remove(0);
+            if editor.buffers.try_get(buffer_handle).is_some() {
+                self.formatting(editor, platform, buffer_handle, save);
+            }
+        }
+        if self.request_state.is_idle() {
+            if let Some(buffer_view_handle) = self.queued_folding_range.take() {
+                if editor.buffer_views.try_get(buffer_view_handle).is_some() {
+                    self.folding_range(editor, platform, buffer_view_handle);
+                }
+            }
+        }
+    }
+
+    // returns false when the server does not provide folding ranges
+    pub fn folding_range(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        buffer_view_handle: BufferViewHandle,
+    ) -> bool {
+        if !self.server_capabilities.folding_range_provider.0 {
+            return false;
+        }
+        if !self.request_state.is_idle() {
+            self.queued_folding_range = Some(buffer_view_handle);
+            return true;
+        }
+
+        helper::send_pending_did_change(self, editor, platform);
+
+        let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+        let buffer_path = &editor.buffers.get(buffer_handle).path;
+        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
+
+        let mut params = JsonObject::default();
+        params.set("textDocument".into(), text_document.into(), &mut self.json);
+
+        self.request_state = RequestState::FoldingRange { buffer_view_handle };
+        self.request(platform, "textDocument/foldingRange", params);
+        true
+    }
+
     pub fn completion(
         &mut self,
         editor: &Editor,
@@ -1149,9 +1521,9 @@ impl Client {
 
         helper::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = helper::text_document_with_id(&self.root, buffer_path, &mut self.json);
-        let position = DocumentPosition::from(buffer_position);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = helper::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(buffer_position, buffer.content());
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -1173,8 +1545,18 @@ impl Client {
     where
         F: FnOnce(&mut io::BufWriter<File>, &mut Json),
     {
+        if self.trace == TraceValue::Off {
+            return;
+        }
         if let Some(ref mut buf) = self.log_file {
             use io::Write;
+            let elapsed = self.start_time.elapsed();
+            let _ = write!(
+                buf,
+                "[{}.{:03}] ",
+                elapsed.as_secs(),
+                elapsed.subsec_millis()
+            );
             writer(buf, &mut self.json);
             let _ = buf.write_all(b"\n----\n\n");
             let _ = buf.flush();
@@ -1347,11 +1729,13 @@ impl Client {
                     );
                     if let Some(range) = params.selection {
                         let buffer_view = editor.buffer_views.get_mut(buffer_view_handle);
+                        let content = editor.buffers.get(buffer_view.buffer_handle).content();
+                        let range = range.to_buffer_range(content);
                         let mut cursors = buffer_view.cursors.mut_guard();
                         cursors.clear();
                         cursors.add(Cursor {
-                            anchor: range.start.into(),
-                            position: range.end.into(),
+                            anchor: range.from,
+                            position: range.to,
                         });
                     }
                     if let Some(true) = params.take_focus {
@@ -1444,9 +1828,12 @@ impl Client {
                 let diagnostics = self
                     .diagnostics
                     .diagnostics_at_path(editor, &self.root, path);
+                let content = diagnostics
+                    .buffer_handle
+                    .map(|handle| editor.buffers.get(handle).content());
                 for diagnostic in params.diagnostics.elements(&self.json) {
                     let diagnostic = DocumentDiagnostic::from_json(diagnostic, &self.json)?;
-                    diagnostics.add(diagnostic, &self.json);
+                    diagnostics.add(diagnostic, content, &self.json);
                 }
                 diagnostics.sort();
                 self.diagnostics.clear_empty();
@@ -1495,8 +1882,15 @@ impl Client {
         let result = match response.result {
             Ok(result) => result,
             Err(error) => {
-                self.request_state = RequestState::Idle;
+                let request_state = std::mem::replace(&mut self.request_state, RequestState::Idle);
                 helper::write_response_error(&mut editor.status_bar, error, &self.json);
+                if let RequestState::Formatting {
+                    buffer_handle,
+                    save: Some(save),
+                } = request_state
+                {
+                    editor.finish_pending_save(buffer_handle, save, false);
+                }
                 return Ok(());
             }
         };
@@ -1540,9 +1934,47 @@ impl Client {
                 Ok(())
             }
             "textDocument/hover" => {
+                let client_handle = match self.request_state {
+                    RequestState::Hover { client_handle } => client_handle,
+                    _ => return Ok(()),
+                };
+                self.request_state = RequestState::Idle;
                 let contents = result.get("contents", &self.json);
                 let info = helper::extract_markup_content(contents, &self.json);
-                editor.status_bar.write(MessageKind::Info).str(info);
+
+                let mut text = editor.string_pool.acquire();
+                if editor.config.lsp_raw_markdown {
+                    text.push_str(info);
+                } else {
+                    markdown::to_plaintext(info, &mut text);
+                }
+
+                // hovers that fit above the status bar are shown there so any key dismisses them
+                let client = clients.get_mut(client_handle);
+                let width = (client.viewport_size.0 as usize).max(1);
+                let row_count: usize = text
+                    .lines()
+                    .map(|l| l.chars().count().max(1).div_ceil(width))
+                    .sum();
+                if row_count <= client.height as _ {
+                    editor.status_bar.write(MessageKind::Info).str(&text);
+                    editor.string_pool.release(text);
+                    return Ok(());
+                }
+
+                let mut capabilities = BufferCapabilities::log();
+                capabilities.auto_close = true;
+                capabilities.dismissable = true;
+                helper::show_in_scratch_buffer(
+                    editor,
+                    platform,
+                    clients,
+                    client_handle,
+                    "lsp.hover",
+                    capabilities,
+                    &text,
+                );
+                editor.string_pool.release(text);
                 Ok(())
             }
             "textDocument/signatureHelp" => {
@@ -1610,11 +2042,19 @@ impl Client {
 
                 if signature.documentation.is_empty() {
                     editor.status_bar.write(MessageKind::Info).str(label);
-                } else {
+                } else if editor.config.lsp_raw_markdown {
                     editor
                         .status_bar
                         .write(MessageKind::Info)
                         .fmt(format_args!("{}\n{}", signature.documentation, label));
+                } else {
+                    let mut documentation = editor.string_pool.acquire();
+                    markdown::to_plaintext(signature.documentation, &mut documentation);
+                    editor
+                        .status_bar
+                        .write(MessageKind::Info)
+                        .fmt(format_args!("{}\n{}", documentation, label));
+                    editor.string_pool.release(documentation);
                 }
                 Ok(())
             }
@@ -1669,12 +2109,10 @@ impl Client {
                             path,
                             BufferCapabilities::text(),
                         );
-                        let position = location.range.start.into();
-                        let mut cursors = editor
-                            .buffer_views
-                            .get_mut(buffer_view_handle)
-                            .cursors
-                            .mut_guard();
+                        let buffer_view = editor.buffer_views.get_mut(buffer_view_handle);
+                        let content = editor.buffers.get(buffer_view.buffer_handle).content();
+                        let position = location.range.start.to_buffer_position(content);
+                        let mut cursors = buffer_view.cursors.mut_guard();
                         cursors.clear();
                         cursors.add(Cursor {
                             anchor: position,
@@ -1694,12 +2132,13 @@ impl Client {
                                 Ok(Uri::Path(path)) => path,
                                 Err(_) => continue,
                             };
+                            let position =
+                                helper::to_buffer_position(editor, path, location.range.start);
                             let path = match path.to_str() {
                                 Some(path) => path,
                                 None => continue,
                             };
 
-                            let position: BufferPosition = location.range.start.into();
                             editor.picker.add_custom_entry_fmt(format_args!(
                                 "{}:{},{}",
                                 path,
@@ -1745,9 +2184,10 @@ impl Client {
                         .find_with_path(&editor.current_directory, path)
                         .map(|h| editor.buffers.get(h))
                     {
+                        let range = location.range.to_buffer_range(buffer.content());
                         buffer
                             .content()
-                            .append_range_text_to_string(location.range.into(), &mut buffer_name);
+                            .append_range_text_to_string(range, &mut buffer_name);
                         break;
                     }
                 }
@@ -1766,8 +2206,8 @@ impl Client {
                 let mut count = 0;
                 let mut context_buffer = BufferContent::new();
 
-                let buffer_view = editor.buffer_views.get(buffer_view_handle);
-                let buffer = editor.buffers.get_mut(buffer_view.buffer_handle);
+                let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+                let buffer = editor.buffers.get_mut(buffer_handle);
 
                 buffer.capabilities = BufferCapabilities::log();
                 buffer.capabilities.auto_close = auto_close_buffer;
@@ -1786,13 +2226,13 @@ impl Client {
                         Ok(Uri::Path(path)) => path,
                         Err(_) => continue,
                     };
+                    let position = helper::to_buffer_position(editor, path, location.range.start);
                     let path = match path.to_str() {
                         Some(path) => path,
                         None => continue,
                     };
 
                     use fmt::Write;
-                    let position: BufferPosition = location.range.start.into();
                     let _ = writeln!(
                         text,
                         "{}:{},{}",
@@ -1828,6 +2268,7 @@ impl Client {
                         text.push('\n');
                     }
 
+                    let buffer = editor.buffers.get_mut(buffer_handle);
                     let position = buffer.content().end();
                     buffer.insert_text(
                         &mut editor.word_database,
@@ -1848,6 +2289,7 @@ impl Client {
                     let _ = writeln!(text, "{} references found\n", count);
                 }
 
+                let buffer = editor.buffers.get_mut(buffer_handle);
                 buffer.insert_text(
                     &mut editor.word_database,
                     BufferPosition::zero(),
@@ -1874,14 +2316,16 @@ impl Client {
                 Ok(())
             }
             "textDocument/prepareRename" => {
-                let (client_handle, buffer_handle, buffer_position) = match self.request_state {
-                    RequestState::Rename {
-                        client_handle,
-                        buffer_handle,
-                        buffer_position,
-                    } => (client_handle, buffer_handle, buffer_position),
-                    _ => return Ok(()),
-                };
+                let (client_handle, buffer_handle, buffer_position, preview) =
+                    match self.request_state {
+                        RequestState::Rename {
+                            client_handle,
+                            buffer_handle,
+                            buffer_position,
+                            preview,
+                        } => (client_handle, buffer_handle, buffer_position, preview),
+                        _ => return Ok(()),
+                    };
                 self.request_state = RequestState::Idle;
                 let result = match result {
                     JsonValue::Null => {
@@ -1912,7 +2356,7 @@ impl Client {
 
                 let buffer = editor.buffers.get(buffer_handle);
 
-                let mut range = range.into();
+                let mut range = range.to_buffer_range(buffer.content());
                 if let Some(true) = default_behaviour {
                     let word = buffer.content().word_at(buffer_position);
                     range = BufferRange::between(word.position, word.end_position());
@@ -1936,27 +2380,113 @@ impl Client {
                 editor.string_pool.release(input);
 
                 self.request_state = RequestState::FinishRename {
+                    client_handle,
                     buffer_handle,
                     buffer_position,
+                    preview,
                 };
                 Ok(())
             }
             "textDocument/rename" => {
-                let edit = WorkspaceEdit::from_json(result, &self.json)?;
-                edit.apply(editor, &mut self.temp_edits, &self.root, &self.json);
+                let edit = WorkspaceEdit::from_json(result.clone(), &self.json)?;
+                let client_handle = match self.request_state {
+                    RequestState::RenamePreview { client_handle } => client_handle,
+                    _ => {
+                        edit.apply(editor, &mut self.temp_edits, &self.root, &self.json);
+                        return Ok(());
+                    }
+                };
+                self.request_state = RequestState::Idle;
+
+                let mut text = editor.string_pool.acquire();
+                edit.write_preview(editor, &self.root, &self.json, &mut text);
+                if text.is_empty() {
+                    editor
+                        .status_bar
+                        .write(MessageKind::Info)
+                        .str("rename has no edits");
+                    editor.string_pool.release(text);
+                    return Ok(());
+                }
+                text.push_str("\nrun 'lsp-rename-apply' to apply these edits\n");
+
+                self.rename_preview.clear();
+                let _ = self.json.write(&mut self.rename_preview, &result);
+
+                helper::show_in_scratch_buffer(
+                    editor,
+                    platform,
+                    clients,
+                    client_handle,
+                    "lsp.rename",
+                    BufferCapabilities::log(),
+                    &text,
+                );
+                editor.string_pool.release(text);
                 Ok(())
             }
             "textDocument/codeAction" => {
-                let client_handle = match self.request_state {
-                    RequestState::CodeAction { client_handle } => client_handle,
+                let (client_handle, apply_single) = match self.request_state {
+                    RequestState::CodeAction {
+                        client_handle,
+                        apply_single,
+                    } => (client_handle, apply_single),
                     _ => return Ok(()),
                 };
                 self.request_state = RequestState::Idle;
-                let actions = match result {
+                let mut actions = match result {
                     JsonValue::Array(actions) => actions,
                     _ => return Ok(()),
                 };
 
+                if !self.code_action_kinds.is_empty() {
+                    // servers may ignore the `only` filter
+                    let kinds = &self.code_action_kinds;
+                    let matching: Vec<_> = actions
+                        .elements(&self.json)
+                        .filter(
+                            |a| match DocumentCodeAction::from_json(a.clone(), &self.json) {
+                                Ok(action) => kinds
+                                    .split(',')
+                                    .map(str::trim)
+                                    .any(|k| !k.is_empty() && action.has_kind(k, &self.json)),
+                                Err(_) => false,
+                            },
+                        )
+                        .collect();
+                    actions = JsonArray::default();
+                    for action in matching {
+                        actions.push(action, &mut self.json);
+                    }
+                }
+
+                if apply_single {
+                    let mut enabled_actions = actions
+                        .clone()
+                        .elements(&self.json)
+                        .filter_map(|a| DocumentCodeAction::from_json(a, &self.json).ok())
+                        .filter(|a| !a.disabled);
+                    match (enabled_actions.next(), enabled_actions.next()) {
+                        (None, _) => {
+                            editor
+                                .status_bar
+                                .write(MessageKind::Error)
+                                .fmt(format_args!(
+                                    "no '{}' code action available",
+                                    self.code_action_kinds
+                                ));
+                            return Ok(());
+                        }
+                        (Some(action), None) => {
+                            action
+                                .edit
+                                .apply(editor, &mut self.temp_edits, &self.root, &self.json);
+                            return Ok(());
+                        }
+                        _ => (),
+                    }
+                }
+
                 editor.picker.clear();
                 for action in actions
                     .clone()
@@ -2034,8 +2564,11 @@ impl Client {
                 Ok(())
             }
             "workspace/symbol" => {
-                let client_handle = match self.request_state {
-                    RequestState::WorkspaceSymbols { client_handle } => client_handle,
+                let (client_handle, interactive) = match self.request_state {
+                    RequestState::WorkspaceSymbols {
+                        client_handle,
+                        interactive,
+                    } => (client_handle, interactive),
                     _ => return Ok(()),
                 };
                 self.request_state = RequestState::Idle;
@@ -2063,36 +2596,78 @@ impl Client {
                     }
                 }
 
-                let mut ctx = ModeContext {
-                    editor,
-                    platform,
-                    clients,
-                    client_handle,
-                };
-                picker::lsp_workspace_symbol::enter_mode(&mut ctx, self.handle());
+                if interactive {
+                    editor
+                        .picker
+                        .filter(WordIndicesIter::empty(), editor.read_line.input());
+                    editor.picker.move_cursor(0);
+                } else {
+                    let mut ctx = ModeContext {
+                        editor,
+                        platform,
+                        clients,
+                        client_handle,
+                    };
+                    picker::lsp_workspace_symbol::enter_mode(&mut ctx, self.handle());
+                }
 
                 self.request_state = RequestState::FinishWorkspaceSymbols;
                 self.request_raw_json.clear();
                 let _ = self.json.write(&mut self.request_raw_json, &symbols.into());
+
+                if interactive {
+                    let query = editor.read_line.input();
+                    self.requery_workspace_symbols(editor, platform, client_handle, query);
+                }
                 Ok(())
             }
             "textDocument/formatting" => {
-                let buffer_handle = match self.request_state {
-                    RequestState::Formatting { buffer_handle } => buffer_handle,
+                let (buffer_handle, save) = match &mut self.request_state {
+                    RequestState::Formatting {
+                        buffer_handle,
+                        save,
+                    } => (*buffer_handle, save.take()),
+                    _ => return Ok(()),
+                };
+                self.request_state = RequestState::Idle;
+                if let JsonValue::Array(edits) = result {
+                    TextEdit::apply_edits(
+                        editor,
+                        buffer_handle,
+                        &mut self.temp_edits,
+                        edits,
+                        &self.json,
+                    );
+                }
+                if let Some(save) = save {
+                    editor.finish_pending_save(buffer_handle, save, true);
+                }
+                Ok(())
+            }
+            "textDocument/foldingRange" => {
+                let buffer_view_handle = match self.request_state {
+                    RequestState::FoldingRange { buffer_view_handle } => buffer_view_handle,
                     _ => return Ok(()),
                 };
                 self.request_state = RequestState::Idle;
-                let edits = match result {
-                    JsonValue::Array(edits) => edits,
+                let ranges = match result {
+                    JsonValue::Array(ranges) => ranges,
                     _ => return Ok(()),
                 };
-                TextEdit::apply_edits(
-                    editor,
-                    buffer_handle,
-                    &mut self.temp_edits,
-                    edits,
-                    &self.json,
-                );
+
+                let mut folds: Vec<_> = ranges
+                    .elements(&self.json)
+                    .filter_map(|r| DocumentFoldingRange::from_json(r, &self.json).ok())
+                    .map(|r| (r.start_line as _, r.end_line as _))
+                    .collect();
+
+                let buffer_view = editor.buffer_views.get(buffer_view_handle);
+                let buffer = editor.buffers.get_mut(buffer_view.buffer_handle);
+                let last_line_index = (buffer.content().line_count() - 1) as _;
+                folds.retain(|&(_, end)| end <= last_line_index);
+                buffer
+                    .folds_mut()
+                    .add_all(&mut folds, &buffer_view.cursors[..]);
                 Ok(())
             }
             "textDocument/completion" => {
@@ -2173,14 +2748,20 @@ impl Client {
                     handle,
                     range,
                     text,
-                    ..
                 } => {
+                    if let Some(diagnostics) = self.diagnostics.buffer_diagnostics_mut(handle) {
+                        diagnostics.on_insert_text(range);
+                    }
                     let text = text.as_str(&editor.events);
-                    let range = BufferRange::between(range.from, range.from);
-                    self.versioned_buffers.add_edit(handle, range, text);
+                    self.versioned_buffers.add_insert(handle, range, text);
+                    helper::discard_stale_rename_preview(self, editor, handle);
                 }
-                EditorEvent::BufferDeleteText { handle, range, .. } => {
-                    self.versioned_buffers.add_edit(handle, range, "");
+                EditorEvent::BufferDeleteText { handle, range } => {
+                    if let Some(diagnostics) = self.diagnostics.buffer_diagnostics_mut(handle) {
+                        diagnostics.on_delete_text(range);
+                    }
+                    self.versioned_buffers.add_delete(handle, range);
+                    helper::discard_stale_rename_preview(self, editor, handle);
                 }
                 EditorEvent::BufferSave { handle, .. } => {
                     self.diagnostics.on_save_buffer(editor, handle, &self.root);
@@ -2193,6 +2774,7 @@ impl Client {
                     helper::send_pending_did_change(self, editor, platform);
                     helper::send_did_close(self, editor, platform, handle);
                 }
+                EditorEvent::Tick => (),
                 EditorEvent::FixCursors { .. } => (),
                 EditorEvent::BufferViewLostFocus { .. } => (),
             }
@@ -2278,6 +2860,7 @@ impl Client {
             .json
             .fmt_string(format_args!("{}", Uri::Path(&self.root)));
         params.set("rootUri".into(), root.into(), &mut self.json);
+        params.set("trace".into(), self.trace.as_str().into(), &mut self.json);
 
         params.set(
             "capabilities".into(),
@@ -2294,6 +2877,56 @@ impl Client {
 mod helper {
     use super::*;
 
+    // replaces the contents of the scratch buffer at `path` with `text` and focuses it
+    // saving a navigation snapshot so it's possible to go back to where the client was
+    pub fn show_in_scratch_buffer(
+        editor: &mut Editor,
+        platform: &mut Platform,
+        clients: &mut client::ClientManager,
+        client_handle: client::ClientHandle,
+        path: &str,
+        capabilities: BufferCapabilities,
+        text: &str,
+    ) {
+        let client = clients.get_mut(client_handle);
+        NavigationHistory::save_client_snapshot(client, &editor.buffer_views);
+
+        let buffer_view_handle = editor.buffer_view_handle_from_path(
+            client_handle,
+            Path::new(path),
+            BufferCapabilities::text(),
+        );
+
+        let buffer_view = editor.buffer_views.get(buffer_view_handle);
+        let buffer = editor.buffers.get_mut(buffer_view.buffer_handle);
+        buffer.capabilities = capabilities;
+
+        let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+        buffer.delete_range(&mut editor.word_database, range, &mut editor.events);
+        buffer.insert_text(
+            &mut editor.word_database,
+            BufferPosition::zero(),
+            text,
+            &mut editor.events,
+        );
+
+        clients
+            .get_mut(client_handle)
+            .set_buffer_view_handle(Some(buffer_view_handle), &mut editor.events);
+        editor.trigger_event_handlers(platform, clients);
+
+        let mut cursors = editor
+            .buffer_views
+            .get_mut(buffer_view_handle)
+            .cursors
+            .mut_guard();
+        cursors.clear();
+        cursors.add(Cursor {
+            anchor: BufferPosition::zero(),
+            position: BufferPosition::zero(),
+        });
+    }
+
     pub fn write_response_error(status_bar: &mut StatusBar, error: ResponseError, json: &Json) {
         status_bar
             .write(MessageKind::Error)
@@ -2314,6 +2947,20 @@ mod helper {
         id
     }
 
+    pub fn to_buffer_position(
+        editor: &Editor,
+        path: &Path,
+        position: DocumentPosition,
+    ) -> BufferPosition {
+        match editor
+            .buffers
+            .find_with_path(&editor.current_directory, path)
+        {
+            Some(handle) => position.to_buffer_position(editor.buffers.get(handle).content()),
+            None => position.into(),
+        }
+    }
+
     pub fn extract_markup_content(content: JsonValue, json: &Json) -> &str {
         match content {
             JsonValue::String(s) => s.as_str(json),
@@ -2356,9 +3003,27 @@ mod helper {
             &mut client.json,
         );
 
+        if let TextDocumentSyncKind::Incremental =
+            client.server_capabilities.text_document_sync.change
+        {
+            client
+                .versioned_buffers
+                .open(buffer_handle, buffer.content());
+        }
         client.notify(platform, "textDocument/didOpen", params);
     }
 
+    // the preview edit positions are no longer valid once a document changes
+    pub fn discard_stale_rename_preview(
+        client: &mut Client,
+        editor: &Editor,
+        buffer_handle: BufferHandle,
+    ) {
+        if editor.buffers.get(buffer_handle).capabilities.can_save {
+            client.rename_preview.clear();
+        }
+    }
+
     pub fn send_pending_did_change(client: &mut Client, editor: &Editor, platform: &mut Platform) {
         let mut versioned_buffers = std::mem::take(&mut client.versioned_buffers);
         for (buffer_handle, versioned_buffer) in versioned_buffers.iter_pending_mut() {
@@ -2389,18 +3054,12 @@ mod helper {
             let mut content_changes = JsonArray::default();
             match client.server_capabilities.text_document_sync.change {
                 TextDocumentSyncKind::None => (),
-                TextDocumentSyncKind::Full => {
-                    let text = client.json.fmt_string(format_args!("{}", buffer.content()));
-                    let mut change_event = JsonObject::default();
-                    change_event.set("text".into(), text.into(), &mut client.json);
-                    content_changes.push(change_event.into(), &mut client.json);
-                }
                 TextDocumentSyncKind::Incremental => {
                     for edit in &versioned_buffer.pending_edits {
                         let mut change_event = JsonObject::default();
 
                         let edit_range =
-                            DocumentRange::from(edit.buffer_range).to_json_value(&mut client.json);
+                            DocumentRange::from(edit.utf16_range).to_json_value(&mut client.json);
                         change_event.set("range".into(), edit_range, &mut client.json);
 
                         let edit_text_range =
@@ -2412,6 +3071,12 @@ mod helper {
                         content_changes.push(change_event.into(), &mut client.json);
                     }
                 }
+                _ => {
+                    let text = client.json.fmt_string(format_args!("{}", buffer.content()));
+                    let mut change_event = JsonObject::default();
+                    change_event.set("text".into(), text.into(), &mut client.json);
+                    content_changes.push(change_event.into(), &mut client.json);
+                }
             }
 
             params.set(
@@ -2665,6 +3330,17 @@ impl ClientManager {
         })
     }
 
+    // diagnostics from the first client that has any for this buffer
+    pub fn buffer_diagnostics(&self, buffer_handle: BufferHandle) -> &[Diagnostic] {
+        for client in self.clients() {
+            let diagnostics = client.diagnostics().buffer_diagnostics(buffer_handle);
+            if !diagnostics.is_empty() {
+                return diagnostics;
+            }
+        }
+        &[]
+    }
+
     pub fn on_process_spawned(
         editor: &mut Editor,
         platform: &mut Platform,
@@ -2723,6 +3399,7 @@ impl ClientManager {
             }
         }
         events.finish(&mut client.protocol);
+        client.send_queued_requests(editor, platform);
 
         editor.lsp.entries[handle.0 as usize] = ClientEntry::Occupied(client);
     }
@@ -2766,7 +3443,7 @@ impl ClientManager {
                 if recipe.running_client.is_some() {
                     continue;
                 }
-                let command = match parse_process_command(&recipe.command) {
+                let command = match parse_process_command(&recipe.command, platform.environment()) {
                     Some(command) => command,
                     None => {
                         editor
@@ -2777,7 +3454,13 @@ impl ClientManager {
                     }
                 };
                 let root = if recipe.root.as_os_str().is_empty() {
-                    editor.current_directory.clone()
+                    let current_directory = &editor.current_directory;
+                    let root_markers = &editor.config.lsp_root_markers;
+                    current_directory
+                        .join(buffer_path)
+                        .parent()
+                        .and_then(|d| find_project_root(d, root_markers))
+                        .unwrap_or_else(|| current_directory.clone())
                 } else {
                     recipe.root.clone()
                 };
@@ -2801,3 +3484,90 @@ impl ClientManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versioned_buffer_utf16_edits() {
+        let mut buffers = VersionedBufferCollection::default();
+        let handle = BufferHandle(0);
+        let buffer = buffers.get_mut(handle);
+        buffer.lines.push("a😀b".into());
+        buffer.lines.push("ção".into());
+
+        let utf16_ranges = |buffers: &mut VersionedBufferCollection| -> Vec<_> {
+            let buffer = buffers.get_mut(handle);
+            let ranges = buffer.pending_edits.iter().map(|e| e.utf16_range).collect();
+            buffer.flush();
+            ranges
+        };
+        let range = |from: (u32, u32), to: (u32, u32)| {
+            BufferRange::between(
+                BufferPosition::line_col(from.0 as _, from.1 as _),
+                BufferPosition::line_col(to.0 as _, to.1 as _),
+            )
+        };
+
+        buffers.add_insert(handle, range((0, 5), (0, 5)), "é\nx");
+        buffers.add_insert(handle, range((1, 1), (1, 1)), "y");
+        assert_eq!(vec!["a😀é", "xyb", "ção"], buffers.get_mut(handle).lines);
+        assert_eq!(
+            vec![range((0, 3), (0, 3)), range((1, 1), (1, 1))],
+            utf16_ranges(&mut buffers)
+        );
+
+        buffers.add_delete(handle, range((0, 1), (1, 1)));
+        buffers.add_delete(handle, range((1, 2), (1, 4)));
+        assert_eq!(vec!["ayb", "ço"], buffers.get_mut(handle).lines);
+        assert_eq!(
+            vec![range((0, 1), (1, 1)), range((1, 1), (1, 2))],
+            utf16_ranges(&mut buffers)
+        );
+    }
+
+    #[test]
+    fn buffer_diagnostic_ranges_follow_edits() {
+        let range = |from: (u32, u32), to: (u32, u32)| {
+            BufferRange::between(
+                BufferPosition::line_col(from.0 as _, from.1 as _),
+                BufferPosition::line_col(to.0 as _, to.1 as _),
+            )
+        };
+
+        let mut diagnostics = BufferDiagnosticCollection {
+            path: PathBuf::new(),
+            buffer_handle: None,
+            diagnostics: vec![Diagnostic {
+                message: String::new(),
+                range: range((1, 4), (1, 8)),
+                severity: DiagnosticSeverity::Error,
+                data: Vec::new(),
+            }],
+            len: 1,
+        };
+        let diagnostic_range =
+            |diagnostics: &BufferDiagnosticCollection| diagnostics.diagnostics[0].range;
+
+        diagnostics.on_insert_text(range((1, 0), (1, 2)));
+        assert_eq!(range((1, 6), (1, 10)), diagnostic_range(&diagnostics));
+        diagnostics.on_insert_text(range((0, 0), (1, 0)));
+        assert_eq!(range((2, 6), (2, 10)), diagnostic_range(&diagnostics));
+        diagnostics.on_insert_text(range((2, 7), (2, 9)));
+        assert_eq!(range((2, 6), (2, 12)), diagnostic_range(&diagnostics));
+        diagnostics.on_insert_text(range((2, 20), (2, 21)));
+        diagnostics.on_insert_text(range((3, 0), (4, 0)));
+        assert_eq!(range((2, 6), (2, 12)), diagnostic_range(&diagnostics));
+
+        diagnostics.on_delete_text(range((2, 0), (2, 2)));
+        assert_eq!(range((2, 4), (2, 10)), diagnostic_range(&diagnostics));
+        diagnostics.on_delete_text(range((2, 5), (2, 7)));
+        assert_eq!(range((2, 4), (2, 8)), diagnostic_range(&diagnostics));
+        diagnostics.on_delete_text(range((2, 9), (2, 12)));
+        diagnostics.on_delete_text(range((3, 0), (4, 0)));
+        assert_eq!(range((2, 4), (2, 8)), diagnostic_range(&diagnostics));
+        diagnostics.on_delete_text(range((1, 0), (2, 0)));
+        assert_eq!(range((1, 4), (1, 8)), diagnostic_range(&diagnostics));
+    }
+}
@@ -0,0 +1,192 @@
+// renders the markdown from hover and signature documentation as plain text:
+// code blocks are indented, emphasis markers removed and links replaced by their text
+pub fn to_plaintext(markdown: &str, output: &mut String) {
+    let mut in_code_block = false;
+    let mut previous_blank = true;
+    for line in markdown.lines() {
+        let line = line.trim_end();
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+
+        if in_code_block {
+            if !line.is_empty() {
+                output.push_str("    ");
+                output.push_str(line);
+            }
+        } else if trimmed.is_empty() || is_rule(trimmed) {
+            if !previous_blank {
+                output.push('\n');
+            }
+            previous_blank = true;
+            continue;
+        } else {
+            output.push_str(&line[..line.len() - trimmed.len()]);
+            let heading = trimmed.trim_start_matches('#');
+            let text = if heading.len() < trimmed.len()
+                && (heading.is_empty() || heading.starts_with(' '))
+            {
+                heading.trim_start()
+            } else {
+                trimmed
+            };
+            let text = match text.strip_prefix("* ").or_else(|| text.strip_prefix("+ ")) {
+                Some(item) => {
+                    output.push_str("- ");
+                    item
+                }
+                None => text,
+            };
+            write_inline(text, output);
+        }
+        output.push('\n');
+        previous_blank = false;
+    }
+    output.truncate(output.trim_end().len());
+}
+
+fn is_rule(line: &str) -> bool {
+    let mut chars = line.chars().filter(|c| *c != ' ');
+    match chars.next() {
+        Some(first @ ('-' | '*' | '_')) => chars.clone().count() >= 2 && chars.all(|c| c == first),
+        _ => false,
+    }
+}
+
+fn write_inline(text: &str, output: &mut String) {
+    let mut previous = None;
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+        let mut len = c.len_utf8();
+        match c {
+            '\\' => match rest[1..].chars().next() {
+                Some(next) if next.is_ascii_punctuation() => {
+                    output.push(next);
+                    len += 1;
+                }
+                _ => output.push(c),
+            },
+            '`' => {
+                let tick_count = rest.len() - rest.trim_start_matches('`').len();
+                let ticks = &rest[..tick_count];
+                match rest[tick_count..].find(ticks) {
+                    Some(code_len) => {
+                        output.push_str(rest[tick_count..tick_count + code_len].trim());
+                        len = tick_count * 2 + code_len;
+                    }
+                    None => {
+                        output.push_str(ticks);
+                        len = tick_count;
+                    }
+                }
+            }
+            '[' | '!' => {
+                let link = if c == '!' { &rest[1..] } else { rest };
+                match parse_link(link) {
+                    Some((label, link_len)) => {
+                        write_inline(label, output);
+                        len = rest.len() - link.len() + link_len;
+                    }
+                    None => output.push(c),
+                }
+            }
+            '*' | '_' => {
+                let run_len = rest.len() - rest.trim_start_matches(c).len();
+                let next = rest[run_len..].chars().next();
+                let is_space = |c: Option<char>| !matches!(c, Some(c) if !c.is_whitespace());
+                let is_word = |c: Option<char>| matches!(c, Some(c) if c.is_alphanumeric());
+                if is_space(previous) && is_space(next)
+                    || c == '_' && is_word(previous) && is_word(next)
+                {
+                    output.push_str(&rest[..run_len]);
+                }
+                len = run_len;
+            }
+            _ => output.push(c),
+        }
+        previous = text[..i + len].chars().next_back();
+        i += len;
+    }
+}
+
+// returns the text of a `[text](url)` link and the length of the whole link
+fn parse_link(text: &str) -> Option<(&str, usize)> {
+    let text_end = find_closing(text, '[', ']')?;
+    let url = &text[text_end + 1..];
+    if !url.starts_with('(') {
+        return None;
+    }
+    let url_end = find_closing(url, '(', ')')?;
+    Some((&text[1..text_end], text_end + 1 + url_end + 1))
+}
+
+fn find_closing(text: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plaintext(markdown: &str) -> String {
+        let mut output = String::new();
+        to_plaintext(markdown, &mut output);
+        output
+    }
+
+    #[test]
+    fn code_blocks() {
+        assert_eq!(
+            "    fn main() {\n        let a_b = 0;\n    }",
+            plaintext("```rust\nfn main() {\n    let a_b = 0;\n}\n```\n")
+        );
+        assert_eq!(
+            "text\n\n    code\n\n    more\nafter",
+            plaintext("text\n\n~~~\ncode\n\nmore\n~~~\nafter")
+        );
+        assert_eq!(
+            "call get_value and *deref*",
+            plaintext("call `get_value` and `` *deref* ``")
+        );
+        assert_eq!("unclosed ` tick", plaintext("unclosed ` tick"));
+    }
+
+    #[test]
+    fn emphasis() {
+        assert_eq!(
+            "bold and italic and also this",
+            plaintext("**bold** and _italic_ and __also *this*__")
+        );
+        assert_eq!("snake_case_name", plaintext("snake_case_name"));
+        assert_eq!("a * b and c _ d", plaintext("a * b and c _ d"));
+        assert_eq!("*escaped* \\ slash", plaintext("\\*escaped\\* \\ slash"));
+    }
+
+    #[test]
+    fn links_and_structure() {
+        assert_eq!(
+            "see Vec::new and image",
+            plaintext("see [`Vec::new`](https://docs.rs/vec) and ![image](a.png)")
+        );
+        assert_eq!("[not a link] (x)", plaintext("[not a link] (x)"));
+        assert_eq!(
+            "Title\n\n- first\n- second\n  - nested\n\nfooter",
+            plaintext("# Title\n\n\n* first\n- second\n  + nested\n\n---\n\nfooter\n\n")
+        );
+        assert_eq!("#hashtag", plaintext("#hashtag"));
+    }
+}
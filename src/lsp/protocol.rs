@@ -6,7 +6,7 @@ use std::{
 };
 
 use crate::{
-    buffer::{BufferCapabilities, BufferHandle},
+    buffer::{BufferCapabilities, BufferContent, BufferHandle},
     buffer_position::{BufferPosition, BufferRange},
     editor::Editor,
     editor_utils::MessageKind,
@@ -15,6 +15,7 @@ use crate::{
         FromJson, Json, JsonArray, JsonConvertError, JsonInteger, JsonKey, JsonObject, JsonString,
         JsonValue,
     },
+    lsp::utf16,
     platform::{Platform, PlatformRequest, ProcessHandle},
 };
 
@@ -262,8 +263,34 @@ impl DocumentPosition {
         );
         value.into()
     }
+
+    pub fn from_buffer_position(position: BufferPosition, content: &BufferContent) -> Self {
+        let line_index = position.line_index as usize;
+        let character = if line_index < content.line_count() {
+            let line = content.line_at(line_index).as_str();
+            utf16::buffer_pos_to_utf16(line, position.column_byte_index as _)
+        } else {
+            position.column_byte_index as _
+        };
+        Self {
+            line: position.line_index as _,
+            character: character as _,
+        }
+    }
+
+    pub fn to_buffer_position(self, content: &BufferContent) -> BufferPosition {
+        let line_index = self.line as usize;
+        let column_byte_index = if line_index < content.line_count() {
+            let line = content.line_at(line_index).as_str();
+            utf16::utf16_to_buffer_pos(line, self.character as _)
+        } else {
+            self.character as _
+        };
+        BufferPosition::line_col(self.line as _, column_byte_index as _)
+    }
 }
-// TODO: handle utf8 to utf16
+// these assume utf16 columns match byte columns which only holds for ascii lines
+// so they are only used when the document content is not available
 impl From<BufferPosition> for DocumentPosition {
     fn from(position: BufferPosition) -> Self {
         Self {
@@ -272,7 +299,6 @@ impl From<BufferPosition> for DocumentPosition {
         }
     }
 }
-// TODO: handle utf16 to utf8
 impl From<DocumentPosition> for BufferPosition {
     fn from(position: DocumentPosition) -> Self {
         Self {
@@ -311,6 +337,20 @@ impl DocumentRange {
         value.set("end".into(), self.end.to_json_value(json), json);
         value.into()
     }
+
+    pub fn from_buffer_range(range: BufferRange, content: &BufferContent) -> Self {
+        Self {
+            start: DocumentPosition::from_buffer_position(range.from, content),
+            end: DocumentPosition::from_buffer_position(range.to, content),
+        }
+    }
+
+    pub fn to_buffer_range(self, content: &BufferContent) -> BufferRange {
+        BufferRange::between(
+            self.start.to_buffer_position(content),
+            self.end.to_buffer_position(content),
+        )
+    }
 }
 impl From<BufferRange> for DocumentRange {
     fn from(range: BufferRange) -> Self {
@@ -384,14 +424,16 @@ impl TextEdit {
         buffer.commit_edits();
         temp_edits.clear();
 
-        for edit in edits.elements(json) {
-            let edit = match TextEdit::from_json(edit, json) {
-                Ok(edit) => edit,
-                Err(_) => continue,
-            };
+        // edit ranges refer to the document before any of them is applied
+        let content = buffer.content();
+        let edits: Vec<_> = edits
+            .elements(json)
+            .filter_map(|e| TextEdit::from_json(e, json).ok())
+            .map(|e| (e.range.to_buffer_range(content), e.new_text))
+            .collect();
 
-            let mut delete_range: BufferRange = edit.range.into();
-            let text = edit.new_text.as_str(&json);
+        for (mut delete_range, text) in edits {
+            let text = text.as_str(json);
 
             for (d, i) in temp_edits.iter() {
                 delete_range.from = delete_range.from.delete(*d);
@@ -592,6 +634,86 @@ pub struct WorkspaceEdit {
     document_changes: JsonArray,
 }
 impl WorkspaceEdit {
+    pub fn write_preview(&self, editor: &Editor, root: &Path, json: &Json, output: &mut String) {
+        use fmt::Write;
+
+        fn write_escaped(text: &str, output: &mut String) {
+            for c in text.chars() {
+                match c {
+                    '\n' => output.push_str("\\n"),
+                    '\t' => output.push_str("\\t"),
+                    c => output.push(c),
+                }
+            }
+        }
+
+        let mut file_content = BufferContent::new();
+        let mut old_text = String::new();
+        for change in self.document_changes.clone().elements(json) {
+            let change = match WorkspaceEditChange::from_json(change, json) {
+                Ok(change) => change,
+                Err(_) => return,
+            };
+            match change {
+                WorkspaceEditChange::DocumentEdit(edit) => {
+                    let path = match Uri::parse(root, edit.uri.as_str(json)) {
+                        Ok(Uri::Path(path)) => path,
+                        Err(_) => return,
+                    };
+                    let content = match editor
+                        .buffers
+                        .find_with_path(&editor.current_directory, path)
+                    {
+                        Some(handle) => editor.buffers.get(handle).content(),
+                        None => {
+                            file_content.clear();
+                            if let Ok(file) = fs::File::open(path) {
+                                let _ = file_content.read(&mut io::BufReader::new(file));
+                            }
+                            &file_content
+                        }
+                    };
+
+                    for edit in edit
+                        .edits
+                        .elements(json)
+                        .filter_map(|e| TextEdit::from_json(e, json).ok())
+                    {
+                        let range = edit.range.to_buffer_range(content);
+                        old_text.clear();
+                        content.append_range_text_to_string(range, &mut old_text);
+
+                        let _ = write!(
+                            output,
+                            "{}:{},{}: ",
+                            path.display(),
+                            range.from.line_index + 1,
+                            range.from.column_byte_index + 1,
+                        );
+                        write_escaped(&old_text, output);
+                        output.push_str(" \u{2192} ");
+                        write_escaped(edit.new_text.as_str(json), output);
+                        output.push('\n');
+                    }
+                }
+                WorkspaceEditChange::CreateFile(op) => {
+                    let _ = writeln!(output, "create file {}", op.uri.as_str(json));
+                }
+                WorkspaceEditChange::RenameFile(op) => {
+                    let _ = writeln!(
+                        output,
+                        "rename file {} \u{2192} {}",
+                        op.old_uri.as_str(json),
+                        op.new_uri.as_str(json)
+                    );
+                }
+                WorkspaceEditChange::DeleteFile(op) => {
+                    let _ = writeln!(output, "delete {}", op.uri.as_str(json));
+                }
+            }
+        }
+    }
+
     pub fn apply(
         &self,
         editor: &mut Editor,
@@ -724,10 +846,39 @@ impl<'json> FromJson<'json> for WorkspaceEdit {
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    #[default]
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+impl DiagnosticSeverity {
+    pub fn from_integer(value: JsonInteger) -> Self {
+        match value {
+            2 => Self::Warning,
+            3 => Self::Information,
+            4 => Self::Hint,
+            _ => Self::Error,
+        }
+    }
+
+    pub fn to_integer(self) -> JsonInteger {
+        match self {
+            Self::Error => 1,
+            Self::Warning => 2,
+            Self::Information => 3,
+            Self::Hint => 4,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct DocumentDiagnostic {
     pub message: JsonString,
     pub range: DocumentRange,
+    pub severity: DiagnosticSeverity,
     pub data: JsonValue,
 }
 impl DocumentDiagnostic {
@@ -735,6 +886,11 @@ impl DocumentDiagnostic {
         let mut value = JsonObject::default();
         value.set("message".into(), self.message.into(), json);
         value.set("range".into(), self.range.to_json_value(json), json);
+        value.set(
+            "severity".into(),
+            JsonValue::Integer(self.severity.to_integer()),
+            json,
+        );
         value.set("data".into(), self.data, json);
         value.into()
     }
@@ -750,6 +906,10 @@ impl<'json> FromJson<'json> for DocumentDiagnostic {
             match key {
                 "message" => this.message = JsonString::from_json(value, json)?,
                 "range" => this.range = DocumentRange::from_json(value, json)?,
+                "severity" => {
+                    this.severity =
+                        DiagnosticSeverity::from_integer(JsonInteger::from_json(value, json)?)
+                }
                 "data" => this.data = value,
                 _ => (),
             }
@@ -761,9 +921,19 @@ impl<'json> FromJson<'json> for DocumentDiagnostic {
 #[derive(Default)]
 pub struct DocumentCodeAction {
     pub title: JsonString,
+    pub kind: JsonString,
     pub edit: WorkspaceEdit,
     pub disabled: bool,
 }
+impl DocumentCodeAction {
+    // kinds are hierarchical so `source` also matches `source.organizeImports`
+    pub fn has_kind(&self, kind: &str, json: &Json) -> bool {
+        match self.kind.as_str(json).strip_prefix(kind) {
+            Some(rest) => rest.is_empty() || rest.starts_with('.'),
+            None => false,
+        }
+    }
+}
 impl<'json> FromJson<'json> for DocumentCodeAction {
     fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
         let value = match value {
@@ -774,6 +944,7 @@ impl<'json> FromJson<'json> for DocumentCodeAction {
         for (key, value) in value.members(json) {
             match key {
                 "title" => this.title = JsonString::from_json(value, json)?,
+                "kind" => this.kind = JsonString::from_json(value, json)?,
                 "edit" => this.edit = WorkspaceEdit::from_json(value, json)?,
                 "disabled" => this.disabled = true,
                 _ => (),
@@ -816,6 +987,29 @@ impl<'json> FromJson<'json> for DocumentSymbolInformation {
     }
 }
 
+#[derive(Default)]
+pub struct DocumentFoldingRange {
+    pub start_line: u32,
+    pub end_line: u32,
+}
+impl<'json> FromJson<'json> for DocumentFoldingRange {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let value = match value {
+            JsonValue::Object(value) => value,
+            _ => return Err(JsonConvertError),
+        };
+        let mut this = Self::default();
+        for (key, value) in value.members(json) {
+            match key {
+                "startLine" => this.start_line = FromJson::from_json(value, json)?,
+                "endLine" => this.end_line = FromJson::from_json(value, json)?,
+                _ => (),
+            }
+        }
+        Ok(this)
+    }
+}
+
 #[derive(Default)]
 pub struct DocumentCompletionItem {
     pub text: JsonString,
@@ -1083,6 +1277,10 @@ impl PendingRequestColection {
         self.pending_requests.push(PendingRequest { id, method });
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (RequestId, &'static str)> + '_ {
+        self.pending_requests.iter().map(|r| (r.id, r.method))
+    }
+
     pub fn take(&mut self, id: RequestId) -> Option<&'static str> {
         for i in 0..self.pending_requests.len() {
             let request = &self.pending_requests[i];
@@ -0,0 +1,72 @@
+// lsp columns count utf16 code units while buffer columns count utf8 bytes
+
+pub fn buffer_pos_to_utf16(line: &str, byte_col: usize) -> usize {
+    let mut utf16_col = 0;
+    for (i, c) in line.char_indices() {
+        if i >= byte_col {
+            break;
+        }
+        utf16_col += c.len_utf16();
+    }
+    utf16_col
+}
+
+// columns past the end of the line or inside a surrogate pair snap to the next char boundary
+pub fn utf16_to_buffer_pos(line: &str, utf16_col: usize) -> usize {
+    let mut col = 0;
+    for (i, c) in line.char_indices() {
+        if col >= utf16_col {
+            return i;
+        }
+        col += c.len_utf16();
+    }
+    line.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_columns() {
+        let line = "let a = 0;";
+        for i in 0..=line.len() {
+            assert_eq!(i, buffer_pos_to_utf16(line, i));
+            assert_eq!(i, utf16_to_buffer_pos(line, i));
+        }
+        assert_eq!(line.len(), utf16_to_buffer_pos(line, 99));
+        assert_eq!(line.len(), buffer_pos_to_utf16(line, 99));
+    }
+
+    #[test]
+    fn bmp_columns() {
+        // 'é' is two bytes in utf8 and one code unit in utf16
+        let line = "café = 1";
+        assert_eq!(3, buffer_pos_to_utf16(line, 3));
+        assert_eq!(4, buffer_pos_to_utf16(line, 5));
+        assert_eq!(8, buffer_pos_to_utf16(line, line.len()));
+
+        assert_eq!(3, utf16_to_buffer_pos(line, 3));
+        assert_eq!(5, utf16_to_buffer_pos(line, 4));
+        assert_eq!(line.len(), utf16_to_buffer_pos(line, 8));
+    }
+
+    #[test]
+    fn astral_columns() {
+        // '😀' is four bytes in utf8 and a surrogate pair in utf16
+        let line = "a😀b";
+        assert_eq!(1, buffer_pos_to_utf16(line, 1));
+        assert_eq!(3, buffer_pos_to_utf16(line, 5));
+        assert_eq!(4, buffer_pos_to_utf16(line, 6));
+
+        assert_eq!(1, utf16_to_buffer_pos(line, 1));
+        assert_eq!(5, utf16_to_buffer_pos(line, 2));
+        assert_eq!(5, utf16_to_buffer_pos(line, 3));
+        assert_eq!(6, utf16_to_buffer_pos(line, 4));
+
+        for byte_col in [0, 1, 5, 6] {
+            let utf16_col = buffer_pos_to_utf16(line, byte_col);
+            assert_eq!(byte_col, utf16_to_buffer_pos(line, utf16_col));
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{
+    buffer::BufferHandle,
+    buffer_position::{BufferPosition, BufferRange},
+};
+
+pub struct Mark {
+    pub name: String,
+    pub buffer_handle: BufferHandle,
+    pub position: BufferPosition,
+}
+
+#[derive(Default)]
+pub struct MarkCollection {
+    marks: Vec<Mark>,
+}
+
+impl MarkCollection {
+    pub fn set(&mut self, name: &str, buffer_handle: BufferHandle, position: BufferPosition) {
+        match self.marks.iter_mut().find(|m| m.name == name) {
+            Some(mark) => {
+                mark.buffer_handle = buffer_handle;
+                mark.position = position;
+            }
+            None => {
+                let index = self.marks.partition_point(|m| m.name.as_str() < name);
+                self.marks.insert(
+                    index,
+                    Mark {
+                        name: name.into(),
+                        buffer_handle,
+                        position,
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Mark> {
+        self.marks.iter().find(|m| m.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Mark> {
+        self.marks.iter()
+    }
+
+    pub fn on_buffer_insert_text(&mut self, buffer_handle: BufferHandle, range: BufferRange) {
+        for mark in &mut self.marks {
+            if mark.buffer_handle == buffer_handle {
+                mark.position = mark.position.insert(range);
+            }
+        }
+    }
+
+    pub fn on_buffer_delete_text(&mut self, buffer_handle: BufferHandle, range: BufferRange) {
+        for mark in &mut self.marks {
+            if mark.buffer_handle == buffer_handle {
+                mark.position = mark.position.delete(range);
+            }
+        }
+    }
+
+    pub fn on_buffer_close(&mut self, buffer_handle: BufferHandle) {
+        self.marks.retain(|m| m.buffer_handle != buffer_handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(from: (u32, u32), to: (u32, u32)) -> BufferRange {
+        BufferRange::between(
+            BufferPosition::line_col(from.0, from.1),
+            BufferPosition::line_col(to.0, to.1),
+        )
+    }
+
+    fn position(marks: &MarkCollection, name: &str) -> Option<(u32, u32)> {
+        marks
+            .get(name)
+            .map(|m| (m.position.line_index, m.position.column_byte_index))
+    }
+
+    #[test]
+    fn set_and_get() {
+        let buffer_a = BufferHandle(0);
+        let buffer_b = BufferHandle(1);
+
+        let mut marks = MarkCollection::default();
+        marks.set("b", buffer_a, BufferPosition::line_col(1, 2));
+        marks.set("a", buffer_b, BufferPosition::line_col(3, 4));
+        marks.set("b", buffer_b, BufferPosition::line_col(5, 6));
+
+        let names: Vec<_> = marks.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(vec!["a", "b"], names);
+        assert_eq!(Some((5, 6)), position(&marks, "b"));
+        assert_eq!(Some(buffer_b), marks.get("b").map(|m| m.buffer_handle));
+        assert_eq!(None, position(&marks, "c"));
+
+        marks.on_buffer_close(buffer_b);
+        assert_eq!(0, marks.iter().count());
+    }
+
+    #[test]
+    fn edits_move_marks() {
+        let buffer_a = BufferHandle(0);
+        let buffer_b = BufferHandle(1);
+
+        let mut marks = MarkCollection::default();
+        marks.set("a", buffer_a, BufferPosition::line_col(2, 4));
+        marks.set("b", buffer_b, BufferPosition::line_col(2, 4));
+
+        marks.on_buffer_insert_text(buffer_a, range((0, 0), (1, 0)));
+        assert_eq!(Some((3, 4)), position(&marks, "a"));
+        assert_eq!(Some((2, 4)), position(&marks, "b"));
+
+        marks.on_buffer_insert_text(buffer_a, range((3, 1), (3, 3)));
+        assert_eq!(Some((3, 6)), position(&marks, "a"));
+        marks.on_buffer_insert_text(buffer_a, range((3, 8), (3, 9)));
+        assert_eq!(Some((3, 6)), position(&marks, "a"));
+
+        marks.on_buffer_delete_text(buffer_a, range((1, 0), (3, 2)));
+        assert_eq!(Some((1, 4)), position(&marks, "a"));
+        marks.on_buffer_delete_text(buffer_a, range((1, 2), (1, 8)));
+        assert_eq!(Some((1, 2)), position(&marks, "a"));
+    }
+}
@@ -1,7 +1,7 @@
 use std::fs;
 
 use crate::{
-    command::{CommandManager, CommandTokenizer, CompletionSource},
+    command::{parse_flag, CommandManager, CommandTokenizer, CompletionSource},
     editor::{EditorControlFlow, KeysIterator},
     editor_utils::{hash_bytes, ReadLinePoll},
     mode::{Mode, ModeContext, ModeKind, ModeState},
@@ -47,6 +47,7 @@ impl ModeState for State {
     }
 
     fn on_exit(ctx: &mut ModeContext) {
+        ctx.editor.commands.pending_count = 0;
         ctx.editor.read_line.input_mut().clear();
         ctx.editor.picker.clear();
     }
@@ -94,6 +95,9 @@ impl ModeState for State {
             ReadLinePoll::Submitted => {
                 let input = ctx.editor.read_line.input();
                 ctx.editor.commands.add_to_history(input);
+                if !ctx.editor.replaying_keys {
+                    ctx.editor.commands.set_last_command(input);
+                }
 
                 let mut command = ctx.editor.string_pool.acquire_with(input);
                 let flow = CommandManager::eval(
@@ -153,7 +157,11 @@ fn update_autocomplete_entries(ctx: &mut ModeContext) {
     ctx.editor.picker.clear_cursor();
 
     let mut arg_count = 0;
+    let mut flag_count = 0;
     for token in tokens {
+        if arg_count == flag_count && parse_flag(token).is_some() {
+            flag_count += 1;
+        }
         arg_count += 1;
         last_token = token;
     }
@@ -163,14 +171,31 @@ fn update_autocomplete_entries(ctx: &mut ModeContext) {
     if input.ends_with(&[' ', '\t'][..]) {
         arg_count += 1;
         pattern = &input[input.len()..];
+    } else if arg_count > 0 && arg_count == flag_count + 1 && pattern == "-" {
+        flag_count += 1;
     }
 
     let mut completion_source = CompletionSource::Custom(&[]);
     if arg_count > 0 {
         if let Some(command) = ctx.editor.commands.find_command(command_name) {
-            let completion_index = arg_count - 1;
-            if completion_index < command.completions.len() {
-                completion_source = command.completions[completion_index];
+            if arg_count == flag_count {
+                pattern = &pattern[1..];
+                completion_source = match pattern.find('=') {
+                    Some(i) => {
+                        let name = &pattern[..i];
+                        pattern = &pattern[i + 1..];
+                        match command.flags.iter().find(|(n, _)| *n == name) {
+                            Some((_, source)) => *source,
+                            None => CompletionSource::Custom(&[]),
+                        }
+                    }
+                    None => CompletionSource::Flags(command.flags),
+                };
+            } else {
+                let completion_index = arg_count - flag_count - 1;
+                if completion_index < command.completions.len() {
+                    completion_source = command.completions[completion_index];
+                }
             }
         }
     } else {
@@ -188,11 +213,35 @@ fn update_autocomplete_entries(ctx: &mut ModeContext) {
                 for command in ctx.editor.commands.builtin_commands() {
                     ctx.editor.picker.add_custom_entry(command.name);
                 }
+                for request in ctx.editor.commands.request_commands() {
+                    if !request.hidden {
+                        ctx.editor.picker.add_custom_entry(&request.name);
+                    }
+                }
             }
             CompletionSource::Buffers => {
                 for buffer in ctx.editor.buffers.iter() {
-                    if let Some(path) = buffer.path.to_str() {
-                        ctx.editor.picker.add_custom_entry(path);
+                    let path = buffer.path.to_str().unwrap_or("");
+                    ctx.editor.picker.add_custom_entry_fmt(format_args!(
+                        "{}:{}",
+                        buffer.handle().0,
+                        path
+                    ));
+                }
+            }
+            CompletionSource::Syntaxes => {
+                for name in ctx.editor.syntaxes.names() {
+                    ctx.editor.picker.add_custom_entry(name);
+                }
+            }
+            CompletionSource::Flags(flags) => {
+                for (name, source) in flags {
+                    match source {
+                        CompletionSource::Custom(&[]) => ctx.editor.picker.add_custom_entry(name),
+                        _ => ctx
+                            .editor
+                            .picker
+                            .add_custom_entry_fmt(format_args!("{}=", name)),
                     }
                 }
             }
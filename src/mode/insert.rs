@@ -3,7 +3,8 @@ use std::{fmt::Write, path::Path};
 use crate::{
     buffer_position::BufferPosition,
     buffer_view::{BufferViewHandle, CursorMovement, CursorMovementKind},
-    editor::{Editor, EditorControlFlow, KeysIterator},
+    command::CommandManager,
+    editor::{Editor, EditorControlFlow, KeysIterator, COMMAND_REPL_BUFFER_PATH},
     lsp,
     mode::{Mode, ModeContext, ModeKind, ModeState},
     platform::Key,
@@ -135,7 +136,17 @@ impl ModeState for State {
             Key::Enter | Key::Ctrl('m') => {
                 let buffer_view = ctx.editor.buffer_views.get(handle);
                 let cursor_count = buffer_view.cursors[..].len();
-                let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+                let buffer_handle = buffer_view.buffer_handle;
+                let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+
+                let mut repl_line_index = None;
+                if cursor_count == 1 && buffer.path == Path::new(COMMAND_REPL_BUFFER_PATH) {
+                    let position = buffer_view.cursors[0].position;
+                    let line = buffer.content().line_at(position.line_index as _).as_str();
+                    if position.column_byte_index as usize == line.len() {
+                        repl_line_index = Some(position.line_index);
+                    }
+                }
 
                 let mut buf = ctx.editor.string_pool.acquire();
                 for i in (0..cursor_count).rev() {
@@ -161,6 +172,20 @@ impl ModeState for State {
                     buf.clear();
                 }
                 ctx.editor.string_pool.release(buf);
+
+                if let Some(line_index) = repl_line_index {
+                    ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+                    let flow = CommandManager::eval_repl_line(
+                        ctx.editor,
+                        ctx.platform,
+                        ctx.clients,
+                        ctx.client_handle,
+                        buffer_handle,
+                        line_index,
+                    );
+                    ctx.editor.trigger_event_handlers(ctx.platform, ctx.clients);
+                    return Some(flow);
+                }
             }
             Key::Char(c) => {
                 let mut buf = [0; std::mem::size_of::<char>()];
@@ -89,18 +89,42 @@ impl State {
                 state.movement_kind,
                 ctx.editor.config.tab_size,
             ),
-            Key::Char('j') => ctx.editor.buffer_views.get_mut(handle).move_cursors(
-                &ctx.editor.buffers,
-                CursorMovement::LinesForward(state.count.max(1) as _),
-                state.movement_kind,
-                ctx.editor.config.tab_size,
-            ),
-            Key::Char('k') => ctx.editor.buffer_views.get_mut(handle).move_cursors(
-                &ctx.editor.buffers,
-                CursorMovement::LinesBackward(state.count.max(1) as _),
-                state.movement_kind,
-                ctx.editor.config.tab_size,
-            ),
+            Key::Char('j') => {
+                let wrap_width = ctx
+                    .clients
+                    .get(ctx.client_handle)
+                    .soft_wrap_width(ctx.editor);
+                let state = &ctx.editor.mode.normal_state;
+                let count = state.count.max(1) as _;
+                let movement = match wrap_width {
+                    Some(wrap_width) => CursorMovement::RowsForward { count, wrap_width },
+                    None => CursorMovement::LinesForward(count),
+                };
+                ctx.editor.buffer_views.get_mut(handle).move_cursors(
+                    &ctx.editor.buffers,
+                    movement,
+                    state.movement_kind,
+                    ctx.editor.config.tab_size,
+                );
+            }
+            Key::Char('k') => {
+                let wrap_width = ctx
+                    .clients
+                    .get(ctx.client_handle)
+                    .soft_wrap_width(ctx.editor);
+                let state = &ctx.editor.mode.normal_state;
+                let count = state.count.max(1) as _;
+                let movement = match wrap_width {
+                    Some(wrap_width) => CursorMovement::RowsBackward { count, wrap_width },
+                    None => CursorMovement::LinesBackward(count),
+                };
+                ctx.editor.buffer_views.get_mut(handle).move_cursors(
+                    &ctx.editor.buffers,
+                    movement,
+                    state.movement_kind,
+                    ctx.editor.config.tab_size,
+                );
+            }
             Key::Char('l') => ctx.editor.buffer_views.get_mut(handle).move_cursors(
                 &ctx.editor.buffers,
                 CursorMovement::ColumnsForward(state.count.max(1) as _),
@@ -716,26 +740,34 @@ impl State {
                 }
             }
             Key::Ctrl('d') => {
-                let half_height = ctx.clients.get(ctx.client_handle).height / 2;
-                ctx.editor.buffer_views.get_mut(handle).move_cursors(
-                    &ctx.editor.buffers,
-                    CursorMovement::LinesForward(
-                        half_height as usize * state.count.max(1) as usize,
-                    ),
-                    state.movement_kind,
-                    ctx.editor.config.tab_size,
-                );
+                let client = ctx.clients.get_mut(ctx.client_handle);
+                let offset = (client.height / 2) as usize * state.count.max(1) as usize;
+                if client.scroll_locked {
+                    let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+                    let line_count = ctx.editor.buffers.get(buffer_handle).content().line_count();
+                    client.scroll.1 = (client.scroll.1 as usize + offset).min(line_count - 1) as _;
+                } else {
+                    ctx.editor.buffer_views.get_mut(handle).move_cursors(
+                        &ctx.editor.buffers,
+                        CursorMovement::LinesForward(offset),
+                        state.movement_kind,
+                        ctx.editor.config.tab_size,
+                    );
+                }
             }
             Key::Ctrl('u') => {
-                let half_height = ctx.clients.get(ctx.client_handle).height / 2;
-                ctx.editor.buffer_views.get_mut(handle).move_cursors(
-                    &ctx.editor.buffers,
-                    CursorMovement::LinesBackward(
-                        half_height as usize * state.count.max(1) as usize,
-                    ),
-                    state.movement_kind,
-                    ctx.editor.config.tab_size,
-                );
+                let client = ctx.clients.get_mut(ctx.client_handle);
+                let offset = (client.height / 2) as usize * state.count.max(1) as usize;
+                if client.scroll_locked {
+                    client.scroll.1 = (client.scroll.1 as usize).saturating_sub(offset) as _;
+                } else {
+                    ctx.editor.buffer_views.get_mut(handle).move_cursors(
+                        &ctx.editor.buffers,
+                        CursorMovement::LinesBackward(offset),
+                        state.movement_kind,
+                        ctx.editor.config.tab_size,
+                    );
+                }
             }
             Key::Char('d') => {
                 let buffer_view = ctx.editor.buffer_views.get(handle);
@@ -1147,6 +1179,31 @@ impl ModeState for State {
             }
         }
 
+        fn dismissable_buffer_view(ctx: &ModeContext) -> Option<BufferViewHandle> {
+            let handle = ctx.clients.get(ctx.client_handle).buffer_view_handle()?;
+            let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+            if ctx
+                .editor
+                .buffers
+                .get(buffer_handle)
+                .capabilities
+                .dismissable
+            {
+                Some(handle)
+            } else {
+                None
+            }
+        }
+
+        fn dismiss_buffer_view(ctx: &mut ModeContext) {
+            NavigationHistory::move_in_history(
+                ctx.clients.get_mut(ctx.client_handle),
+                ctx.editor,
+                NavigationMovement::PreviousBuffer,
+            );
+        }
+
+        let dismissable_handle = dismissable_buffer_view(ctx);
         let state = &mut ctx.editor.mode.normal_state;
 
         let mut handled_keys = false;
@@ -1154,6 +1211,10 @@ impl ModeState for State {
 
         match keys.next(&ctx.editor.buffered_keys) {
             Key::None => handled_keys = true,
+            Key::Esc if dismissable_handle.is_some() => {
+                handled_keys = true;
+                dismiss_buffer_view(ctx);
+            }
             Key::Ctrl('z') => return Some(EditorControlFlow::Suspend),
             Key::Char('q') => match ctx.editor.recording_macro.take() {
                 Some(_) => handled_keys = true,
@@ -1179,15 +1240,21 @@ impl ModeState for State {
                             for _ in 0..state.count.max(1) {
                                 let keys = ctx.editor.registers.get(key);
                                 match ctx.editor.buffered_keys.parse(keys) {
-                                    Ok(keys) => match ctx.editor.execute_keys(
-                                        ctx.platform,
-                                        ctx.clients,
-                                        ctx.client_handle,
-                                        keys,
-                                    ) {
-                                        EditorControlFlow::Continue => (),
-                                        flow => return Some(flow),
-                                    },
+                                    Ok(keys) => {
+                                        let replaying_keys = ctx.editor.replaying_keys;
+                                        ctx.editor.replaying_keys = true;
+                                        let flow = ctx.editor.execute_keys(
+                                            ctx.platform,
+                                            ctx.clients,
+                                            ctx.client_handle,
+                                            keys,
+                                        );
+                                        ctx.editor.replaying_keys = replaying_keys;
+                                        match flow {
+                                            EditorControlFlow::Continue => (),
+                                            flow => return Some(flow),
+                                        }
+                                    }
                                     Err(error) => ctx
                                         .editor
                                         .status_bar
@@ -1202,6 +1269,7 @@ impl ModeState for State {
             }
             Key::Char(':') => {
                 handled_keys = true;
+                ctx.editor.commands.pending_count = state.count;
                 Mode::change_to(ctx, ModeKind::Command);
             }
             Key::Char('g' | 'G') => {
@@ -1435,9 +1503,13 @@ where
     if search_ranges.is_empty() {
         let search = ctx.editor.registers.get(SEARCH_REGISTER);
         if !search.is_empty() {
-            match ctx.editor.aux_pattern.compile_searcher(search) {
+            match ctx
+                .editor
+                .search_options
+                .compile_searcher(&mut ctx.editor.aux_pattern, search)
+            {
                 Ok(()) => {
-                    buffer.set_search(&ctx.editor.aux_pattern);
+                    buffer.set_search(&ctx.editor.aux_pattern, ctx.editor.search_options);
                     search_ranges = buffer.search_ranges();
                 }
                 Err(error) => {
@@ -1523,8 +1595,11 @@ fn search_word_or_move_to_it(
         register.push_str("L/");
         register.push_str(text);
 
-        let _ = ctx.editor.aux_pattern.compile_searcher(register);
-        buffer.set_search(&ctx.editor.aux_pattern);
+        let _ = ctx
+            .editor
+            .search_options
+            .compile_searcher(&mut ctx.editor.aux_pattern, register);
+        buffer.set_search(&ctx.editor.aux_pattern, ctx.editor.search_options);
 
         drop(cursors);
 
@@ -11,12 +11,14 @@ use crate::{
     navigation_history::NavigationHistory,
     picker::EntrySource,
     platform::Key,
+    syntax::find_definition_column,
     word_database::WordIndicesIter,
 };
 
 pub struct State {
     on_client_keys:
         fn(ctx: &mut ModeContext, &mut KeysIterator, ReadLinePoll) -> Option<EditorControlFlow>,
+    on_preview: fn(ctx: &mut ModeContext),
     lsp_client_handle: Option<lsp::ClientHandle>,
 }
 
@@ -24,6 +26,7 @@ impl Default for State {
     fn default() -> Self {
         Self {
             on_client_keys: |_, _, _| Some(EditorControlFlow::Continue),
+            on_preview: |_| (),
             lsp_client_handle: None,
         }
     }
@@ -37,10 +40,10 @@ impl ModeState for State {
     fn on_exit(ctx: &mut ModeContext) {
         ctx.editor.read_line.input_mut().clear();
         ctx.editor.picker.clear();
+        ctx.editor.mode.picker_state.on_preview = |_| ();
     }
 
     fn on_client_keys(ctx: &mut ModeContext, keys: &mut KeysIterator) -> Option<EditorControlFlow> {
-        let this = &mut ctx.editor.mode.picker_state;
         let poll = ctx.editor.read_line.poll(
             ctx.platform,
             &mut ctx.editor.string_pool,
@@ -79,6 +82,10 @@ impl ModeState for State {
                     let entry_count = ctx.editor.picker.len() as isize;
                     ctx.editor.picker.move_cursor(entry_count - cursor - 1);
                 }
+                Key::Tab => {
+                    ctx.editor.picker.toggle_current_entry_selection();
+                    ctx.editor.picker.move_cursor(1);
+                }
                 _ => {
                     ctx.editor
                         .picker
@@ -88,7 +95,11 @@ impl ModeState for State {
             }
         }
 
-        (this.on_client_keys)(ctx, keys, poll)
+        if let ReadLinePoll::Pending = poll {
+            (ctx.editor.mode.picker_state.on_preview)(ctx);
+        }
+
+        (ctx.editor.mode.picker_state.on_client_keys)(ctx, keys, poll)
     }
 }
 
@@ -114,29 +125,44 @@ pub mod buffer {
                 }
             }
 
-            let path = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
-                Some((_, entry)) => entry,
-                _ => {
-                    Mode::change_to(ctx, ModeKind::default());
-                    return Some(EditorControlFlow::Continue);
-                }
-            };
-
             let client = ctx.clients.get_mut(ctx.client_handle);
             NavigationHistory::save_client_snapshot(client, &ctx.editor.buffer_views);
 
-            let path = ctx.editor.string_pool.acquire_with(path);
+            if ctx.editor.picker.has_selected_entries() {
+                let mut paths = ctx.editor.string_pool.acquire();
+                for path in ctx
+                    .editor
+                    .picker
+                    .selected_entries(&ctx.editor.word_database)
+                {
+                    paths.push_str(path);
+                    paths.push('\0');
+                }
+                for path in paths.split_terminator('\0') {
+                    open_buffer(ctx, path);
+                }
+                ctx.editor.string_pool.release(paths);
+            } else if let Some((_, path)) =
+                ctx.editor.picker.current_entry(&ctx.editor.word_database)
+            {
+                let path = ctx.editor.string_pool.acquire_with(path);
+                open_buffer(ctx, &path);
+                ctx.editor.string_pool.release(path);
+            }
+
+            Mode::change_to(ctx, ModeKind::default());
+            Some(EditorControlFlow::Continue)
+        }
+
+        fn open_buffer(ctx: &mut ModeContext, path: &str) {
             let buffer_view_handle = ctx.editor.buffer_view_handle_from_path(
                 ctx.client_handle,
-                Path::new(&path),
+                Path::new(path),
                 BufferCapabilities::text(),
             );
-            ctx.editor.string_pool.release(path);
-
-            client.set_buffer_view_handle(Some(buffer_view_handle), &mut ctx.editor.events);
-
-            Mode::change_to(ctx, ModeKind::default());
-            Some(EditorControlFlow::Continue)
+            ctx.clients
+                .get_mut(ctx.client_handle)
+                .set_buffer_view_handle(Some(buffer_view_handle), &mut ctx.editor.events);
         }
 
         ctx.editor.read_line.set_prompt("buffer:");
@@ -149,9 +175,34 @@ pub mod buffer {
         ctx.editor.picker.filter(WordIndicesIter::empty(), "");
         ctx.editor.picker.move_cursor(0);
 
+        fn on_preview(ctx: &mut ModeContext) {
+            let mut preview = std::mem::take(ctx.editor.picker.preview_mut());
+            preview.clear();
+
+            let buffer_handle = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+                Some((_, path)) => ctx
+                    .editor
+                    .buffers
+                    .find_with_path(&ctx.editor.current_directory, Path::new(path)),
+                None => None,
+            };
+            if let Some(buffer_handle) = buffer_handle {
+                let line_count = ctx.editor.config.picker_max_height as usize;
+                let content = ctx.editor.buffers.get(buffer_handle).content();
+                for line in content.lines().take(line_count) {
+                    preview.push_str(line.as_str());
+                    preview.push('\n');
+                }
+            }
+
+            *ctx.editor.picker.preview_mut() = preview;
+        }
+
         if ctx.editor.picker.len() > 0 {
             ctx.editor.mode.picker_state.on_client_keys = on_client_keys;
             Mode::change_to(ctx, ModeKind::Picker);
+            ctx.editor.mode.picker_state.on_preview = on_preview;
+            on_preview(ctx);
         } else {
             ctx.editor
                 .status_bar
@@ -161,64 +212,104 @@ pub mod buffer {
     }
 }
 
-pub mod lsp_definition {
+pub mod bookmark {
     use super::*;
 
-    pub fn enter_mode(ctx: &mut ModeContext, client_handle: lsp::ClientHandle) {
+    pub fn enter_mode(ctx: &mut ModeContext) {
         fn on_client_keys(
             ctx: &mut ModeContext,
             _: &mut KeysIterator,
             poll: ReadLinePoll,
         ) -> Option<EditorControlFlow> {
             match poll {
-                ReadLinePoll::Pending => Some(EditorControlFlow::Continue),
-                ReadLinePoll::Submitted => {
-                    if let Some((_, entry)) =
-                        ctx.editor.picker.current_entry(&ctx.editor.word_database)
-                    {
-                        let (path, position) = parse_path_and_position(entry);
-                        let position = match position {
-                            Some(position) => position,
-                            None => BufferPosition::zero(),
-                        };
-
-                        let client = ctx.clients.get_mut(ctx.client_handle);
-                        NavigationHistory::save_client_snapshot(client, &ctx.editor.buffer_views);
-
-                        let path = ctx.editor.string_pool.acquire_with(path);
-                        let buffer_view_handle = ctx.editor.buffer_view_handle_from_path(
-                            ctx.client_handle,
-                            Path::new(&path),
-                            BufferCapabilities::text(),
-                        );
-                        ctx.editor.string_pool.release(path);
-                        let mut cursors = ctx
-                            .editor
-                            .buffer_views
-                            .get_mut(buffer_view_handle)
-                            .cursors
-                            .mut_guard();
-                        cursors.clear();
-                        cursors.add(Cursor {
-                            anchor: position,
-                            position,
-                        });
-
-                        client.set_buffer_view_handle(
-                            Some(buffer_view_handle),
-                            &mut ctx.editor.events,
-                        );
-                    }
-                    Mode::change_to(ctx, ModeKind::default());
-                    Some(EditorControlFlow::Continue)
-                }
+                ReadLinePoll::Pending => return Some(EditorControlFlow::Continue),
+                ReadLinePoll::Submitted => (),
                 ReadLinePoll::Canceled => {
                     Mode::change_to(ctx, ModeKind::default());
-                    Some(EditorControlFlow::Continue)
+                    return Some(EditorControlFlow::Continue);
                 }
             }
+
+            let bookmark = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+                Some((EntrySource::Custom(i), _)) => ctx.editor.bookmarks.get(i),
+                _ => None,
+            };
+            if let Some(bookmark) = bookmark {
+                let path = ctx.editor.string_pool.acquire_with(&bookmark.path);
+                let line_index = bookmark.line_index;
+
+                let client = ctx.clients.get_mut(ctx.client_handle);
+                NavigationHistory::save_client_snapshot(client, &ctx.editor.buffer_views);
+
+                let buffer_view_handle = ctx.editor.buffer_view_handle_from_path(
+                    ctx.client_handle,
+                    Path::new(&path),
+                    BufferCapabilities::text(),
+                );
+                ctx.editor.string_pool.release(path);
+
+                let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+                let position = ctx
+                    .editor
+                    .buffers
+                    .get(buffer_view.buffer_handle)
+                    .content()
+                    .saturate_position(BufferPosition::line_col(line_index, 0));
+                let mut cursors = buffer_view.cursors.mut_guard();
+                cursors.clear();
+                cursors.add(Cursor {
+                    anchor: position,
+                    position,
+                });
+
+                ctx.clients
+                    .get_mut(ctx.client_handle)
+                    .set_buffer_view_handle(Some(buffer_view_handle), &mut ctx.editor.events);
+            }
+
+            Mode::change_to(ctx, ModeKind::default());
+            Some(EditorControlFlow::Continue)
+        }
+
+        ctx.editor.read_line.set_prompt("bookmark:");
+        ctx.editor.picker.clear();
+
+        for bookmark in ctx.editor.bookmarks.iter() {
+            if bookmark.label.is_empty() {
+                ctx.editor.picker.add_custom_entry_fmt(format_args!(
+                    "{}:{}",
+                    bookmark.path,
+                    bookmark.line_index + 1
+                ));
+            } else {
+                ctx.editor.picker.add_custom_entry_fmt(format_args!(
+                    "{} {}:{}",
+                    bookmark.label,
+                    bookmark.path,
+                    bookmark.line_index + 1
+                ));
+            }
         }
 
+        ctx.editor.picker.filter(WordIndicesIter::empty(), "");
+        ctx.editor.picker.move_cursor(0);
+
+        if ctx.editor.picker.len() > 0 {
+            ctx.editor.mode.picker_state.on_client_keys = on_client_keys;
+            Mode::change_to(ctx, ModeKind::Picker);
+        } else {
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Error)
+                .str("no bookmarks");
+        }
+    }
+}
+
+pub mod lsp_definition {
+    use super::*;
+
+    pub fn enter_mode(ctx: &mut ModeContext, client_handle: lsp::ClientHandle) {
         ctx.editor.read_line.set_prompt("definition:");
         ctx.editor.picker.filter(WordIndicesIter::empty(), "");
         ctx.editor.picker.move_cursor(0);
@@ -230,6 +321,101 @@ pub mod lsp_definition {
             Mode::change_to(ctx, ModeKind::Picker);
         }
     }
+
+    pub(super) fn on_client_keys(
+        ctx: &mut ModeContext,
+        _: &mut KeysIterator,
+        poll: ReadLinePoll,
+    ) -> Option<EditorControlFlow> {
+        match poll {
+            ReadLinePoll::Pending => Some(EditorControlFlow::Continue),
+            ReadLinePoll::Submitted => {
+                if let Some((_, entry)) = ctx.editor.picker.current_entry(&ctx.editor.word_database)
+                {
+                    let (path, position) = parse_path_and_position(entry);
+                    let position = match position {
+                        Some(position) => position,
+                        None => BufferPosition::zero(),
+                    };
+
+                    let client = ctx.clients.get_mut(ctx.client_handle);
+                    NavigationHistory::save_client_snapshot(client, &ctx.editor.buffer_views);
+
+                    let path = ctx.editor.string_pool.acquire_with(path);
+                    let buffer_view_handle = ctx.editor.buffer_view_handle_from_path(
+                        ctx.client_handle,
+                        Path::new(&path),
+                        BufferCapabilities::text(),
+                    );
+                    ctx.editor.string_pool.release(path);
+                    let mut cursors = ctx
+                        .editor
+                        .buffer_views
+                        .get_mut(buffer_view_handle)
+                        .cursors
+                        .mut_guard();
+                    cursors.clear();
+                    cursors.add(Cursor {
+                        anchor: position,
+                        position,
+                    });
+
+                    client.set_buffer_view_handle(Some(buffer_view_handle), &mut ctx.editor.events);
+                }
+                Mode::change_to(ctx, ModeKind::default());
+                Some(EditorControlFlow::Continue)
+            }
+            ReadLinePoll::Canceled => {
+                Mode::change_to(ctx, ModeKind::default());
+                Some(EditorControlFlow::Continue)
+            }
+        }
+    }
+}
+
+pub mod goto_symbol {
+    use super::*;
+
+    pub fn enter_mode(ctx: &mut ModeContext, word: &str) {
+        ctx.editor.read_line.set_prompt("definition:");
+        ctx.editor.picker.clear();
+
+        for buffer in ctx.editor.buffers.iter() {
+            let path = match buffer.path.to_str() {
+                Some(path) if !path.is_empty() => path,
+                _ => continue,
+            };
+            let definitions = ctx
+                .editor
+                .syntaxes
+                .get(buffer.syntax_handle())
+                .definitions();
+
+            for (line_index, line) in buffer.content().lines().enumerate() {
+                if let Some(column) = find_definition_column(definitions, line.as_str(), word) {
+                    ctx.editor.picker.add_custom_entry_fmt(format_args!(
+                        "{}:{},{}",
+                        path,
+                        line_index + 1,
+                        column + 1
+                    ));
+                }
+            }
+        }
+
+        ctx.editor.picker.filter(WordIndicesIter::empty(), "");
+        ctx.editor.picker.move_cursor(0);
+
+        if ctx.editor.picker.len() > 0 {
+            ctx.editor.mode.picker_state.on_client_keys = super::lsp_definition::on_client_keys;
+            Mode::change_to(ctx, ModeKind::Picker);
+        } else {
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!("no definition found for '{}'", word));
+        }
+    }
 }
 
 pub mod lsp_code_action {
@@ -345,42 +531,41 @@ pub mod lsp_document_symbol {
 pub mod lsp_workspace_symbol {
     use super::*;
 
-    pub fn enter_mode(ctx: &mut ModeContext, client_handle: lsp::ClientHandle) {
-        fn on_client_keys(
-            ctx: &mut ModeContext,
-            _: &mut KeysIterator,
-            poll: ReadLinePoll,
-        ) -> Option<EditorControlFlow> {
-            match poll {
-                ReadLinePoll::Pending => Some(EditorControlFlow::Continue),
-                ReadLinePoll::Submitted => {
-                    if let Some(handle) = ctx.editor.mode.picker_state.lsp_client_handle {
-                        let index = match ctx.editor.picker.current_entry(&ctx.editor.word_database)
-                        {
-                            Some((EntrySource::Custom(i), _)) => i,
-                            _ => 0,
-                        };
-                        let clients = &mut *ctx.clients;
-                        let client_handle = ctx.client_handle;
-                        lsp::ClientManager::access(ctx.editor, handle, |e, c| {
-                            c.finish_workspace_symbols(e, clients, client_handle, index);
-                        });
-                    }
-                    Mode::change_to(ctx, ModeKind::default());
-                    Some(EditorControlFlow::Continue)
+    fn on_client_keys(
+        ctx: &mut ModeContext,
+        _: &mut KeysIterator,
+        poll: ReadLinePoll,
+    ) -> Option<EditorControlFlow> {
+        match poll {
+            ReadLinePoll::Pending => Some(EditorControlFlow::Continue),
+            ReadLinePoll::Submitted => {
+                if let Some(handle) = ctx.editor.mode.picker_state.lsp_client_handle {
+                    let index = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+                        Some((EntrySource::Custom(i), _)) => i,
+                        _ => 0,
+                    };
+                    let clients = &mut *ctx.clients;
+                    let client_handle = ctx.client_handle;
+                    lsp::ClientManager::access(ctx.editor, handle, |e, c| {
+                        c.finish_workspace_symbols(e, clients, client_handle, index);
+                    });
                 }
-                ReadLinePoll::Canceled => {
-                    if let Some(handle) = ctx.editor.mode.picker_state.lsp_client_handle {
-                        lsp::ClientManager::access(ctx.editor, handle, |_, c| {
-                            c.cancel_current_request();
-                        });
-                    }
-                    Mode::change_to(ctx, ModeKind::default());
-                    Some(EditorControlFlow::Continue)
+                Mode::change_to(ctx, ModeKind::default());
+                Some(EditorControlFlow::Continue)
+            }
+            ReadLinePoll::Canceled => {
+                if let Some(handle) = ctx.editor.mode.picker_state.lsp_client_handle {
+                    lsp::ClientManager::access(ctx.editor, handle, |_, c| {
+                        c.cancel_current_request();
+                    });
                 }
+                Mode::change_to(ctx, ModeKind::default());
+                Some(EditorControlFlow::Continue)
             }
         }
+    }
 
+    pub fn enter_mode(ctx: &mut ModeContext, client_handle: lsp::ClientHandle) {
         ctx.editor.read_line.set_prompt("workspace symbol:");
         ctx.editor.picker.filter(WordIndicesIter::empty(), "");
         ctx.editor.picker.move_cursor(0);
@@ -396,4 +581,39 @@ pub mod lsp_workspace_symbol {
             });
         }
     }
+
+    // entries are filled in by the server's responses as the query is typed
+    pub fn enter_interactive_mode(
+        ctx: &mut ModeContext,
+        client_handle: lsp::ClientHandle,
+        query: &str,
+    ) {
+        fn on_client_keys(
+            ctx: &mut ModeContext,
+            keys: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorControlFlow> {
+            if let ReadLinePoll::Pending = poll {
+                if let Some(handle) = ctx.editor.mode.picker_state.lsp_client_handle {
+                    let platform = &mut *ctx.platform;
+                    let client_handle = ctx.client_handle;
+                    lsp::ClientManager::access(ctx.editor, handle, |e, c| {
+                        let query = e.read_line.input();
+                        c.requery_workspace_symbols(e, platform, client_handle, query);
+                    });
+                }
+            }
+            self::on_client_keys(ctx, keys, poll)
+        }
+
+        ctx.editor.picker.clear();
+        ctx.editor.read_line.set_prompt("workspace symbol:");
+
+        let state = &mut ctx.editor.mode.picker_state;
+        state.on_client_keys = on_client_keys;
+        state.lsp_client_handle = Some(client_handle);
+        Mode::change_to(ctx, ModeKind::Picker);
+
+        ctx.editor.read_line.input_mut().push_str(query);
+    }
 }
@@ -1,19 +1,24 @@
 use crate::{
     buffer_position::BufferPositionIndex,
     buffer_view::CursorMovementKind,
-    cursor::CursorCollection,
+    cursor::{Cursor, CursorCollection},
     editor::{EditorControlFlow, KeysIterator},
     editor_utils::{parse_process_command, MessageKind, ReadLinePoll},
     lsp,
     mode::{Mode, ModeContext, ModeKind, ModeState},
     pattern::Pattern,
-    platform::SharedBuf,
+    platform::{Key, SharedBuf},
 };
 
 pub struct State {
     on_client_keys:
         fn(&mut ModeContext, &mut KeysIterator, ReadLinePoll) -> Option<EditorControlFlow>,
     lsp_client_handle: Option<lsp::ClientHandle>,
+    previous_main_cursor: Cursor,
+    previous_scroll: (BufferPositionIndex, BufferPositionIndex),
+    replace_pattern: Pattern,
+    replace_text: String,
+    replace_count: usize,
 }
 
 impl Default for State {
@@ -21,6 +26,11 @@ impl Default for State {
         Self {
             on_client_keys: |_, _, _| Some(EditorControlFlow::Continue),
             lsp_client_handle: None,
+            previous_main_cursor: Cursor::zero(),
+            previous_scroll: (0, 0),
+            replace_pattern: Pattern::new(),
+            replace_text: String::new(),
+            replace_count: 0,
         }
     }
 }
@@ -41,6 +51,18 @@ impl ModeState for State {
             &ctx.editor.buffered_keys,
             keys,
         );
+        match poll {
+            ReadLinePoll::Pending => {
+                keys.index = keys.index.saturating_sub(1);
+                match keys.next(&ctx.editor.buffered_keys) {
+                    Key::Ctrl('p') | Key::Up => ctx.editor.read_line.move_in_history(-1),
+                    Key::Ctrl('n') | Key::Down => ctx.editor.read_line.move_in_history(1),
+                    _ => (),
+                }
+            }
+            ReadLinePoll::Submitted => ctx.editor.read_line.add_to_history(),
+            ReadLinePoll::Canceled => (),
+        }
         let func = ctx.editor.mode.read_line_state.on_client_keys;
         func(ctx, keys, poll)
     }
@@ -49,10 +71,7 @@ impl ModeState for State {
 pub mod search {
     use super::*;
 
-    use crate::{
-        navigation_history::{NavigationHistory, NavigationMovement},
-        register::SEARCH_REGISTER,
-    };
+    use crate::{config::Config, navigation_history::NavigationHistory, register::SEARCH_REGISTER};
 
     pub fn enter_mode(ctx: &mut ModeContext) {
         fn on_client_keys(
@@ -74,11 +93,7 @@ pub mod search {
                         let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
                         let search_ranges = buffer.search_ranges();
                         if search_ranges.is_empty() {
-                            NavigationHistory::move_in_history(
-                                ctx.clients.get_mut(ctx.client_handle),
-                                ctx.editor,
-                                NavigationMovement::Backward,
-                            );
+                            restore_previous_position(ctx);
                         } else {
                             let position = buffer_view.cursors.main_cursor().position;
                             ctx.editor.mode.normal_state.search_index =
@@ -86,20 +101,18 @@ pub mod search {
                                     Ok(i) => i,
                                     Err(i) => i,
                                 };
+                            save_previous_position_snapshot(ctx);
                         }
                     }
 
                     let register = ctx.editor.registers.get_mut(SEARCH_REGISTER);
                     register.clear();
-                    register.push_str(ctx.editor.read_line.input());
+                    push_search_text(&ctx.editor.config, ctx.editor.read_line.input(), register);
                     Mode::change_to(ctx, ModeKind::default());
                 }
                 ReadLinePoll::Canceled => {
-                    NavigationHistory::move_in_history(
-                        ctx.clients.get_mut(ctx.client_handle),
-                        ctx.editor,
-                        NavigationMovement::Backward,
-                    );
+                    restore_previous_position(ctx);
+                    restore_previous_search(ctx);
                     Mode::change_to(ctx, ModeKind::default());
                 }
             }
@@ -107,10 +120,13 @@ pub mod search {
             Some(EditorControlFlow::Continue)
         }
 
-        NavigationHistory::save_client_snapshot(
-            ctx.clients.get_mut(ctx.client_handle),
-            &ctx.editor.buffer_views,
-        );
+        let client = ctx.clients.get(ctx.client_handle);
+        if let Some(handle) = client.buffer_view_handle() {
+            let state = &mut ctx.editor.mode.read_line_state;
+            state.previous_main_cursor = *ctx.editor.buffer_views.get(handle).cursors.main_cursor();
+            state.previous_scroll = client.scroll;
+        }
+
         ctx.editor.read_line.set_prompt("search:");
         update_search(ctx);
 
@@ -118,32 +134,119 @@ pub mod search {
         Mode::change_to(ctx, ModeKind::ReadLine);
     }
 
+    fn push_search_text(config: &Config, input: &str, text: &mut String) {
+        let has_prefix = matches!(input.as_bytes(), [b'l' | b'L' | b'p' | b'P', b'/', ..]);
+        if config.search_with_pattern && !has_prefix {
+            if input.chars().all(char::is_lowercase) {
+                text.push_str("p/");
+            } else {
+                text.push_str("P/");
+            }
+        }
+        text.push_str(input);
+    }
+
+    fn restore_previous_position(ctx: &mut ModeContext) {
+        let client = ctx.clients.get_mut(ctx.client_handle);
+        let handle = match client.buffer_view_handle() {
+            Some(handle) => handle,
+            None => return,
+        };
+        let state = &ctx.editor.mode.read_line_state;
+        client.scroll = state.previous_scroll;
+
+        let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+        let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle).content();
+        let mut cursors = buffer_view.cursors.mut_guard();
+        let main_cursor = cursors.main_cursor();
+        main_cursor.anchor = buffer.saturate_position(state.previous_main_cursor.anchor);
+        main_cursor.position = buffer.saturate_position(state.previous_main_cursor.position);
+    }
+
+    // the preview already moved the main cursor so the snapshot is taken from where the search began
+    fn save_previous_position_snapshot(ctx: &mut ModeContext) {
+        let client = ctx.clients.get_mut(ctx.client_handle);
+        let handle = match client.buffer_view_handle() {
+            Some(handle) => handle,
+            None => return,
+        };
+        let scroll = client.scroll;
+        let main_cursor = *ctx.editor.buffer_views.get(handle).cursors.main_cursor();
+
+        restore_previous_position(ctx);
+        let client = ctx.clients.get_mut(ctx.client_handle);
+        NavigationHistory::save_client_snapshot(client, &ctx.editor.buffer_views);
+        client.scroll = scroll;
+        *ctx.editor
+            .buffer_views
+            .get_mut(handle)
+            .cursors
+            .mut_guard()
+            .main_cursor() = main_cursor;
+    }
+
+    fn restore_previous_search(ctx: &mut ModeContext) {
+        ctx.editor.aux_pattern.clear();
+        for buffer in ctx.editor.buffers.iter_mut() {
+            buffer.set_search(&ctx.editor.aux_pattern, ctx.editor.search_options);
+        }
+
+        let handle = match ctx.clients.get(ctx.client_handle).buffer_view_handle() {
+            Some(handle) => handle,
+            None => return,
+        };
+        let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+        let search = ctx.editor.registers.get(SEARCH_REGISTER);
+        if let Ok(()) = ctx
+            .editor
+            .search_options
+            .compile_searcher(&mut ctx.editor.aux_pattern, search)
+        {
+            ctx.editor
+                .buffers
+                .get_mut(buffer_handle)
+                .set_search(&ctx.editor.aux_pattern, ctx.editor.search_options);
+        }
+    }
+
     fn update_search(ctx: &mut ModeContext) {
+        let handle = ctx.clients.get(ctx.client_handle).buffer_view_handle();
+        let buffer_handle = handle.map(|h| ctx.editor.buffer_views.get(h).buffer_handle);
+
         ctx.editor.aux_pattern.clear();
         for buffer in ctx.editor.buffers.iter_mut() {
-            buffer.set_search(&ctx.editor.aux_pattern);
+            if Some(buffer.handle()) != buffer_handle {
+                buffer.set_search(&ctx.editor.aux_pattern, ctx.editor.search_options);
+            }
         }
 
-        let handle = match ctx.clients.get_mut(ctx.client_handle).buffer_view_handle() {
+        let handle = match handle {
             Some(handle) => handle,
             None => return,
         };
         let buffer_view = ctx.editor.buffer_views.get_mut(handle);
         let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
 
+        let mut search = ctx.editor.string_pool.acquire();
+        push_search_text(
+            &ctx.editor.config,
+            ctx.editor.read_line.input(),
+            &mut search,
+        );
         let _ = ctx
             .editor
-            .aux_pattern
-            .compile_searcher(&ctx.editor.read_line.input());
-        buffer.set_search(&ctx.editor.aux_pattern);
+            .searcher
+            .search(buffer, &search, ctx.editor.search_options);
+        ctx.editor.string_pool.release(search);
         let search_ranges = buffer.search_ranges();
 
+        let mut cursors = buffer_view.cursors.mut_guard();
+        let main_cursor = cursors.main_cursor();
+        *main_cursor = ctx.editor.mode.read_line_state.previous_main_cursor;
         if search_ranges.is_empty() {
             return;
         }
 
-        let mut cursors = buffer_view.cursors.mut_guard();
-        let main_cursor = cursors.main_cursor();
         match search_ranges.binary_search_by_key(&main_cursor.position, |r| r.from) {
             Ok(i) => main_cursor.position = search_ranges[i].from,
             Err(0) => main_cursor.position = search_ranges[0].from,
@@ -662,7 +765,7 @@ pub mod process {
         let command = ctx.editor.read_line.input();
         let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
         for (i, cursor) in buffer_view.cursors[..].iter().enumerate() {
-            let command = match parse_process_command(&command) {
+            let command = match parse_process_command(&command, ctx.platform.environment()) {
                 Some(command) => command,
                 None => continue,
             };
@@ -719,3 +822,234 @@ pub mod lsp_rename {
         ctx.editor.read_line.input_mut().push_str(placeholder);
     }
 }
+
+pub mod replace_confirm {
+    use super::*;
+
+    use crate::{
+        buffer::SearchOptions,
+        buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
+        buffer_view::BufferViewHandle,
+        navigation_history::NavigationHistory,
+        pattern::PatternError,
+    };
+
+    pub fn enter_mode(
+        ctx: &mut ModeContext,
+        pattern: &str,
+        replacement: &str,
+    ) -> Result<(), PatternError> {
+        fn on_client_keys(
+            ctx: &mut ModeContext,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorControlFlow> {
+            let handle = match ctx.clients.get(ctx.client_handle).buffer_view_handle() {
+                Some(handle) => handle,
+                None => {
+                    Mode::change_to(ctx, ModeKind::default());
+                    return Some(EditorControlFlow::Continue);
+                }
+            };
+
+            let finished = match poll {
+                ReadLinePoll::Pending => {
+                    let answer = ctx.editor.read_line.input().chars().next_back();
+                    ctx.editor.read_line.input_mut().clear();
+                    match answer {
+                        Some('y') => {
+                            let from = current_match_range(ctx, handle).from;
+                            let position = replace_current_match(ctx, handle);
+                            match advance_past_empty_match(ctx, handle, from, position) {
+                                Some(position) => !move_to_next_match(ctx, handle, position),
+                                None => true,
+                            }
+                        }
+                        Some('n') => {
+                            let range = current_match_range(ctx, handle);
+                            match advance_past_empty_match(ctx, handle, range.from, range.to) {
+                                Some(position) => !move_to_next_match(ctx, handle, position),
+                                None => true,
+                            }
+                        }
+                        Some('a') => {
+                            replace_remaining_matches(ctx, handle);
+                            true
+                        }
+                        Some('q') => true,
+                        _ => false,
+                    }
+                }
+                ReadLinePoll::Submitted | ReadLinePoll::Canceled => true,
+            };
+
+            if finished {
+                let buffer_view = ctx.editor.buffer_views.get(handle);
+                let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+                buffer.commit_edits();
+
+                let replace_pattern = &mut ctx.editor.mode.read_line_state.replace_pattern;
+                replace_pattern.clear();
+                buffer.set_search(replace_pattern, SearchOptions::default());
+
+                ctx.editor
+                    .status_bar
+                    .write(MessageKind::Info)
+                    .fmt(format_args!(
+                        "{} replacements",
+                        ctx.editor.mode.read_line_state.replace_count
+                    ));
+                Mode::change_to(ctx, ModeKind::default());
+            }
+
+            Some(EditorControlFlow::Continue)
+        }
+
+        let handle = match ctx.clients.get(ctx.client_handle).buffer_view_handle() {
+            Some(handle) => handle,
+            None => return Ok(()),
+        };
+        ctx.editor
+            .mode
+            .read_line_state
+            .replace_pattern
+            .compile(pattern)?;
+
+        let position = ctx
+            .editor
+            .buffer_views
+            .get(handle)
+            .cursors
+            .main_cursor()
+            .to_range()
+            .from;
+        NavigationHistory::save_client_snapshot(
+            ctx.clients.get_mut(ctx.client_handle),
+            &ctx.editor.buffer_views,
+        );
+        if !move_to_next_match(ctx, handle, position) {
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Info)
+                .str("no matches");
+            return Ok(());
+        }
+
+        let state = &mut ctx.editor.mode.read_line_state;
+        state.on_client_keys = on_client_keys;
+        state.replace_text.clear();
+        state.replace_text.push_str(replacement);
+        state.replace_count = 0;
+
+        ctx.editor.read_line.set_prompt("replace? [y/n/a/q]:");
+        Mode::change_to(ctx, ModeKind::ReadLine);
+        Ok(())
+    }
+
+    fn move_to_next_match(
+        ctx: &mut ModeContext,
+        handle: BufferViewHandle,
+        position: BufferPosition,
+    ) -> bool {
+        let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        buffer.set_search(
+            &ctx.editor.mode.read_line_state.replace_pattern,
+            SearchOptions::default(),
+        );
+
+        let search_ranges = buffer.search_ranges();
+        let index = search_ranges.partition_point(|r| r.from < position);
+        match search_ranges.get(index) {
+            Some(range) => {
+                let mut cursors = buffer_view.cursors.mut_guard();
+                let main_cursor = cursors.main_cursor();
+                main_cursor.anchor = range.from;
+                main_cursor.position = range.to;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn current_match_range(ctx: &ModeContext, handle: BufferViewHandle) -> BufferRange {
+        ctx.editor
+            .buffer_views
+            .get(handle)
+            .cursors
+            .main_cursor()
+            .to_range()
+    }
+
+    // a match that ends where it began would be found again so the search resumes one char later
+    fn advance_past_empty_match(
+        ctx: &ModeContext,
+        handle: BufferViewHandle,
+        from: BufferPosition,
+        position: BufferPosition,
+    ) -> Option<BufferPosition> {
+        if position != from {
+            return Some(position);
+        }
+
+        let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+        let content = ctx.editor.buffers.get(buffer_handle).content();
+        let line = content.line_at(position.line_index as _).as_str();
+        match line[position.column_byte_index as usize..].chars().next() {
+            Some(c) => Some(BufferPosition::line_col(
+                position.line_index,
+                position.column_byte_index + c.len_utf8() as BufferPositionIndex,
+            )),
+            None if (position.line_index as usize) + 1 < content.line_count() => {
+                Some(BufferPosition::line_col(position.line_index + 1, 0))
+            }
+            None => None,
+        }
+    }
+
+    fn replace_current_match(ctx: &mut ModeContext, handle: BufferViewHandle) -> BufferPosition {
+        let buffer_view = ctx.editor.buffer_views.get(handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        let range = buffer_view.cursors.main_cursor().to_range();
+
+        let state = &mut ctx.editor.mode.read_line_state;
+        state.replace_count += 1;
+
+        buffer.delete_range(&mut ctx.editor.word_database, range, &mut ctx.editor.events);
+        buffer
+            .insert_text(
+                &mut ctx.editor.word_database,
+                range.from,
+                &state.replace_text,
+                &mut ctx.editor.events,
+            )
+            .to
+    }
+
+    fn replace_remaining_matches(ctx: &mut ModeContext, handle: BufferViewHandle) {
+        let buffer_view = ctx.editor.buffer_views.get(handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        let position = buffer_view.cursors.main_cursor().to_range().from;
+        buffer.set_search(
+            &ctx.editor.mode.read_line_state.replace_pattern,
+            SearchOptions::default(),
+        );
+
+        let search_ranges = buffer.search_ranges();
+        let index = search_ranges.partition_point(|r| r.from < position);
+        let ranges = search_ranges[index..].to_vec();
+
+        let state = &mut ctx.editor.mode.read_line_state;
+        state.replace_count += ranges.len();
+
+        for &range in ranges.iter().rev() {
+            buffer.delete_range(&mut ctx.editor.word_database, range, &mut ctx.editor.events);
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                range.from,
+                &state.replace_text,
+                &mut ctx.editor.events,
+            );
+        }
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     buffer::BufferHandle,
     buffer_view::{BufferView, BufferViewCollection},
     client::Client,
+    config::Config,
     cursor::Cursor,
     editor::Editor,
 };
@@ -50,6 +51,17 @@ impl NavigationHistory {
         }
     }
 
+    // saves a snapshot before motions that may move cursors far away unless `jumplist` is disabled
+    pub fn save_client_jump_snapshot(
+        client: &mut Client,
+        buffer_views: &BufferViewCollection,
+        config: &Config,
+    ) {
+        if config.jumplist {
+            Self::save_client_snapshot(client, buffer_views);
+        }
+    }
+
     fn buffer_view_equals_to_snapshot(
         &self,
         buffer_view: &BufferView,
@@ -1,8 +1,8 @@
-use std::fmt;
+use std::{fmt, ops::Range};
 
 use crate::word_database::{WordDatabase, WordIndicesIter};
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum EntrySource {
     Custom(usize),
     WordDatabase(usize),
@@ -11,20 +11,54 @@ pub enum EntrySource {
 struct FilteredEntry {
     pub source: EntrySource,
     pub score: u32,
+    pub matched_indices: Range<u32>,
 }
 
-#[derive(Default)]
 pub struct Picker {
     fuzzy_matcher: FuzzyMatcher,
+    fuzzy_matching: bool,
+    preview: String,
     custom_entries_len: usize,
     custom_entries_buffer: Vec<String>,
     filtered_entries: Vec<FilteredEntry>,
+    matched_indices: Vec<u32>,
+    selected_entries: Vec<EntrySource>,
 
     cursor: Option<usize>,
     scroll: usize,
 }
 
+impl Default for Picker {
+    fn default() -> Self {
+        Self {
+            fuzzy_matcher: FuzzyMatcher::default(),
+            fuzzy_matching: true,
+            preview: String::new(),
+            custom_entries_len: 0,
+            custom_entries_buffer: Vec::new(),
+            filtered_entries: Vec::new(),
+            matched_indices: Vec::new(),
+            selected_entries: Vec::new(),
+
+            cursor: None,
+            scroll: 0,
+        }
+    }
+}
+
 impl Picker {
+    pub fn set_fuzzy_matching(&mut self, enabled: bool) {
+        self.fuzzy_matching = enabled;
+    }
+
+    pub fn preview(&self) -> &str {
+        &self.preview
+    }
+
+    pub fn preview_mut(&mut self) -> &mut String {
+        &mut self.preview
+    }
+
     pub fn cursor(&self) -> Option<usize> {
         self.cursor
     }
@@ -102,6 +136,9 @@ impl Picker {
     pub fn clear(&mut self) {
         self.custom_entries_len = 0;
         self.filtered_entries.clear();
+        self.matched_indices.clear();
+        self.selected_entries.clear();
+        self.preview.clear();
         self.cursor = None;
         self.scroll = 0;
     }
@@ -136,13 +173,22 @@ impl Picker {
 
     pub fn filter(&mut self, word_indices: WordIndicesIter, pattern: &str) {
         self.filtered_entries.clear();
+        self.matched_indices.clear();
 
         for (i, word) in word_indices {
-            let score = self.fuzzy_matcher.score(word, pattern);
+            let score = self.score(word, pattern);
             if score != 0 {
+                let matched_indices = push_matched_indices(
+                    &mut self.matched_indices,
+                    &self.fuzzy_matcher,
+                    self.fuzzy_matching,
+                    word,
+                    pattern,
+                );
                 self.filtered_entries.push(FilteredEntry {
                     source: EntrySource::WordDatabase(i),
                     score,
+                    matched_indices,
                 });
             }
         }
@@ -164,18 +210,93 @@ impl Picker {
 
     fn filter_custom_entry(&mut self, index: usize, pattern: &str) -> bool {
         let entry = &self.custom_entries_buffer[index];
-        let score = self.fuzzy_matcher.score(entry, pattern);
+        let score = if self.fuzzy_matching {
+            self.fuzzy_matcher.score(entry, pattern)
+        } else {
+            substring_score(entry, pattern)
+        };
         if score == 0 {
             return false;
         }
 
+        let matched_indices = push_matched_indices(
+            &mut self.matched_indices,
+            &self.fuzzy_matcher,
+            self.fuzzy_matching,
+            entry,
+            pattern,
+        );
         self.filtered_entries.push(FilteredEntry {
             source: EntrySource::Custom(index),
             score,
+            matched_indices,
         });
         true
     }
 
+    fn score(&mut self, text: &str, pattern: &str) -> u32 {
+        if self.fuzzy_matching {
+            self.fuzzy_matcher.score(text, pattern)
+        } else {
+            substring_score(text, pattern)
+        }
+    }
+
+    // `index` is the position of `entry` among the filtered entries
+    pub fn matched_chars<'a>(
+        &'a self,
+        index: usize,
+        entry: &'a str,
+    ) -> impl 'a + Iterator<Item = (char, bool)> {
+        let matched_indices = match self.filtered_entries.get(index) {
+            Some(e) => {
+                &self.matched_indices
+                    [e.matched_indices.start as usize..e.matched_indices.end as usize]
+            }
+            None => &[],
+        };
+
+        let mut matched_indices = matched_indices.iter().peekable();
+        entry.char_indices().map(move |(i, c)| {
+            let matched = matched_indices.next_if(|&&m| m as usize == i).is_some();
+            (c, matched)
+        })
+    }
+
+    pub fn toggle_current_entry_selection(&mut self) {
+        let source = match self.cursor {
+            Some(cursor) => self.filtered_entries[cursor].source,
+            None => return,
+        };
+        match self.selected_entries.iter().position(|&s| s == source) {
+            Some(i) => {
+                self.selected_entries.remove(i);
+            }
+            None => self.selected_entries.push(source),
+        }
+    }
+
+    pub fn is_selected(&self, index: usize) -> bool {
+        match self.filtered_entries.get(index) {
+            Some(entry) => self.selected_entries.contains(&entry.source),
+            None => false,
+        }
+    }
+
+    pub fn has_selected_entries(&self) -> bool {
+        !self.selected_entries.is_empty()
+    }
+
+    pub fn selected_entries<'a>(
+        &'a self,
+        words: &'a WordDatabase,
+    ) -> impl 'a + ExactSizeIterator<Item = &'a str> {
+        let custom_entries = &self.custom_entries_buffer[..];
+        self.selected_entries
+            .iter()
+            .map(move |&s| entry_from_source(s, custom_entries, words))
+    }
+
     pub fn current_entry<'a>(&'a self, words: &'a WordDatabase) -> Option<(EntrySource, &'a str)> {
         let entry = &self.filtered_entries[self.cursor?];
         let source = entry.source;
@@ -199,12 +320,65 @@ fn filtered_to_picker_entry<'a>(
     custom_entries: &'a [String],
     words: &'a WordDatabase,
 ) -> &'a str {
-    match entry.source {
+    entry_from_source(entry.source, custom_entries, words)
+}
+
+fn entry_from_source<'a>(
+    source: EntrySource,
+    custom_entries: &'a [String],
+    words: &'a WordDatabase,
+) -> &'a str {
+    match source {
         EntrySource::Custom(i) => &custom_entries[i],
         EntrySource::WordDatabase(i) => words.word_at(i),
     }
 }
 
+// stores the byte indices of the chars in `text` that matched `pattern` when it was last scored
+fn push_matched_indices(
+    matched_indices: &mut Vec<u32>,
+    fuzzy_matcher: &FuzzyMatcher,
+    fuzzy_matching: bool,
+    text: &str,
+    pattern: &str,
+) -> Range<u32> {
+    let start = matched_indices.len();
+    if fuzzy_matching {
+        matched_indices.extend(
+            fuzzy_matcher
+                .best_match_char_indices(text)
+                .map(|i| i as u32),
+        );
+        matched_indices[start..].reverse();
+    } else if let Some(i) = find_ignore_ascii_case(text, pattern) {
+        let matched = &text[i..i + pattern.len()];
+        matched_indices.extend(matched.char_indices().map(|(j, _)| (i + j) as u32));
+    }
+    start as u32..matched_indices.len() as u32
+}
+
+fn find_ignore_ascii_case(text: &str, pattern: &str) -> Option<usize> {
+    if pattern.len() > text.len() {
+        return None;
+    }
+    let pattern = pattern.as_bytes();
+    text.as_bytes()
+        .windows(pattern.len())
+        .position(|w| w.eq_ignore_ascii_case(pattern))
+}
+
+fn substring_score(text: &str, pattern: &str) -> u32 {
+    if pattern.is_empty() {
+        return 1;
+    }
+
+    match find_ignore_ascii_case(text, pattern) {
+        Some(0) => 2 + (text.len() == pattern.len()) as u32,
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
 const FIRST_CHAR_SCORE: u32 = 1;
 const WORD_BOUNDARY_MATCH_SCORE: u32 = 2;
 const CONSECUTIVE_MATCH_SCORE: u32 = 3;
@@ -212,33 +386,40 @@ const CONSECUTIVE_MATCH_SCORE: u32 = 3;
 struct FuzzyMatch {
     rest_index: u32,
     score: u32,
+    previous_match_index: u32,
 }
 
 #[derive(Default)]
 struct FuzzyMatcher {
-    previous_matches: Vec<FuzzyMatch>,
-    next_matches: Vec<FuzzyMatch>,
+    matches: Vec<FuzzyMatch>,
+    best_match_index: Option<u32>,
 }
 impl FuzzyMatcher {
     pub fn score(&mut self, text: &str, pattern: &str) -> u32 {
+        self.matches.clear();
+        self.best_match_index = None;
         if pattern.is_empty() {
             return 1;
         }
 
-        self.previous_matches.clear();
-        self.previous_matches.push(FuzzyMatch {
+        self.matches.push(FuzzyMatch {
             rest_index: 0,
             score: 0,
+            previous_match_index: 0,
         });
+        let mut previous_matches = 0..1;
 
         for pattern_char in pattern.chars() {
-            self.next_matches.clear();
+            let next_matches_start = self.matches.len();
+
+            for previous_match_index in previous_matches {
+                let previous_rest_index = self.matches[previous_match_index].rest_index;
+                let previous_score = self.matches[previous_match_index].score;
 
-            for previous_match in &self.previous_matches {
                 let mut previous_text_char = '\0';
-                for (i, text_char) in text[previous_match.rest_index as usize..].char_indices() {
+                for (i, text_char) in text[previous_rest_index as usize..].char_indices() {
                     if text_char.eq_ignore_ascii_case(&pattern_char) {
-                        let (matched, mut score) = if i == 0 && previous_match.rest_index != 0 {
+                        let (matched, mut score) = if i == 0 && previous_rest_index != 0 {
                             (true, CONSECUTIVE_MATCH_SCORE)
                         } else if !text_char.is_ascii_alphanumeric() {
                             (true, 0)
@@ -251,14 +432,15 @@ impl FuzzyMatcher {
                         };
 
                         if matched {
-                            if i == 0 && previous_match.rest_index == 0 {
+                            if i == 0 && previous_rest_index == 0 {
                                 score += FIRST_CHAR_SCORE;
                             }
 
-                            let rest_index =
-                                previous_match.rest_index + (i + text_char.len_utf8()) as u32;
-                            let score = previous_match.score + score;
-                            self.next_matches.push(FuzzyMatch { rest_index, score });
+                            self.matches.push(FuzzyMatch {
+                                rest_index: previous_rest_index + (i + text_char.len_utf8()) as u32,
+                                score: previous_score + score,
+                                previous_match_index: previous_match_index as u32,
+                            });
                         }
                     }
 
@@ -266,16 +448,17 @@ impl FuzzyMatcher {
                 }
             }
 
-            if self.next_matches.is_empty() {
+            if self.matches.len() == next_matches_start {
                 return 0;
             }
-            std::mem::swap(&mut self.previous_matches, &mut self.next_matches);
+            previous_matches = next_matches_start..self.matches.len();
         }
 
         let mut best_score = 0;
-        for previous_match in &self.previous_matches {
-            if best_score < previous_match.score {
-                best_score = previous_match.score;
+        for i in previous_matches {
+            if best_score < self.matches[i].score {
+                best_score = self.matches[i].score;
+                self.best_match_index = Some(i as u32);
             }
         }
         if best_score > 0 {
@@ -283,6 +466,23 @@ impl FuzzyMatcher {
         }
         best_score
     }
+
+    // byte indices into `text` of the chars chosen by the last call to `score`, in reverse order
+    pub fn best_match_char_indices<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> impl 'a + Iterator<Item = usize> {
+        let mut match_index = self.best_match_index;
+        std::iter::from_fn(move || {
+            let m = &self.matches[match_index? as usize];
+            if m.rest_index == 0 {
+                return None;
+            }
+            match_index = Some(m.previous_match_index);
+            let c = text[..m.rest_index as usize].chars().next_back()?;
+            Some(m.rest_index as usize - c.len_utf8())
+        })
+    }
 }
 
 #[cfg(test)]
@@ -355,4 +555,55 @@ mod tests {
             fuzzy_matcher.score(&big_repetitive_text, &big_repetitive_text),
         );
     }
+
+    #[test]
+    fn substring_matching() {
+        assert_eq!(1, substring_score("abc", ""));
+        assert_eq!(0, substring_score("abc", "ac"));
+        assert_eq!(1, substring_score("xabc", "ABC"));
+        assert_eq!(2, substring_score("abcx", "abc"));
+        assert_eq!(3, substring_score("abc", "abc"));
+
+        fn matched(picker: &mut Picker, entry: &str, pattern: &str) -> String {
+            picker.clear();
+            picker.add_custom_entry_filtered(entry, pattern);
+            picker
+                .matched_chars(0, entry)
+                .map(|(c, matched)| if matched { c } else { '.' })
+                .collect()
+        }
+
+        let mut picker = Picker::default();
+        picker.set_fuzzy_matching(false);
+        assert_eq!("...st/s.....", matched(&mut picker, "first/second", "st/s"));
+
+        picker.set_fuzzy_matching(true);
+        assert_eq!("f..../s.....", matched(&mut picker, "first/second", "f/s"));
+        assert_eq!("a...c..", matched(&mut picker, "abc cde", "ac"));
+    }
+
+    #[test]
+    fn picker_selection() {
+        let words = WordDatabase::new();
+        let mut picker = Picker::default();
+        picker.add_custom_entry("first");
+        picker.add_custom_entry("second");
+        picker.filter(WordIndicesIter::empty(), "");
+        assert!(!picker.has_selected_entries());
+
+        picker.move_cursor(0);
+        picker.toggle_current_entry_selection();
+        picker.move_cursor(1);
+        picker.toggle_current_entry_selection();
+        assert!(picker.is_selected(0));
+        assert!(picker.is_selected(1));
+
+        picker.toggle_current_entry_selection();
+        assert!(!picker.is_selected(1));
+        let selected: Vec<_> = picker.selected_entries(&words).collect();
+        assert_eq!(1, selected.len());
+
+        picker.clear();
+        assert!(!picker.has_selected_entries());
+    }
 }
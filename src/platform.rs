@@ -2,9 +2,10 @@ use std::{
     io,
     process::{Command, Stdio},
     sync::{mpsc, Arc},
+    time::Duration,
 };
 
-use crate::{client::ClientHandle, editor_utils::parse_process_command, lsp};
+use crate::{buffer::BufferHandle, client::ClientHandle, editor_utils::parse_process_command, lsp};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Key {
@@ -52,20 +53,33 @@ pub enum PlatformRequest {
     KillProcess {
         handle: ProcessHandle,
     },
+    SetTickInterval {
+        tag: TickTag,
+        interval: Option<Duration>,
+    },
+}
+
+// each subsystem that needs ticks owns an interval and ticks happen at the shortest active one
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TickTag {
+    TailedFile(BufferHandle),
 }
 
 #[derive(Clone, Copy)]
 pub enum ProcessTag {
     Buffer(usize),
+    PipeBuffer(usize),
     Lsp(lsp::ClientHandle),
+    // output is discarded and the process is only tracked so it's reaped on exit
+    Ignored,
 }
 
 #[derive(Clone, Copy)]
 pub struct ProcessHandle(pub usize);
 
 pub struct Platform {
-    read_from_clipboard: Option<fn(&mut String)>,
-    write_to_clipboard: Option<fn(&str)>,
+    read_from_clipboard: Option<fn(&mut String) -> bool>,
+    write_to_clipboard: Option<fn(&str) -> bool>,
     flush_requests: fn(),
     request_sender: mpsc::Sender<PlatformRequest>,
     needs_flushing: bool,
@@ -74,6 +88,7 @@ pub struct Platform {
     internal_clipboard: String,
     pub copy_command: String,
     pub paste_command: String,
+    environment: Vec<(String, String)>,
 }
 impl Platform {
     pub fn new(flush_requests: fn(), request_sender: mpsc::Sender<PlatformRequest>) -> Self {
@@ -87,20 +102,86 @@ impl Platform {
             internal_clipboard: String::new(),
             copy_command: String::new(),
             paste_command: String::new(),
+            environment: Vec::new(),
+        }
+    }
+
+    pub fn environment(&self) -> &[(String, String)] {
+        &self.environment
+    }
+
+    pub fn environment_var(&self, name: &str) -> Option<String> {
+        match self.environment.iter().find(|(n, _)| n == name) {
+            Some((_, value)) => Some(value.clone()),
+            None => std::env::var(name).ok(),
+        }
+    }
+
+    pub fn set_environment_var(&mut self, name: &str, value: &str) {
+        match self.environment.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => {
+                v.clear();
+                v.push_str(value);
+            }
+            None => self.environment.push((name.into(), value.into())),
         }
     }
 
+    pub fn detect_clipboard_commands(&mut self) -> Option<&'static str> {
+        static PROVIDERS: &[(&str, &str, &str)] = &[
+            ("wl-copy", "wl-copy", "wl-paste --no-newline"),
+            (
+                "xclip",
+                "xclip -selection clipboard -in",
+                "xclip -selection clipboard -out",
+            ),
+            (
+                "xsel",
+                "xsel --clipboard --input",
+                "xsel --clipboard --output",
+            ),
+            ("pbcopy", "pbcopy", "pbpaste"),
+        ];
+
+        if self.read_from_clipboard.is_some() && self.write_to_clipboard.is_some() {
+            self.copy_command.clear();
+            self.paste_command.clear();
+            return Some("native");
+        }
+
+        let path = self.environment_var("PATH")?;
+        let is_in_path = |command: &str| {
+            let name = command.split(' ').next().unwrap_or(command);
+            std::env::split_paths(&path).any(|dir| dir.join(name).is_file())
+        };
+
+        let &(name, copy_command, paste_command) = PROVIDERS
+            .iter()
+            .find(|(_, copy, paste)| is_in_path(copy) && is_in_path(paste))?;
+        self.copy_command.clear();
+        self.copy_command.push_str(copy_command);
+        self.paste_command.clear();
+        self.paste_command.push_str(paste_command);
+        Some(name)
+    }
+
     pub fn set_clipboard_api(
         &mut self,
-        read_from_clipboard: fn(&mut String),
-        write_to_clipboard: fn(&str),
+        read_from_clipboard: fn(&mut String) -> bool,
+        write_to_clipboard: fn(&str) -> bool,
     ) {
         self.read_from_clipboard = Some(read_from_clipboard);
         self.write_to_clipboard = Some(write_to_clipboard);
     }
 
     pub fn read_from_clipboard(&self, text: &mut String) {
-        if let Some(mut command) = parse_process_command(&self.paste_command) {
+        if let Some(read_from_clipboard) = self.read_from_clipboard {
+            if read_from_clipboard(text) {
+                return;
+            }
+        }
+
+        if let Some(mut command) = parse_process_command(&self.paste_command, &self.environment) {
             command.stdin(Stdio::null());
             command.stdout(Stdio::piped());
             command.stderr(Stdio::null());
@@ -110,15 +191,19 @@ impl Platform {
                     text.push_str(&output);
                 }
             }
-        } else if let Some(read_from_clipboard) = self.read_from_clipboard {
-            read_from_clipboard(text);
         } else {
             text.push_str(&self.internal_clipboard);
         }
     }
 
     pub fn write_to_clipboard(&mut self, text: &str) {
-        if let Some(mut command) = parse_process_command(&self.copy_command) {
+        if let Some(write_to_clipboard) = self.write_to_clipboard {
+            if write_to_clipboard(text) {
+                return;
+            }
+        }
+
+        if let Some(mut command) = parse_process_command(&self.copy_command, &self.environment) {
             command.stdin(Stdio::piped());
             command.stdout(Stdio::null());
             command.stderr(Stdio::null());
@@ -129,8 +214,6 @@ impl Platform {
                 }
                 let _ = child.wait();
             }
-        } else if let Some(write_to_clipboard) = self.write_to_clipboard {
-            write_to_clipboard(text);
         } else {
             self.internal_clipboard.clear();
             self.internal_clipboard.push_str(text);
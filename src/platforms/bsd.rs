@@ -9,7 +9,7 @@ use std::{
 };
 
 use pepper::{
-    application::{AnyError, ApplicationEvent, ClientApplication, ServerApplication},
+    application::{AnyError, ApplicationEvent, ClientApplication, ServerApplication, ServerTimers},
     client::ClientHandle,
     platform::{BufPool, Key, Platform, PlatformRequest, ProcessHandle},
     Args,
@@ -17,8 +17,8 @@ use pepper::{
 
 mod unix_utils;
 use unix_utils::{
-    get_terminal_size, is_pipped, parse_terminal_keys, read, read_from_connection, run,
-    suspend_process, Process, RawMode,
+    get_terminal_size, is_pipped, parse_terminal_keys, read, read_from_connection,
+    read_stdin_and_reopen_tty, run, suspend_process, Process, RawMode,
 };
 
 const MAX_CLIENT_COUNT: usize = 20;
@@ -208,7 +208,7 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
     let mut processes = [NONE_PROCESS; MAX_PROCESS_COUNT];
     let mut buf_pool = BufPool::default();
 
-    let mut timeout = Some(ServerApplication::idle_duration());
+    let mut timers = ServerTimers::default();
 
     const CLIENTS_START_INDEX: usize = 1 + 1;
     const CLIENTS_LAST_INDEX: usize = CLIENTS_START_INDEX + MAX_CLIENT_COUNT - 1;
@@ -216,10 +216,11 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
     const PROCESSES_LAST_INDEX: usize = PROCESSES_START_INDEX + MAX_PROCESS_COUNT - 1;
 
     loop {
-        let events = kqueue.wait(&mut kqueue_events, timeout);
+        let events = kqueue.wait(&mut kqueue_events, timers.timeout());
+        for event in timers.expired_events() {
+            event_sender.send(event)?;
+        }
         if events.len() == 0 {
-            timeout = None;
-            event_sender.send(ApplicationEvent::Idle)?;
             continue;
         }
 
@@ -279,7 +280,10 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
                                     break;
                                 }
                                 if !spawned {
-                                    event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                    event_sender.send(ApplicationEvent::ProcessExit {
+                                        tag,
+                                        success: false,
+                                    })?;
                                 }
                             }
                             PlatformRequest::WriteToProcess { handle, buf } => {
@@ -290,9 +294,10 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
                                             kqueue.remove(Event::Fd(fd));
                                         }
                                         let tag = process.tag();
-                                        process.kill();
+                                        let success = process.kill();
                                         processes[index] = None;
-                                        event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                        event_sender
+                                            .send(ApplicationEvent::ProcessExit { tag, success })?;
                                     }
                                 }
                             }
@@ -308,11 +313,15 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
                                         kqueue.remove(Event::Fd(fd));
                                     }
                                     let tag = process.tag();
-                                    process.kill();
+                                    let success = process.kill();
                                     processes[index] = None;
-                                    event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                    event_sender
+                                        .send(ApplicationEvent::ProcessExit { tag, success })?;
                                 }
                             }
+                            PlatformRequest::SetTickInterval { tag, interval } => {
+                                timers.set_tick_interval(tag, interval);
+                            }
                         }
                     }
                 }
@@ -355,7 +364,7 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
                         }
                     }
 
-                    timeout = Some(ServerApplication::idle_duration());
+                    timers.on_client_activity();
                 }
                 PROCESSES_START_INDEX..=PROCESSES_LAST_INDEX => {
                     let index = event_index - PROCESSES_START_INDEX;
@@ -364,15 +373,20 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
                         match process.read(&mut buf_pool) {
                             Ok(None) => (),
                             Ok(Some(buf)) if !buf.as_bytes().is_empty() => {
-                                event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                event_sender.send(ApplicationEvent::ProcessOutput { tag, buf })?;
                             }
                             _ => {
                                 if let Some(fd) = process.try_as_raw_fd() {
                                     kqueue.remove(Event::Fd(fd));
                                 }
-                                process.kill();
+                                let success = process.wait_exit();
+                                if let Some(buf) = process.take_error_output(&mut buf_pool) {
+                                    event_sender
+                                        .send(ApplicationEvent::ProcessErrorOutput { tag, buf })?;
+                                }
                                 processes[index] = None;
-                                event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                event_sender
+                                    .send(ApplicationEvent::ProcessExit { tag, success })?;
                             }
                         }
                     }
@@ -386,10 +400,15 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
 fn run_client(args: Args, mut connection: UnixStream) {
     use io::{Read, Write};
 
+    let mut stdin_content = Vec::new();
+    if args.stdin {
+        read_stdin_and_reopen_tty(&mut stdin_content);
+    }
+
     let is_pipped = is_pipped();
     let stdout = io::stdout();
     let mut application = ClientApplication::new(stdout.lock(), is_pipped);
-    let bytes = application.init(args);
+    let bytes = application.init(args, &stdin_content);
     if connection.write_all(bytes).is_err() {
         return;
     }
@@ -470,4 +489,9 @@ fn run_client(args: Args, mut connection: UnixStream) {
     }
 
     drop(raw_mode);
+    let exit_code = application.exit_code();
+    drop(application);
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
 }
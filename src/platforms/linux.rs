@@ -9,7 +9,7 @@ use std::{
 };
 
 use pepper::{
-    application::{AnyError, ApplicationEvent, ClientApplication, ServerApplication},
+    application::{AnyError, ApplicationEvent, ClientApplication, ServerApplication, ServerTimers},
     client::ClientHandle,
     platform::{BufPool, Key, Platform, PlatformRequest, ProcessHandle},
     Args,
@@ -17,8 +17,8 @@ use pepper::{
 
 mod unix_utils;
 use unix_utils::{
-    get_terminal_size, is_pipped, parse_terminal_keys, read, read_from_connection, run,
-    suspend_process, Process, RawMode,
+    get_terminal_size, is_pipped, parse_terminal_keys, read, read_from_connection,
+    read_stdin_and_reopen_tty, run, suspend_process, Process, RawMode,
 };
 
 const MAX_CLIENT_COUNT: usize = 20;
@@ -197,7 +197,7 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
     let mut processes = [NONE_PROCESS; MAX_PROCESS_COUNT];
     let mut buf_pool = BufPool::default();
 
-    let mut timeout = Some(ServerApplication::idle_duration());
+    let mut timers = ServerTimers::default();
 
     const CLIENTS_START_INDEX: usize = 1 + 1;
     const CLIENTS_LAST_INDEX: usize = CLIENTS_START_INDEX + MAX_CLIENT_COUNT - 1;
@@ -210,10 +210,11 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
     let mut epoll_events = EpollEvents::new();
 
     loop {
-        let events = epoll.wait(&mut epoll_events, timeout);
+        let events = epoll.wait(&mut epoll_events, timers.timeout());
+        for event in timers.expired_events() {
+            event_sender.send(event)?;
+        }
         if events.len() == 0 {
-            timeout = None;
-            event_sender.send(ApplicationEvent::Idle)?;
             continue;
         }
 
@@ -269,7 +270,10 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
                                     break;
                                 }
                                 if !spawned {
-                                    event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                    event_sender.send(ApplicationEvent::ProcessExit {
+                                        tag,
+                                        success: false,
+                                    })?;
                                 }
                             }
                             PlatformRequest::WriteToProcess { handle, buf } => {
@@ -280,9 +284,10 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
                                             epoll.remove(fd);
                                         }
                                         let tag = process.tag();
-                                        process.kill();
+                                        let success = process.kill();
                                         processes[index] = None;
-                                        event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                        event_sender
+                                            .send(ApplicationEvent::ProcessExit { tag, success })?;
                                     }
                                 }
                             }
@@ -298,11 +303,15 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
                                         epoll.remove(fd);
                                     }
                                     let tag = process.tag();
-                                    process.kill();
+                                    let success = process.kill();
                                     processes[index] = None;
-                                    event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                    event_sender
+                                        .send(ApplicationEvent::ProcessExit { tag, success })?;
                                 }
                             }
+                            PlatformRequest::SetTickInterval { tag, interval } => {
+                                timers.set_tick_interval(tag, interval);
+                            }
                         }
                     }
                 }
@@ -341,7 +350,7 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
                         }
                     }
 
-                    timeout = Some(ServerApplication::idle_duration());
+                    timers.on_client_activity();
                 }
                 PROCESSES_START_INDEX..=PROCESSES_LAST_INDEX => {
                     let index = event_index - PROCESSES_START_INDEX;
@@ -356,9 +365,14 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
                                 if let Some(fd) = process.try_as_raw_fd() {
                                     epoll.remove(fd);
                                 }
-                                process.kill();
+                                let success = process.wait_exit();
+                                if let Some(buf) = process.take_error_output(&mut buf_pool) {
+                                    event_sender
+                                        .send(ApplicationEvent::ProcessErrorOutput { tag, buf })?;
+                                }
                                 processes[index] = None;
-                                event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                event_sender
+                                    .send(ApplicationEvent::ProcessExit { tag, success })?;
                             }
                         }
                     }
@@ -372,10 +386,15 @@ fn run_server(args: Args, listener: UnixListener) -> Result<(), AnyError> {
 fn run_client(args: Args, mut connection: UnixStream) {
     use io::{Read, Write};
 
+    let mut stdin_content = Vec::new();
+    if args.stdin {
+        read_stdin_and_reopen_tty(&mut stdin_content);
+    }
+
     let is_pipped = is_pipped();
     let stdout = io::stdout();
     let mut application = ClientApplication::new(stdout.lock(), is_pipped);
-    let bytes = application.init(args);
+    let bytes = application.init(args, &stdin_content);
     if connection.write_all(bytes).is_err() {
         return;
     }
@@ -466,4 +485,9 @@ fn run_client(args: Args, mut connection: UnixStream) {
     }
 
     drop(raw_mode);
+    let exit_code = application.exit_code();
+    drop(application);
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
 }
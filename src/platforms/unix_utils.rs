@@ -11,8 +11,8 @@ use std::{
 };
 
 use pepper::{
-    application::{AnyError, ClientApplication},
-    editor_utils::hash_bytes,
+    application::{AnyError, ClientApplication, ServerApplication},
+    editor_utils::{hash_bytes, session_directory},
     platform::{BufPool, Key, ProcessTag, SharedBuf},
     Args,
 };
@@ -21,37 +21,38 @@ pub fn run(
     server_fn: fn(Args, UnixListener) -> Result<(), AnyError>,
     client_fn: fn(Args, UnixStream),
 ) {
-    let args = Args::parse();
-
-    let mut session_path = String::new();
-    session_path.push_str("/tmp/");
-    session_path.push_str(env!("CARGO_PKG_NAME"));
-    session_path.push('/');
-
-    match args.session {
-        Some(ref name) => session_path.push_str(name),
-        None => {
-            use io::Write;
-
-            let current_dir = env::current_dir().expect("could not retrieve the current directory");
-            let current_dir_bytes = current_dir.as_os_str().as_bytes();
-            let current_directory_hash = hash_bytes(current_dir_bytes);
-
-            let mut hash_buf = [0u8; 16];
-            let mut cursor = io::Cursor::new(&mut hash_buf[..]);
-            write!(&mut cursor, "{:x}", current_directory_hash).unwrap();
-            let len = cursor.position() as usize;
-            let name = std::str::from_utf8(&hash_buf[..len]).unwrap();
-            session_path.push_str(name);
-        }
+    let mut args = Args::parse();
+
+    if args.session.is_none() {
+        use io::Write;
+
+        let current_dir = env::current_dir().expect("could not retrieve the current directory");
+        let current_dir_bytes = current_dir.as_os_str().as_bytes();
+        let current_directory_hash = hash_bytes(current_dir_bytes);
+
+        let mut hash_buf = [0u8; 16];
+        let mut cursor = io::Cursor::new(&mut hash_buf[..]);
+        write!(&mut cursor, "{:x}", current_directory_hash).unwrap();
+        let len = cursor.position() as usize;
+        let name = std::str::from_utf8(&hash_buf[..len]).unwrap();
+        args.session = Some(name.into());
+    }
+
+    let mut session_path = session_directory();
+    if let Some(ref name) = args.session {
+        session_path.push(name);
     }
 
     if args.print_session {
-        print!("{}", session_path);
+        print!("{}", session_path.display());
         return;
     }
 
-    let session_path = Path::new(&session_path);
+    if args.batch {
+        std::process::exit(ServerApplication::run_batch(args));
+    }
+
+    let session_path = session_path.as_path();
 
     fn start_server(session_path: &Path) -> UnixListener {
         if let Some(dir) = session_path.parent() {
@@ -134,6 +135,23 @@ pub fn is_pipped() -> bool {
     unsafe { libc::isatty(libc::STDIN_FILENO) == 0 }
 }
 
+pub fn read_stdin_and_reopen_tty(content: &mut Vec<u8>) {
+    if !is_pipped() {
+        return;
+    }
+
+    use io::Read;
+    let _ = io::stdin().lock().read_to_end(content);
+
+    unsafe {
+        let tty = libc::open(b"/dev/tty\0".as_ptr() as _, libc::O_RDWR);
+        if tty >= 0 {
+            libc::dup2(tty, libc::STDIN_FILENO);
+            libc::close(tty);
+        }
+    }
+}
+
 pub fn read(fd: RawFd, buf: &mut [u8]) -> Result<usize, ()> {
     let len = unsafe { libc::read(fd, buf.as_mut_ptr() as _, buf.len() as _) };
     if len >= 0 {
@@ -165,6 +183,9 @@ pub fn read_from_connection(
     }
 }
 
+const PROCESS_EXIT_WAIT_COUNT: usize = 100;
+const PROCESS_EXIT_WAIT_INTERVAL: Duration = Duration::from_millis(1);
+
 pub struct Process {
     alive: bool,
     child: Child,
@@ -224,14 +245,57 @@ impl Process {
         self.child.stdin = None;
     }
 
-    pub fn kill(&mut self) {
+    // returns whether the process exited successfully
+    // its output was closed, so it's given a moment to exit on its own before being killed
+    pub fn wait_exit(&mut self) -> bool {
+        if !self.alive {
+            return false;
+        }
+
+        for _ in 0..PROCESS_EXIT_WAIT_COUNT {
+            match self.child.try_wait() {
+                Ok(Some(status)) => {
+                    self.alive = false;
+                    return status.success();
+                }
+                Ok(None) => std::thread::sleep(PROCESS_EXIT_WAIT_INTERVAL),
+                Err(_) => break,
+            }
+        }
+        self.kill()
+    }
+
+    // reads what is already in its stderr without waiting for more
+    pub fn take_error_output(&mut self, buf_pool: &mut BufPool) -> Option<SharedBuf> {
+        use io::Read;
+        let mut stderr = self.child.stderr.take()?;
+        let fd = stderr.as_raw_fd();
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+
+        let mut buf = buf_pool.acquire();
+        let write = buf.write();
+        let _ = stderr.read_to_end(write);
+        let buf = buf.share();
+        buf_pool.release(buf.clone());
+        if buf.as_bytes().is_empty() {
+            None
+        } else {
+            Some(buf)
+        }
+    }
+
+    // returns whether the process exited successfully
+    pub fn kill(&mut self) -> bool {
         if !self.alive {
-            return;
+            return false;
         }
 
         self.alive = false;
         let _ = self.child.kill();
-        let _ = self.child.wait();
+        matches!(self.child.wait(), Ok(status) if status.success())
     }
 }
 impl Drop for Process {
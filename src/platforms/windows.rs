@@ -1,6 +1,9 @@
 use std::{
     env, io,
-    os::windows::{ffi::OsStrExt, io::IntoRawHandle},
+    os::windows::{
+        ffi::OsStrExt,
+        io::{AsRawHandle, IntoRawHandle},
+    },
     process::Child,
     ptr::NonNull,
     sync::atomic::{AtomicPtr, Ordering},
@@ -23,7 +26,8 @@ use winapi::{
         ioapiset::GetOverlappedResult,
         minwinbase::OVERLAPPED,
         namedpipeapi::{
-            ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, SetNamedPipeHandleState,
+            ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PeekNamedPipe,
+            SetNamedPipeHandleState,
         },
         processenv::{GetCommandLineW, GetStdHandle},
         processthreadsapi::{CreateProcessW, PROCESS_INFORMATION, STARTUPINFOW},
@@ -44,7 +48,10 @@ use winapi::{
             RIGHT_CTRL_PRESSED, WINDOW_BUFFER_SIZE_EVENT,
         },
         winnls::CP_UTF8,
-        winnt::{GENERIC_READ, GENERIC_WRITE, HANDLE, MAXIMUM_WAIT_OBJECTS},
+        winnt::{
+            FILE_SHARE_READ, FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE, HANDLE,
+            MAXIMUM_WAIT_OBJECTS,
+        },
         winuser::{
             CloseClipboard, EmptyClipboard, GetClipboardData, OpenClipboard, SetClipboardData,
             CF_UNICODETEXT, VK_BACK, VK_DELETE, VK_DOWN, VK_END, VK_ESCAPE, VK_F1, VK_F24, VK_HOME,
@@ -54,7 +61,7 @@ use winapi::{
 };
 
 use pepper::{
-    application::{AnyError, ApplicationEvent, ClientApplication, ServerApplication},
+    application::{AnyError, ApplicationEvent, ClientApplication, ServerApplication, ServerTimers},
     client::ClientHandle,
     editor_utils::hash_bytes,
     platform::{
@@ -69,38 +76,38 @@ const MAX_EVENT_COUNT: usize = 1 + 1 + MAX_CLIENT_COUNT + MAX_PROCESS_COUNT;
 const _ASSERT_MAX_EVENT_COUNT_IS_64: [(); 64] = [(); MAX_EVENT_COUNT];
 
 const CLIENT_EVENT_BUFFER_LEN: usize = 32;
+const PROCESS_EXIT_WAIT_COUNT: usize = 100;
+const PROCESS_EXIT_WAIT_INTERVAL: Duration = Duration::from_millis(1);
 static PIPE_PREFIX: &str = r#"\\.\pipe\"#;
 
 pub fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
 
-    let mut pipe_path = Vec::new();
-    let mut hash_buf = [0u8; 16];
-    let session_name = match args.session {
-        Some(ref name) => name.as_str(),
-        None => {
-            use io::Write;
-
-            let current_dir = env::current_dir().expect("could not retrieve the current directory");
-            let current_dir_bytes: Vec<_> = current_dir
-                .as_os_str()
-                .encode_wide()
-                .map(|s| {
-                    let bytes = s.to_le_bytes();
-                    std::iter::once(bytes[0]).chain(std::iter::once(bytes[1]))
-                })
-                .flatten()
-                .collect();
-
-            let current_directory_hash = hash_bytes(&current_dir_bytes);
-            let mut cursor = io::Cursor::new(&mut hash_buf[..]);
-            write!(&mut cursor, "{:x}", current_directory_hash).unwrap();
-            let len = cursor.position() as usize;
-            std::str::from_utf8(&hash_buf[..len]).unwrap()
-        }
-    };
+    if args.session.is_none() {
+        use io::Write;
+
+        let current_dir = env::current_dir().expect("could not retrieve the current directory");
+        let current_dir_bytes: Vec<_> = current_dir
+            .as_os_str()
+            .encode_wide()
+            .map(|s| {
+                let bytes = s.to_le_bytes();
+                std::iter::once(bytes[0]).chain(std::iter::once(bytes[1]))
+            })
+            .flatten()
+            .collect();
+
+        let current_directory_hash = hash_bytes(&current_dir_bytes);
+        let mut hash_buf = [0u8; 16];
+        let mut cursor = io::Cursor::new(&mut hash_buf[..]);
+        write!(&mut cursor, "{:x}", current_directory_hash).unwrap();
+        let len = cursor.position() as usize;
+        let name = std::str::from_utf8(&hash_buf[..len]).unwrap();
+        args.session = Some(name.into());
+    }
+    let session_name = args.session.as_deref().unwrap_or("");
 
-    pipe_path.clear();
+    let mut pipe_path = Vec::new();
     pipe_path.extend(PIPE_PREFIX.encode_utf16());
     pipe_path.extend(session_name.encode_utf16());
     pipe_path.push(0);
@@ -110,6 +117,10 @@ pub fn main() {
         return;
     }
 
+    if args.batch {
+        std::process::exit(ServerApplication::run_batch(args));
+    }
+
     let input_handle = get_std_handle(STD_INPUT_HANDLE);
     let output_handle = get_std_handle(STD_OUTPUT_HANDLE);
 
@@ -382,15 +393,18 @@ fn wait_for_multiple_objects(handles: &[HANDLE], timeout: Option<Duration>) -> O
     }
 }
 
-fn read_from_clipboard(text: &mut String) {
-    let clipboard = Clipboard::open();
+fn read_from_clipboard(text: &mut String) -> bool {
+    let clipboard = match Clipboard::open() {
+        Some(clipboard) => clipboard,
+        None => return false,
+    };
     let handle = unsafe { GetClipboardData(CF_UNICODETEXT) };
     if handle == NULL {
-        return;
+        return true;
     }
     let data = match global_lock::<u16>(handle) {
         Some(data) => data,
-        None => return,
+        None => return false,
     };
     let data = data.as_ptr();
     let len = unsafe {
@@ -431,10 +445,14 @@ fn read_from_clipboard(text: &mut String) {
     }
     global_unlock(handle);
     drop(clipboard);
+    true
 }
 
-fn write_to_clipboard(text: &str) {
-    let clipboard = Clipboard::open();
+fn write_to_clipboard(text: &str) -> bool {
+    let clipboard = match Clipboard::open() {
+        Some(clipboard) => clipboard,
+        None => return false,
+    };
     let len = unsafe {
         MultiByteToWideChar(
             CP_UTF8,
@@ -449,13 +467,13 @@ fn write_to_clipboard(text: &str) {
         let size = (len as usize + 1) * std::mem::size_of::<u16>();
         let handle = unsafe { GlobalAlloc(GMEM_MOVEABLE, size as _) };
         if handle == NULL {
-            return;
+            return false;
         }
         let data = match global_lock::<u16>(handle) {
             Some(data) => data.as_ptr(),
             None => {
                 unsafe { GlobalFree(handle) };
-                return;
+                return false;
             }
         };
         unsafe {
@@ -468,9 +486,11 @@ fn write_to_clipboard(text: &str) {
         let result = unsafe { SetClipboardData(CF_UNICODETEXT, handle) };
         if result == NULL {
             unsafe { GlobalFree(handle) };
+            return false;
         }
     }
     drop(clipboard);
+    true
 }
 
 struct Handle(pub HANDLE);
@@ -529,20 +549,18 @@ impl Drop for Event {
 
 struct Clipboard;
 impl Clipboard {
-    pub fn open() -> Self {
+    pub fn open() -> Option<Self> {
         let result = unsafe { OpenClipboard(std::ptr::null_mut()) };
         if result == FALSE {
-            panic!("could not open clipboard");
+            None
+        } else {
+            Some(Self)
         }
-        Self
     }
 }
 impl Drop for Clipboard {
     fn drop(&mut self) {
-        let result = unsafe { CloseClipboard() };
-        if result == FALSE {
-            panic!("could not close clipboard");
-        }
+        unsafe { CloseClipboard() };
     }
 }
 
@@ -797,15 +815,65 @@ impl AsyncProcess {
         self.child.stdin = None;
     }
 
-    pub fn kill(&mut self) {
+    // returns whether the process exited successfully
+    // its output was closed, so it's given a moment to exit on its own before being killed
+    pub fn wait_exit(&mut self) -> bool {
+        if !self.alive {
+            return false;
+        }
+
+        for _ in 0..PROCESS_EXIT_WAIT_COUNT {
+            match self.child.try_wait() {
+                Ok(Some(status)) => {
+                    self.alive = false;
+                    self.stdout = None;
+                    return status.success();
+                }
+                Ok(None) => std::thread::sleep(PROCESS_EXIT_WAIT_INTERVAL),
+                Err(_) => break,
+            }
+        }
+        self.kill()
+    }
+
+    // reads what is already in its stderr without waiting for more
+    pub fn take_error_output(&mut self, buf_pool: &mut BufPool) -> Option<SharedBuf> {
+        use io::Read;
+        let mut stderr = self.child.stderr.take()?;
+        let mut available: DWORD = 0;
+        let result = unsafe {
+            PeekNamedPipe(
+                stderr.as_raw_handle() as _,
+                NULL,
+                0,
+                std::ptr::null_mut(),
+                &mut available,
+                std::ptr::null_mut(),
+            )
+        };
+        if result == FALSE || available == 0 {
+            return None;
+        }
+
+        let mut buf = buf_pool.acquire();
+        let write = buf.write_with_len(available as _);
+        let len = stderr.read(write).unwrap_or(0);
+        write.truncate(len);
+        let buf = buf.share();
+        buf_pool.release(buf.clone());
+        Some(buf)
+    }
+
+    // returns whether the process exited successfully
+    pub fn kill(&mut self) -> bool {
         if !self.alive {
-            return;
+            return false;
         }
 
         self.alive = false;
         self.stdout = None;
         let _ = self.child.kill();
-        let _ = self.child.wait();
+        matches!(self.child.wait(), Ok(status) if status.success())
     }
 }
 impl Drop for AsyncProcess {
@@ -881,7 +949,7 @@ fn run_server(args: Args, pipe_path: &[u16]) -> Result<(), AnyError> {
     let mut processes = [NONE_ASYNC_PROCESS; MAX_PROCESS_COUNT];
     let mut buf_pool = BufPool::default();
 
-    let mut timeout = Some(ServerApplication::idle_duration());
+    let mut timers = ServerTimers::default();
 
     loop {
         events.track(&new_request_event, EventSource::NewRequest);
@@ -899,7 +967,12 @@ fn run_server(args: Args, pipe_path: &[u16]) -> Result<(), AnyError> {
             }
         }
 
-        match events.wait_next(timeout) {
+        let event_source = events.wait_next(timers.timeout());
+        for event in timers.expired_events() {
+            event_sender.send(event)?;
+        }
+
+        match event_source {
             Some(EventSource::NewRequest) => {
                 for request in request_receiver.try_iter() {
                     match request {
@@ -940,16 +1013,20 @@ fn run_server(args: Args, pipe_path: &[u16]) -> Result<(), AnyError> {
                                 break;
                             }
                             if !spawned {
-                                event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                event_sender.send(ApplicationEvent::ProcessExit {
+                                    tag,
+                                    success: false,
+                                })?;
                             }
                         }
                         PlatformRequest::WriteToProcess { handle, buf } => {
                             if let Some(ref mut process) = processes[handle.0] {
                                 if !process.write(buf.as_bytes()) {
                                     let tag = process.tag;
-                                    process.kill();
+                                    let success = process.kill();
                                     processes[handle.0] = None;
-                                    event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                    event_sender
+                                        .send(ApplicationEvent::ProcessExit { tag, success })?;
                                 }
                             }
                         }
@@ -961,11 +1038,15 @@ fn run_server(args: Args, pipe_path: &[u16]) -> Result<(), AnyError> {
                         PlatformRequest::KillProcess { handle } => {
                             if let Some(ref mut process) = processes[handle.0] {
                                 let tag = process.tag;
-                                process.kill();
+                                let success = process.kill();
                                 processes[handle.0] = None;
-                                event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                event_sender
+                                    .send(ApplicationEvent::ProcessExit { tag, success })?;
                             }
                         }
+                        PlatformRequest::SetTickInterval { tag, interval } => {
+                            timers.set_tick_interval(tag, interval);
+                        }
                     }
                 }
             }
@@ -998,7 +1079,7 @@ fn run_server(args: Args, pipe_path: &[u16]) -> Result<(), AnyError> {
                     }
                 }
 
-                timeout = Some(ServerApplication::idle_duration());
+                timers.on_client_activity();
             }
             Some(EventSource::Process(i)) => {
                 if let Some(ref mut process) = processes[i] {
@@ -1011,18 +1092,20 @@ fn run_server(args: Args, pipe_path: &[u16]) -> Result<(), AnyError> {
                             }
                             _ => {
                                 process.stdout = None;
-                                process.kill();
+                                let success = process.wait_exit();
+                                if let Some(buf) = process.take_error_output(&mut buf_pool) {
+                                    event_sender
+                                        .send(ApplicationEvent::ProcessErrorOutput { tag, buf })?;
+                                }
                                 processes[i] = None;
-                                event_sender.send(ApplicationEvent::ProcessExit { tag })?;
+                                event_sender
+                                    .send(ApplicationEvent::ProcessExit { tag, success })?;
                             }
                         }
                     }
                 }
             }
-            None => {
-                timeout = None;
-                event_sender.send(ApplicationEvent::Idle)?;
-            }
+            None => (),
         }
     }
 }
@@ -1123,13 +1206,47 @@ impl ConnectionToServer {
     }
 }
 
+fn read_stdin_and_reopen_console(input_handle: Handle, content: &mut Vec<u8>) -> Handle {
+    if !is_pipped(&input_handle) {
+        return input_handle;
+    }
+
+    use io::Read;
+    let _ = io::stdin().lock().read_to_end(content);
+
+    let path: Vec<u16> = "CONIN$\0".encode_utf16().collect();
+    let handle = unsafe {
+        CreateFileW(
+            path.as_ptr(),
+            GENERIC_READ | GENERIC_WRITE,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            std::ptr::null_mut(),
+            OPEN_EXISTING,
+            0,
+            NULL,
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        input_handle
+    } else {
+        Handle(handle)
+    }
+}
+
 fn run_client(args: Args, pipe_path: &[u16], input_handle: Handle, output_handle: Option<Handle>) {
     let mut connection = ConnectionToServer::connect(pipe_path);
 
+    let mut stdin_content = Vec::new();
+    let input_handle = if args.stdin {
+        read_stdin_and_reopen_console(input_handle, &mut stdin_content)
+    } else {
+        input_handle
+    };
+
     let is_pipped = is_pipped(&input_handle);
     let stdout = io::stdout();
     let mut application = ClientApplication::new(stdout.lock(), is_pipped);
-    let bytes = application.init(args);
+    let bytes = application.init(args, &stdin_content);
     if !connection.write(bytes) {
         return;
     }
@@ -1213,6 +1330,11 @@ fn run_client(args: Args, pipe_path: &[u16], input_handle: Handle, output_handle
 
     drop(console_input_mode);
     drop(console_output_mode);
+    let exit_code = application.exit_code();
+    drop(application);
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
 }
 
 fn parse_console_events(
@@ -1220,6 +1342,10 @@ fn parse_console_events(
     keys: &mut Vec<Key>,
     resize: &mut Option<(usize, usize)>,
 ) {
+    const ALT_PRESSED_MASK: DWORD = LEFT_ALT_PRESSED | RIGHT_ALT_PRESSED;
+    const CTRL_PRESSED_MASK: DWORD = LEFT_CTRL_PRESSED | RIGHT_CTRL_PRESSED;
+
+    let mut high_surrogate = None;
     for event in console_events {
         match event.EventType {
             KEY_EVENT => {
@@ -1233,6 +1359,11 @@ fn parse_console_events(
                 let unicode_char = unsafe { *event.uChar.UnicodeChar() };
                 let repeat_count = event.wRepeatCount as usize;
 
+                let alt_pressed = control_key_state & ALT_PRESSED_MASK != 0;
+                let ctrl_pressed = control_key_state & CTRL_PRESSED_MASK != 0;
+                // AltGr is reported as ctrl + alt and already produces the final char
+                let alt_gr_pressed = alt_pressed && ctrl_pressed && unicode_char != 0;
+
                 const CHAR_A: i32 = b'A' as _;
                 const CHAR_Z: i32 = b'Z' as _;
                 let key = match keycode {
@@ -1256,14 +1387,11 @@ fn parse_console_events(
                             _ => continue,
                         }
                     }
-                    CHAR_A..=CHAR_Z => {
-                        const ALT_PRESSED_MASK: DWORD = LEFT_ALT_PRESSED | RIGHT_ALT_PRESSED;
-                        const CTRL_PRESSED_MASK: DWORD = LEFT_CTRL_PRESSED | RIGHT_CTRL_PRESSED;
-
-                        if control_key_state & ALT_PRESSED_MASK != 0 {
+                    CHAR_A..=CHAR_Z if !alt_gr_pressed => {
+                        if alt_pressed {
                             let c = (keycode - CHAR_A) as u8 + b'a';
                             Key::Alt(c.to_ascii_lowercase() as _)
-                        } else if control_key_state & CTRL_PRESSED_MASK != 0 {
+                        } else if ctrl_pressed {
                             let c = (keycode - CHAR_A) as u8 + b'a';
                             Key::Ctrl(c.to_ascii_lowercase() as _)
                         } else {
@@ -1273,10 +1401,36 @@ fn parse_console_events(
                             }
                         }
                     }
-                    _ => match std::char::decode_utf16(std::iter::once(unicode_char)).next() {
-                        Some(Ok(c)) if c.is_ascii_graphic() => Key::Char(c),
-                        _ => continue,
-                    },
+                    _ => {
+                        let c = match unicode_char {
+                            0xd800..=0xdbff => {
+                                high_surrogate = Some(unicode_char);
+                                continue;
+                            }
+                            0xdc00..=0xdfff => match high_surrogate.take() {
+                                Some(high) => {
+                                    let units = [high, unicode_char];
+                                    match std::char::decode_utf16(units.iter().copied()).next() {
+                                        Some(Ok(c)) => c,
+                                        _ => continue,
+                                    }
+                                }
+                                None => continue,
+                            },
+                            _ => match std::char::from_u32(unicode_char as _) {
+                                Some(c) => c,
+                                None => continue,
+                            },
+                        };
+
+                        if c.is_control() {
+                            continue;
+                        } else if alt_pressed && !alt_gr_pressed {
+                            Key::Alt(c)
+                        } else {
+                            Key::Char(c)
+                        }
+                    }
                 };
 
                 for _ in 0..repeat_count {
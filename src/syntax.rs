@@ -6,9 +6,12 @@ use crate::{
     editor_utils::hash_bytes,
     glob::{Glob, InvalidGlobError},
     pattern::{MatchResult, Pattern, PatternError, PatternState},
+    word_database::{WordDatabase, WordIter, WordKind},
 };
 
 const MAX_HIGHLIGHT_COUNT: usize = 2048;
+pub const DEFAULT_DEFINITIONS: &str =
+    "fn {}|def {}|function {}|class {}|struct {}|enum {}|trait {}|type {}|let {}|const {}|var {}|local {}";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenKind {
@@ -71,9 +74,14 @@ impl Default for LineParseState {
     }
 }
 
+#[derive(Clone)]
 pub struct Syntax {
+    name: String,
     glob_hash: u64,
+    glob_pattern: String,
     glob: Glob,
+    line_comment: String,
+    definitions: String,
     rules: [Pattern; 7],
 }
 
@@ -82,8 +90,12 @@ impl Syntax {
         let mut text_pattern = Pattern::new();
         let _ = text_pattern.compile("%a{%w_}|_{%w_}");
         Self {
+            name: String::new(),
             glob_hash: 0,
+            glob_pattern: String::new(),
             glob: Glob::default(),
+            line_comment: String::new(),
+            definitions: String::new(),
             rules: [
                 Pattern::new(),
                 Pattern::new(),
@@ -96,16 +108,89 @@ impl Syntax {
         }
     }
 
+    pub fn set_name(&mut self, name: &str) {
+        self.name.clear();
+        self.name.push_str(name);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn set_glob(&mut self, pattern: &str) -> Result<(), InvalidGlobError> {
         self.glob_hash = hash_bytes(pattern.as_bytes());
+        self.glob_pattern.clear();
+        self.glob_pattern.push_str(pattern);
         self.glob.compile(pattern)
     }
 
+    pub fn glob_pattern(&self) -> &str {
+        &self.glob_pattern
+    }
+
+    pub fn set_line_comment(&mut self, prefix: &str) {
+        self.line_comment.clear();
+        self.line_comment.push_str(prefix);
+    }
+
+    pub fn line_comment(&self) -> &str {
+        &self.line_comment
+    }
+
+    pub fn set_definitions(&mut self, templates: &str) {
+        self.definitions.clear();
+        self.definitions.push_str(templates);
+    }
+
+    pub fn definitions(&self) -> &str {
+        if self.definitions.is_empty() {
+            DEFAULT_DEFINITIONS
+        } else {
+            &self.definitions
+        }
+    }
+
     pub fn set_rule(&mut self, kind: TokenKind, pattern: &str) -> Result<(), PatternError> {
         self.rules[kind as usize].compile(pattern)
     }
 
     fn parse_line(
+        &self,
+        line: &str,
+        previous_parse_state: LineParseState,
+        max_line_length: usize,
+        tokens: &mut Vec<Token>,
+    ) -> LineParseState {
+        let parse_state = if line.len() > max_line_length {
+            // a line too long to parse continues whatever token the previous line left unfinished
+            let kind = match previous_parse_state {
+                LineParseState::Unfinished(kind, _) => kind,
+                _ => TokenKind::Text,
+            };
+            tokens.clear();
+            tokens.push(Token {
+                kind,
+                from: 0,
+                to: line.len() as _,
+            });
+            match previous_parse_state {
+                LineParseState::Dirty => LineParseState::Finished,
+                state => state,
+            }
+        } else {
+            self.parse_line_unchecked(line, previous_parse_state, tokens)
+        };
+        debug_assert!(
+            is_valid_line_parse(line, tokens, parse_state),
+            "invalid parse of line {:?}: {:?} {:?}",
+            line,
+            tokens,
+            parse_state,
+        );
+        parse_state
+    }
+
+    fn parse_line_unchecked(
         &self,
         line: &str,
         previous_parse_state: LineParseState,
@@ -215,6 +300,46 @@ impl Syntax {
     }
 }
 
+// tokens must cover the whole line contiguously, start and end at char boundaries
+// and, when the line is left unfinished, the last token must be of the unfinished kind
+fn is_valid_line_parse(line: &str, tokens: &[Token], parse_state: LineParseState) -> bool {
+    let mut index = 0;
+    for token in tokens {
+        let from = token.from as usize;
+        let to = token.to as usize;
+        if from != index || to < from || to > line.len() || !line.is_char_boundary(to) {
+            return false;
+        }
+        index = to;
+    }
+    if index != line.len() {
+        return false;
+    }
+
+    match parse_state {
+        LineParseState::Dirty => false,
+        LineParseState::Finished => true,
+        LineParseState::Unfinished(kind, _) => tokens.last().map(|t| t.kind) == Some(kind),
+    }
+}
+
+pub fn shebang_syntax_name(line: &str) -> Option<&str> {
+    let line = line.strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|w| !w.starts_with('-'))?;
+    }
+
+    let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    match interpreter {
+        "" => None,
+        "sh" | "bash" | "dash" | "ksh" | "zsh" => Some("sh"),
+        "node" | "nodejs" | "deno" => Some("javascript"),
+        _ => Some(interpreter),
+    }
+}
+
 #[derive(Clone, Copy, Default, PartialEq, Eq)]
 pub struct SyntaxHandle(usize);
 
@@ -241,6 +366,29 @@ impl SyntaxCollection {
         None
     }
 
+    pub fn find_handle_by_name(&self, name: &str) -> Option<SyntaxHandle> {
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut iter = self.syntaxes.iter().enumerate();
+        iter.next();
+        for (i, syntax) in iter {
+            if syntax.name == name || syntax.glob_pattern == name {
+                return Some(SyntaxHandle(i));
+            }
+        }
+
+        None
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.syntaxes[1..]
+            .iter()
+            .map(Syntax::name)
+            .filter(|n| !n.is_empty())
+    }
+
     pub fn add(&mut self, syntax: Syntax) {
         for s in &mut self.syntaxes {
             if s.glob_hash == syntax.glob_hash {
@@ -340,10 +488,14 @@ impl HighlightedBuffer {
         self.dirty_line_indexes.push(range.from.line_index);
     }
 
+    // when a word database is passed, it's kept with the words of every highlighted line
+    // that are not inside comments or strings
     pub fn highlight_dirty_lines(
         &mut self,
         syntax: &Syntax,
         buffer: &BufferContent,
+        max_line_length: usize,
+        mut word_database: Option<&mut WordDatabase>,
     ) -> HighlightResult {
         if self.dirty_line_indexes.is_empty() {
             return HighlightResult::Complete;
@@ -377,10 +529,24 @@ impl HighlightedBuffer {
                 let hline = &mut self.lines[index as usize];
 
                 let previous_state = hline.parse_state;
-                previous_parse_state =
-                    syntax.parse_line(bline, previous_parse_state, &mut hline.tokens);
+                if let Some(word_database) = word_database.as_deref_mut() {
+                    if previous_state != LineParseState::Dirty {
+                        for_each_code_word(bline, &hline.tokens, |w| word_database.remove(w));
+                    }
+                }
+
+                previous_parse_state = syntax.parse_line(
+                    bline,
+                    previous_parse_state,
+                    max_line_length,
+                    &mut hline.tokens,
+                );
                 hline.parse_state = previous_parse_state;
 
+                if let Some(word_database) = word_database.as_deref_mut() {
+                    for_each_code_word(bline, &hline.tokens, |w| word_database.add(w));
+                }
+
                 index += 1;
                 highlight_count += 1;
 
@@ -410,6 +576,75 @@ impl HighlightedBuffer {
             &[]
         }
     }
+
+    // tokens of a line that was not edited since it was last highlighted
+    pub fn clean_line_tokens(&self, line_index: usize) -> Option<&[Token]> {
+        match self.lines[..self.highlighted_len].get(line_index) {
+            Some(line) if line.parse_state != LineParseState::Dirty => Some(&line.tokens),
+            _ => None,
+        }
+    }
+}
+
+// calls `f` with every identifier in `line` that is not inside a comment or string token
+pub fn for_each_code_word<F>(line: &str, tokens: &[Token], mut f: F)
+where
+    F: FnMut(&str),
+{
+    let mut tokens = tokens
+        .iter()
+        .filter(|t| matches!(t.kind, TokenKind::Comment | TokenKind::String))
+        .peekable();
+    for word in WordIter(line).of_kind(WordKind::Identifier) {
+        let index = (word.as_ptr() as usize - line.as_ptr() as usize) as BufferPositionIndex;
+        while tokens.next_if(|t| t.to <= index).is_some() {}
+        match tokens.peek() {
+            Some(token) if token.contains(index) => (),
+            _ => f(word),
+        }
+    }
+}
+
+pub fn find_definition_column(definitions: &str, line: &str, word: &str) -> Option<usize> {
+    fn is_word_char(c: Option<char>) -> bool {
+        matches!(c.map(WordKind::from_char), Some(WordKind::Identifier))
+    }
+
+    if word.is_empty() {
+        return None;
+    }
+
+    for (column, _) in line.match_indices(word) {
+        let end = column + word.len();
+        if is_word_char(line[..column].chars().next_back())
+            || is_word_char(line[end..].chars().next())
+        {
+            continue;
+        }
+
+        for template in definitions.split('|') {
+            let (prefix, suffix) = match template.find("{}") {
+                Some(i) => (&template[..i], &template[i + 2..]),
+                None => continue,
+            };
+            if !line[..column].ends_with(prefix) || !line[end..].starts_with(suffix) {
+                continue;
+            }
+            // so that a `{} =` template does not match comparisons like `x == 1`
+            if suffix.ends_with('=') && line[end + suffix.len()..].starts_with('=') {
+                continue;
+            }
+            let prefix_start = column - prefix.len();
+            if prefix.starts_with(|c| is_word_char(Some(c)))
+                && is_word_char(line[..prefix_start].chars().next_back())
+            {
+                continue;
+            }
+            return Some(column);
+        }
+    }
+
+    None
 }
 
 #[cfg(test)]
@@ -446,12 +681,51 @@ mod tests {
         assert_eq!(slice, &line[token.from as usize..token.to as usize]);
     }
 
+    fn assert_valid_line_parse(
+        syntax: &Syntax,
+        line: &str,
+        previous_parse_state: LineParseState,
+        tokens: &mut Vec<Token>,
+    ) -> LineParseState {
+        let parse_state = syntax.parse_line_unchecked(line, previous_parse_state, tokens);
+        assert!(
+            is_valid_line_parse(line, tokens, parse_state),
+            "invalid parse of line {:?}: {:?} {:?}",
+            line,
+            tokens,
+            parse_state,
+        );
+        parse_state
+    }
+
+    fn rust_like_syntax() -> Syntax {
+        let mut syntax = Syntax::new();
+        syntax
+            .set_rule(TokenKind::Keyword, "fn|let|mut|pub")
+            .unwrap();
+        syntax.set_rule(TokenKind::Type, "%u{%w_}").unwrap();
+        syntax
+            .set_rule(TokenKind::Symbol, "%(|%)|%{|%}|:|;|,|=|<|>|*|/")
+            .unwrap();
+        syntax
+            .set_rule(TokenKind::Literal, "'\\{!'.}|'.'|%d{%w_}")
+            .unwrap();
+        syntax
+            .set_rule(TokenKind::String, "\"{(\\\")!\".$}")
+            .unwrap();
+        syntax
+            .set_rule(TokenKind::Comment, "//{.}|/*{!(*/).$}")
+            .unwrap();
+        syntax
+    }
+
     #[test]
     fn no_syntax() {
         let syntax = Syntax::new();
         let mut tokens = Vec::new();
         let line = " fn main() ;  ";
-        let parse_state = syntax.parse_line(line, LineParseState::Finished, &mut tokens);
+        let parse_state =
+            syntax.parse_line(line, LineParseState::Finished, usize::MAX, &mut tokens);
 
         assert_eq!(LineParseState::Finished, parse_state);
         assert_eq!(6, tokens.len());
@@ -470,7 +744,8 @@ mod tests {
 
         let mut tokens = Vec::new();
         let line = " fn main() ;  ";
-        let parse_state = syntax.parse_line(line, LineParseState::Finished, &mut tokens);
+        let parse_state =
+            syntax.parse_line(line, LineParseState::Finished, usize::MAX, &mut tokens);
 
         assert_eq!(LineParseState::Finished, parse_state);
         assert_eq!(6, tokens.len());
@@ -490,7 +765,8 @@ mod tests {
 
         let mut tokens = Vec::new();
         let line = " fn main() ;  ";
-        let parse_state = syntax.parse_line(line, LineParseState::Finished, &mut tokens);
+        let parse_state =
+            syntax.parse_line(line, LineParseState::Finished, usize::MAX, &mut tokens);
 
         assert_eq!(LineParseState::Finished, parse_state);
         assert_eq!(6, tokens.len());
@@ -512,7 +788,8 @@ mod tests {
         let line1 = "only comment";
         let line2 = "still comment */ after";
 
-        let line0_kind = syntax.parse_line(line0, LineParseState::Finished, &mut tokens);
+        let line0_kind =
+            syntax.parse_line(line0, LineParseState::Finished, usize::MAX, &mut tokens);
         match line0_kind {
             LineParseState::Unfinished(i, _) => assert_eq!(TokenKind::Comment, i),
             _ => panic!("{:?}", line0_kind),
@@ -521,7 +798,7 @@ mod tests {
         assert_token("before", TokenKind::Text, line0, &tokens[0]);
         assert_token(" /* comment", TokenKind::Comment, line0, &tokens[1]);
 
-        let line1_kind = syntax.parse_line(line1, line0_kind, &mut tokens);
+        let line1_kind = syntax.parse_line(line1, line0_kind, usize::MAX, &mut tokens);
         match line1_kind {
             LineParseState::Unfinished(i, _) => assert_eq!(TokenKind::Comment, i),
             _ => panic!("{:?}", line1_kind),
@@ -529,13 +806,160 @@ mod tests {
         assert_eq!(1, tokens.len());
         assert_token("only comment", TokenKind::Comment, line1, &tokens[0]);
 
-        let line2_kind = syntax.parse_line(line2, line1_kind, &mut tokens);
+        let line2_kind = syntax.parse_line(line2, line1_kind, usize::MAX, &mut tokens);
         assert_eq!(LineParseState::Finished, line2_kind);
         assert_eq!(2, tokens.len());
         assert_token("still comment */", TokenKind::Comment, line2, &tokens[0]);
         assert_token(" after", TokenKind::Text, line2, &tokens[1]);
     }
 
+    #[test]
+    fn shebang_syntax_names() {
+        assert_eq!(Some("python"), shebang_syntax_name("#!/usr/bin/env python"));
+        assert_eq!(
+            Some("python"),
+            shebang_syntax_name("#!/usr/bin/python3.11 -u")
+        );
+        assert_eq!(Some("sh"), shebang_syntax_name("#!/bin/bash"));
+        assert_eq!(Some("sh"), shebang_syntax_name("#! /bin/sh"));
+        assert_eq!(
+            Some("javascript"),
+            shebang_syntax_name("#!/usr/bin/env -S node")
+        );
+        assert_eq!(Some("ruby"), shebang_syntax_name("#!/usr/bin/ruby"));
+        assert_eq!(None, shebang_syntax_name("#!"));
+        assert_eq!(None, shebang_syntax_name("#!/usr/bin/env"));
+        assert_eq!(None, shebang_syntax_name("print('#!/bin/sh')"));
+    }
+
+    #[test]
+    fn line_parse_invariants() {
+        const FRAGMENTS: &[&str] = &[
+            "",
+            " ",
+            "\t",
+            "fn",
+            "main",
+            "Type",
+            "(",
+            ")",
+            "{",
+            "}",
+            ";",
+            "=",
+            "0",
+            "123_u8",
+            "'a'",
+            "'é'",
+            "'\\",
+            "\"",
+            "\"str\"",
+            "\\\"",
+            "/*",
+            "*/",
+            "//",
+            "/",
+            "*",
+            "é",
+            "ção",
+            "日本語",
+            "🦀",
+            "a🦀b",
+            "\u{301}",
+            "_",
+            "x_1",
+        ];
+
+        let syntaxes = [Syntax::new(), rust_like_syntax()];
+        let mut tokens = Vec::new();
+        let mut line = String::new();
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+
+        for syntax in &syntaxes {
+            let mut parse_state = LineParseState::Finished;
+            for _ in 0..2000 {
+                line.clear();
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                let mut fragments = seed;
+                for _ in 0..(seed % 8) {
+                    fragments /= FRAGMENTS.len() as u64;
+                    line.push_str(FRAGMENTS[(fragments % FRAGMENTS.len() as u64) as usize]);
+                }
+
+                assert_valid_line_parse(syntax, &line, LineParseState::Finished, &mut tokens);
+                parse_state = assert_valid_line_parse(syntax, &line, parse_state, &mut tokens);
+            }
+        }
+    }
+
+    #[test]
+    fn line_parse_multi_byte_boundaries() {
+        let syntax = rust_like_syntax();
+        let mut tokens = Vec::new();
+        for line in &[
+            "é",
+            " é",
+            "é ",
+            "日本語 fn",
+            "fn 🦀()",
+            "/* 🦀",
+            "\"🦀",
+            "'🦀'",
+            "x\u{301}y",
+            "\u{301}",
+        ] {
+            let parse_state =
+                assert_valid_line_parse(&syntax, line, LineParseState::Finished, &mut tokens);
+            assert_valid_line_parse(&syntax, line, parse_state, &mut tokens);
+        }
+    }
+
+    #[test]
+    fn line_longer_than_max_line_length() {
+        let mut syntax = Syntax::new();
+        syntax.set_rule(TokenKind::Keyword, "fn").unwrap();
+        syntax.set_rule(TokenKind::Comment, "/*{!(*/).$}").unwrap();
+
+        let mut tokens = Vec::new();
+        let line = "fn ".repeat(1_000);
+        let parse_state = syntax.parse_line(&line, LineParseState::Finished, 100, &mut tokens);
+        assert_eq!(LineParseState::Finished, parse_state);
+        assert_eq!(1, tokens.len());
+        assert_token(&line, TokenKind::Text, &line, &tokens[0]);
+
+        let line = "/* comment";
+        let parse_state = syntax.parse_line(line, LineParseState::Finished, 5, &mut tokens);
+        assert_eq!(LineParseState::Finished, parse_state);
+        assert_eq!(1, tokens.len());
+        assert_token(line, TokenKind::Text, line, &tokens[0]);
+
+        let parse_state =
+            syntax.parse_line(line, LineParseState::Finished, line.len(), &mut tokens);
+        assert!(matches!(
+            parse_state,
+            LineParseState::Unfinished(TokenKind::Comment, _)
+        ));
+
+        let long_line = "still comment";
+        let long_line_state = syntax.parse_line(long_line, parse_state, 5, &mut tokens);
+        assert_eq!(parse_state, long_line_state);
+        assert_eq!(1, tokens.len());
+        assert_token(long_line, TokenKind::Comment, long_line, &tokens[0]);
+
+        let mut tokens = Vec::new();
+        let parse_state = syntax.parse_line(
+            &line.repeat(2),
+            LineParseState::Finished,
+            line.len(),
+            &mut tokens,
+        );
+        assert_eq!(LineParseState::Finished, parse_state);
+        assert_eq!(1, tokens.len());
+        assert_eq!(TokenKind::Text, tokens[0].kind);
+    }
+
     #[test]
     fn editing_highlighted_buffer() {
         let mut syntax = Syntax::new();
@@ -547,7 +971,7 @@ mod tests {
 
         let range = buffer.insert_text(BufferPosition::zero(), "/*\n*/");
         highlighted.on_insert(range);
-        highlighted.highlight_dirty_lines(&syntax, &buffer);
+        highlighted.highlight_dirty_lines(&syntax, &buffer, usize::MAX, None);
         assert_eq!(buffer.line_count(), highlighted.lines.len());
 
         {
@@ -559,7 +983,7 @@ mod tests {
 
         let range = buffer.insert_text(BufferPosition::line_col(1, 0), "'");
         highlighted.on_insert(range);
-        highlighted.highlight_dirty_lines(&syntax, &buffer);
+        highlighted.highlight_dirty_lines(&syntax, &buffer, usize::MAX, None);
 
         {
             let mut tokens = highlighted_tokens(&highlighted);
@@ -579,7 +1003,7 @@ mod tests {
 
         let range = buffer.insert_text(BufferPosition::zero(), "/*\n\n\n*/");
         highlighted.on_insert(range);
-        highlighted.highlight_dirty_lines(&syntax, &buffer);
+        highlighted.highlight_dirty_lines(&syntax, &buffer, usize::MAX, None);
         assert_eq!(buffer.line_count(), highlighted.lines.len());
 
         let mut tokens = highlighted_tokens(&highlighted);
@@ -600,7 +1024,7 @@ mod tests {
 
         let range = buffer.insert_text(BufferPosition::zero(), "/*\n* /\n*/");
         highlighted.on_insert(range);
-        highlighted.highlight_dirty_lines(&syntax, &buffer);
+        highlighted.highlight_dirty_lines(&syntax, &buffer, usize::MAX, None);
 
         let range = BufferRange::between(
             BufferPosition::line_col(1, 1),
@@ -608,7 +1032,7 @@ mod tests {
         );
         buffer.delete_range(range);
         highlighted.on_delete(range);
-        highlighted.highlight_dirty_lines(&syntax, &buffer);
+        highlighted.highlight_dirty_lines(&syntax, &buffer, usize::MAX, None);
 
         let mut parse_states = highlighted.lines[..highlighted.highlighted_len]
             .iter()
@@ -641,7 +1065,7 @@ mod tests {
 
         let range = buffer.insert_text(BufferPosition::zero(), "/ *\na\n*/");
         highlighted.on_insert(range);
-        highlighted.highlight_dirty_lines(&syntax, &buffer);
+        highlighted.highlight_dirty_lines(&syntax, &buffer, usize::MAX, None);
 
         let range = BufferRange::between(
             BufferPosition::line_col(0, 1),
@@ -649,7 +1073,7 @@ mod tests {
         );
         buffer.delete_range(range);
         highlighted.on_delete(range);
-        highlighted.highlight_dirty_lines(&syntax, &buffer);
+        highlighted.highlight_dirty_lines(&syntax, &buffer, usize::MAX, None);
 
         let mut tokens = highlighted_tokens(&highlighted);
         assert_next_token(&mut tokens, TokenKind::Comment, 0..2);
@@ -668,7 +1092,7 @@ mod tests {
 
         let range = buffer.insert_text(BufferPosition::zero(), "a\n/*\nb\nc*/");
         highlighted.on_insert(range);
-        highlighted.highlight_dirty_lines(&syntax, &buffer);
+        highlighted.highlight_dirty_lines(&syntax, &buffer, usize::MAX, None);
         assert_eq!(buffer.line_count(), highlighted.highlighted_len);
 
         {
@@ -683,7 +1107,7 @@ mod tests {
         let range = BufferRange::between(BufferPosition::zero(), BufferPosition::line_col(1, 1));
         buffer.delete_range(range);
         highlighted.on_delete(range);
-        highlighted.highlight_dirty_lines(&syntax, &buffer);
+        highlighted.highlight_dirty_lines(&syntax, &buffer, usize::MAX, None);
         assert_eq!(buffer.line_count(), highlighted.highlighted_len);
 
         {
@@ -696,4 +1120,46 @@ mod tests {
             assert_eq!(None, tokens.next());
         }
     }
+
+    #[test]
+    fn definition_column() {
+        let definitions = "fn {}|let {} =|{}:";
+        assert_eq!(
+            Some(3),
+            find_definition_column(definitions, "fn main() {", "main")
+        );
+        assert_eq!(
+            Some(7),
+            find_definition_column(definitions, "pub fn main() {", "main")
+        );
+        assert_eq!(
+            Some(4),
+            find_definition_column(definitions, "let x = 2;", "x")
+        );
+        assert_eq!(
+            Some(0),
+            find_definition_column(definitions, "label:", "label")
+        );
+        assert_eq!(None, find_definition_column(definitions, "main();", "main"));
+        assert_eq!(
+            None,
+            find_definition_column(definitions, "fn mainly() {", "main")
+        );
+        assert_eq!(
+            None,
+            find_definition_column(definitions, "dfn main() {", "main")
+        );
+        assert_eq!(None, find_definition_column(definitions, "let xy = 2", "x"));
+        assert_eq!(None, find_definition_column(definitions, "fn main() {", ""));
+        assert_eq!(
+            Some(11),
+            find_definition_column(definitions, "main(); fn main() {", "main")
+        );
+
+        let definitions = "local {}|{} =|{}=";
+        assert_eq!(Some(0), find_definition_column(definitions, "x = 1", "x"));
+        assert_eq!(Some(0), find_definition_column(definitions, "x=1", "x"));
+        assert_eq!(None, find_definition_column(definitions, "if x == 1", "x"));
+        assert_eq!(None, find_definition_column(definitions, "if x==1", "x"));
+    }
 }
@@ -33,6 +33,11 @@ macro_rules! theme_colors {
                     _ => None,
                 }
             }
+
+            pub fn reset(&mut self) {
+                let default = Self::default();
+                $(self.$color = default.$color;)*
+            }
         }
     }
 }
@@ -40,7 +45,19 @@ macro_rules! theme_colors {
 theme_colors! {
     background,
     active_line_background,
+    color_column,
+    line_number,
+    diagnostic_error,
+    diagnostic_warning,
+    diagnostic_hint,
+    change_added,
+    change_modified,
+    change_deleted,
     highlight,
+    word_highlight,
+    trailing_whitespace,
+    mixed_indent,
+    indent_guide,
     normal_cursor,
     select_cursor,
     insert_cursor,
@@ -68,7 +85,19 @@ pub fn gruvbox_theme() -> Theme {
     Theme {
         background: Color::from_u32(0x1d2021),
         active_line_background: Color::from_u32(0x282828),
+        color_column: Color::from_u32(0x32302f),
+        line_number: Color::from_u32(0x7c6f64),
+        diagnostic_error: Color::from_u32(0xfb4934),
+        diagnostic_warning: Color::from_u32(0xfabd2f),
+        diagnostic_hint: Color::from_u32(0x83a598),
+        change_added: Color::from_u32(0xb8bb26),
+        change_modified: Color::from_u32(0x83a598),
+        change_deleted: Color::from_u32(0xfb4934),
         highlight: Color::from_u32(0xfabd2f),
+        word_highlight: Color::from_u32(0x3c3836),
+        trailing_whitespace: Color::from_u32(0x9d0006),
+        mixed_indent: Color::from_u32(0x076678),
+        indent_guide: Color::from_u32(0x3c3836),
         normal_cursor: Color::from_u32(0xcc241d),
         insert_cursor: Color::from_u32(0xfabd2f),
         select_cursor: Color::from_u32(0x458588),
@@ -4,12 +4,16 @@ use crate::{
     buffer_position::{BufferPosition, BufferRange},
     buffer_view::{BufferViewHandle, CursorMovementKind},
     client::ClientManager,
+    config::{LineNumbers, RenderWhitespace},
+    diff::ChangeSign,
     editor::Editor,
     editor_utils::MessageKind,
+    lsp::{Diagnostic, DiagnosticSeverity},
     mode::ModeKind,
     platform::Platform,
     syntax::{Token, TokenKind},
     theme::Color,
+    word_database::WordKind,
 };
 
 pub static ENTER_ALTERNATE_BUFFER_CODE: &[u8] = b"\x1b[?1049h";
@@ -59,6 +63,11 @@ pub fn set_underlined(buf: &mut Vec<u8>) {
     buf.extend_from_slice(b"\x1b[4m");
 }
 
+pub fn set_underline_color(buf: &mut Vec<u8>, color: Color) {
+    use io::Write;
+    let _ = write!(buf, "\x1b[58;2;{};{};{}m", color.0, color.1, color.2);
+}
+
 pub fn set_not_underlined(buf: &mut Vec<u8>) {
     buf.extend_from_slice(b"\x1b[24m");
 }
@@ -164,17 +173,35 @@ fn draw_buffer_view(
 
     let buffer_content = buffer.content();
     let highlighted_buffer = buffer.highlighted();
+    let folds = buffer.folds();
     let search_ranges = buffer.search_ranges();
     let search_ranges_end_index = search_ranges.len().saturating_sub(1);
 
-    let mut diagnostics: &[_] = &[];
-    for client in ctx.editor.lsp.clients() {
-        diagnostics = client.diagnostics().buffer_diagnostics(buffer.handle());
-        if !diagnostics.is_empty() {
-            break;
+    let mut word_ranges = Vec::new();
+    if ctx.editor.config.highlight_current_word {
+        let main_cursor = buffer_view.cursors.main_cursor();
+        let word = buffer_content.word_at(main_cursor.position);
+        if main_cursor.anchor == main_cursor.position && word.kind == WordKind::Identifier {
+            let mut lines_end = ctx.scroll.1 as usize;
+            let mut visible_line_count = 0;
+            while lines_end < buffer_content.line_count() && visible_line_count < ctx.draw_height {
+                if !folds.is_hidden(lines_end as _) {
+                    visible_line_count += 1;
+                }
+                lines_end += 1;
+            }
+
+            buffer_content.find_word_ranges(
+                word.text,
+                ctx.scroll.1 as usize..lines_end,
+                &mut word_ranges,
+            );
+            word_ranges.retain(|r| r.from != word.position);
         }
     }
-    let diagnostics = diagnostics;
+    let word_ranges_end_index = word_ranges.len().saturating_sub(1);
+
+    let diagnostics = ctx.editor.lsp.buffer_diagnostics(buffer.handle());
     let diagnostics_end_index = diagnostics.len().saturating_sub(1);
 
     let display_position_offset = BufferPosition::line_col(ctx.scroll.1 as _, ctx.scroll.0 as _);
@@ -202,6 +229,16 @@ fn draw_buffer_view(
         }
     }
 
+    let mut current_word_range_index = word_ranges.len();
+    let mut current_word_range = BufferRange::zero();
+    for (i, &range) in word_ranges.iter().enumerate() {
+        if display_position_offset < range.to {
+            current_word_range_index = i;
+            current_word_range = range;
+            break;
+        }
+    }
+
     let mut current_diagnostic_index = diagnostics.len();
     let mut current_diagnostic_range = BufferRange::zero();
     for (i, diagnostic) in diagnostics.iter().enumerate() {
@@ -216,24 +253,66 @@ fn draw_buffer_view(
     set_background_color(buf, ctx.editor.theme.background);
     set_not_underlined(buf);
 
+    let gutter_width = ctx
+        .editor
+        .config
+        .gutter_width(buffer_content.line_count(), !diagnostics.is_empty());
+    let change_signs = if ctx.editor.config.change_signs {
+        buffer.change_signs()
+    } else {
+        &[]
+    };
+    let number_gutter_width = gutter_width - ctx.editor.config.change_signs as u16;
+    let text_width = ctx.viewport_size.0.saturating_sub(gutter_width);
+    let wrap_width = ctx.editor.config.soft_wrap_width(text_width);
+    let color_column_x = match ctx.editor.config.color_column {
+        0 => usize::MAX,
+        column => (column as usize - 1)
+            .checked_sub(ctx.scroll.0 as _)
+            .unwrap_or(usize::MAX),
+    };
+
+    let mut marker_diagnostic_index = 0;
     let mut lines_drawn_count = 0;
-    for (line_index, line) in buffer_content
-        .lines()
-        .enumerate()
-        .skip(ctx.scroll.1 as _)
-        .take(ctx.draw_height as _)
-    {
+    for (line_index, line) in buffer_content.lines().enumerate().skip(ctx.scroll.1 as _) {
+        if lines_drawn_count >= ctx.draw_height {
+            break;
+        }
+        if folds.is_hidden(line_index as _) {
+            continue;
+        }
+
         #[derive(Clone, Copy, PartialEq, Eq)]
         enum DrawState {
             Token(TokenKind),
             Selection(TokenKind),
             Highlight,
+            WordHighlight(TokenKind),
+            TrailingWhitespace,
+            MixedIndent,
             Cursor,
         }
 
         lines_drawn_count += 1;
 
         let line = line.as_str();
+        let line_content_end = line.trim_end().len();
+        let trailing_whitespace_start = if ctx.editor.config.highlight_trailing_whitespace {
+            line_content_end
+        } else {
+            line.len()
+        };
+        let mixed_indent_end = if ctx.editor.config.highlight_mixed_indent {
+            let indentation = &line[..line.len() - line.trim_start().len()];
+            if indentation.contains(' ') && indentation.contains('\t') {
+                indentation.len()
+            } else {
+                0
+            }
+        } else {
+            0
+        };
+
         let mut draw_state = DrawState::Token(TokenKind::Text);
         let mut was_inside_diagnostic_range = false;
         let mut x = 0;
@@ -246,10 +325,33 @@ fn draw_buffer_view(
             ctx.editor.theme.background
         };
 
+        draw_change_sign(ctx, buf, change_signs, line_index);
+        draw_line_number(ctx, buf, number_gutter_width, line_index, active_line_index);
+        while marker_diagnostic_index < diagnostics.len()
+            && (diagnostics[marker_diagnostic_index].range.to.line_index as usize) < line_index
+        {
+            marker_diagnostic_index += 1;
+        }
+        let line_diagnostics = &diagnostics[marker_diagnostic_index..];
+        draw_diagnostic_marker(ctx, buf, number_gutter_width, line_diagnostics, line_index);
         set_background_color(buf, background_color);
         set_foreground_color(buf, ctx.editor.theme.token_text);
 
+        let indentation_len = if ctx.editor.config.indent_guides && !line.trim().is_empty() {
+            line.len() - line.trim_start().len()
+        } else {
+            0
+        };
+        let mut indentation_x = 0;
+
         for (char_index, c) in line.char_indices().chain(iter::once((line.len(), '\n'))) {
+            let indentation_column = indentation_x;
+            if char_index < indentation_len {
+                indentation_x += match c {
+                    '\t' => ctx.editor.config.tab_size.get() as usize,
+                    _ => 1,
+                };
+            }
             if char_index < ctx.scroll.0 as _ {
                 continue;
             }
@@ -302,6 +404,15 @@ fn draw_buffer_view(
             let inside_search_range = current_search_range.from <= char_position
                 && char_position < current_search_range.to;
 
+            while current_word_range.to <= char_position
+                && current_word_range_index < word_ranges_end_index
+            {
+                current_word_range_index += 1;
+                current_word_range = word_ranges[current_word_range_index];
+            }
+            let inside_word_range =
+                current_word_range.from <= char_position && char_position < current_word_range.to;
+
             while current_diagnostic_range.to < char_position
                 && current_diagnostic_index < diagnostics_end_index
             {
@@ -314,6 +425,8 @@ fn draw_buffer_view(
             if inside_diagnostic_range != was_inside_diagnostic_range {
                 was_inside_diagnostic_range = inside_diagnostic_range;
                 if inside_diagnostic_range {
+                    let severity = diagnostics[current_diagnostic_index].severity;
+                    set_underline_color(buf, diagnostic_color(ctx, severity));
                     set_underlined(buf);
                 } else {
                     set_not_underlined(buf);
@@ -338,29 +451,113 @@ fn draw_buffer_view(
                     set_background_color(buf, ctx.editor.theme.highlight);
                     set_foreground_color(buf, background_color);
                 }
+            } else if inside_word_range {
+                if draw_state != DrawState::WordHighlight(token_kind) {
+                    draw_state = DrawState::WordHighlight(token_kind);
+                    set_background_color(buf, ctx.editor.theme.word_highlight);
+                    set_foreground_color(buf, text_color);
+                }
+            } else if char_index < mixed_indent_end {
+                if draw_state != DrawState::MixedIndent {
+                    draw_state = DrawState::MixedIndent;
+                    set_background_color(buf, ctx.editor.theme.mixed_indent);
+                    set_foreground_color(buf, text_color);
+                }
+            } else if trailing_whitespace_start <= char_index && char_index < line.len() {
+                if draw_state != DrawState::TrailingWhitespace {
+                    draw_state = DrawState::TrailingWhitespace;
+                    set_background_color(buf, ctx.editor.theme.trailing_whitespace);
+                    set_foreground_color(buf, text_color);
+                }
             } else if draw_state != DrawState::Token(token_kind) {
                 draw_state = DrawState::Token(token_kind);
                 set_background_color(buf, background_color);
                 set_foreground_color(buf, text_color);
             }
 
+            let char_width = match c {
+                '\t' => ctx.editor.config.tab_size.get() as usize,
+                _ => 1,
+            };
+            if let Some(wrap_width) = wrap_width {
+                if x > 0 && x + char_width > wrap_width {
+                    if lines_drawn_count >= ctx.draw_height {
+                        buf.truncate(buf_len);
+                        break;
+                    }
+                    lines_drawn_count += 1;
+                    x = 0;
+
+                    set_background_color(buf, background_color);
+                    clear_until_new_line(buf);
+                    move_cursor_to_next_line(buf);
+                    draw_change_sign(ctx, buf, change_signs, usize::MAX);
+                    draw_line_number(ctx, buf, number_gutter_width, usize::MAX, active_line_index);
+                    draw_diagnostic_marker(ctx, buf, number_gutter_width, &[], line_index);
+                    set_background_color(buf, background_color);
+                    draw_state = DrawState::Token(TokenKind::Whitespace);
+                    set_foreground_color(buf, ctx.editor.theme.token_whitespace);
+                }
+            }
+
+            let on_color_column = draw_state == DrawState::Token(token_kind)
+                && x <= color_column_x
+                && color_column_x < x + char_width;
+            if on_color_column {
+                set_background_color(buf, ctx.editor.theme.color_column);
+            }
+
+            let render_whitespace_glyph = match ctx.editor.config.render_whitespace {
+                RenderWhitespace::Off => false,
+                RenderWhitespace::Trailing => {
+                    line_content_end <= char_index && char_index < line.len()
+                }
+                RenderWhitespace::All => true,
+            };
+            // selections and highlights keep their own foreground color
+            let color_whitespace_glyph = draw_state == DrawState::Token(token_kind);
+            let draw_indent_guide = char_index < indentation_len
+                && indentation_column % ctx.editor.config.tab_size.get() as usize == 0
+                && draw_state == DrawState::Token(token_kind);
+
             let previous_x = x;
             match c {
                 '\n' => {
                     x += 1;
-                    buf.push(b' ');
+                    if render_whitespace_glyph {
+                        draw_whitespace_glyph(ctx, buf, "¬", color_whitespace_glyph, text_color);
+                    } else {
+                        buf.push(b' ');
+                    }
                 }
                 ' ' => {
                     x += 1;
-                    buf.push(ctx.editor.config.visual_space);
+                    if draw_indent_guide {
+                        draw_indent_guide_glyph(ctx, buf, text_color);
+                    } else if render_whitespace_glyph {
+                        draw_whitespace_glyph(ctx, buf, "·", color_whitespace_glyph, text_color);
+                    } else {
+                        buf.push(ctx.editor.config.visual_space);
+                    }
                 }
                 '\t' => {
                     let tab_size = ctx.editor.config.tab_size.get() as usize;
                     x += tab_size;
 
-                    buf.push(ctx.editor.config.visual_tab_first);
+                    if draw_indent_guide {
+                        draw_indent_guide_glyph(ctx, buf, text_color);
+                    } else if render_whitespace_glyph {
+                        draw_whitespace_glyph(ctx, buf, "→", color_whitespace_glyph, text_color);
+                    } else {
+                        buf.push(ctx.editor.config.visual_tab_first);
+                    }
+                    let repeat = if render_whitespace_glyph {
+                        b' '
+                    } else {
+                        ctx.editor.config.visual_tab_repeat
+                    };
                     for _ in 0..tab_size - 1 {
-                        buf.push(ctx.editor.config.visual_tab_repeat);
+                        buf.push(repeat);
                     }
                 }
                 _ => {
@@ -369,7 +566,11 @@ fn draw_buffer_view(
                 }
             }
 
-            if x > ctx.viewport_size.0 as _ {
+            if on_color_column {
+                set_background_color(buf, background_color);
+            }
+
+            if x > text_width as _ {
                 x = previous_x;
                 buf.truncate(buf_len);
                 break;
@@ -378,7 +579,27 @@ fn draw_buffer_view(
 
         set_background_color(buf, background_color);
 
-        if x < ctx.viewport_size.0 as _ {
+        if let Some(fold) = folds.fold_at(line_index as _) {
+            use io::Write;
+            set_foreground_color(buf, ctx.editor.theme.token_whitespace);
+            let buf_len = buf.len();
+            let _ = write!(buf, " ... {} lines", fold.hidden_line_count());
+            let summary_len = (buf.len() - buf_len).min((text_width as usize).saturating_sub(x));
+            buf.truncate(buf_len + summary_len);
+            x += summary_len;
+        }
+
+        if x <= color_column_x && color_column_x < text_width as _ {
+            for _ in x..color_column_x {
+                buf.push(b' ');
+            }
+            set_background_color(buf, ctx.editor.theme.color_column);
+            buf.push(b' ');
+            set_background_color(buf, background_color);
+            x = color_column_x + 1;
+        }
+
+        if x < text_width as _ {
             clear_until_new_line(buf);
         }
 
@@ -396,6 +617,131 @@ fn draw_buffer_view(
     }
 }
 
+fn draw_indent_guide_glyph(ctx: &RenderContext, buf: &mut Vec<u8>, text_color: Color) {
+    set_foreground_color(buf, ctx.editor.theme.indent_guide);
+    buf.extend_from_slice("│".as_bytes());
+    set_foreground_color(buf, text_color);
+}
+
+fn draw_whitespace_glyph(
+    ctx: &RenderContext,
+    buf: &mut Vec<u8>,
+    glyph: &str,
+    colored: bool,
+    text_color: Color,
+) {
+    if colored {
+        set_foreground_color(buf, ctx.editor.theme.token_whitespace);
+        buf.extend_from_slice(glyph.as_bytes());
+        set_foreground_color(buf, text_color);
+    } else {
+        buf.extend_from_slice(glyph.as_bytes());
+    }
+}
+
+fn draw_change_sign(
+    ctx: &RenderContext,
+    buf: &mut Vec<u8>,
+    change_signs: &[ChangeSign],
+    line_index: usize,
+) {
+    if !ctx.editor.config.change_signs {
+        return;
+    }
+
+    set_background_color(buf, ctx.editor.theme.background);
+    match change_signs.get(line_index) {
+        Some(ChangeSign::Added) => {
+            set_foreground_color(buf, ctx.editor.theme.change_added);
+            buf.push(b'+');
+        }
+        Some(ChangeSign::Modified) => {
+            set_foreground_color(buf, ctx.editor.theme.change_modified);
+            buf.push(b'~');
+        }
+        Some(ChangeSign::Deleted) => {
+            set_foreground_color(buf, ctx.editor.theme.change_deleted);
+            buf.push(b'_');
+        }
+        Some(ChangeSign::None) | None => buf.push(b' '),
+    }
+}
+
+fn draw_line_number(
+    ctx: &RenderContext,
+    buf: &mut Vec<u8>,
+    gutter_width: u16,
+    line_index: usize,
+    active_line_index: usize,
+) {
+    if gutter_width == 0 {
+        return;
+    }
+
+    set_background_color(buf, ctx.editor.theme.background);
+    set_foreground_color(buf, ctx.editor.theme.line_number);
+
+    let width = gutter_width as usize - 1;
+    let number = match ctx.editor.config.line_numbers {
+        _ if line_index == usize::MAX => None,
+        LineNumbers::Off => None,
+        LineNumbers::Absolute => Some(line_index + 1),
+        LineNumbers::Hybrid if line_index == active_line_index => Some(line_index + 1),
+        LineNumbers::Relative | LineNumbers::Hybrid => Some(line_index.abs_diff(active_line_index)),
+    };
+
+    use io::Write;
+    let _ = match number {
+        Some(number) => write!(buf, "{:>width$}", number, width = width),
+        None => write!(buf, "{:width$}", "", width = width),
+    };
+}
+
+fn diagnostic_color(ctx: &RenderContext, severity: DiagnosticSeverity) -> Color {
+    match severity {
+        DiagnosticSeverity::Error => ctx.editor.theme.diagnostic_error,
+        DiagnosticSeverity::Warning => ctx.editor.theme.diagnostic_warning,
+        DiagnosticSeverity::Information | DiagnosticSeverity::Hint => {
+            ctx.editor.theme.diagnostic_hint
+        }
+    }
+}
+
+fn draw_diagnostic_marker(
+    ctx: &RenderContext,
+    buf: &mut Vec<u8>,
+    gutter_width: u16,
+    diagnostics: &[Diagnostic],
+    line_index: usize,
+) {
+    if gutter_width == 0 {
+        return;
+    }
+
+    // diagnostics are sorted by where they start so the ones after this line can be skipped
+    let line_index = line_index as u32;
+    let severity = diagnostics
+        .iter()
+        .take_while(|d| d.range.from.line_index <= line_index)
+        .filter(|d| line_index <= d.range.to.line_index)
+        .map(|d| d.severity)
+        .min_by_key(|s| s.to_integer());
+
+    match severity {
+        Some(severity) => {
+            let marker: &[u8] = match severity {
+                DiagnosticSeverity::Error => b"E",
+                DiagnosticSeverity::Warning => b"W",
+                DiagnosticSeverity::Information => b"I",
+                DiagnosticSeverity::Hint => b"H",
+            };
+            set_foreground_color(buf, diagnostic_color(ctx, severity));
+            buf.extend_from_slice(marker);
+        }
+        None => buf.push(b' '),
+    }
+}
+
 fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
     if !ctx.has_focus {
         return;
@@ -404,7 +750,7 @@ fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
     let cursor = ctx.editor.picker.cursor().unwrap_or(usize::MAX - 1);
     let scroll = ctx.editor.picker.scroll();
 
-    let width = ctx.viewport_size.0 as _;
+    let width = ctx.viewport_size.0 as usize;
     let height = ctx
         .editor
         .picker
@@ -414,6 +760,12 @@ fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
     let background_normal_color = ctx.editor.theme.statusbar_inactive_background;
     let background_selected_color = ctx.editor.theme.statusbar_active_background;
     let foreground_color = ctx.editor.theme.token_text;
+    let matched_foreground_color = ctx.editor.theme.token_keyword;
+
+    let preview = ctx.editor.picker.preview();
+    let mut preview_lines = preview.lines();
+    let preview_width = if preview.is_empty() { 0 } else { width / 2 };
+    let width = width - preview_width;
 
     set_background_color(buf, background_normal_color);
     set_foreground_color(buf, foreground_color);
@@ -434,6 +786,11 @@ fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
 
         let mut x = 0;
 
+        if ctx.editor.picker.is_selected(i) {
+            buf.extend_from_slice(b"* ");
+            x += 2;
+        }
+
         fn print_char(buf: &mut Vec<u8>, x: &mut usize, c: char) {
             let mut char_buf = [0; std::mem::size_of::<char>()];
 
@@ -445,21 +802,39 @@ fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
         }
 
         let name_char_count = entry.chars().count();
-        if name_char_count < width {
-            for c in entry.chars() {
-                print_char(buf, &mut x, c);
-            }
+        let skip = if x + name_char_count < width {
+            0
         } else {
             buf.extend_from_slice(b"...");
             x += 3;
-            let name_char_count = name_char_count + 3;
-            for c in entry.chars().skip(name_char_count.saturating_sub(width)) {
+            (x + name_char_count).saturating_sub(width)
+        };
+        for (c, matched) in ctx.editor.picker.matched_chars(i, entry).skip(skip) {
+            if matched {
+                set_foreground_color(buf, matched_foreground_color);
+                print_char(buf, &mut x, c);
+                set_foreground_color(buf, foreground_color);
+            } else {
                 print_char(buf, &mut x, c);
             }
         }
         for _ in x..width {
             buf.push(b' ');
         }
+
+        if preview_width > 0 {
+            set_background_color(buf, background_normal_color);
+            buf.push(b' ');
+            x = 1;
+            if let Some(line) = preview_lines.next() {
+                for c in line.chars().take(preview_width - 1) {
+                    print_char(buf, &mut x, c);
+                }
+            }
+            for _ in x..preview_width {
+                buf.push(b' ');
+            }
+        }
         x = 0;
 
         if x < width {
@@ -477,6 +852,9 @@ fn draw_statusbar(
     let view_name;
     let needs_save;
     let main_cursor_position;
+    let line_count;
+    let byte_len;
+    let syntax_name;
     let search_ranges;
 
     match buffer_view_handle {
@@ -487,12 +865,18 @@ fn draw_statusbar(
             view_name = buffer.path.to_str().unwrap_or("");
             needs_save = buffer.needs_save();
             main_cursor_position = buffer_view.cursors.main_cursor().position;
+            line_count = buffer.content().line_count();
+            byte_len = buffer.content().byte_len();
+            syntax_name = ctx.editor.syntaxes.get(buffer.syntax_handle()).name();
             search_ranges = buffer.search_ranges();
         }
         None => {
             view_name = "";
             needs_save = false;
             main_cursor_position = BufferPosition::zero();
+            line_count = 0;
+            byte_len = 0;
+            syntax_name = "";
             search_ranges = &[];
         }
     }
@@ -643,20 +1027,57 @@ fn draw_statusbar(
             buf.push(b' ');
         }
 
-        if needs_save {
-            buf.push(b'*');
-        }
-
-        let (char_count, view_name) = take_chars(view_name, half_available_width);
-        if char_count == half_available_width {
-            buf.extend_from_slice(TOO_LONG_PREFIX);
-        }
-        buf.extend_from_slice(view_name.as_bytes());
+        if buffer_view_handle.is_some() {
+            let mut format = ctx.editor.config.status_line_format.chars();
+            while let Some(c) = format.next() {
+                let c = match c {
+                    '%' => format.next(),
+                    _ => {
+                        let _ = write!(buf, "{}", c);
+                        continue;
+                    }
+                };
 
-        if !view_name.is_empty() {
-            let line_number = main_cursor_position.line_index + 1;
-            let column_number = main_cursor_position.column_byte_index + 1;
-            let _ = write!(buf, ":{},{}", line_number, column_number);
+                let line_number = main_cursor_position.line_index as usize + 1;
+                match c {
+                    Some('f') => {
+                        // so pathless buffers show `1,1` instead of `:1,1`
+                        if view_name.is_empty() && format.clone().next() == Some(':') {
+                            format.next();
+                        }
+                        let (char_count, view_name) = take_chars(view_name, half_available_width);
+                        if char_count == half_available_width {
+                            buf.extend_from_slice(TOO_LONG_PREFIX);
+                        }
+                        buf.extend_from_slice(view_name.as_bytes());
+                    }
+                    Some('l') => {
+                        let _ = write!(buf, "{}", line_number);
+                    }
+                    Some('c') => {
+                        let column_number = main_cursor_position.column_byte_index + 1;
+                        let _ = write!(buf, "{}", column_number);
+                    }
+                    Some('m') => {
+                        if needs_save {
+                            buf.push(b'*');
+                        }
+                    }
+                    Some('y') => buf.extend_from_slice(syntax_name.as_bytes()),
+                    Some('p') => {
+                        let percent = line_number * 100 / line_count.max(1);
+                        let _ = write!(buf, "{}%", percent);
+                    }
+                    Some('b') => {
+                        let _ = write!(buf, "{}", byte_len);
+                    }
+                    Some('%') => buf.push(b'%'),
+                    Some(c) => {
+                        let _ = write!(buf, "%{}", c);
+                    }
+                    None => buf.push(b'%'),
+                }
+            }
         }
         buf.push(b' ');
 